@@ -0,0 +1,53 @@
+//! Compares parsing through `compare_strs` (UTF-8 validation and copy into a `String` first)
+//! with `compare_slices` on a large generated document.
+//! Run with `cargo bench --bench parse`.
+use std::time::{Duration, Instant};
+
+use json_diff_ng::{compare_slices, compare_strs_with_options, CompareOptions};
+
+fn large_document(entries: usize, changed: usize) -> Vec<u8> {
+    let items: Vec<String> = (0..entries)
+        .map(|i| {
+            let value = if i == changed { "changed" } else { "value" };
+            format!(r#"{{"id": {i}, "name": "entry number {i} with some text", "tags": ["a", "b", "c"], "value": "{value}"}}"#)
+        })
+        .collect();
+    format!(r#"{{"items": [{}]}}"#, items.join(",")).into_bytes()
+}
+
+fn measure(name: &str, runs: u32, mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..runs {
+        f();
+    }
+    let per_run = start.elapsed() / runs;
+    println!("{name:>14}: {per_run:?} per run");
+    per_run
+}
+
+fn main() {
+    let left = large_document(200_000, usize::MAX);
+    let right = large_document(200_000, 1234);
+    println!("document size: {} MB", left.len() / 1_000_000);
+    let options = CompareOptions::default();
+    let runs = 5;
+
+    // the pass compare_slices avoids: validating and copying the bytes into a String
+    measure("utf8 + copy", runs, || {
+        let left = String::from_utf8(left.clone()).unwrap();
+        let right = String::from_utf8(right.clone()).unwrap();
+        assert_eq!(left.len(), right.len() - 2);
+    });
+    let strs = measure("compare_strs", runs, || {
+        // file contents arrive as bytes, so the strs path has to convert them first
+        let left = String::from_utf8(left.clone()).unwrap();
+        let right = String::from_utf8(right.clone()).unwrap();
+        let diffs = compare_strs_with_options(&left, &right, &options).unwrap();
+        assert_eq!(diffs.count_diffs().total(), 1);
+    });
+    let slices = measure("compare_slices", runs, || {
+        let diffs = compare_slices(&left, &right, &options).unwrap();
+        assert_eq!(diffs.count_diffs().total(), 1);
+    });
+    println!("difference of the full runs: {:?}", strs.abs_diff(slices));
+}
@@ -0,0 +1,76 @@
+//! Counts the allocations of `compare_raw` and of parsing both documents for `compare_serde_values_with_options`
+//! on large, mostly equal documents. Run with `cargo bench --bench raw`.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use serde_json::value::RawValue;
+use serde_json::Value;
+
+use json_diff_ng::raw::compare_raw;
+use json_diff_ng::{compare_serde_values_with_options, CompareOptions};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn large_document(sections: usize, changed: usize) -> String {
+    let sections: Vec<String> = (0..sections)
+        .map(|i| {
+            let value = if i == changed { "changed" } else { "value" };
+            let items: Vec<String> = (0..20)
+                .map(|j| format!(r#"{{"id": {j}, "name": "entry {i}/{j}", "tags": ["a", "b"]}}"#))
+                .collect();
+            format!(
+                r#""section {i}": {{"items": [{}], "value": "{value}"}}"#,
+                items.join(",")
+            )
+        })
+        .collect();
+    format!("{{{}}}", sections.join(","))
+}
+
+fn measure(name: &str, f: impl FnOnce() -> usize) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let diffs = f();
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    println!("{name:>14}: {allocations:>9} allocations, {elapsed:?}, {diffs} diffs");
+}
+
+fn main() {
+    let left = large_document(5_000, usize::MAX);
+    let right = large_document(5_000, 1234);
+    println!("document size: {} MB", left.len() / 1_000_000);
+    let options = CompareOptions::default();
+
+    let (left, right): (&RawValue, &RawValue) = (
+        serde_json::from_str(&left).unwrap(),
+        serde_json::from_str(&right).unwrap(),
+    );
+    measure("parsed", || {
+        let a: Value = serde_json::from_str(left.get()).unwrap();
+        let b: Value = serde_json::from_str(right.get()).unwrap();
+        let diffs = compare_serde_values_with_options(&a, &b, &options).unwrap();
+        diffs.count_diffs().total()
+    });
+    measure("compare_raw", || {
+        let diffs = compare_raw(left, right, &options).unwrap();
+        diffs.count_diffs().total()
+    });
+}
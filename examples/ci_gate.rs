@@ -0,0 +1,25 @@
+//! Fails a build only on changed values outside of build metadata, and resolves the failing paths in the
+//! original documents.
+use json_diff_ng::prelude::*;
+use serde_json::json;
+
+fn main() -> Result<()> {
+    let golden = json!({"list": [3, 1, 2], "meta": {"build": 1}});
+    let actual = json!({"list": [4, 1, 2], "meta": {"build": 2}});
+    let diffs = compare_serde_values(&golden, &actual, true, &[])?;
+    let policy = GatePolicy::default()
+        .fail_on([DiffType::Mismatch])
+        .allow("/meta");
+    let outcome = ci_gate(&diffs, &policy);
+    for (path, left, right) in resolve_all(&diffs, &golden, &actual) {
+        println!("{path}: {left:?} -> {right:?}");
+    }
+    // a build script would exit non-zero on a failing verdict
+    println!(
+        "{:?} with {} failures",
+        outcome.verdict,
+        outcome.failures.len()
+    );
+    assert_eq!(outcome.tolerated, 1);
+    Ok(())
+}
@@ -0,0 +1,17 @@
+//! Compares two documents, ignoring a volatile key and the order of an array, and prints the diffs.
+use json_diff_ng::prelude::*;
+use serde_json::json;
+
+fn main() -> Result<()> {
+    let left = json!({"id": 1, "tags": ["a", "b"], "updated": "monday", "gone": true});
+    let right = json!({"id": 2, "tags": ["b", "a"], "updated": "tuesday"});
+    let options = CompareOptions::default()
+        .sort_arrays(true)
+        .ignore_keys(vec![Regex::new("^updated$").unwrap()]);
+    let diffs = compare_serde_values_with_options(&left, &right, &options)?;
+    for (diff_type, entry) in diffs.all_diffs() {
+        println!("{diff_type}: {entry}");
+    }
+    assert_eq!(diffs.verdict(), Verdict::Fail);
+    Ok(())
+}
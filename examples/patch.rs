@@ -0,0 +1,13 @@
+//! Turns a comparison into a JSON patch and applies it to the left document.
+use json_diff_ng::prelude::*;
+use serde_json::json;
+
+fn main() -> Result<()> {
+    let left = json!({"a": {"b": [1, 2, 3]}, "gone": true});
+    let right = json!({"a": {"b": [1, 3, 4]}});
+    let diffs = compare_serde_values(&left, &right, false, &[])?;
+    let patch = patch::to_json_patch(&diffs);
+    println!("{patch}");
+    assert_eq!(patch::apply(&left, &patch)?, right);
+    Ok(())
+}
@@ -0,0 +1,22 @@
+//! Renders one comparison in the report formats.
+use json_diff_ng::prelude::*;
+
+fn main() -> Result<()> {
+    let options = CompareOptions::default().labels("expected", "actual");
+    let diffs = compare_strs_with_options(
+        r#"{"total": 12000.5, "items": [1, 2]}"#,
+        r#"{"total": 12500.25, "items": [1, 3]}"#,
+        &options,
+    )?;
+    let format = ValueFormat::default()
+        .thousands_separator(Some(','))
+        .decimals(Some(1));
+    println!("{}", report::to_markdown_with_format(&diffs, &format));
+    println!(
+        "{}",
+        report::to_json_with_options(&diffs, &JsonOptions::default().dedupe_values(true))
+    );
+    let summary: Summary = report::summary(&diffs);
+    println!("{summary:?}");
+    Ok(())
+}
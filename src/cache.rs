@@ -0,0 +1,320 @@
+//! Memoization of comparisons for test matrices comparing the same documents over and over.
+//! A [`CachingComparator`] keys its results by hashes of both inputs, and keeps the most recently used ones
+//! up to a fixed number.
+//! ```rust
+//! use json_diff_ng::cache::CachingComparator;
+//! use json_diff_ng::CompareOptions;
+//! let cache = CachingComparator::new(CompareOptions::default().build().unwrap(), 16).canonical(true);
+//! let golden = r#"{"a": 1, "b": 2}"#;
+//! let first = cache.compare_strs(golden, r#"{"a": 1, "b": 3}"#).unwrap();
+//! // same content, different formatting and key order
+//! let second = cache.compare_strs(golden, r#"{"b":3,"a":1}"#).unwrap();
+//! assert_eq!(first, second);
+//! assert_eq!(cache.len(), 1);
+//! ```
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, MutexGuard};
+
+use serde_json::Value;
+
+use crate::canonical::canonicalize;
+use crate::process::parse_input;
+use crate::{Comparator, Mismatch, Result, Side};
+
+/// Hashes of the left and the right input.
+type Key = (u64, u64);
+
+/// [`Comparator`] returning stored results for inputs it compared before.
+/// Inputs are looked up by a 64 bit hash of their bytes, serialized [`serde_json::Value`]s by their
+/// serialization, and a result is only served if its stored inputs are the same bytes. With
+/// [`CachingComparator::canonical`] inputs are hashed in canonical form instead, see [`canonicalize`].
+/// Failed comparisons are not stored. Results served from the cache do not reach the
+/// [`crate::observer::Observer`] of the options, their [`crate::prelude::ResourceUsage`] is the one of the
+/// comparison that produced them. Options holding closures, a [`CompareOptions::key_normalizer`],
+/// [`CompareOptions::filter_array_elements`] or [`CompareOptions::array_identity`], are compared every time,
+/// as nothing tells whether the closures decide the same way for the same inputs.
+///
+/// [`CompareOptions::key_normalizer`]: crate::CompareOptions::key_normalizer
+/// [`CompareOptions::filter_array_elements`]: crate::CompareOptions::filter_array_elements
+/// [`CompareOptions::array_identity`]: crate::CompareOptions::array_identity
+#[derive(Debug)]
+pub struct CachingComparator {
+    comparator: Comparator,
+    capacity: usize,
+    canonical: bool,
+    /// Whether the options hold no closures
+    cacheable: bool,
+    entries: Mutex<Entries>,
+}
+
+/// Stored results with their last use, and the keys by last use to find the least recently used one.
+#[derive(Debug, Default)]
+struct Entries {
+    results: BTreeMap<Key, Entry>,
+    by_use: BTreeMap<u64, Key>,
+    tick: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    last_use: u64,
+    /// Bytes of the left and the right input, to tell inputs of the same hashes apart
+    inputs: (Vec<u8>, Vec<u8>),
+    mismatch: Mismatch,
+}
+
+impl CachingComparator {
+    /// Wraps `comparator`, keeping up to `capacity` results. A capacity of zero stores nothing.
+    pub fn new(comparator: Comparator, capacity: usize) -> Self {
+        let options = comparator.options();
+        let cacheable = options.key_normalizer.is_none()
+            && options.array_element_filter.is_none()
+            && options.array_identity.is_none();
+        Self {
+            comparator,
+            capacity,
+            canonical: false,
+            cacheable,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Hash inputs in canonical form, so documents differing only in formatting and key order share results.
+    /// The shared result is the one of the first comparison, which reports values as formatted in its inputs.
+    pub fn canonical(mut self, canonical: bool) -> Self {
+        self.canonical = canonical;
+        self
+    }
+
+    /// The wrapped comparator.
+    pub fn comparator(&self) -> &Comparator {
+        &self.comparator
+    }
+
+    /// Number of stored results.
+    pub fn len(&self) -> usize {
+        self.lock().results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops all stored results.
+    pub fn clear(&self) {
+        *self.lock() = Entries::default();
+    }
+
+    /// Same as [`Comparator::compare`], served from the cache for inputs compared before.
+    pub fn compare(&self, a: &Value, b: &Value) -> Result<Mismatch> {
+        if !self.cacheable {
+            return self.comparator.compare(a, b);
+        }
+        let (a_bytes, b_bytes) = (self.serialize(a)?, self.serialize(b)?);
+        self.cached(&a_bytes, &b_bytes, || self.comparator.compare(a, b))
+    }
+
+    /// Same as [`Comparator::compare_strs`], served from the cache for inputs compared before.
+    pub fn compare_strs(&self, a: &str, b: &str) -> Result<Mismatch> {
+        self.compare_slices(a.as_bytes(), b.as_bytes())
+    }
+
+    /// Same as [`Comparator::compare_slices`], served from the cache for inputs compared before.
+    /// Without [`CachingComparator::canonical`] the bytes are hashed as they are, without parsing them on a hit.
+    pub fn compare_slices(&self, a: &[u8], b: &[u8]) -> Result<Mismatch> {
+        if !self.canonical || !self.cacheable {
+            return self.cached(a, b, || self.comparator.compare_slices(a, b));
+        }
+        let max_depth = self.comparator.options().max_depth;
+        let a = parse_input(a, Side::Left, max_depth)?;
+        let b = parse_input(b, Side::Right, max_depth)?;
+        self.compare(&a, &b)
+    }
+
+    fn serialize(&self, value: &Value) -> Result<Vec<u8>> {
+        Ok(match self.canonical {
+            true => serde_json::to_vec(&canonicalize(value))?,
+            false => serde_json::to_vec(value)?,
+        })
+    }
+
+    /// The stored result for the inputs `a` and `b`, or the one of `compare` which is stored.
+    fn cached(
+        &self,
+        a: &[u8],
+        b: &[u8],
+        compare: impl FnOnce() -> Result<Mismatch>,
+    ) -> Result<Mismatch> {
+        if !self.cacheable || self.capacity == 0 {
+            return compare();
+        }
+        let key = (hash(a), hash(b));
+        if let Some(mismatch) = self.lock().get(key, a, b) {
+            return Ok(mismatch);
+        }
+        // compared without holding the lock, concurrent misses of the same key compare twice
+        let mismatch = compare()?;
+        let entry = Entry {
+            last_use: 0,
+            inputs: (a.to_vec(), b.to_vec()),
+            mismatch: mismatch.clone(),
+        };
+        self.lock().insert(key, entry, self.capacity);
+        Ok(mismatch)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Entries {
+    fn touch(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    /// The result stored for `key` if it was compared from the inputs `a` and `b`.
+    fn get(&mut self, key: Key, a: &[u8], b: &[u8]) -> Option<Mismatch> {
+        let tick = self.touch();
+        let entry = self.results.get_mut(&key)?;
+        if entry.inputs.0 != a || entry.inputs.1 != b {
+            return None;
+        }
+        self.by_use.remove(&entry.last_use);
+        self.by_use.insert(tick, key);
+        entry.last_use = tick;
+        Some(entry.mismatch.clone())
+    }
+
+    /// Stores `entry`, replacing any other one of the same `key`.
+    fn insert(&mut self, key: Key, mut entry: Entry, capacity: usize) {
+        entry.last_use = self.touch();
+        let tick = entry.last_use;
+        if let Some(replaced) = self.results.insert(key, entry) {
+            self.by_use.remove(&replaced.last_use);
+        }
+        self.by_use.insert(tick, key);
+        while self.results.len() > capacity {
+            let Some((_, oldest)) = self.by_use.pop_first() else {
+                break;
+            };
+            self.results.remove(&oldest);
+        }
+    }
+}
+
+fn hash(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::observer::{ObservedEvent, RecordingObserver};
+    use crate::CompareOptions;
+
+    fn observed_cache(capacity: usize) -> (Arc<RecordingObserver>, CachingComparator) {
+        let observer = Arc::new(RecordingObserver::default());
+        let options = CompareOptions::default().observer(observer.clone());
+        let cache = CachingComparator::new(options.build().unwrap(), capacity);
+        (observer, cache)
+    }
+
+    fn comparisons(observer: &RecordingObserver) -> usize {
+        observer
+            .events()
+            .iter()
+            .filter(|event| **event == ObservedEvent::Start)
+            .count()
+    }
+
+    #[test]
+    fn repeated_pairs_skip_the_comparison() {
+        let (observer, cache) = observed_cache(4);
+        let (golden, actual) = (json!({"a": [1, 2], "b": 1}), json!({"a": [1, 3], "b": 1}));
+        let first = cache.compare(&golden, &actual).unwrap();
+        let second = cache.compare(&golden, &actual.clone()).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(comparisons(&observer), 1);
+        assert_eq!(first, cache.comparator().compare(&golden, &actual).unwrap());
+        // swapped sides are another pair
+        cache.compare(&actual, &golden).unwrap();
+        assert_eq!(comparisons(&observer), 3);
+    }
+
+    #[test]
+    fn formatting_only_hits_when_canonical() {
+        let (golden, reformatted) = (r#"{"a": 1, "b": [2]}"#, "{\"b\":[2],\n\"a\":1}");
+        let (observer, cache) = observed_cache(4);
+        cache.compare_strs(golden, golden).unwrap();
+        cache.compare_strs(golden, reformatted).unwrap();
+        assert_eq!(comparisons(&observer), 2);
+        let (observer, cache) = observed_cache(4);
+        let cache = cache.canonical(true);
+        let first = cache.compare_strs(golden, golden).unwrap();
+        let second = cache.compare_strs(golden, reformatted).unwrap();
+        assert_eq!(comparisons(&observer), 1);
+        assert_eq!(first, second);
+        assert!(cache.compare_strs(golden, "{").is_err());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn least_recently_used_results_are_evicted() {
+        let (observer, cache) = observed_cache(2);
+        let values: Vec<Value> = (0..3).map(|i| json!({ "v": i })).collect();
+        cache.compare(&values[0], &values[0]).unwrap();
+        cache.compare(&values[0], &values[1]).unwrap();
+        // refresh the first pair, so the second one is the oldest
+        cache.compare(&values[0], &values[0]).unwrap();
+        cache.compare(&values[0], &values[2]).unwrap();
+        assert_eq!((cache.len(), comparisons(&observer)), (2, 3));
+        cache.compare(&values[0], &values[0]).unwrap();
+        assert_eq!(comparisons(&observer), 3);
+        cache.compare(&values[0], &values[1]).unwrap();
+        assert_eq!(comparisons(&observer), 4);
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn results_are_only_served_for_the_same_inputs() {
+        let mut entries = Entries::default();
+        let mismatch = Mismatch::empty();
+        let entry = Entry {
+            last_use: 0,
+            inputs: (b"1".to_vec(), b"2".to_vec()),
+            mismatch: mismatch.clone(),
+        };
+        // inputs of the same hashes
+        entries.insert((7, 7), entry, 4);
+        assert_eq!(entries.get((7, 7), b"1", b"3"), None);
+        assert_eq!(entries.get((7, 7), b"1", b"2"), Some(mismatch));
+    }
+
+    #[test]
+    fn options_with_closures_are_compared_every_time() {
+        let observer = Arc::new(RecordingObserver::default());
+        let options = CompareOptions::default()
+            .observer(observer.clone())
+            .key_normalizer(|key: &str| key.to_lowercase());
+        let cache = CachingComparator::new(options.build().unwrap(), 4);
+        let (left, right) = (json!({"A": 1}), json!({"a": 2}));
+        let first = cache.compare(&left, &right).unwrap();
+        assert_eq!(first, cache.compare(&left, &right).unwrap());
+        cache.compare_strs("[1]", "[2]").unwrap();
+        assert_eq!(comparisons(&observer), 3);
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,54 @@
+//! Canonical formatting of json documents, e.g. for golden files under version control.
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde_json::Value;
+
+use crate::Result;
+
+/// Returns a copy of `value` with the keys of all objects sorted, array order is kept.
+pub fn canonicalize(value: &Value) -> Value {
+    match value {
+        Value::Array(a) => Value::Array(a.iter().map(canonicalize).collect()),
+        Value::Object(o) => {
+            let mut entries: Vec<_> = o.iter().collect();
+            entries.sort_by_key(|(k, _)| *k);
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k.clone(), canonicalize(v)))
+                    .collect(),
+            )
+        }
+        v => v.clone(),
+    }
+}
+
+/// Serializes `value` canonically: sorted keys, pretty-printed with two spaces and a trailing newline.
+/// Documents equal in content always serialize to the same string.
+pub fn to_canonical_string(value: &Value) -> Result<String> {
+    let mut serialized = serde_json::to_string_pretty(&canonicalize(value))?;
+    serialized.push('\n');
+    Ok(serialized)
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn canonical_string_sorts_keys_only() {
+        let value = json!({"b": [3, 1, {"z": 1, "a": 2}], "a": null});
+        assert_eq!(
+            to_canonical_string(&value).unwrap(),
+            "{\n  \"a\": null,\n  \"b\": [\n    3,\n    1,\n    {\n      \"a\": 2,\n      \"z\": 1\n    }\n  ]\n}\n"
+        );
+        let reordered = json!({"a": null, "b": [3, 1, {"a": 2, "z": 1}]});
+        assert_eq!(
+            to_canonical_string(&value).unwrap(),
+            to_canonical_string(&reordered).unwrap()
+        );
+    }
+}
@@ -0,0 +1,150 @@
+#![doc = include_str!("../docs/cookbook.md")]
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde_json::Value;
+
+use crate::mismatch::Verdict;
+use crate::pointer::{extract, is_within};
+use crate::{DiffPath, DiffRecord, DiffType, Mismatch, Side};
+
+/// Which diffs fail a [`ci_gate`]. By default every diff that is neither informational nor
+/// [expected](Mismatch::is_expected) fails, like [`Mismatch::verdict`] does.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct GatePolicy {
+    /// Diff types that fail the gate, all types if empty
+    pub fail_on: Vec<DiffType>,
+    /// JSON pointers below which diffs are tolerated
+    pub allowed: Vec<String>,
+    /// Number of failing diffs tolerated before the gate fails
+    pub max_failures: usize,
+}
+
+impl GatePolicy {
+    pub fn fail_on(mut self, diff_types: impl IntoIterator<Item = DiffType>) -> Self {
+        self.fail_on = diff_types.into_iter().collect();
+        self
+    }
+
+    pub fn allow(mut self, pointer: impl Into<String>) -> Self {
+        self.allowed.push(pointer.into());
+        self
+    }
+
+    pub fn max_failures(mut self, max_failures: usize) -> Self {
+        self.max_failures = max_failures;
+        self
+    }
+
+    /// Whether `record` of `mismatch` counts against the gate.
+    pub fn fails(&self, mismatch: &Mismatch, record: &DiffRecord) -> bool {
+        let pointer = record.path.to_pointer();
+        !record.is_informational()
+            && !mismatch.is_expected(record)
+            && (self.fail_on.is_empty() || self.fail_on.contains(&record.diff_type))
+            && !self
+                .allowed
+                .iter()
+                .any(|allowed| is_within(&pointer, allowed))
+    }
+}
+
+/// Outcome of a [`ci_gate`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct GateOutcome {
+    pub verdict: Verdict,
+    /// Records counting against the gate, see [`GatePolicy::fails`]
+    pub failures: Vec<DiffRecord>,
+    /// Number of diffs tolerated by the policy
+    pub tolerated: usize,
+}
+
+/// Checks `mismatch` against `policy`. A partial comparison, see [`Mismatch::is_partial`], never passes.
+pub fn ci_gate(mismatch: &Mismatch, policy: &GatePolicy) -> GateOutcome {
+    let (failures, tolerated): (Vec<DiffRecord>, Vec<DiffRecord>) = mismatch
+        .records()
+        .into_iter()
+        .partition(|record| policy.fails(mismatch, record));
+    let failed = failures.iter().map(DiffRecord::count).sum::<usize>();
+    let verdict = match mismatch.is_partial() || failed > policy.max_failures {
+        true => Verdict::Fail,
+        false => Verdict::Pass,
+    };
+    GateOutcome {
+        verdict,
+        failures,
+        tolerated: tolerated.iter().map(DiffRecord::count).sum(),
+    }
+}
+
+/// Reported path of each diff of `mismatch` with the values it refers to in the original `left` and `right`
+/// documents, `None` on a side the value does not exist on. Paths are translated with
+/// [`Mismatch::paths_for_side`], so sorted or filtered arrays resolve to the elements they were taken from.
+pub fn resolve_all<'a>(
+    mismatch: &Mismatch,
+    left: &'a Value,
+    right: &'a Value,
+) -> Vec<(DiffPath, Option<&'a Value>, Option<&'a Value>)> {
+    let resolve = |side: Side, document: &'a Value| {
+        mismatch
+            .paths_for_side(side)
+            .into_iter()
+            .map(move |(_, side_path)| match side_path.exists {
+                true => extract(document, &side_path.path.to_pointer()).ok(),
+                false => None,
+            })
+    };
+    mismatch
+        .records()
+        .into_iter()
+        .zip(resolve(Side::Left, left).zip(resolve(Side::Right, right)))
+        .map(|(record, (l, r))| (record.path, l, r))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+    use crate::compare_serde_values;
+
+    #[test]
+    fn gate_counts_failures_per_policy() {
+        let left = json!({"a": 1, "meta": {"build": 1}, "gone": true});
+        let right = json!({"a": 2, "meta": {"build": 2}});
+        let diffs = compare_serde_values(&left, &right, false, &[]).unwrap();
+        let strict = ci_gate(&diffs, &GatePolicy::default());
+        assert_eq!((strict.verdict, strict.failures.len()), (Verdict::Fail, 3));
+        let policy = GatePolicy::default()
+            .fail_on([DiffType::Mismatch])
+            .allow("/meta");
+        let outcome = ci_gate(&diffs, &policy);
+        assert_eq!(outcome.failures.len(), 1);
+        assert_eq!(outcome.tolerated, 2);
+        assert_eq!(
+            ci_gate(&diffs, &policy.max_failures(1)).verdict,
+            Verdict::Pass
+        );
+    }
+
+    #[test]
+    fn resolve_all_reads_the_original_documents() {
+        let left = json!({"list": [3, 1, 2], "gone": true});
+        let right = json!({"list": [4, 1, 2]});
+        let diffs = compare_serde_values(&left, &right, true, &[]).unwrap();
+        let resolved: Vec<(String, Option<&Value>, Option<&Value>)> =
+            resolve_all(&diffs, &left, &right)
+                .into_iter()
+                .map(|(path, l, r)| (path.to_string(), l, r))
+                .collect();
+        assert_eq!(
+            resolved,
+            vec![
+                (".list.[2]".to_string(), Some(&json!(3)), Some(&json!(4))),
+                (".gone".to_string(), Some(&json!(true)), None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use serde_json::Number;
+
+/// A decimal number as sign, significant digits without leading or trailing zeros, and the exponent of the last digit.
+#[derive(Debug, PartialEq, Eq)]
+struct Decimal {
+    negative: bool,
+    digits: Vec<u8>,
+    exponent: i64,
+}
+
+/// Whether `a` and `b` are equal after rounding their decimal representations half away from zero to `places`
+/// fractional digits. The digits are rounded as text, so no precision is lost to binary floating point in the rounding.
+/// Without the `arbitrary_precision` feature `serde_json` parses numbers beyond `f64` precision to their nearest `f64`,
+/// which is what is rounded then.
+pub(crate) fn equal_at(a: &Number, b: &Number, places: u32) -> bool {
+    match (
+        rounded(&a.to_string(), places),
+        rounded(&b.to_string(), places),
+    ) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Whether `a` and `b` differ by at most `absolute`, or by at most `relative` times the larger of their magnitudes.
+/// Pairs of integers are compared exactly, integers too large for `i64` and `u64` are floats already.
+pub(crate) fn within_tolerance(a: &Number, b: &Number, absolute: f64, relative: f64) -> bool {
+    if !a.is_f64() && !b.is_f64() {
+        return a == b;
+    }
+    match (a.as_f64(), b.as_f64()) {
+        (Some(x), Some(y)) => {
+            let difference = (x - y).abs();
+            difference <= absolute || difference <= relative * x.abs().max(y.abs())
+        }
+        _ => a == b,
+    }
+}
+
+/// Parses `text` like `-12.5e3` and rounds it to `places` fractional digits, `None` if it is no number.
+fn rounded(text: &str, places: u32) -> Option<Decimal> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().ok()?),
+        None => (text, 0),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let mut digits = Vec::with_capacity(integer.len() + fraction.len());
+    for c in integer.chars().chain(fraction.chars()) {
+        digits.push(c.to_digit(10)? as u8);
+    }
+    if digits.is_empty() {
+        return None;
+    }
+    let mut exponent = exponent.checked_sub(i64::try_from(fraction.len()).ok()?)?;
+    let scale = -i64::from(places);
+    if exponent < scale {
+        let dropped = usize::try_from(scale - exponent).ok()?;
+        let kept = digits.len().saturating_sub(dropped);
+        let round_up = dropped <= digits.len() && digits[kept] >= 5;
+        digits.truncate(kept);
+        if round_up {
+            increment(&mut digits);
+        }
+        exponent = scale;
+    }
+    while digits.last() == Some(&0) {
+        digits.pop();
+        exponent += 1;
+    }
+    let leading_zeros = digits.iter().take_while(|d| **d == 0).count();
+    digits.drain(..leading_zeros);
+    if digits.is_empty() {
+        return Some(Decimal {
+            negative: false,
+            digits,
+            exponent: 0,
+        });
+    }
+    Some(Decimal {
+        negative,
+        digits,
+        exponent,
+    })
+}
+
+/// Adds one to the last digit, carrying over.
+fn increment(digits: &mut Vec<u8>) {
+    for digit in digits.iter_mut().rev() {
+        if *digit < 9 {
+            *digit += 1;
+            return;
+        }
+        *digit = 0;
+    }
+    digits.insert(0, 1);
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{compare_strs_with_options, CompareOptions};
+
+    fn number(value: serde_json::Value) -> Number {
+        match value {
+            serde_json::Value::Number(n) => n,
+            other => panic!("expected a number, got {other}"),
+        }
+    }
+
+    #[test]
+    fn tolerances_apply_to_floats_only() {
+        let within =
+            |a, b, absolute, relative| within_tolerance(&number(a), &number(b), absolute, relative);
+        assert!(within(json!(0.30000000000000004), json!(0.3), 1e-9, 0.0));
+        assert!(within(json!(1000.0), json!(1001.0), 0.0, 1e-3));
+        assert!(!within(json!(1000.0), json!(1002.0), 1.0, 1e-3));
+        assert!(within(json!(1), json!(1.0000001), 1e-5, 0.0));
+        assert!(!within(json!(1), json!(2), 5.0, 0.0));
+        assert!(within(json!(1e300), json!(1.0000001e300), 0.0, 1e-6));
+    }
+
+    #[test]
+    fn rounds_half_away_from_zero() {
+        let equal = |a, b, places| equal_at(&number(a), &number(b), places);
+        assert!(equal(json!(10.004), json!(10.0049), 2));
+        assert!(!equal(json!(10.004), json!(10.006), 2));
+        assert!(equal(json!(0.995), json!(1), 2));
+        assert!(equal(json!(-0.004), json!(0), 2));
+        assert!(equal(json!(-2.345), json!(-2.35), 2));
+        assert!(equal(json!(0.1 + 0.2), json!(0.3), 2));
+        assert!(equal(json!(1250), json!(1250.0), 0));
+        assert!(!equal(json!(1e20), json!(1.00000000000001e20), 0));
+    }
+
+    #[test]
+    fn exponents_and_large_numbers_are_exact() {
+        assert_eq!(rounded("1.5e3", 0), rounded("1500", 0));
+        assert_eq!(rounded("25e-3", 2), rounded("0.03", 2));
+        assert_eq!(rounded("4e-3", 2), rounded("0", 2));
+        // 2^53 + 1 is not representable as f64
+        assert!(!equal_at(
+            &number(json!(9007199254740993u64)),
+            &number(json!(9007199254740992u64)),
+            2
+        ));
+        assert_eq!(
+            rounded("123456789012345678901234567890.125", 2),
+            rounded("123456789012345678901234567890.13", 2)
+        );
+        assert_ne!(
+            rounded("123456789012345678901234567890.12", 2),
+            rounded("123456789012345678901234567891.12", 2)
+        );
+    }
+
+    #[test]
+    fn parsed_numbers_beyond_f64_precision_need_arbitrary_precision() {
+        let options = CompareOptions::default().decimal_places(2);
+        let equal = |a, b| {
+            compare_strs_with_options(a, b, &options)
+                .unwrap()
+                .is_empty()
+        };
+        let (a, b) = (
+            "123456789012345678901234567890.12",
+            "123456789012345678901234567891.12",
+        );
+        // without the feature both parse to the same f64
+        assert_eq!(equal(a, b), !cfg!(feature = "arbitrary_precision"));
+        assert!(equal("10.004", "10.0049"));
+        assert!(!equal("10.004", "10.006"));
+    }
+
+    #[cfg(feature = "arbitrary_precision")]
+    #[test]
+    fn decimals_beyond_f64_precision_round_exactly() {
+        let options = CompareOptions::default().decimal_places(2);
+        let equal = |a, b| {
+            compare_strs_with_options(a, b, &options)
+                .unwrap()
+                .is_empty()
+        };
+        assert!(equal(
+            "123456789012345678901234567890.125",
+            "123456789012345678901234567890.13"
+        ));
+        assert!(!equal(
+            "123456789012345678901234567890.124",
+            "123456789012345678901234567890.13"
+        ));
+        assert!(!equal("9007199254740993", "9007199254740992"));
+        let diffs = compare_strs_with_options(
+            "[0.123456789012345678901]",
+            "[0.123456789012345678902]",
+            &CompareOptions::default().decimal_places(21),
+        )
+        .unwrap();
+        assert_eq!(
+            diffs.all_diffs()[0].1.to_string(),
+            ".[0].(0.123456789012345678901 != 0.123456789012345678902)"
+        );
+    }
+}
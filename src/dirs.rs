@@ -0,0 +1,184 @@
+//! Comparison of two directory trees of json files, pairing files by their path relative to the roots.
+//! With the `parallel` feature the pairs are compared on the rayon thread pool.
+//! ```rust
+//! use json_diff_ng::{dirs::compare_dirs, CompareOptions};
+//! let root = std::env::temp_dir().join(format!("json_diff_ng_doc_{}", std::process::id()));
+//! for side in ["left", "right"] {
+//!     std::fs::create_dir_all(root.join(side)).unwrap();
+//!     std::fs::write(root.join(side).join("a.json"), r#"{"a": 1}"#).unwrap();
+//! }
+//! let report = compare_dirs(root.join("left"), root.join("right"), &CompareOptions::default()).unwrap();
+//! assert!(report.is_empty());
+//! std::fs::remove_dir_all(root).unwrap();
+//! ```
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use vg_errortools::FatIOError;
+
+use crate::process::Comparator;
+use crate::{CompareOptions, Error, Mismatch, Result};
+
+/// Outcome of all file pairs of a directory comparison, ordered by relative path.
+#[derive(Debug)]
+pub struct DirReport {
+    pub entries: Vec<DirEntry>,
+}
+
+/// Outcome for one relative path of a directory comparison.
+#[derive(Debug)]
+pub struct DirEntry {
+    /// Path of the file relative to both roots
+    pub path: PathBuf,
+    pub outcome: FileOutcome,
+}
+
+/// What comparing the files at one relative path yielded.
+#[derive(Debug)]
+pub enum FileOutcome {
+    /// Both files were compared, the mismatch may be empty
+    Compared(Box<Mismatch>),
+    /// The file exists below the left root only
+    LeftOnly,
+    /// The file exists below the right root only
+    RightOnly,
+    /// Reading or parsing either file failed, the other files are compared regardless
+    Failed(Error),
+}
+
+impl DirReport {
+    /// Whether both trees hold the same files with equal contents.
+    pub fn is_empty(&self) -> bool {
+        self.entries
+            .iter()
+            .all(|entry| matches!(&entry.outcome, FileOutcome::Compared(m) if m.is_empty()))
+    }
+
+    /// Entries of the files that differ, exist on one side only or failed.
+    pub fn differing(&self) -> impl Iterator<Item = &DirEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| !matches!(&entry.outcome, FileOutcome::Compared(m) if m.is_empty()))
+    }
+}
+
+/// Compares all `.json` files below `left` and `right`, pairing them by relative path.
+/// Only failing to list either tree is an error, failures of single files are reported in their [`DirEntry`].
+pub fn compare_dirs(
+    left: impl AsRef<Path>,
+    right: impl AsRef<Path>,
+    options: &CompareOptions,
+) -> Result<DirReport> {
+    let (left, right) = (left.as_ref(), right.as_ref());
+    let comparator = options.build()?;
+    let left_files = json_files(left)?;
+    let right_files = json_files(right)?;
+    let paths: Vec<&PathBuf> = left_files.union(&right_files).collect();
+    let compare = |path: &&PathBuf| DirEntry {
+        path: path.to_path_buf(),
+        outcome: match (left_files.contains(*path), right_files.contains(*path)) {
+            (true, true) => compare_files(&comparator, &left.join(path), &right.join(path)),
+            (true, false) => FileOutcome::LeftOnly,
+            _ => FileOutcome::RightOnly,
+        },
+    };
+    // both variants keep the order of the sorted paths, however the comparisons finish
+    #[cfg(feature = "parallel")]
+    let entries = {
+        use rayon::prelude::*;
+        paths.par_iter().map(compare).collect()
+    };
+    #[cfg(not(feature = "parallel"))]
+    let entries = paths.iter().map(compare).collect();
+    Ok(DirReport { entries })
+}
+
+fn compare_files(comparator: &Comparator, left: &Path, right: &Path) -> FileOutcome {
+    match comparator.compare_files(left, right) {
+        Ok(mismatch) => FileOutcome::Compared(Box::new(mismatch)),
+        Err(e) => FileOutcome::Failed(e),
+    }
+}
+
+/// Paths of all files with a `.json` extension below `root`, relative to it.
+fn json_files(root: &Path) -> Result<BTreeSet<PathBuf>> {
+    let mut files = BTreeSet::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let io_error = |e| Error::from(FatIOError::from_std_io_err(e, dir.clone()));
+        for entry in std::fs::read_dir(&dir).map_err(io_error)? {
+            let path = entry.map_err(io_error)?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|e| e == "json") {
+                if let Ok(relative) = path.strip_prefix(root) {
+                    files.insert(relative.to_path_buf());
+                }
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::TempDir;
+
+    use crate::ErrorCode;
+
+    use super::*;
+
+    fn write(root: &Path, path: &str, contents: &str) {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn trees_with_all_kinds_of_files() {
+        let (left, right) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        for root in [left.path(), right.path()] {
+            write(root, "equal.json", r#"{"a": [1, 2]}"#);
+            write(root, "notes.txt", "not compared");
+        }
+        write(left.path(), "nested/differs.json", r#"{"a": 1}"#);
+        write(right.path(), "nested/differs.json", r#"{"a": 2}"#);
+        write(left.path(), "gone.json", "{}");
+        write(right.path(), "added/new.json", "[]");
+        write(left.path(), "broken.json", "[]");
+        write(right.path(), "broken.json", "{");
+
+        let report = compare_dirs(left.path(), right.path(), &CompareOptions::default()).unwrap();
+        assert!(!report.is_empty());
+        let paths: Vec<_> = report.entries.iter().map(|e| e.path.clone()).collect();
+        let expected: Vec<PathBuf> = [
+            "added/new.json",
+            "broken.json",
+            "equal.json",
+            "gone.json",
+            "nested/differs.json",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+        assert_eq!(paths, expected);
+        let outcomes: Vec<_> = report.entries.iter().map(|e| &e.outcome).collect();
+        assert!(matches!(outcomes[0], FileOutcome::RightOnly));
+        assert!(matches!(outcomes[1], FileOutcome::Failed(e) if e.code() == ErrorCode::ParseRight));
+        assert!(matches!(outcomes[2], FileOutcome::Compared(m) if m.is_empty()));
+        assert!(matches!(outcomes[3], FileOutcome::LeftOnly));
+        let FileOutcome::Compared(differs) = outcomes[4] else {
+            panic!("expected a comparison, got {:?}", outcomes[4]);
+        };
+        assert_eq!(differs.all_diffs()[0].1.to_string(), ".a.(1 != 2)");
+        assert_eq!(report.differing().count(), 4);
+    }
+
+    #[test]
+    fn missing_root_is_an_error() {
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("missing");
+        let err = compare_dirs(dir.path(), &missing, &CompareOptions::default()).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::Io);
+    }
+}
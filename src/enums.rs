@@ -1,20 +1,461 @@
-use std::collections::HashMap;
-use std::fmt::{Display, Formatter};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use core::str::FromStr;
 
+use serde::de::Error as _;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value;
-use thiserror::Error;
+#[cfg(feature = "std")]
 use vg_errortools::FatIOError;
 
-#[derive(Debug, Error)]
+use crate::mismatch::compact_len;
+use crate::normalize::{escape_invisible, write_visible};
+use crate::process::OptionConflict;
+use crate::string_diff::StringDiff;
+
+/// Errors of this crate. Errors are not `Clone`, as parse and io errors wrap errors that are not either.
+/// They compare equal if their [`Error::kind`] and [`Error::context`] are equal, regardless of messages and wrapped errors.
+#[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
-    #[error("Misc error: {0}")]
     Misc(String),
-    #[error("Error opening file: {0}")]
-    IOError(#[from] FatIOError),
-    #[error("Error parsing first json: {0}")]
-    JSON(#[from] serde_json::Error),
-    #[error("Regex compilation error: {0}")]
-    Regex(#[from] regex::Error),
+    #[cfg(feature = "std")]
+    IOError(FatIOError),
+    JSON(serde_json::Error),
+    ParseLeft(serde_json::Error),
+    ParseRight(serde_json::Error),
+    Regex(regex::Error),
+    DepthLimitExceeded(usize),
+    KeyCollision(String, String, String),
+    InvalidPointer(String),
+    PointerNotFound(String, String),
+    /// The pointer of [`crate::CompareOptions::unwrap_left`] or [`crate::CompareOptions::unwrap_right`]
+    /// does not resolve in the document of the side, with the nearest existing ancestor
+    UnwrapNotFound(Side, String, String),
+    ParseDocument(Side, usize, serde_json::Error),
+    Structure(String),
+    InvalidPatch(String),
+    InvalidMapping(String),
+    InvalidReport(String),
+    /// Rules of a [`crate::report::Classifier`] read from json are malformed
+    InvalidClassifier(String),
+    InvalidOptions(Vec<OptionConflict>),
+    /// A table of [`crate::tabular::compare_csv`] is malformed or lacks a named column
+    InvalidTable(Side, String),
+    /// Paths of a comparison cannot be resolved in the original left document, see [`crate::Mismatch::resolve_entry`]
+    RequiresNormalizedDocument,
+    /// An input could not be read from git, e.g. outside of a repository or for an unknown revision
+    Git(String),
+    /// An input of `actual` bytes exceeds [`crate::CompareOptions::max_input_bytes`]
+    InputTooLarge {
+        actual: u64,
+        limit: u64,
+    },
+    /// The root of the document of `side` is neither an object nor an array,
+    /// see [`crate::CompareOptions::require_container_roots`]
+    UnexpectedRootType {
+        side: Side,
+        found: ValueKind,
+    },
+    /// A snapshot of a prepared document is damaged or was prepared with other options,
+    /// see `snapshot::PreparedDocument::load`
+    InvalidSnapshot(String),
+    /// Accepted paths of [`crate::prelude::apply_selected`] that are no diffs or do not resolve in the document
+    UnresolvedSelection(Vec<DiffPath>),
+    /// The operation at an index of a [`crate::transform::Transform`] is malformed or does not apply
+    InvalidTransform(usize, String),
+    /// Internal `$ref` pointers refer to each other in a cycle, listed from its first reference back to it,
+    /// see [`crate::CompareOptions::resolve_internal_refs`]
+    RefCycle(Vec<String>),
+    /// Expanding internal `$ref` pointers produces more values than the limit,
+    /// see [`crate::CompareOptions::max_resolved_values`]
+    RefExpansionTooLarge(usize),
+    /// An error with the file, side or JSON pointer it concerns, see [`Error::with_context`]
+    Context(Box<Error>, ErrorContext),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Misc(e) => write!(f, "Misc error: {e}"),
+            #[cfg(feature = "std")]
+            Error::IOError(e) => write!(f, "Error opening file: {e}"),
+            Error::JSON(e) => write!(f, "Error parsing json: {e}{}", depth_hint(e)),
+            Error::ParseLeft(e) => write!(f, "Error parsing left json: {e}{}", depth_hint(e)),
+            Error::ParseRight(e) => write!(f, "Error parsing right json: {e}{}", depth_hint(e)),
+            Error::Regex(e) => write!(f, "Regex compilation error: {e}"),
+            Error::DepthLimitExceeded(depth) => {
+                write!(f, "Input nesting exceeds the maximum depth of {depth}")
+            }
+            Error::KeyCollision(first, second, normalized) => write!(
+                f,
+                "Keys {first:?} and {second:?} of one object both normalize to {normalized:?}"
+            ),
+            Error::InvalidPointer(pointer) => write!(
+                f,
+                "Invalid JSON pointer {pointer}, pointers must be empty or start with '/'"
+            ),
+            Error::PointerNotFound(pointer, ancestor) => {
+                write!(
+                    f,
+                    "JSON pointer {pointer} does not resolve, nearest existing ancestor is {}",
+                    ancestor_name(ancestor)
+                )
+            }
+            Error::UnwrapNotFound(side, pointer, ancestor) => write!(
+                f,
+                "Cannot unwrap the {side} document at {pointer}, nearest existing ancestor is {}",
+                ancestor_name(ancestor)
+            ),
+            Error::ParseDocument(side, index, e) => {
+                write!(
+                    f,
+                    "Error parsing {side} document {index}: {e}{}",
+                    depth_hint(e)
+                )
+            }
+            Error::Structure(e) => write!(f, "Incoherent diff structure: {e}"),
+            Error::InvalidPatch(e) => write!(f, "Invalid JSON patch: {e}"),
+            Error::InvalidMapping(e) => write!(f, "Invalid key mapping: {e}"),
+            Error::InvalidReport(e) => write!(f, "Invalid report: {e}"),
+            Error::InvalidClassifier(e) => write!(f, "Invalid classifier: {e}"),
+            Error::InvalidOptions(conflicts) => {
+                write!(f, "Invalid options: {}", join_conflicts(conflicts))
+            }
+            Error::InvalidTable(side, e) => write!(f, "Invalid {side} table: {e}"),
+            Error::RequiresNormalizedDocument => write!(
+                f,
+                "Paths of the comparison only resolve in the unwrapped or transformed left document that was compared"
+            ),
+            Error::Git(e) => write!(f, "Cannot read input from git: {e}"),
+            Error::InputTooLarge { actual, limit } => write!(
+                f,
+                "Input of {actual} bytes exceeds the maximum input size of {limit} bytes"
+            ),
+            Error::UnexpectedRootType { side, found } => write!(
+                f,
+                "The {side} document is a {}, not an object or array",
+                found.type_name()
+            ),
+            Error::InvalidSnapshot(e) => write!(f, "Invalid snapshot: {e}"),
+            Error::InvalidTransform(index, e) => {
+                write!(f, "Invalid transformation: operation {index}: {e}")
+            }
+            Error::RefCycle(cycle) => write!(f, "Cyclic $ref: {}", cycle.join(" -> ")),
+            Error::RefExpansionTooLarge(limit) => {
+                write!(f, "Expanding $ref pointers exceeds the maximum of {limit} values")
+            }
+            Error::UnresolvedSelection(paths) => {
+                let paths: Vec<String> = paths.iter().map(ToString::to_string).collect();
+                write!(f, "Selected diffs do not resolve: {}", paths.join(", "))
+            }
+            Error::Context(e, _) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            Error::IOError(e) => Some(e),
+            #[cfg(feature = "std")]
+            Error::Regex(e) => Some(e),
+            Error::JSON(e) => Some(e),
+            Error::Context(e, _) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<FatIOError> for Error {
+    fn from(value: FatIOError) -> Self {
+        Self::IOError(value)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(value: serde_json::Error) -> Self {
+        Self::JSON(value)
+    }
+}
+
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind() == other.kind() && self.context() == other.context()
+    }
+}
+
+impl From<regex::Error> for Error {
+    fn from(value: regex::Error) -> Self {
+        Self::Regex(value)
+    }
+}
+
+/// Suggests the `unbounded_depth` feature for errors of `serde_json`'s recursion limit, which it lifts.
+fn depth_hint(e: &serde_json::Error) -> &'static str {
+    match !cfg!(feature = "unbounded_depth") && e.to_string().starts_with("recursion limit exceeded") {
+        true => ", enable the unbounded_depth feature of json_diff_ng to parse inputs nested deeper than 128 levels",
+        false => "",
+    }
+}
+
+fn ancestor_name(ancestor: &str) -> &str {
+    match ancestor.is_empty() {
+        true => "the document root",
+        false => ancestor,
+    }
+}
+
+fn join_conflicts(conflicts: &[OptionConflict]) -> String {
+    let conflicts: Vec<String> = conflicts.iter().map(ToString::to_string).collect();
+    conflicts.join("; ")
+}
+
+impl Error {
+    /// Stable code of this error for programmatic handling, independent of the message wording.
+    /// ```rust
+    /// use json_diff_ng::{compare_strs, ErrorCode};
+    /// let err = compare_strs("{", "{}", false, &[]).unwrap_err();
+    /// assert_eq!(err.code(), ErrorCode::ParseLeft);
+    /// assert_eq!(err.code().to_string(), "parse_left");
+    /// ```
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::Misc(_) => ErrorCode::Other,
+            #[cfg(feature = "std")]
+            Error::IOError(_) => ErrorCode::Io,
+            Error::JSON(_) => ErrorCode::Parse,
+            Error::ParseLeft(_)
+            | Error::ParseDocument(Side::Left, _, _)
+            | Error::InvalidTable(Side::Left, _) => ErrorCode::ParseLeft,
+            Error::ParseRight(_)
+            | Error::ParseDocument(Side::Right, _, _)
+            | Error::InvalidTable(Side::Right, _) => ErrorCode::ParseRight,
+            Error::Regex(_) => ErrorCode::Regex,
+            Error::DepthLimitExceeded(_)
+            | Error::InputTooLarge { .. }
+            | Error::RefExpansionTooLarge(_) => ErrorCode::LimitExceeded,
+            Error::KeyCollision(_, _, _) => ErrorCode::KeyCollision,
+            Error::InvalidPointer(_) => ErrorCode::InvalidPointer,
+            Error::PointerNotFound(_, _) | Error::UnwrapNotFound(_, _, _) => {
+                ErrorCode::PointerNotFound
+            }
+            Error::Structure(_) => ErrorCode::Structure,
+            Error::InvalidPatch(_) => ErrorCode::InvalidPatch,
+            Error::InvalidMapping(_) => ErrorCode::InvalidMapping,
+            Error::InvalidReport(_) => ErrorCode::InvalidReport,
+            Error::InvalidClassifier(_) => ErrorCode::InvalidClassifier,
+            Error::InvalidOptions(_) => ErrorCode::InvalidOptions,
+            Error::RequiresNormalizedDocument => ErrorCode::RequiresNormalizedDocument,
+            Error::Git(_) => ErrorCode::Git,
+            Error::UnexpectedRootType { .. } => ErrorCode::UnexpectedRootType,
+            Error::InvalidSnapshot(_) => ErrorCode::InvalidSnapshot,
+            Error::UnresolvedSelection(_) => ErrorCode::UnresolvedSelection,
+            Error::InvalidTransform(_, _) => ErrorCode::InvalidTransform,
+            Error::RefCycle(_) => ErrorCode::RefCycle,
+            Error::Context(e, _) => e.code(),
+        }
+    }
+
+    /// What went wrong, for comparing error conditions without matching on the wrapped errors.
+    /// ```rust
+    /// use json_diff_ng::{compare_strs, Error, ErrorCode};
+    /// let err = compare_strs("{", "{}", false, &[]).unwrap_err();
+    /// assert_eq!(err.kind(), ErrorCode::ParseLeft);
+    /// assert_ne!(err, compare_strs("{}", "{", false, &[]).unwrap_err());
+    /// ```
+    pub fn kind(&self) -> ErrorCode {
+        self.code()
+    }
+
+    /// Attaches `context` to this error. Context already attached takes precedence over `context`,
+    /// being closer to the cause, `context` only fills its gaps. The message of the error stays the same.
+    /// ```rust
+    /// use json_diff_ng::ErrorContext;
+    /// use json_diff_ng::{Error, Side};
+    /// let err = Error::Misc("broken".to_string()).with_context(ErrorContext::default().pointer("/a"));
+    /// let err = err.with_context(ErrorContext::default().file("left.json").side(Side::Left).pointer("/b"));
+    /// assert_eq!((err.file(), err.side(), err.pointer()), (Some("left.json"), Some(Side::Left), Some("/a")));
+    /// assert_eq!(err.to_string(), "Misc error: broken");
+    /// ```
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        match self {
+            Error::Context(e, existing) => Error::Context(e, existing.or(context)),
+            e => Error::Context(Box::new(e), context),
+        }
+    }
+
+    /// The context attached by [`Error::with_context`].
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::Context(_, context) => Some(context),
+            _ => None,
+        }
+    }
+
+    /// This error without its context.
+    pub fn without_context(&self) -> &Error {
+        match self {
+            Error::Context(e, _) => e,
+            e => e,
+        }
+    }
+
+    /// Path of the file the error concerns, if known.
+    pub fn file(&self) -> Option<&str> {
+        self.context()?.file.as_deref()
+    }
+
+    /// Side of the comparison the error concerns, from its context or else from the error itself,
+    /// e.g. [`Side::Left`] for [`Error::ParseLeft`].
+    pub fn side(&self) -> Option<Side> {
+        match self {
+            Error::Context(e, context) => context.side.or_else(|| e.side()),
+            Error::ParseLeft(_) => Some(Side::Left),
+            Error::ParseRight(_) => Some(Side::Right),
+            Error::UnwrapNotFound(side, _, _)
+            | Error::ParseDocument(side, _, _)
+            | Error::InvalidTable(side, _)
+            | Error::UnexpectedRootType { side, .. } => Some(*side),
+            _ => None,
+        }
+    }
+
+    /// JSON pointer of the value the error concerns, if known.
+    pub fn pointer(&self) -> Option<&str> {
+        self.context()?.pointer.as_deref()
+    }
+}
+
+/// Where an [`Error`] occurred, attached by [`Error::with_context`]. File based comparisons like
+/// [`crate::compare_files`] attach the file and side, failing unwrap pointers their side and pointer.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ErrorContext {
+    /// Path of the file
+    pub file: Option<String>,
+    pub side: Option<Side>,
+    /// JSON pointer of the value
+    pub pointer: Option<String>,
+}
+
+impl ErrorContext {
+    pub fn file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    pub fn side(mut self, side: Side) -> Self {
+        self.side = Some(side);
+        self
+    }
+
+    pub fn pointer(mut self, pointer: impl Into<String>) -> Self {
+        self.pointer = Some(pointer.into());
+        self
+    }
+
+    /// These fields, with the missing ones taken from `other`.
+    fn or(self, other: ErrorContext) -> Self {
+        Self {
+            file: self.file.or(other.file),
+            side: self.side.or(other.side),
+            pointer: self.pointer.or(other.pointer),
+        }
+    }
+}
+
+/// Stable discriminant of an [`Error`], serialized and displayed in `snake_case`.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Reading or writing a file failed
+    Io,
+    /// Json input not attributed to a side could not be parsed
+    Parse,
+    /// The left input could not be parsed
+    ParseLeft,
+    /// The right input could not be parsed
+    ParseRight,
+    /// A key regex does not compile
+    Regex,
+    /// The diff structure built during comparison is incoherent
+    Structure,
+    /// The input exceeds a configured limit
+    LimitExceeded,
+    /// Two keys of one object normalize to the same key
+    KeyCollision,
+    /// A json pointer is malformed
+    InvalidPointer,
+    /// A json pointer does not resolve in its document
+    PointerNotFound,
+    /// A JSON patch is malformed or does not apply
+    InvalidPatch,
+    /// A key mapping is malformed or does not apply, see [`crate::transform::Mapping`]
+    InvalidMapping,
+    /// A stored report is malformed, see [`crate::report::records_from_json`]
+    InvalidReport,
+    /// Classification rules are malformed, see [`crate::report::Classifier::from_json`]
+    InvalidClassifier,
+    /// Options conflict with each other, see [`crate::CompareOptions::validate`]
+    InvalidOptions,
+    /// Diff paths do not resolve in the original document, see [`crate::Mismatch::resolve_entry`]
+    RequiresNormalizedDocument,
+    /// An input could not be read from git
+    Git,
+    /// A document root is not an object or array, see [`crate::CompareOptions::require_container_roots`]
+    UnexpectedRootType,
+    /// A snapshot of a prepared document is damaged or does not match the options
+    InvalidSnapshot,
+    /// Selected diffs do not resolve, see [`crate::prelude::apply_selected`]
+    UnresolvedSelection,
+    /// A transformation is malformed or does not apply, see [`crate::transform::Transform`]
+    InvalidTransform,
+    /// Internal `$ref` pointers form a cycle, see [`crate::CompareOptions::resolve_internal_refs`]
+    RefCycle,
+    /// Any other error
+    Other,
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let code = match self {
+            ErrorCode::Io => "io",
+            ErrorCode::Parse => "parse",
+            ErrorCode::ParseLeft => "parse_left",
+            ErrorCode::ParseRight => "parse_right",
+            ErrorCode::Regex => "regex",
+            ErrorCode::Structure => "structure",
+            ErrorCode::LimitExceeded => "limit_exceeded",
+            ErrorCode::KeyCollision => "key_collision",
+            ErrorCode::InvalidPointer => "invalid_pointer",
+            ErrorCode::PointerNotFound => "pointer_not_found",
+            ErrorCode::InvalidPatch => "invalid_patch",
+            ErrorCode::InvalidMapping => "invalid_mapping",
+            ErrorCode::InvalidReport => "invalid_report",
+            ErrorCode::InvalidClassifier => "invalid_classifier",
+            ErrorCode::InvalidOptions => "invalid_options",
+            ErrorCode::RequiresNormalizedDocument => "requires_normalized_document",
+            ErrorCode::Git => "git",
+            ErrorCode::UnexpectedRootType => "unexpected_root_type",
+            ErrorCode::InvalidSnapshot => "invalid_snapshot",
+            ErrorCode::UnresolvedSelection => "unresolved_selection",
+            ErrorCode::InvalidTransform => "invalid_transform",
+            ErrorCode::RefCycle => "ref_cycle",
+            ErrorCode::Other => "other",
+        };
+        write!(f, "{code}")
+    }
 }
 
 impl From<String> for Error {
@@ -23,128 +464,586 @@ impl From<String> for Error {
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// Tree of the diffs of one category, see [`crate::Mismatch`]. Serializes to json mirroring the compared documents:
+/// objects hold their differing children by key, leaves are arrays of the left and the right value, which holds a
+/// value present on one side only twice, and `null` is an empty tree. Arrays and redacted leaves are objects with a single reserved key, `{"$elements": [[index, child], ...]}`
+/// and `{"$redacted": [left kind, right kind]}`, as json arrays cannot skip the equal elements. Keys of the documents
+/// starting with `$` are escaped by another `$`, so that `{"$elements": 1}` serializes as `{"$$elements": ...}`.
+/// ```rust
+/// use json_diff_ng::DiffTreeNode;
+/// use serde_json::json;
+/// let node = DiffTreeNode::object([("a", DiffTreeNode::array([(1, DiffTreeNode::leaf(1, 2))]))]);
+/// assert_eq!(serde_json::to_value(&node).unwrap(), json!({"a": {"$elements": [[1, [1, 2]]]}}));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
 pub enum DiffTreeNode {
     Null,
-    Value(Value, Value),
-    Node(HashMap<String, DiffTreeNode>),
+    /// Leaf of differing values, or of a value present on one side only, held twice. Leaves of a comparison
+    /// share equal captured subtrees, see [`crate::Mismatch::share_values`]
+    Value(Arc<Value>, Arc<Value>),
+    /// Leaf compared without capturing its values, holding the kinds of the left and right value,
+    /// see [`crate::CompareOptions::no_value_capture`]
+    Redacted(ValueKind, ValueKind),
+    Node(BTreeMap<String, DiffTreeNode>),
     Array(Vec<(usize, DiffTreeNode)>),
 }
 
 impl<'a> DiffTreeNode {
+    /// A pair of differing values, or a one-sided value when `left` and `right` are equal.
+    /// ```rust
+    /// use json_diff_ng::DiffTreeNode;
+    /// let node = DiffTreeNode::object([("a", DiffTreeNode::array([(1, DiffTreeNode::leaf(1, 2))]))]);
+    /// assert_eq!(node.get_diffs()[0].to_string(), ".a.[1].(1 != 2)");
+    /// ```
+    pub fn leaf(left: impl Into<Value>, right: impl Into<Value>) -> Self {
+        let (left, right) = (left.into(), right.into());
+        match left == right {
+            true => Self::one_sided(left),
+            false => DiffTreeNode::Value(Arc::new(left), Arc::new(right)),
+        }
+    }
+
+    /// A leaf of `value` present on one side only, holding the same allocation twice.
+    pub(crate) fn one_sided(value: Value) -> Self {
+        Self::one_sided_shared(Arc::new(value))
+    }
+
+    /// Same as [`DiffTreeNode::one_sided`] for a value that may be shared with other leaves.
+    pub(crate) fn one_sided_shared(value: Arc<Value>) -> Self {
+        DiffTreeNode::Value(value.clone(), value)
+    }
+
+    /// An object node with the given children, see [`DiffTreeNode::leaf`].
+    pub fn object<K: Into<String>>(children: impl IntoIterator<Item = (K, DiffTreeNode)>) -> Self {
+        DiffTreeNode::Node(children.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+
+    /// An array node with the given children at their indices, see [`DiffTreeNode::leaf`].
+    /// Children are kept sorted by index, which [`DiffTreeNode::get_index`] relies on.
+    pub fn array(children: impl IntoIterator<Item = (usize, DiffTreeNode)>) -> Self {
+        let mut children: Vec<_> = children.into_iter().collect();
+        children.sort_by_key(|(index, _)| *index);
+        DiffTreeNode::Array(children)
+    }
+
+    /// The child stored for array index `index`, if this is an array node with a diff there.
+    /// ```rust
+    /// use json_diff_ng::DiffTreeNode;
+    /// let node = DiffTreeNode::array([(17, DiffTreeNode::leaf(1, 2)), (3, DiffTreeNode::leaf(3, 4))]);
+    /// assert_eq!(node.get_index(17), Some(&DiffTreeNode::leaf(1, 2)));
+    /// assert_eq!(node.get_index(4), None);
+    /// ```
+    pub fn get_index(&self, index: usize) -> Option<&DiffTreeNode> {
+        match self {
+            // arrays built as the variant or read back may hold their children in any order
+            DiffTreeNode::Array(v) => v.iter().find(|(i, _)| *i == index).map(|(_, child)| child),
+            _ => None,
+        }
+    }
+
+    /// The child stored for object key `key`, if this is an object node with a diff there.
+    pub fn get_key(&self, key: &str) -> Option<&DiffTreeNode> {
+        match self {
+            DiffTreeNode::Node(o) => o.get(key),
+            _ => None,
+        }
+    }
+
+    /// The direct children of an object or array node with their path elements.
+    /// Array children come in index order, leaves have no children.
+    pub fn children(&self) -> Box<dyn Iterator<Item = (PathElement<'_>, &DiffTreeNode)> + '_> {
+        match self {
+            DiffTreeNode::Node(o) => Box::new(o.iter().map(|(k, v)| (PathElement::Object(k), v))),
+            DiffTreeNode::Array(v) => {
+                Box::new(v.iter().map(|(i, k)| (PathElement::ArrayEntry(*i), k)))
+            }
+            DiffTreeNode::Null | DiffTreeNode::Value(_, _) | DiffTreeNode::Redacted(_, _) => {
+                Box::new(core::iter::empty())
+            }
+        }
+    }
+
+    /// Replaces the values of all leaves by their kinds, see [`DiffTreeNode::Redacted`].
+    pub fn redact_values(&mut self) {
+        match self {
+            DiffTreeNode::Value(l, r) => {
+                *self = DiffTreeNode::Redacted(ValueKind::of(l), ValueKind::of(r));
+            }
+            DiffTreeNode::Node(o) => o.values_mut().for_each(DiffTreeNode::redact_values),
+            DiffTreeNode::Array(v) => v.iter_mut().for_each(|(_, node)| node.redact_values()),
+            DiffTreeNode::Null | DiffTreeNode::Redacted(_, _) => {}
+        }
+    }
+
     pub fn get_diffs(&'a self) -> Vec<DiffEntry<'a>> {
         let mut buf = Vec::new();
-        self.follow_path(&mut buf, &[]);
+        self.visit_diffs(&mut Vec::new(), &mut |entry| buf.push(entry));
         buf
     }
 
-    pub fn follow_path<'b>(
+    /// Number of entries [`DiffTreeNode::get_diffs`] would yield, without collecting them.
+    pub fn count_diffs(&self) -> usize {
+        self.count_diffs_below(false)
+    }
+
+    fn count_diffs_below(&self, is_map_child: bool) -> usize {
+        match self {
+            DiffTreeNode::Null => usize::from(is_map_child),
+            DiffTreeNode::Value(_, _) | DiffTreeNode::Redacted(_, _) => 1,
+            DiffTreeNode::Node(o) => o.values().map(|v| v.count_diffs_below(true)).sum(),
+            DiffTreeNode::Array(v) => v.iter().map(|(_, k)| k.count_diffs_below(false)).sum(),
+        }
+    }
+
+    /// Passes each entry [`DiffTreeNode::get_diffs`] would yield to `visit`, in the same order, without
+    /// collecting them. `path` is the path of this node and is restored before returning.
+    pub(crate) fn visit_diffs(
         &'a self,
-        diffs: &mut Vec<DiffEntry<'a>>,
-        offset: &'b [PathElement<'a>],
+        path: &mut Vec<PathElement<'a>>,
+        visit: &mut dyn FnMut(DiffEntry<'a>),
     ) {
         match self {
             DiffTreeNode::Null => {
-                let is_map_child = offset
+                let is_map_child = path
                     .last()
                     .map(|o| matches!(o, PathElement::Object(_)))
                     .unwrap_or_default();
                 if is_map_child {
-                    diffs.push(DiffEntry {
-                        path: offset.to_vec(),
+                    visit(DiffEntry {
+                        path: path.clone(),
                         values: None,
+                        redacted: None,
+                        diff_type: None,
                     });
                 }
             }
-            DiffTreeNode::Value(l, r) => diffs.push(DiffEntry {
-                path: offset.to_vec(),
-                values: Some((l, r)),
+            DiffTreeNode::Value(l, r) => visit(DiffEntry {
+                path: path.clone(),
+                values: Some((l.as_ref(), r.as_ref())),
+                redacted: None,
+                diff_type: None,
+            }),
+            DiffTreeNode::Redacted(l, r) => visit(DiffEntry {
+                path: path.clone(),
+                values: None,
+                redacted: Some((*l, *r)),
+                diff_type: None,
             }),
             DiffTreeNode::Node(o) => {
                 for (k, v) in o {
-                    let mut new_offset = offset.to_vec();
-                    new_offset.push(PathElement::Object(k));
-                    v.follow_path(diffs, &new_offset);
+                    path.push(PathElement::Object(k));
+                    v.visit_diffs(path, visit);
+                    path.pop();
                 }
             }
             DiffTreeNode::Array(v) => {
                 for (l, k) in v {
-                    let mut new_offset = offset.to_vec();
-                    new_offset.push(PathElement::ArrayEntry(*l));
-                    k.follow_path(diffs, &new_offset);
+                    path.push(PathElement::ArrayEntry(*l));
+                    k.visit_diffs(path, visit);
+                    path.pop();
                 }
             }
         }
     }
 }
 
-#[derive(Debug)]
+impl Serialize for DiffTreeNode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        match self {
+            DiffTreeNode::Null => serializer.serialize_unit(),
+            DiffTreeNode::Value(l, r) => (l.as_ref(), r.as_ref()).serialize(serializer),
+            DiffTreeNode::Redacted(l, r) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(REDACTED_KEY, &(l, r))?;
+                map.end()
+            }
+            DiffTreeNode::Node(children) => {
+                let mut map = serializer.serialize_map(Some(children.len()))?;
+                for (key, child) in children {
+                    match key.starts_with('$') {
+                        true => map.serialize_entry(&format!("${key}"), child)?,
+                        false => map.serialize_entry(key, child)?,
+                    }
+                }
+                map.end()
+            }
+            DiffTreeNode::Array(elements) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(ELEMENTS_KEY, elements)?;
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DiffTreeNode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+        DiffTreeNode::from_json(Value::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+/// Key of the single entry of the serialized [`DiffTreeNode::Array`].
+const ELEMENTS_KEY: &str = "$elements";
+/// Key of the single entry of the serialized [`DiffTreeNode::Redacted`].
+const REDACTED_KEY: &str = "$redacted";
+
+impl DiffTreeNode {
+    /// Reads a tree in the serialized form of [`DiffTreeNode`].
+    fn from_json(value: Value) -> core::result::Result<Self, String> {
+        let single_entry = |map: &serde_json::Map<String, Value>, key: &str| {
+            map.len() == 1 && map.contains_key(key)
+        };
+        Ok(match value {
+            Value::Null => DiffTreeNode::Null,
+            Value::Array(pair) => {
+                let Ok([l, r]) = <[Value; 2]>::try_from(pair) else {
+                    return Err("leaves must be arrays of the left and right value".to_string());
+                };
+                Self::leaf(l, r)
+            }
+            Value::Object(mut map) if single_entry(&map, REDACTED_KEY) => {
+                let kinds = map.remove(REDACTED_KEY).unwrap_or_default();
+                let (l, r) = serde_json::from_value(kinds).map_err(|e| e.to_string())?;
+                DiffTreeNode::Redacted(l, r)
+            }
+            Value::Object(mut map) if single_entry(&map, ELEMENTS_KEY) => {
+                let elements = map.remove(ELEMENTS_KEY).unwrap_or_default();
+                let elements: Vec<(usize, Value)> =
+                    serde_json::from_value(elements).map_err(|e| e.to_string())?;
+                let elements = elements
+                    .into_iter()
+                    .map(|(index, child)| Ok((index, Self::from_json(child)?)))
+                    .collect::<core::result::Result<_, String>>()?;
+                DiffTreeNode::Array(elements)
+            }
+            Value::Object(map) => DiffTreeNode::Node(
+                map.into_iter()
+                    .map(|(key, child)| {
+                        let key = match key.strip_prefix('$') {
+                            Some(escaped) if escaped.starts_with('$') => escaped.to_string(),
+                            _ => key,
+                        };
+                        Ok((key, Self::from_json(child)?))
+                    })
+                    .collect::<core::result::Result<_, String>>()?,
+            ),
+            other => {
+                return Err(format!(
+                    "unexpected {} in a diff tree",
+                    ValueKind::of(&other)
+                ))
+            }
+        })
+    }
+}
+
+/// Category of a diff, serialized in `snake_case`, e.g. `"left_extra"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
 pub enum DiffType {
     RootMismatch,
     LeftExtra,
     RightExtra,
     Mismatch,
+    /// An array element occurs a different number of times on both sides
+    MultiplicityChange,
+    /// A sorted array holds the same elements in a different order,
+    /// see [`crate::CompareOptions::report_order_differences`]
+    OrderOnly,
+    /// An object key missing on one side has a counterpart on the other side that looks alike,
+    /// see [`crate::normalize::lookalike`], or is spelled alike, see [`crate::CompareOptions::canonical_keys`]
+    KeyAlmostEqual,
+    /// Arrays compared as [`crate::ArrayMode::Matrix`] have differing numbers of rows or columns
+    DimensionMismatch,
+    /// The values at a path that must differ are equal or missing on both sides,
+    /// see [`crate::Expectation::MustDiffer`]
+    ExpectedChangeMissing,
+    /// An object on one side is an array on the other. The contents are not compared and are rendered
+    /// by their types only, like `object != array`, see [`ValueKind::is_container_change`]
+    TypeChange,
 }
 
 impl Display for DiffType {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         let msg = match self {
             DiffType::RootMismatch => "Mismatch at root.",
             DiffType::LeftExtra => "Extra on left",
             DiffType::RightExtra => "Extra on right",
             DiffType::Mismatch => "Mismatched",
+            DiffType::MultiplicityChange => "Multiplicity changed",
+            DiffType::OrderOnly => "Order differs",
+            DiffType::KeyAlmostEqual => "Key almost equal",
+            DiffType::DimensionMismatch => "Dimensions differ",
+            DiffType::ExpectedChangeMissing => "Expected change missing",
+            DiffType::TypeChange => "Type changed",
         };
         write!(f, "{}", msg)
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+impl FromStr for DiffType {
+    type Err = Error;
+
+    /// Parses the [`Display`] form of a diff type, e.g. `Extra on left`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            DiffType::RootMismatch,
+            DiffType::LeftExtra,
+            DiffType::RightExtra,
+            DiffType::Mismatch,
+            DiffType::MultiplicityChange,
+            DiffType::OrderOnly,
+            DiffType::KeyAlmostEqual,
+            DiffType::DimensionMismatch,
+            DiffType::ExpectedChangeMissing,
+            DiffType::TypeChange,
+        ]
+        .into_iter()
+        .find(|diff_type| diff_type.to_string() == s)
+        .ok_or_else(|| Error::InvalidReport(format!("unknown diff type {s:?}")))
+    }
+}
+
+impl DiffType {
+    /// Whether diffs of this type hold a value present on one side only.
+    pub(crate) fn is_one_sided(self) -> bool {
+        matches!(self, DiffType::LeftExtra | DiffType::RightExtra)
+    }
+}
+
+/// Step of a path, serialized as the key or the index.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(untagged)]
 pub enum PathElement<'a> {
     Object(&'a str),
     ArrayEntry(usize),
 }
 
 impl<'a> PathElement<'a> {
+    /// The child of `v` this element refers to. Keys only resolve in objects and indices only in arrays,
+    /// so the key `"0"` never refers to the first element of an array, nor the index 0 to the key `"0"`.
     pub fn resolve<'b>(&self, v: &'b serde_json::Value) -> Option<&'b serde_json::Value> {
-        match self {
-            PathElement::Object(o) => v.get(o),
-            PathElement::ArrayEntry(i) => v.get(*i),
+        match (self, v) {
+            (PathElement::Object(o), Value::Object(map)) => map.get(*o),
+            (PathElement::ArrayEntry(i), Value::Array(array)) => array.get(*i),
+            _ => None,
         }
     }
 
+    /// Same as [`PathElement::resolve`] for a mutable child.
     pub fn resolve_mut<'b>(
         &self,
         v: &'b mut serde_json::Value,
     ) -> Option<&'b mut serde_json::Value> {
-        match self {
-            PathElement::Object(o) => v.get_mut(o),
-            PathElement::ArrayEntry(i) => v.get_mut(*i),
+        match (self, v) {
+            (PathElement::Object(o), Value::Object(map)) => map.get_mut(*o),
+            (PathElement::ArrayEntry(i), Value::Array(array)) => array.get_mut(*i),
+            _ => None,
         }
     }
 }
 
-/// A view on a single end-node of the [`DiffTreeNode`] tree.
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+/// A view on a single end-node of the [`DiffTreeNode`] tree. Serializes with its path as array of keys and indices,
+/// its values as `[left, right]` and the kinds of redacted values as `[left kind, right kind]`, each `null` if absent.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
 pub struct DiffEntry<'a> {
     pub path: Vec<PathElement<'a>>,
     pub values: Option<(&'a serde_json::Value, &'a serde_json::Value)>,
+    /// Kinds of the left and right value of a leaf whose values were not captured
+    pub redacted: Option<(ValueKind, ValueKind)>,
+    /// Category of the diff, set by [`crate::Mismatch::entries`] and [`crate::Mismatch::all_diffs`].
+    /// Entries of a single tree from [`DiffTreeNode::get_diffs`] do not know their category
+    pub diff_type: Option<DiffType>,
 }
 
 impl<'a> DiffEntry<'a> {
+    /// Kinds of the left and right value of a leaf, whether captured or not.
+    pub fn kinds(&self) -> Option<(ValueKind, ValueKind)> {
+        self.redacted.or_else(|| {
+            self.values
+                .map(|(l, r)| (ValueKind::of(l), ValueKind::of(r)))
+        })
+    }
+
+    /// Whether this leaf is an object on one side and an array on the other, see [`DiffType::TypeChange`].
+    pub fn is_type_change(&self) -> bool {
+        self.kinds()
+            .is_some_and(|(l, r)| ValueKind::is_container_change(l, r))
+    }
+
+    /// Whether this entry refers to the compared documents themselves, whose path is empty.
+    pub fn is_root(&self) -> bool {
+        self.path.is_empty()
+    }
+
+    /// The value at the path of this entry in `value`, `value` itself for the [root](DiffEntry::is_root).
+    /// Each element is resolved by [`PathElement::resolve`].
     pub fn resolve<'b>(&'a self, value: &'b serde_json::Value) -> Option<&'b serde_json::Value> {
-        let mut return_value = value;
-        for a in &self.path {
-            return_value = a.resolve(return_value)?;
+        self.try_resolve(value).ok()
+    }
+
+    /// Same as [`DiffEntry::resolve`], failing with [`Error::PointerNotFound`] naming the path of this entry and
+    /// the longest part of it that resolves.
+    pub fn try_resolve<'b>(
+        &self,
+        value: &'b serde_json::Value,
+    ) -> Result<&'b serde_json::Value, Error> {
+        let mut resolved = value;
+        for (depth, element) in self.path.iter().enumerate() {
+            resolved = element.resolve(resolved).ok_or_else(|| {
+                let pointer = |elements: &[PathElement]| {
+                    DiffPath(elements.iter().map(OwnedPathElement::from).collect()).to_pointer()
+                };
+                Error::PointerNotFound(pointer(&self.path), pointer(&self.path[..depth]))
+            })?;
+        }
+        Ok(resolved)
+    }
+
+    /// Size change of mismatched strings, arrays or objects, see [`SizeDelta::between`].
+    pub fn size_delta(&self) -> Option<SizeDelta> {
+        match self.values {
+            Some((l, r)) if l != r => SizeDelta::between(l, r),
+            _ => None,
         }
-        Some(return_value)
     }
+
+    /// Shape of the value of a one-sided entry, `None` for mismatched values and entries without a value.
+    /// Entries without a [`DiffEntry::diff_type`] are one-sided if they hold the same value twice, like the
+    /// leaves of one-sided diffs do.
+    pub fn value_kind(&self) -> Option<ValueKind> {
+        let (l, r) = self.values?;
+        let one_sided = match self.diff_type {
+            Some(diff_type) => diff_type.is_one_sided(),
+            None => core::ptr::eq(l, r),
+        };
+        one_sided.then(|| ValueKind::of(l))
+    }
+}
+
+/// Type and size of a json value, e.g. to render "array with 52 elements" without inspecting the value.
+/// Serializes in `snake_case`, containers with their size like `{"array": 52}`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueKind {
+    Null,
+    Bool,
+    Number,
+    String,
+    /// Array with its number of elements
+    Array(usize),
+    /// Object with its number of keys
+    Object(usize),
+}
+
+impl ValueKind {
+    pub fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => ValueKind::Null,
+            Value::Bool(_) => ValueKind::Bool,
+            Value::Number(_) => ValueKind::Number,
+            Value::String(_) => ValueKind::String,
+            Value::Array(a) => ValueKind::Array(a.len()),
+            Value::Object(o) => ValueKind::Object(o.len()),
+        }
+    }
+
+    pub fn is_container(&self) -> bool {
+        matches!(self, ValueKind::Array(_) | ValueKind::Object(_))
+    }
+
+    /// Whether `left` and `right` are an object and an array, which are reported as [`DiffType::TypeChange`].
+    pub fn is_container_change(left: Self, right: Self) -> bool {
+        matches!(
+            (left, right),
+            (ValueKind::Object(_), ValueKind::Array(_))
+                | (ValueKind::Array(_), ValueKind::Object(_))
+        )
+    }
+
+    /// Name of the json type without the size, e.g. `object`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ValueKind::Null => "null",
+            ValueKind::Bool => "bool",
+            ValueKind::Number => "number",
+            ValueKind::String => "string",
+            ValueKind::Array(_) => "array",
+            ValueKind::Object(_) => "object",
+        }
+    }
+}
+
+impl Display for ValueKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ValueKind::Null => write!(f, "null"),
+            ValueKind::Bool => write!(f, "bool"),
+            ValueKind::Number => write!(f, "number"),
+            ValueKind::String => write!(f, "string"),
+            ValueKind::Array(1) => write!(f, "array with 1 element"),
+            ValueKind::Array(len) => write!(f, "array with {len} elements"),
+            ValueKind::Object(1) => write!(f, "object with 1 key"),
+            ValueKind::Object(len) => write!(f, "object with {len} keys"),
+        }
+    }
+}
+
+/// How much a mismatched string, array or object grew, positive, or shrank, negative, see [`DiffRecord::size_delta`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct SizeDelta {
+    /// Change of the number of chars of strings, of elements of arrays or of keys of objects
+    pub length: i64,
+    /// Change of the size of the compact json in bytes
+    pub bytes: i64,
 }
 
+impl SizeDelta {
+    /// Change from `left` to `right`, `None` unless both are strings, both arrays or both objects.
+    pub fn between(left: &Value, right: &Value) -> Option<Self> {
+        let delta = |left: usize, right: usize| right as i64 - left as i64;
+        let length = match (left, right) {
+            (Value::String(l), Value::String(r)) => delta(l.chars().count(), r.chars().count()),
+            (Value::Array(l), Value::Array(r)) => delta(l.len(), r.len()),
+            (Value::Object(l), Value::Object(r)) => delta(l.len(), r.len()),
+            _ => return None,
+        };
+        Some(Self {
+            length,
+            bytes: delta(compact_len(left), compact_len(right)),
+        })
+    }
+
+    /// Whether the values are of the same length and size, e.g. strings with a single char replaced.
+    pub fn is_zero(&self) -> bool {
+        self.length == 0 && self.bytes == 0
+    }
+}
+
+/// Formats like `length +3, -12 bytes`.
+impl Display for SizeDelta {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "length {:+}, {:+} bytes", self.length, self.bytes)
+    }
+}
+
+/// Formats the path and values like `.a.(1 != 2)`, the alternate form `{:#}` prefixes the
+/// [`DiffEntry::diff_type`] if known, like a [`DiffRecord`]: `Mismatched: .a.(1 != 2)`.
 impl Display for DiffEntry<'_> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        if let Some(diff_type) = self.diff_type.filter(|_| f.alternate()) {
+            write!(f, "{diff_type}: ")?;
+        }
         for element in &self.path {
             write!(f, ".{element}")?;
         }
+        if let Some((l, r)) = self.kinds().filter(|_| self.is_type_change()) {
+            return write!(f, ".({} != {})", l.type_name(), r.type_name());
+        }
+        if self.redacted.is_some() {
+            write!(f, ".(values differ)")?;
+        }
         if let Some((l, r)) = &self.values {
             if l != r {
                 write!(f, ".({l} != {r})")?;
@@ -157,11 +1056,9 @@ impl Display for DiffEntry<'_> {
 }
 
 impl Display for PathElement<'_> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
-            PathElement::Object(o) => {
-                write!(f, "{o}")
-            }
+            PathElement::Object(o) => write_visible(f, o),
             PathElement::ArrayEntry(l) => {
                 write!(f, "[{l}]")
             }
@@ -169,6 +1066,328 @@ impl Display for PathElement<'_> {
     }
 }
 
+/// Owned counterpart of [`PathElement`].
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum OwnedPathElement {
+    Object(String),
+    ArrayEntry(usize),
+}
+
+impl From<&PathElement<'_>> for OwnedPathElement {
+    fn from(value: &PathElement<'_>) -> Self {
+        match value {
+            PathElement::Object(o) => OwnedPathElement::Object(o.to_string()),
+            PathElement::ArrayEntry(i) => OwnedPathElement::ArrayEntry(*i),
+        }
+    }
+}
+
+impl Display for OwnedPathElement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            OwnedPathElement::Object(o) => write_visible(f, o),
+            OwnedPathElement::ArrayEntry(l) => write!(f, "[{l}]"),
+        }
+    }
+}
+
+/// Owned path to a diff, independent of the lifetime of the compared values.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DiffPath(pub Vec<OwnedPathElement>);
+
+impl DiffPath {
+    /// Path of the compared documents themselves.
+    pub const ROOT: DiffPath = DiffPath(Vec::new());
+
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Borrowed view on this path, e.g. to resolve it with [`PathElement::resolve`].
+    pub fn elements(&self) -> Vec<PathElement<'_>> {
+        self.0
+            .iter()
+            .map(|e| match e {
+                OwnedPathElement::Object(o) => PathElement::Object(o),
+                OwnedPathElement::ArrayEntry(i) => PathElement::ArrayEntry(*i),
+            })
+            .collect()
+    }
+
+    /// This path as JSON pointer, e.g. `/a/0/b~1c`.
+    pub fn to_pointer(&self) -> String {
+        self.0
+            .iter()
+            .map(|element| match element {
+                OwnedPathElement::Object(key) => format!("/{}", crate::pointer::escape(key)),
+                OwnedPathElement::ArrayEntry(i) => format!("/{i}"),
+            })
+            .collect()
+    }
+}
+
+impl Display for DiffPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        for element in &self.0 {
+            write!(f, ".{element}")?;
+        }
+        Ok(())
+    }
+}
+
+/// One of the two compared documents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Display for Side {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Side::Left => write!(f, "left"),
+            Side::Right => write!(f, "right"),
+        }
+    }
+}
+
+/// Path of a diff within the original document of one [`Side`], see [`crate::Mismatch::paths_for_side`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SidePath {
+    /// Path in the original document, if the diff does not exist on the side this is the path of its closest container
+    pub path: DiffPath,
+    /// Whether the diffed value exists in the document of the side
+    pub exists: bool,
+}
+
+/// Values of a [`DiffRecord`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiffValues {
+    /// The diff carries no value
+    Empty,
+    /// Value present on one side only
+    Single(Value),
+    /// Differing values of the left and right side
+    Pair(Value, Value),
+    /// Stands in for this many diffs at the path which were not reported one by one
+    Summary { suppressed: usize },
+    /// Number of occurrences of an array element on the left and right side
+    Multiplicity {
+        element: Value,
+        left: usize,
+        right: usize,
+    },
+    /// Kinds of the left and right value, whose contents were not captured
+    Redacted(ValueKind, ValueKind),
+    /// Spellings of an object key on the left and right side that look alike
+    KeyVariants { left: String, right: String },
+}
+
+impl DiffValues {
+    /// Replaces captured values by their kinds, see [`DiffValues::Redacted`].
+    pub fn redact(&mut self) {
+        *self = match self {
+            DiffValues::Single(v) | DiffValues::Multiplicity { element: v, .. } => {
+                DiffValues::Redacted(ValueKind::of(v), ValueKind::of(v))
+            }
+            DiffValues::Pair(l, r) => DiffValues::Redacted(ValueKind::of(l), ValueKind::of(r)),
+            _ => return,
+        };
+    }
+
+    /// Kinds of the left and right value of a pair, captured or not.
+    pub fn kinds(&self) -> Option<(ValueKind, ValueKind)> {
+        match self {
+            DiffValues::Pair(l, r) => Some((ValueKind::of(l), ValueKind::of(r))),
+            DiffValues::Redacted(l, r) => Some((*l, *r)),
+            _ => None,
+        }
+    }
+}
+
+/// Comparison pass that produced a diff, recorded with [`crate::CompareOptions::record_origins`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Origin {
+    /// Value of an object key missing on one side or differing between both
+    ObjectKey,
+    /// Array element inserted or removed according to the sequence diff, or compared at its position in a template array
+    ArrayPositional,
+    /// Array element paired up, or left over, inside a block of replaced elements
+    ArrayReplacedBlock,
+    /// Element of an array compared after sorting
+    ArraySorted,
+    /// Array element paired up by, or without counterpart for, its identity, see [`crate::CompareOptions::array_identity`]
+    ArrayKeyed,
+    /// Cell of a matrix compared with the cell at the same row and column, see [`crate::ArrayMode::Matrix`]
+    MatrixCell,
+    /// Array element paired up by its fingerprint, or aligned with the elements left over after that,
+    /// see [`crate::ArrayMode::FingerprintFirst`]
+    ArrayFingerprint,
+    /// Member of an object compared as the set of its keys, see [`crate::CompareOptions::coerce_object_set`]
+    ObjectSet,
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Origin::ObjectKey => write!(f, "object key"),
+            Origin::ArrayPositional => write!(f, "array position"),
+            Origin::ArrayReplacedBlock => write!(f, "replaced array block"),
+            Origin::ArraySorted => write!(f, "sorted array"),
+            Origin::ArrayKeyed => write!(f, "array identity"),
+            Origin::MatrixCell => write!(f, "matrix cell"),
+            Origin::ArrayFingerprint => write!(f, "array fingerprint"),
+            Origin::ObjectSet => write!(f, "object set"),
+        }
+    }
+}
+
+/// Owned, self-contained form of a single diff.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffRecord {
+    pub diff_type: DiffType,
+    pub path: DiffPath,
+    pub values: DiffValues,
+    /// Comparison pass that produced the diff, if recorded
+    pub origin: Option<Origin>,
+    /// Equal elements around the diff in its innermost array, only with [`crate::CompareOptions::array_context`]
+    pub context: Option<ArrayContext>,
+    /// Changed lines of mismatched long strings, only with [`crate::CompareOptions::string_diffs`]
+    pub string_diff: Option<StringDiff>,
+}
+
+/// Indices of the elements equal on both sides nearest to a diff inside an array, as pairs of the compared
+/// left and right index. Displayed like `equal before 9/9, 10/10; equal after 12/12`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArrayContext {
+    /// Equal elements preceding the diff, the nearest last
+    pub before: Vec<(usize, usize)>,
+    /// Equal elements following the diff, the nearest first
+    pub after: Vec<(usize, usize)>,
+}
+
+impl Display for ArrayContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let pairs = |pairs: &[(usize, usize)]| match pairs.is_empty() {
+            true => "none".to_string(),
+            false => pairs
+                .iter()
+                .map(|(left, right)| format!("{left}/{right}"))
+                .collect::<Vec<_>>()
+                .join(", "),
+        };
+        write!(
+            f,
+            "equal before {}; equal after {}",
+            pairs(&self.before),
+            pairs(&self.after)
+        )
+    }
+}
+
+impl DiffRecord {
+    pub fn from_entry(diff_type: DiffType, entry: &DiffEntry<'_>) -> Self {
+        let values = match (entry.values, entry.redacted) {
+            (_, Some((l, r))) => DiffValues::Redacted(l, r),
+            (None, _) => DiffValues::Empty,
+            (Some((value, _)), _) if diff_type.is_one_sided() => DiffValues::Single(value.clone()),
+            (Some((l, r)), _) => DiffValues::Pair(l.clone(), r.clone()),
+        };
+        Self {
+            diff_type,
+            path: DiffPath(entry.path.iter().map(Into::into).collect()),
+            values,
+            origin: None,
+            context: None,
+            string_diff: None,
+        }
+    }
+
+    /// Size change of mismatched strings, arrays or objects, see [`SizeDelta::between`].
+    pub fn size_delta(&self) -> Option<SizeDelta> {
+        match &self.values {
+            DiffValues::Pair(l, r) => SizeDelta::between(l, r),
+            _ => None,
+        }
+    }
+
+    /// Record of an entry from [`crate::Mismatch::entries`], `None` if the entry does not know its [`DiffType`].
+    pub fn from_typed_entry(entry: &DiffEntry<'_>) -> Option<Self> {
+        Some(Self::from_entry(entry.diff_type?, entry))
+    }
+
+    /// Whether the record only informs about the documents and does not count as a diff on its own,
+    /// see [`DiffType::OrderOnly`] and [`DiffType::KeyAlmostEqual`].
+    pub fn is_informational(&self) -> bool {
+        matches!(
+            self.diff_type,
+            DiffType::OrderOnly | DiffType::KeyAlmostEqual
+        )
+    }
+
+    /// Number of diffs this record stands for.
+    pub fn count(&self) -> usize {
+        match self.values {
+            DiffValues::Summary { suppressed } => suppressed,
+            _ => 1,
+        }
+    }
+
+    /// Writes the [`Display`] form of the record without its diff type, e.g. `.a.(1 != 2)`.
+    pub(crate) fn write_body(&self, f: &mut impl core::fmt::Write) -> core::fmt::Result {
+        write!(f, "{}", self.path)?;
+        if self.has_printed_values() {
+            f.write_str(".(")?;
+            self.write_values(f)?;
+            f.write_str(")")?;
+        }
+        Ok(())
+    }
+
+    /// Whether [`DiffRecord::write_values`] writes anything.
+    pub(crate) fn has_printed_values(&self) -> bool {
+        match &self.values {
+            DiffValues::Empty => false,
+            values if self.diff_type == DiffType::TypeChange => values.kinds().is_some(),
+            _ => true,
+        }
+    }
+
+    /// Writes the values of the record as they appear in parentheses of its [`Display`] form, e.g. `1 != 2`.
+    pub(crate) fn write_values(&self, f: &mut impl core::fmt::Write) -> core::fmt::Result {
+        match &self.values {
+            DiffValues::Empty => Ok(()),
+            values if self.diff_type == DiffType::TypeChange => match values.kinds() {
+                Some((l, r)) => write!(f, "{} != {}", l.type_name(), r.type_name()),
+                None => Ok(()),
+            },
+            DiffValues::Single(v) => write!(f, "{v}"),
+            DiffValues::Pair(l, r) => write!(f, "{l} != {r}"),
+            DiffValues::Summary { suppressed } => write!(f, "... {suppressed} more"),
+            DiffValues::Multiplicity {
+                element,
+                left,
+                right,
+            } => write!(f, "{element}: {left} → {right}"),
+            DiffValues::Redacted(_, _) => write!(f, "values differ"),
+            DiffValues::KeyVariants { left, right } => write!(
+                f,
+                "\"{}\" ≈ \"{}\"",
+                escape_invisible(left),
+                escape_invisible(right)
+            ),
+        }
+    }
+}
+
+impl Display for DiffRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}: ", self.diff_type)?;
+        self.write_body(f)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use serde_json::json;
@@ -176,6 +1395,8 @@ mod test {
     use crate::compare_serde_values;
     use crate::sort::sort_value;
 
+    use super::*;
+
     #[test]
     fn test_resolve() {
         let data1 = json! {["a",{"c": ["d","f"] },"b"]};
@@ -193,4 +1414,228 @@ mod test {
         let val = diff.resolve(&data2_sorted);
         assert_eq!(val.unwrap().as_str().unwrap(), "e");
     }
+
+    #[test]
+    fn numeric_keys_and_indices_resolve_only_in_their_containers() {
+        let value = json!({"0": [{"1": "x"}], "list": ["a"]});
+        let entry = |path: Vec<PathElement<'static>>| DiffEntry {
+            path,
+            ..DiffEntry::default()
+        };
+        let nested = entry(vec![
+            PathElement::Object("0"),
+            PathElement::ArrayEntry(0),
+            PathElement::Object("1"),
+        ]);
+        assert_eq!(nested.resolve(&value), Some(&json!("x")));
+        assert_eq!(
+            entry(vec![PathElement::ArrayEntry(0)]).resolve(&value),
+            None
+        );
+        let key_in_array = entry(vec![PathElement::Object("list"), PathElement::Object("0")]);
+        assert_eq!(key_in_array.resolve(&value), None);
+        assert_eq!(
+            key_in_array.try_resolve(&value).unwrap_err().to_string(),
+            "JSON pointer /list/0 does not resolve, nearest existing ancestor is /list"
+        );
+        let mut value = value;
+        assert!(PathElement::Object("0")
+            .resolve_mut(&mut value["list"])
+            .is_none());
+
+        let root = entry(Vec::new());
+        assert!(root.is_root() && DiffPath::ROOT.is_root());
+        assert_eq!(root.resolve(&value), Some(&value));
+        let diffs =
+            compare_serde_values(&json!({"0": [1]}), &json!({"0": [2]}), false, &[]).unwrap();
+        let (_, diff) = &diffs.all_diffs()[0];
+        assert_eq!(diff.resolve(&json!({"0": [1]})), Some(&json!(1)));
+        assert_eq!(diff.resolve(&json!([[1]])), None);
+    }
+
+    #[test]
+    fn size_deltas_of_grown_and_shrunken_values() {
+        let delta = |l: Value, r: Value| SizeDelta::between(&l, &r);
+        let grown = delta(json!("abc"), json!("abcdéf")).unwrap();
+        assert_eq!((grown.length, grown.bytes), (3, 4));
+        assert_eq!(grown.to_string(), "length +3, +4 bytes");
+        let shrunken = delta(json!([1, 2, 3]), json!([1])).unwrap();
+        assert_eq!((shrunken.length, shrunken.bytes), (-2, -4));
+        let added = delta(json!({"a": 1}), json!({"a": 1, "b": 2, "c": 3})).unwrap();
+        assert_eq!((added.length, added.bytes), (2, 12));
+        assert_eq!(delta(json!(1), json!("1")), None);
+        assert_eq!(delta(json!(1), json!(20)), None);
+        assert_eq!(delta(json!([]), json!({})), None);
+    }
+
+    #[test]
+    fn one_sided_values_carry_their_kind() {
+        let data1 = json! {{"items": [1, 2, 3], "meta": {"a": 1, "b": 2}, "name": "x", "same": 1}};
+        let data2 = json! {{"same": 2}};
+        let diffs = compare_serde_values(&data1, &data2, false, &[]).unwrap();
+        let left_only = diffs.left_only.get_diffs();
+        let kind_of = |key: &str| {
+            left_only
+                .iter()
+                .find(|d| d.path == vec![PathElement::Object(key)])
+                .and_then(|d| d.value_kind())
+        };
+        assert_eq!(kind_of("items"), Some(ValueKind::Array(3)));
+        assert_eq!(kind_of("meta"), Some(ValueKind::Object(2)));
+        assert_eq!(kind_of("name"), Some(ValueKind::String));
+        assert_eq!(
+            kind_of("items").unwrap().to_string(),
+            "array with 3 elements"
+        );
+        let unequal = diffs.unequal_values.get_diffs();
+        assert_eq!(unequal[0].value_kind(), None);
+    }
+
+    #[test]
+    fn one_sided_entries_are_told_by_their_type() {
+        let value = json!({"a": [1, 2]});
+        let entry = |diff_type| DiffEntry {
+            path: vec![PathElement::Object("x")],
+            values: Some((&value, &value)),
+            redacted: None,
+            diff_type,
+        };
+        let mismatch = entry(Some(DiffType::Mismatch));
+        assert_eq!(mismatch.value_kind(), None);
+        let record = DiffRecord::from_entry(DiffType::Mismatch, &mismatch);
+        assert_eq!(record.values, DiffValues::Pair(value.clone(), value.clone()));
+        let extra = entry(Some(DiffType::LeftExtra));
+        assert_eq!(extra.value_kind(), Some(ValueKind::Object(1)));
+        let record = DiffRecord::from_entry(DiffType::LeftExtra, &extra);
+        assert_eq!(record.values, DiffValues::Single(value.clone()));
+        // untyped entries of one-sided trees hold their value twice, also once read back
+        assert_eq!(entry(None).value_kind(), Some(ValueKind::Object(1)));
+        let tree: DiffTreeNode = serde_json::from_value(json!({"x": [[1], [1]]})).unwrap();
+        assert_eq!(tree.get_diffs()[0].value_kind(), Some(ValueKind::Array(1)));
+    }
+
+    #[test]
+    fn missing_array_is_a_single_entry_with_contents() {
+        let data1 = json! {{"list": []}};
+        let data2 = json! {{"list": [], "extra": [{"a": 1}, {"b": 2}]}};
+        let diffs = compare_serde_values(&data1, &data2, false, &[]).unwrap();
+        let right_only = diffs.right_only.get_diffs();
+        assert_eq!(right_only.len(), 1);
+        assert_eq!(
+            right_only[0].values.map(|(v, _)| v),
+            Some(&json! {[{"a": 1}, {"b": 2}]})
+        );
+        assert_eq!(right_only[0].value_kind(), Some(ValueKind::Array(2)));
+    }
+
+    #[test]
+    fn tree_lookups_by_index_and_key() {
+        let node = DiffTreeNode::object([(
+            "list",
+            DiffTreeNode::array([
+                (17, DiffTreeNode::leaf(1, 2)),
+                (2, DiffTreeNode::leaf("a", "b")),
+                (9, DiffTreeNode::leaf(true, false)),
+            ]),
+        )]);
+        let list = node.get_key("list").unwrap();
+        assert_eq!(list.get_index(17), Some(&DiffTreeNode::leaf(1, 2)));
+        assert_eq!(list.get_index(2), Some(&DiffTreeNode::leaf("a", "b")));
+        assert_eq!(list.get_index(3), None);
+        assert_eq!(node.get_key("missing"), None);
+        assert_eq!(node.get_index(0), None);
+        assert_eq!(list.get_key("list"), None);
+
+        let indices: Vec<_> = list.children().map(|(element, _)| element).collect();
+        assert_eq!(
+            indices,
+            vec![
+                PathElement::ArrayEntry(2),
+                PathElement::ArrayEntry(9),
+                PathElement::ArrayEntry(17)
+            ]
+        );
+        assert_eq!(list.get_index(9).unwrap().children().count(), 0);
+
+        let unsorted =
+            DiffTreeNode::Array(vec![(9, DiffTreeNode::leaf(1, 2)), (2, DiffTreeNode::Null)]);
+        assert_eq!(unsorted.get_index(9), Some(&DiffTreeNode::leaf(1, 2)));
+        let read: DiffTreeNode =
+            serde_json::from_value(json!({"$elements": [[9, [1, 2]], [2, [3, 4]]]})).unwrap();
+        assert_eq!(read.get_index(2), Some(&DiffTreeNode::leaf(3, 4)));
+    }
+
+    #[test]
+    fn every_error_has_a_code() {
+        use crate::{compare_multi_document_strs, compare_strs, pointer, CompareOptions};
+
+        let code = |result: crate::Result<()>| result.unwrap_err().code();
+        let compare = |a: &str, b: &str, options: &CompareOptions| {
+            crate::compare_strs_with_options(a, b, options).map(|_| ())
+        };
+        let options = CompareOptions::default();
+
+        assert_eq!(
+            code(compare_strs("{", "{}", false, &[]).map(|_| ())),
+            ErrorCode::ParseLeft
+        );
+        assert_eq!(
+            code(compare_strs("{}", "{", false, &[]).map(|_| ())),
+            ErrorCode::ParseRight
+        );
+        assert_eq!(
+            code(compare_multi_document_strs("1 {", "1 2", &options).map(|_| ())),
+            ErrorCode::ParseLeft
+        );
+        assert_eq!(
+            code(compare_multi_document_strs("1 2", "1 {", &options).map(|_| ())),
+            ErrorCode::ParseRight
+        );
+        let regex = CompareOptions::default().ignore_key_patterns(["("]);
+        assert_eq!(code(compare("{}", "{}", &regex)), ErrorCode::Regex);
+        let shallow = CompareOptions::default().max_depth(1);
+        assert_eq!(
+            code(compare("[[1]]", "[[1]]", &shallow)),
+            ErrorCode::LimitExceeded
+        );
+        let normalized = CompareOptions::default().key_normalizer(|k| k.to_lowercase());
+        assert_eq!(
+            code(compare(r#"{"a": 1, "A": 2}"#, "{}", &normalized)),
+            ErrorCode::KeyCollision
+        );
+
+        let value = json!({"a": 1});
+        let extract = |p: &str| pointer::extract(&value, p).map(|_| ());
+        assert_eq!(code(extract("a")), ErrorCode::InvalidPointer);
+        assert_eq!(code(extract("/b")), ErrorCode::PointerNotFound);
+
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+        let io: Error = FatIOError::from_std_io_err(io, "file.json".into()).into();
+        assert_eq!(io.code(), ErrorCode::Io);
+        let json: Error = serde_json::from_str::<Value>("{").unwrap_err().into();
+        assert_eq!(json.code(), ErrorCode::Parse);
+        assert_eq!(Error::from("other".to_string()).code(), ErrorCode::Other);
+        assert_eq!(
+            Error::Structure("broken".into()).code(),
+            ErrorCode::Structure
+        );
+        let patch = crate::patch::apply(&value, &json!({}));
+        assert_eq!(code(patch.map(|_| ())), ErrorCode::InvalidPatch);
+    }
+
+    #[test]
+    fn error_codes_serialize_like_they_display() {
+        for code in [
+            ErrorCode::LimitExceeded,
+            ErrorCode::Io,
+            ErrorCode::PointerNotFound,
+        ] {
+            let serialized = serde_json::to_value(code).unwrap();
+            assert_eq!(serialized, Value::String(code.to_string()));
+            assert_eq!(
+                serde_json::from_value::<ErrorCode>(serialized).unwrap(),
+                code
+            );
+        }
+    }
 }
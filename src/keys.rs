@@ -0,0 +1,108 @@
+//! The decision which object keys take part in a comparison, shared by comparing, sorting and fingerprinting.
+//! ```rust
+//! use json_diff_ng::{CompareOptions, Side};
+//! let comparator = CompareOptions::default()
+//!     .ignore_key_patterns(["^_"])
+//!     .include_key_patterns(["^_id$"])
+//!     .build()
+//!     .unwrap();
+//! let keys = comparator.key_matcher(Side::Left);
+//! assert!(keys.is_ignored("_rev"));
+//! assert!(keys.is_compared("_id"));
+//! ```
+use regex::Regex;
+
+/// Decides which object keys take part in comparing and sorting.
+/// A key is ignored if it matches any of the ignore regexes and none of the include overrides.
+/// Regexes match anywhere in the key unless they are anchored with `^` and `$`.
+#[derive(Clone, Copy, Debug)]
+pub struct KeyMatcher<'a> {
+    ignore_keys: &'a [Regex],
+    include_overrides: &'a [Regex],
+}
+
+impl<'a> KeyMatcher<'a> {
+    pub fn new(ignore_keys: &'a [Regex], include_overrides: &'a [Regex]) -> Self {
+        Self {
+            ignore_keys,
+            include_overrides,
+        }
+    }
+
+    pub fn is_ignored(&self, key: &str) -> bool {
+        self.ignore_keys.iter().any(|r| r.is_match(key))
+            && !self.include_overrides.iter().any(|r| r.is_match(key))
+    }
+
+    pub fn is_compared(&self, key: &str) -> bool {
+        !self.is_ignored(key)
+    }
+
+    pub(crate) fn ignores_any(&self) -> bool {
+        !self.ignore_keys.is_empty()
+    }
+}
+
+/// Key matchers of the left and right document, which differ with per-side ignores.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SideKeyMatchers<'a> {
+    pub(crate) left: KeyMatcher<'a>,
+    pub(crate) right: KeyMatcher<'a>,
+}
+
+impl SideKeyMatchers<'_> {
+    /// Whether either side ignores keys, which makes arrays of both sides get sorted alike.
+    pub(crate) fn ignores_any(&self) -> bool {
+        self.left.ignores_any() || self.right.ignores_any()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    fn regexes(patterns: &[&str]) -> Vec<Regex> {
+        patterns.iter().map(|p| Regex::new(p).unwrap()).collect()
+    }
+
+    #[test]
+    fn empty_pattern_lists_compare_every_key() {
+        let overrides = regexes(&["id"]);
+        for keys in [KeyMatcher::new(&[], &[]), KeyMatcher::new(&[], &overrides)] {
+            assert!(["", "id", "_rev", "größe"]
+                .iter()
+                .all(|k| keys.is_compared(k)));
+            assert!(!keys.ignores_any());
+        }
+    }
+
+    #[test]
+    fn unanchored_patterns_match_anywhere_in_the_key() {
+        let (unanchored, anchored) = (regexes(&["id"]), regexes(&["^id$"]));
+        let keys = KeyMatcher::new(&unanchored, &[]);
+        assert!(keys.is_ignored("id") && keys.is_ignored("uuid") && keys.is_ignored("ids"));
+        let keys = KeyMatcher::new(&anchored, &[]);
+        assert!(keys.is_ignored("id"));
+        assert!(keys.is_compared("uuid") && keys.is_compared("ids") && keys.is_compared("ID"));
+    }
+
+    #[test]
+    fn overrides_win_over_ignores() {
+        let (ignore, include) = (regexes(&["^_", "secret"]), regexes(&["^_id$"]));
+        let keys = KeyMatcher::new(&ignore, &include);
+        assert!(keys.is_ignored("_rev") && keys.is_ignored("my_secret"));
+        assert!(keys.is_compared("_id") && keys.is_compared("name"));
+    }
+
+    #[test]
+    fn unicode_keys_match_by_character() {
+        let ignore = regexes(&["^grö(ß|ss)e$", r"^\p{Greek}+$", "^.$"]);
+        let keys = KeyMatcher::new(&ignore, &[]);
+        assert!(keys.is_ignored("größe") && keys.is_ignored("grösse"));
+        assert!(keys.is_ignored("αβγ") && keys.is_compared("abc"));
+        // a single character, not a single byte
+        assert!(keys.is_ignored("日") && keys.is_compared("日本"));
+    }
+}
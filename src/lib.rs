@@ -13,7 +13,7 @@
 //! assert_eq!(diffs.len(), 1);
 //! assert_eq!(
 //!   diffs.first().unwrap().to_string(),
-//!   r#".[0].c.[1].("f" != "e")"#
+//!   r#".[2].c.[1].("f" != "e")"#
 //! );
 //! ```
 //! ## How to handle the results
@@ -56,21 +56,92 @@
 //! assert_eq!(val.unwrap().as_str().unwrap(), "f");
 //! let val = diff.resolve(&data2_sorted);
 //! assert_eq!(val.unwrap().as_str().unwrap(), "e");
+//! // or let the mismatch translate the path to the original document
+//! let val = diffs.resolve_entry(diff, &data1).unwrap();
+//! assert_eq!(val.unwrap().as_str().unwrap(), "f");
 //! ```
 //!
+//! ## Prelude
+//! `use json_diff_ng::prelude::*;` imports the supported API, see [`prelude`]. The modules `enums`, `mismatch` and
+//! `process` implementing the comparison are hidden from the docs and become private in the next release,
+//! their public items are re-exported there. Option structs and the
+//! [`Error`] and [`DiffType`] enums are `#[non_exhaustive]`, new options and variants are not breaking changes.
+//!
+//! ## Without `std`
+//! Disabling the default `std` feature builds the comparison core with `#![no_std]` and `alloc`.
+//! Comparing files and directories, [`cache`], [`observer::RecordingObserver`] and the timing in
+//! [`prelude::ResourceUsage::duration`] need `std`, key regexes are compiled without the `std` optimizations of `regex`.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
+pub use enums::ArrayContext;
 pub use enums::DiffEntry;
+pub use enums::DiffPath;
+pub use enums::DiffRecord;
 pub use enums::DiffTreeNode;
 pub use enums::DiffType;
+pub use enums::DiffValues;
 pub use enums::Error;
+pub use enums::ErrorCode;
+pub use enums::ErrorContext;
+pub use enums::Origin;
 pub use enums::PathElement;
+pub use enums::Side;
+pub use enums::SidePath;
+pub use enums::SizeDelta;
+pub use enums::ValueKind;
 pub use mismatch::Mismatch;
+#[cfg(feature = "std")]
+pub use process::compare_files;
+pub use process::compare_multi_document_strs;
 pub use process::compare_serde_values;
+pub use process::compare_serde_values_with_options;
+pub use process::compare_slices;
 pub use process::compare_strs;
+pub use process::compare_strs_with_options;
+pub use process::diff_at;
+pub use process::ArrayMode;
+pub use process::CompareMode;
+pub use process::CompareOptions;
+pub use process::Expectation;
+pub use process::Comparator;
+pub use process::OptionConflict;
+pub use process::ParseMode;
+pub use process::ValueRule;
 
+#[cfg(feature = "std")]
+pub mod cache;
+pub mod canonical;
+pub mod cookbook;
+mod decimal;
+#[cfg(feature = "std")]
+pub mod dirs;
+#[doc(hidden)]
 pub mod enums;
+mod keys;
+#[doc(hidden)]
 pub mod mismatch;
+mod myers;
+pub mod normalize;
+pub mod observer;
+pub mod patch;
+pub mod pointer;
+pub mod prelude;
+#[doc(hidden)]
 pub mod process;
+pub mod raw;
+pub mod report;
+pub mod sink;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
 pub mod sort;
+pub mod string_diff;
+#[cfg(feature = "tabular")]
+pub mod tabular;
+mod template;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod transform;
 
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
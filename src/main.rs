@@ -1,17 +1,144 @@
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Read, Write};
+use std::ops::ControlFlow;
+use std::path::Path;
+
 use clap::Parser;
 use clap::Subcommand;
+use clap::ValueEnum;
+use serde::de::IgnoredAny;
+use serde_json::Value;
+use vg_errortools::FatIOError;
+
+use json_diff_ng::canonical::to_canonical_string;
+use json_diff_ng::dirs::{compare_dirs, FileOutcome};
+use json_diff_ng::normalize;
+use json_diff_ng::patch::{self, to_json_patch};
+use json_diff_ng::prelude::{
+    compare_reports, numeric_delta, size_change, value_size, Verdict, Warning,
+};
+use json_diff_ng::pointer::extract;
+use json_diff_ng::report;
+use json_diff_ng::report::{records_from_json, Classifier, ColorMode, FormatOptions, Messages};
+use json_diff_ng::sink::compare_with_sink;
+use json_diff_ng::snapshot::{is_snapshot_path, PreparedDocument};
+use json_diff_ng::tabular::{compare_csv, RowOutcome, TableOptions};
+use json_diff_ng::transform::{apply_mapping_with_warnings, Mapping, Transform};
+use json_diff_ng::{
+    CompareOptions, DiffPath, DiffRecord, DiffType, DiffValues, Error, ErrorCode, ErrorContext,
+    Expectation, Mismatch, ParseMode, Result, Side,
+};
 
-use json_diff_ng::{compare_strs, Mismatch, Result};
+/// File name of stdin in file inputs.
+const STDIN: &str = "-";
+
+/// `writeln!` to the output of [`run`], returning an io error from the enclosing function if it fails.
+macro_rules! outln {
+    ($out:expr, $($arg:tt)*) => {
+        writeln!($out, $($arg)*).map_err(output_error)?
+    };
+}
 
 #[derive(Subcommand, Clone)]
 /// Input selection
 enum Mode {
-    /// File input
+    /// File input, `-` reads a file from stdin, for both files two concatenated documents like `stdin`
     #[clap(short_flag = 'f')]
     File { file_1: String, file_2: String },
+    /// Read two concatenated documents from stdin, e.g. {"a": 1} {"a": 2}
+    Stdin,
     /// Read from CLI
     #[clap(short_flag = 'd')]
     Direct { json_1: String, json_2: String },
+    /// Compare an actual file with a golden file and overwrite the golden with the canonically formatted actual one
+    Accept {
+        actual: String,
+        golden: String,
+        /// Overwrite without asking
+        #[clap(long)]
+        yes: bool,
+        /// Do not keep the old golden as <golden>.bak
+        #[clap(long)]
+        no_backup: bool,
+    },
+    /// Print the RFC 6902 JSON patch transforming the left file into the right one
+    Patch {
+        left: String,
+        right: String,
+        /// Verify that applying the patch to the left file yields the right one
+        #[clap(long)]
+        check: bool,
+    },
+    /// Compare all .json files of two directory trees, pairing them by relative path
+    Dirs { left: String, right: String },
+    /// Print which diffs appeared, disappeared or changed their values between two json reports
+    ReportDiff { old: String, new: String },
+    /// Compare two CSV tables row by row, the cells of json columns are compared as json documents
+    Csv {
+        left: String,
+        right: String,
+        /// Columns holding json documents, e.g. payload,meta
+        #[clap(long, value_delimiter = ',')]
+        json_cols: Vec<String>,
+        /// Column pairing the rows of both tables, rows are paired by position without it
+        #[clap(long)]
+        key_col: Option<String>,
+        /// The tables are tab separated
+        #[clap(long)]
+        tsv: bool,
+    },
+    /// Write a snapshot of a parsed json file, e.g. golden.json golden.snap. Comparisons accept it as a file
+    /// input in place of the json file, without parsing it again
+    Prepare { input: String, snapshot: String },
+    /// Compare a file as committed in two git revisions, e.g. HEAD~1 HEAD config.json
+    Git {
+        rev_1: String,
+        rev_2: String,
+        /// Path of the file, relative to the current directory
+        path: String,
+    },
+}
+
+/// Significance of a diff for --top.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Rank {
+    /// Absolute difference of mismatched numbers
+    NumericDelta,
+    /// Size of the larger value in bytes of compact json
+    ValueSize,
+    /// Absolute change of the size of mismatched strings, arrays and objects in bytes of compact json
+    SizeChange,
+}
+
+/// Spelling object keys are matched by for --key-case.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum KeyCase {
+    /// Keys differing only in case match, e.g. CustomerId and customerId
+    Lower,
+    /// camelCase, PascalCase, kebab-case and snake_case spellings match
+    Snake,
+    /// snake_case, kebab-case and camelCase spellings match
+    Camel,
+}
+
+/// When diffs are printed in color for --color.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Color {
+    /// Color if the output is a terminal and NO_COLOR is not set
+    Auto,
+    Always,
+    Never,
+}
+
+/// How the diffs of a comparison are printed.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// One line per diff
+    Text,
+    /// A graphviz digraph of the diff tree
+    Dot,
+    /// A json document listing the diffs under left_only, right_only and mismatches
+    Json,
 }
 
 #[derive(Parser)]
@@ -26,45 +153,1135 @@ struct Args {
     #[clap(short, long)]
     /// Exclude a given list of keys by regex.
     exclude_keys: Option<Vec<String>>,
+
+    #[clap(long)]
+    /// Compare keys matching any of these regexes even if they are excluded
+    include_keys: Option<Vec<String>>,
+
+    #[clap(long)]
+    /// Exclude the values at paths matching any of these regexes, e.g. '^\.items\.\[\d+\]\.id$'
+    exclude_paths: Option<Vec<String>>,
+
+    #[clap(long)]
+    /// Exclude the values at and below these JSON pointers, e.g. /metadata/timestamps
+    exclude_pointers: Option<Vec<String>>,
+
+    #[clap(long)]
+    /// Treat strings matching any of these regexes on both sides as equal, e.g. '^[0-9a-f-]{36}$' for UUIDs
+    ignore_values: Option<Vec<String>>,
+
+    #[clap(long, value_delimiter = ',')]
+    /// Pair array elements by the value of the first of these members they have instead of by position, e.g. id
+    identity_key: Vec<String>,
+
+    #[clap(long, value_parser = identity_rule)]
+    /// Pair the elements of the array at a JSON pointer by a member, e.g. /users=email
+    identity_key_at: Vec<(String, String)>,
+
+    #[clap(short, long)]
+    /// Group diffs by their top-level key and print per-group counts
+    group_by_root: bool,
+
+    #[clap(long)]
+    /// Compare only the subdocument of the first input at this JSON pointer, e.g. /spec/template,
+    /// of every document with --multi-doc
+    left_pointer: Option<String>,
+
+    #[clap(long)]
+    /// Compare only the subdocument of the second input at this JSON pointer
+    right_pointer: Option<String>,
+
+    #[clap(long)]
+    /// Compare the whole input if its pointer does not resolve, instead of failing
+    unwrap_fallback: bool,
+
+    #[clap(long)]
+    /// Fail if the root of either input is not an object or array, e.g. a bare string or null
+    require_container: bool,
+
+    #[clap(long)]
+    /// Treat object members holding null like missing members
+    null_equals_missing: bool,
+
+    #[clap(long, value_enum)]
+    /// Match object keys by this spelling, diffs name the keys as spelled in the first input
+    key_case: Option<KeyCase>,
+
+    #[clap(long)]
+    /// Inputs are streams of whitespace-separated json documents, compared pairwise by position
+    multi_doc: bool,
+
+    #[clap(long)]
+    /// Print which comparison pass produced each diff
+    explain: bool,
+
+    #[clap(long)]
+    /// Print only the paths of values present on one side, not the values themselves
+    no_values: bool,
+
+    #[clap(long)]
+    /// Name of the first input in reports, defaults to its path
+    label_left: Option<String>,
+
+    #[clap(long)]
+    /// Name of the second input in reports, defaults to its path
+    label_right: Option<String>,
+
+    #[clap(long)]
+    /// Print diffs grouped by category instead of ordered by path
+    unsorted: bool,
+
+    #[clap(long, conflicts_with = "multi_doc")]
+    /// Json file moving values of the first input to new paths before comparing,
+    /// e.g. {"/user/name": "/profile/displayName"}
+    map: Option<String>,
+
+    #[clap(long)]
+    /// Json file of operations editing the first input before comparing,
+    /// e.g. [{"op": "remove", "path": "/debug"}, {"op": "default", "path": "/retries", "value": 0}]
+    transform_left: Option<String>,
+
+    #[clap(long)]
+    /// Json file of operations editing the second input before comparing
+    transform_right: Option<String>,
+
+    #[clap(long)]
+    /// Replace internal references like {"$ref": "#/definitions/Pet"} by the values they point to before comparing
+    resolve_refs: bool,
+
+    #[clap(long, alias = "output", value_enum, default_value_t = Format::Text)]
+    /// Output format of the diffs
+    format: Format,
+
+    #[clap(long, value_enum, default_value_t = Color::Auto)]
+    /// Print diffs grouped by their parent path, left only in red, right only in green and mismatches in yellow
+    color: Color,
+
+    #[clap(long, conflicts_with_all = ["multi_doc", "format"])]
+    /// Print one page of the json report holding up to this many diffs, ordered by path
+    page_size: Option<usize>,
+
+    #[clap(long, requires = "page_size", default_value_t = 0)]
+    /// Zero-based index of the page printed with --page-size
+    page: usize,
+
+    #[clap(long, conflicts_with_all = ["multi_doc", "format", "page_size", "group_by_root"])]
+    /// Print only this many diffs of the highest significance, see --rank-by
+    top: Option<usize>,
+
+    #[clap(long, value_enum, requires = "top", default_value_t = Rank::NumericDelta)]
+    /// Significance of diffs for --top
+    rank_by: Rank,
+
+    #[clap(long, conflicts_with_all = ["multi_doc", "format", "page_size", "top", "group_by_root"])]
+    /// Print each diff as soon as it is found, in the order found, followed by a line with the number of diffs
+    stream: bool,
+
+    #[clap(long, requires = "stream", value_parser = clap::value_parser!(u64).range(1..))]
+    /// Stop streaming after this many diffs
+    max_diffs: Option<u64>,
+
+    #[clap(long)]
+    /// JSON pointers whose values must differ, e.g. /version. Diffs below them are expected
+    must_differ: Option<Vec<String>>,
+
+    #[clap(long)]
+    /// Json file translating the diff categories, e.g. {"mismatch": "Abweichung"}
+    messages: Option<String>,
+
+    #[clap(long, conflicts_with_all = ["multi_doc", "page_size", "top", "stream"])]
+    /// Json file of rules sorting diffs into categories by path, e.g. [{"glob": "/prices/**", "category": "pricing"}].
+    /// The number of diffs per category is printed below the diffs, or added to the summary of --format json
+    classify: Option<String>,
+
+    #[clap(long, requires = "classify")]
+    /// Categories failing the comparison, diffs of other categories are printed but pass
+    fail_on_category: Option<Vec<String>>,
+
+    #[clap(long, default_value_t = 1 << 30)]
+    /// Input files larger than this many bytes are rejected instead of being read
+    max_input_size: u64,
+
+    #[clap(short, long)]
+    /// Do not print warnings about options, key mappings or the compared inputs
+    quiet: bool,
+}
+
+/// How the diffs of a comparison are printed.
+#[derive(Clone, Copy)]
+struct Report<'a> {
+    explain: bool,
+    values: bool,
+    sorted: bool,
+    messages: &'a Messages,
 }
 
-fn main() -> Result<()> {
+impl Report<'_> {
+    /// Drops the value of a one-sided record unless values are printed.
+    fn shape(&self, mut record: DiffRecord) -> DiffRecord {
+        if !self.values && matches!(record.values, DiffValues::Single(_)) {
+            record.values = DiffValues::Empty;
+        }
+        record
+    }
+
+    /// The printed line of a record.
+    fn line(&self, record: DiffRecord) -> String {
+        self.messages.record(&self.shape(record))
+    }
+
+    /// Options printing the records colored, see [`report::format_diffs_with_options`].
+    fn format_options(&self) -> FormatOptions {
+        FormatOptions::default()
+            .color(ColorMode::Always)
+            .values(self.values)
+            .explain(self.explain)
+            .sorted(self.sorted)
+            .messages(self.messages.clone())
+    }
+
+    /// The records of `mismatch` in the printed order.
+    fn records(&self, mismatch: &Mismatch) -> Vec<DiffRecord> {
+        match self.sorted {
+            true => mismatch.records_sorted(),
+            false => mismatch.records(),
+        }
+    }
+}
+
+fn main() {
     let args = Args::parse();
-    println!("Getting input");
-    let (json_1, json_2) = match args.cmd {
-        Mode::Direct { json_2, json_1 } => (json_1, json_2),
-        Mode::File { file_2, file_1 } => {
-            let d1 = vg_errortools::fat_io_wrap_std(file_1, &std::fs::read_to_string)?;
-            let d2 = vg_errortools::fat_io_wrap_std(file_2, &std::fs::read_to_string)?;
+    let mut stdout = std::io::stdout();
+    let status = run(args, &mut stdout).unwrap_or_else(|e| {
+        let code = e.code();
+        // io errors name their file themselves
+        match e.file() {
+            Some(file) if code != ErrorCode::Io => eprintln!("Error [{code}]: {file}: {e}"),
+            _ => eprintln!("Error [{code}]: {e}"),
+        }
+        exit_status(code)
+    });
+    let _ = stdout.flush();
+    std::process::exit(status);
+}
+
+/// Exit status of a run that compared its inputs, 0 if they pass and 1 if they differ.
+fn compared(passed: bool) -> i32 {
+    match passed {
+        true => 0,
+        false => 1,
+    }
+}
+
+/// Exit status for a failed run, 1 is reserved for inputs that differ. Io and parse errors exit with 2 like all errors
+/// without a status of their own.
+fn exit_status(code: ErrorCode) -> i32 {
+    match code {
+        ErrorCode::Structure
+        | ErrorCode::LimitExceeded
+        | ErrorCode::KeyCollision
+        | ErrorCode::RefCycle
+        | ErrorCode::UnexpectedRootType => 5,
+        ErrorCode::Git => 6,
+        _ => 2,
+    }
+}
+
+/// Runs the command of `args`, printing the diffs to `out` and warnings to stderr.
+/// Returns the exit status, see [`compared`] and [`exit_status`].
+fn run(args: Args, out: &mut impl Write) -> Result<i32> {
+    let messages = match &args.messages {
+        Some(path) => {
+            serde_json::from_slice(&vg_errortools::fat_io_wrap_std(path, &std::fs::read)?)?
+        }
+        None => Messages::default(),
+    };
+    let classifier = match &args.classify {
+        Some(path) => {
+            let rules = vg_errortools::fat_io_wrap_std(path, &std::fs::read)?;
+            let classifier = Classifier::from_json(&serde_json::from_slice(&rules)?)?;
+            let categories = classifier.categories();
+            let mut fail_on = args.fail_on_category.iter().flatten();
+            if let Some(unknown) = fail_on.find(|name| !categories.contains(name.as_str())) {
+                return Err(Error::InvalidClassifier(format!(
+                    "--fail-on-category {unknown} names no category of the rules"
+                )));
+            }
+            Some(classifier)
+        }
+        None => None,
+    };
+    let report = Report {
+        explain: args.explain,
+        values: !args.no_values,
+        sorted: !args.unsorted,
+        messages: &messages,
+    };
+    // the patch, the graph and the json are printed as the only output, so they can be piped
+    let piped = matches!(args.cmd, Mode::Patch { .. })
+        || args.format != Format::Text
+        || args.page_size.is_some();
+    let progress = |out: &mut dyn Write, message: &str| match piped || args.stream {
+        true => Ok(()),
+        false => writeln!(out, "{message}").map_err(output_error),
+    };
+    if let Mode::ReportDiff { old, new } = &args.cmd {
+        return report_diff(old, new, out);
+    }
+    // conflicting options are reported before any input is read, once for all comparisons
+    let options = with_transforms(compare_options(&args), &args)?;
+    options.validate().map_err(Error::InvalidOptions)?;
+    if !args.quiet {
+        for conflict in options.conflicts() {
+            eprintln!("Warning: {conflict}");
+        }
+    }
+    if let Mode::Dirs { .. } | Mode::Csv { .. } = &args.cmd {
+        if args.multi_doc
+            || args.left_pointer.is_some()
+            || args.right_pointer.is_some()
+            || args.map.is_some()
+            || args.classify.is_some()
+        {
+            return Err(Error::Misc(
+                "dirs and csv do not support --multi-doc, pointers, --map or --classify"
+                    .to_string(),
+            ));
+        }
+    }
+    // the golden is overwritten with the compared actual, which would be a part or a mapped copy of it
+    if let Mode::Accept { .. } = &args.cmd {
+        if args.left_pointer.is_some() || args.right_pointer.is_some() || args.map.is_some() {
+            return Err(Error::Misc(
+                "accept does not support pointers or --map, it writes the whole actual input"
+                    .to_string(),
+            ));
+        }
+    }
+    if let Mode::Dirs { left, right } = &args.cmd {
+        return compare_dir_trees(left, right, &options, report, out);
+    }
+    if let Mode::Prepare { input, snapshot } = &args.cmd {
+        let json = read_input(input, Side::Left, args.max_input_size)?;
+        let prepared = PreparedDocument::parse(&options.build()?, &json)
+            .map_err(|e| e.with_context(ErrorContext::default().file(input)))?;
+        prepared.save(snapshot)?;
+        outln!(out, "Prepared {snapshot}");
+        return Ok(0);
+    }
+    if let Mode::Csv {
+        left,
+        right,
+        json_cols,
+        key_col,
+        tsv,
+    } = &args.cmd
+    {
+        let table_options = TableOptions::default().compare_options(options);
+        let table_options = match key_col {
+            Some(column) => table_options.key_column(column),
+            None => table_options,
+        };
+        let table_options = match tsv {
+            true => table_options.tsv(),
+            false => table_options,
+        };
+        let read = |path: &str, side: Side| {
+            let table = read_input(path, side, args.max_input_size)?;
+            String::from_utf8(table).map_err(|e| {
+                io_error(
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+                    path,
+                )
+            })
+        };
+        let tables = (read(left, Side::Left)?, read(right, Side::Right)?);
+        return compare_tables(&tables, json_cols, &table_options, report, out);
+    }
+    if (args.format != Format::Text || args.page_size.is_some() || args.top.is_some() || args.stream)
+        && (args.multi_doc
+            || !matches!(
+                args.cmd,
+                Mode::File { .. } | Mode::Direct { .. } | Mode::Git { .. } | Mode::Stdin
+            ))
+    {
+        return Err(Error::Misc(
+            "--format dot and json, --page-size, --top and --stream support single file and direct comparisons only"
+                .to_string(),
+        ));
+    }
+    progress(out, "Getting input")?;
+    // documents loaded from snapshots, their json is left empty
+    let mut prepared = (None, None);
+    let (json_1, json_2) = match &args.cmd {
+        Mode::Direct { json_2, json_1 } => {
+            (json_1.clone().into_bytes(), json_2.clone().into_bytes())
+        }
+        Mode::Dirs { .. } | Mode::ReportDiff { .. } | Mode::Csv { .. } | Mode::Prepare { .. } => {
+            unreachable!(
+                "directories, reports and tables are compared and snapshots prepared above"
+            )
+        }
+        Mode::Git { rev_1, rev_2, path } => {
+            (read_revision(rev_1, path)?, read_revision(rev_2, path)?)
+        }
+        Mode::Stdin => split_documents(&read_input(STDIN, Side::Left, args.max_input_size)?)?,
+        Mode::File { file_1, file_2 } if file_1 == STDIN && file_2 == STDIN => {
+            split_documents(&read_input(STDIN, Side::Left, args.max_input_size)?)?
+        }
+        Mode::Accept {
+            actual: file_1,
+            golden: file_2,
+            ..
+        } => {
+            let d1 = read_input(file_1, Side::Left, args.max_input_size)?;
+            let d2 = read_input(file_2, Side::Right, args.max_input_size)?;
+            (d1, d2)
+        }
+        Mode::File { file_2, file_1 }
+        | Mode::Patch {
+            left: file_1,
+            right: file_2,
+            ..
+        } => {
+            let (d1, p1) = read_document(file_1, Side::Left, &options, args.max_input_size)?;
+            let (d2, p2) = read_document(file_2, Side::Right, &options, args.max_input_size)?;
+            prepared = (p1, p2);
             (d1, d2)
         }
     };
-    println!("Evaluation exclusion regex list");
-    let exclusion_keys = args
-        .exclude_keys
-        .as_ref()
-        .map(|v| {
-            v.iter()
-                .map(|k| regex::Regex::new(k).map_err(|e| e.into()))
-                .collect::<Result<Vec<regex::Regex>>>()
-                .unwrap_or_default()
-        })
+    if matches!(args.cmd, Mode::Patch { .. }) && args.sort_arrays {
+        return Err(Error::Misc(
+            "patch does not support --sort-arrays, its indices would refer to the sorted arrays"
+                .to_string(),
+        ));
+    }
+    progress(out, "Evaluation exclusion regex list")?;
+    let comparator = options.build()?;
+    let comparing = match &options.labels {
+        Some(labels) => format!("Comparing {} with {}", labels.left, labels.right),
+        None => "Comparing".to_string(),
+    };
+    if args.multi_doc {
+        if matches!(args.cmd, Mode::Accept { .. } | Mode::Patch { .. }) {
+            return Err(Error::Misc(
+                "accept and patch do not support --multi-doc".to_string(),
+            ));
+        }
+        if prepared.0.is_some() || prepared.1.is_some() {
+            return Err(Error::Misc(
+                "--multi-doc does not support snapshots".to_string(),
+            ));
+        }
+        progress(out, &comparing)?;
+        let mismatches = comparator.compare_multi_document_slices(&json_1, &json_2)?;
+        progress(out, "Printing results")?;
+        let mut comparison_result = true;
+        for (index, mismatch) in mismatches.into_iter().enumerate() {
+            print_warnings(&mismatch, args.quiet);
+            if mismatch.is_empty() {
+                continue;
+            }
+            outln!(out, "Document {index}:");
+            comparison_result &= if args.group_by_root {
+                check_diffs_grouped(&mismatch, report, out)?
+            } else {
+                check_diffs(&mismatch, report, out)?
+            };
+        }
+        return Ok(compared(comparison_result));
+    }
+    // only a byte order mark and whitespace are skipped, never a prefix warned about
+    let parse = |prepared: Option<serde_json::Value>, json: &[u8], side: Side| match prepared {
+        Some(value) => Ok::<_, Error>(value),
+        None => comparator.parse(json, side).map(|(value, _)| value),
+    };
+    let value_1 = parse(prepared.0, &json_1, Side::Left)?;
+    let value_2 = parse(prepared.1, &json_2, Side::Right)?;
+    let left = select(&value_1, Side::Left, &args)?;
+    let right = select(&value_2, Side::Right, &args)?;
+    let mapped;
+    let left = match &args.map {
+        Some(map) => {
+            mapped = map_left(left, map, args.quiet)?;
+            &mapped
+        }
+        None => left,
+    };
+    if args.stream {
+        return stream_diffs(left, right, &options, report, args.max_diffs, out);
+    }
+    progress(out, &comparing)?;
+    let mismatch = comparator.compare(left, right)?;
+    print_warnings(&mismatch, args.quiet);
+    if let Mode::Patch { check, .. } = &args.cmd {
+        return print_patch(&mismatch, left, right, *check, out);
+    }
+    match args.format {
+        Format::Text => {}
+        Format::Dot if classifier.is_some() => {
+            return Err(Error::Misc("--classify does not support --format dot".to_string()));
+        }
+        Format::Dot => {
+            write!(out, "{}", report::dot(&mismatch)).map_err(output_error)?;
+            return Ok(compared(mismatch.verdict() == Verdict::Pass));
+        }
+        Format::Json => {
+            let passed = mismatch.verdict() == Verdict::Pass;
+            let (diffs, passed) = match &classifier {
+                Some(classifier) => {
+                    let classified = mismatch.classified_diffs(classifier);
+                    let fail_on = args.fail_on_category.as_deref();
+                    let diffs = report::to_json_classified(&mismatch, classifier);
+                    (diffs, categories_pass(&mismatch, &classified, fail_on).unwrap_or(passed))
+                }
+                None => (report::to_json_by_side(&mismatch), passed),
+            };
+            outln!(out, "{}", serde_json::to_string_pretty(&diffs)?);
+            return Ok(compared(passed));
+        }
+    }
+    if let Some(page_size) = args.page_size {
+        let pages = report::to_value_paged(&mismatch, page_size);
+        let page = pages.get(args.page).ok_or_else(|| {
+            Error::Misc(format!(
+                "page {} does not exist, the report has {} pages",
+                args.page,
+                pages.len()
+            ))
+        })?;
+        outln!(out, "{}", serde_json::to_string_pretty(page)?);
+        return Ok(compared(mismatch.verdict() == Verdict::Pass));
+    }
+    if let Some(k) = args.top {
+        let scorer = match args.rank_by {
+            Rank::NumericDelta => numeric_delta,
+            Rank::ValueSize => value_size,
+            Rank::SizeChange => size_change,
+        };
+        progress(out, "Printing results")?;
+        for (score, diff_type, entry) in mismatch.top_diffs(k, scorer) {
+            let line = report.line(DiffRecord::from_entry(diff_type, &entry));
+            outln!(out, "{line} (score {score})");
+        }
+        return Ok(compared(mismatch.verdict() == Verdict::Pass));
+    }
+    progress(out, "Printing results")?;
+    let comparison_result = if args.group_by_root {
+        check_diffs_grouped(&mismatch, report, out)?
+    } else if let ColorMode::Always = color_mode(args.color) {
+        let diffs = report::format_diffs_with_options(&mismatch, &report.format_options());
+        write!(out, "{diffs}").map_err(output_error)?;
+        mismatch.verdict() == Verdict::Pass
+    } else {
+        check_diffs(&mismatch, report, out)?
+    };
+    let comparison_result = match &classifier {
+        Some(classifier) => {
+            let fail_on = args.fail_on_category.as_deref();
+            check_categories(&mismatch, classifier, fail_on, report, out)?
+                .unwrap_or(comparison_result)
+        }
+        None => comparison_result,
+    };
+    if let Mode::Accept {
+        golden,
+        yes,
+        no_backup,
+        ..
+    } = &args.cmd
+    {
+        if comparison_result {
+            return Ok(0);
+        }
+        if !*yes && !confirm(golden)? {
+            outln!(out, "Golden {golden} not updated");
+            return Ok(1);
+        }
+        write_golden(Path::new(golden), &to_canonical_string(left)?, !*no_backup)?;
+        outln!(out, "Updated golden {golden}");
+        return Ok(0);
+    }
+    Ok(compared(comparison_result))
+}
+
+/// Adds the transformations read from the files of --transform-left and --transform-right to `options`.
+fn with_transforms(options: CompareOptions, args: &Args) -> Result<CompareOptions> {
+    let read = |path: &String| -> Result<Transform> {
+        let transform = vg_errortools::fat_io_wrap_std(path, &std::fs::read)?;
+        Transform::from_json(&serde_json::from_slice(&transform)?)
+            .map_err(|e| e.with_context(ErrorContext::default().file(path)))
+    };
+    let options = match &args.transform_left {
+        Some(path) => options.pre_transform_left(read(path)?),
+        None => options,
+    };
+    Ok(match &args.transform_right {
+        Some(path) => options.pre_transform_right(read(path)?),
+        None => options,
+    })
+}
+
+/// The subdocument of `value` at `--left-pointer` or `--right-pointer` of `side`, or `value` itself with a warning
+/// if the pointer does not resolve and `--unwrap-fallback` is given.
+fn select<'v>(value: &'v Value, side: Side, args: &Args) -> Result<&'v Value> {
+    let pointer = match side {
+        Side::Left => &args.left_pointer,
+        Side::Right => &args.right_pointer,
+    };
+    let Some(pointer) = pointer else {
+        return Ok(value);
+    };
+    match extract(value, pointer) {
+        Ok(inner) => Ok(inner),
+        Err(Error::PointerNotFound(_, _)) if args.unwrap_fallback => {
+            if !args.quiet {
+                let pointer = pointer.clone();
+                eprintln!("Warning: {}", Warning::UnwrapFallback { side, pointer });
+            }
+            Ok(value)
+        }
+        Err(Error::PointerNotFound(pointer, ancestor)) => {
+            let context = ErrorContext::default().side(side).pointer(&pointer);
+            Err(Error::UnwrapNotFound(side, pointer, ancestor).with_context(context))
+        }
+        Err(e) => Err(e.with_context(ErrorContext::default().side(side))),
+    }
+}
+
+/// Applies the key mapping read from the file `map` to the left input, warnings go to stderr unless `quiet`.
+fn map_left(left: &Value, map: &str, quiet: bool) -> Result<Value> {
+    let mapping = vg_errortools::fat_io_wrap_std(map, &std::fs::read)?;
+    let mapping = Mapping::from_json(&serde_json::from_slice(&mapping)?)?;
+    let (mapped, warnings) = apply_mapping_with_warnings(left, &mapping)?;
+    if !quiet {
+        for warning in warnings {
+            eprintln!("Warning: {warning}");
+        }
+    }
+    Ok(mapped)
+}
+
+/// Prints the warnings of a comparison to stderr unless `quiet`, options are reported once up front.
+fn print_warnings(mismatch: &Mismatch, quiet: bool) {
+    if quiet {
+        return;
+    }
+    for warning in &mismatch.warnings {
+        if !matches!(warning, Warning::Options(_)) {
+            eprintln!("Warning: {warning}");
+        }
+    }
+}
+
+/// Parses a `--identity-key-at` rule like `/users=email` into its pointer and key.
+fn identity_rule(rule: &str) -> std::result::Result<(String, String), String> {
+    match rule.rsplit_once('=') {
+        Some((pointer, key)) if !key.is_empty() => Ok((pointer.to_string(), key.to_string())),
+        _ => Err(format!(
+            "expected POINTER=KEY, e.g. /users=email, not {rule:?}"
+        )),
+    }
+}
+
+fn compare_options(args: &Args) -> CompareOptions {
+    let options = CompareOptions::default()
+        .sort_arrays(args.sort_arrays)
+        .ignore_key_patterns(args.exclude_keys.clone().unwrap_or_default())
+        .include_key_patterns(args.include_keys.clone().unwrap_or_default())
+        .ignore_path_patterns(args.exclude_paths.clone().unwrap_or_default())
+        .ignore_value_patterns(args.ignore_values.clone().unwrap_or_default())
+        .record_origins(args.explain)
+        .unwrap_fallback(args.unwrap_fallback)
+        .require_container_roots(args.require_container)
+        .null_equals_missing(args.null_equals_missing)
+        .resolve_internal_refs(args.resolve_refs)
+        .parse_mode(ParseMode::Tolerant { skip_prefix: false })
+        .max_input_bytes(Some(args.max_input_size));
+    let options = match args.key_case {
+        Some(KeyCase::Lower) => options.key_normalizer(normalize::lowercase),
+        Some(KeyCase::Snake) => options.key_normalizer(normalize::snake_case),
+        Some(KeyCase::Camel) => options.key_normalizer(normalize::camel_case),
+        None => options,
+    };
+    // the comparator unwraps every document of a stream, single documents are selected up front,
+    // so that --map and patch see the subdocuments, see `select`
+    let options = match (&args.left_pointer, args.multi_doc) {
+        (Some(pointer), true) => options.unwrap_left(pointer),
+        _ => options,
+    };
+    let options = match (&args.right_pointer, args.multi_doc) {
+        (Some(pointer), true) => options.unwrap_right(pointer),
+        _ => options,
+    };
+    let options = args
+        .exclude_pointers
+        .iter()
+        .flatten()
+        .fold(options, |options, pointer| options.ignore_pointer(pointer));
+    let options = args.identity_key_at.iter().fold(
+        options.identity_keys(&args.identity_key),
+        |options, (pointer, key)| options.identity_key_at(pointer, key),
+    );
+    let options = args
+        .must_differ
+        .iter()
+        .flatten()
+        .fold(options, |options, pointer| {
+            options.expect(pointer, Expectation::MustDiffer)
+        });
+    let paths = match &args.cmd {
+        Mode::File { file_1, file_2 } => Some((file_1.clone(), file_2.clone())),
+        Mode::Accept { actual, golden, .. } => Some((actual.clone(), golden.clone())),
+        Mode::Patch { left, right, .. }
+        | Mode::Dirs { left, right }
+        | Mode::Csv { left, right, .. } => Some((left.clone(), right.clone())),
+        Mode::Git { rev_1, rev_2, path } => {
+            Some((format!("{rev_1}:{path}"), format!("{rev_2}:{path}")))
+        }
+        Mode::Stdin => Some(("<stdin>".to_string(), "<stdin>".to_string())),
+        Mode::Direct { .. } | Mode::ReportDiff { .. } | Mode::Prepare { .. } => None,
+    };
+    let left = args.label_left.as_ref().or(paths.as_ref().map(|p| &p.0));
+    let right = args.label_right.as_ref().or(paths.as_ref().map(|p| &p.1));
+    match (left, right) {
+        (None, None) => options,
+        (left, right) => options.labels(
+            left.map_or("left", String::as_str),
+            right.map_or("right", String::as_str),
+        ),
+    }
+}
+
+/// Prints the diffs of each differing file pair below `left` and `right`. The status is 1 if any files differ,
+/// or the one of the first error if any file could not be compared.
+fn compare_dir_trees(
+    left: &str,
+    right: &str,
+    options: &CompareOptions,
+    report: Report<'_>,
+    out: &mut impl Write,
+) -> Result<i32> {
+    let dir_report = compare_dirs(left, right, options)?;
+    let labels = options.labels.clone().unwrap_or_default();
+    let mut failure = None;
+    for entry in dir_report.differing() {
+        let path = entry.path.display();
+        match &entry.outcome {
+            FileOutcome::Compared(mismatch) => {
+                outln!(out, "{path}:");
+                for record in report.records(mismatch) {
+                    outln!(out, "  {}", report.line(record));
+                }
+            }
+            FileOutcome::LeftOnly => outln!(out, "{path}: only in {}", labels.left),
+            FileOutcome::RightOnly => outln!(out, "{path}: only in {}", labels.right),
+            FileOutcome::Failed(e) => {
+                outln!(out, "{path}: Error [{}]: {e}", e.code());
+                failure = failure.or(Some(e.code()));
+            }
+        }
+    }
+    match failure {
+        Some(code) => Ok(exit_status(code)),
+        None => Ok(compared(dir_report.is_empty())),
+    }
+}
+
+/// Prints the diffs of each differing row of the left and right table, the status is 1 if any rows differ.
+fn compare_tables(
+    (left, right): &(String, String),
+    json_columns: &[String],
+    options: &TableOptions,
+    report: Report<'_>,
+    out: &mut impl Write,
+) -> Result<i32> {
+    let json_columns: Vec<&str> = json_columns.iter().map(String::as_str).collect();
+    let rows = compare_csv(left, right, &json_columns, options)?;
+    let labels = options.compare.labels.clone().unwrap_or_default();
+    for row in &rows {
+        let key = &row.key;
+        match &row.outcome {
+            RowOutcome::Compared(mismatch) => {
+                outln!(out, "Row {key}:");
+                for record in report.records(mismatch) {
+                    outln!(out, "  {}", report.line(record));
+                }
+            }
+            RowOutcome::LeftOnly => outln!(out, "Row {key}: only in {}", labels.left),
+            RowOutcome::RightOnly => outln!(out, "Row {key}: only in {}", labels.right),
+        }
+    }
+    Ok(compared(rows.is_empty()))
+}
+
+/// Prints the changes between the json reports `old` and `new`, the status is 1 if there are any.
+fn report_diff(old: &str, new: &str, out: &mut impl Write) -> Result<i32> {
+    let read = |path: &str| -> Result<Vec<DiffRecord>> {
+        let report = vg_errortools::fat_io_wrap_std(path, &std::fs::read)?;
+        records_from_json(&serde_json::from_slice(&report)?)
+    };
+    let delta = compare_reports(&read(old)?, &read(new)?);
+    for record in &delta.appeared {
+        outln!(out, "Appeared: {record}");
+    }
+    for record in &delta.disappeared {
+        outln!(out, "Disappeared: {record}");
+    }
+    for (before, after) in &delta.changed_values {
+        outln!(out, "Changed: {before}");
+        outln!(out, "     to: {after}");
+    }
+    Ok(compared(delta.is_empty()))
+}
+
+/// Prints the JSON patch of `mismatch`, with `check` the status is 1 if it does not turn `left` into `right`.
+fn print_patch(
+    mismatch: &Mismatch,
+    left: &Value,
+    right: &Value,
+    check: bool,
+    out: &mut impl Write,
+) -> Result<i32> {
+    let patch = to_json_patch(mismatch);
+    outln!(out, "{}", serde_json::to_string_pretty(&patch)?);
+    if check && !patch::check(left, right, &patch)? {
+        eprintln!("Applying the patch to the left input does not yield the right input");
+        return Ok(1);
+    }
+    Ok(0)
+}
+
+/// Asks whether to overwrite the golden, declines without asking if stdout is no terminal.
+fn confirm(golden: &str) -> Result<bool> {
+    if !std::io::stdout().is_terminal() {
+        return Ok(false);
+    }
+    print!("Overwrite {golden}? [y/N] ");
+    std::io::stdout().flush().map_err(|e| io_error(e, golden))?;
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| io_error(e, golden))?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Replaces the golden by writing a temporary file next to it and renaming it over the golden,
+/// so an interrupted update never leaves a truncated golden behind.
+fn write_golden(golden: &Path, contents: &str, backup: bool) -> Result<()> {
+    let file_name = golden
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
         .unwrap_or_default();
-    println!("Comparing");
-    let mismatch = compare_strs(&json_1, &json_2, args.sort_arrays, &exclusion_keys)?;
-    println!("Printing results");
-    let comparison_result = check_diffs(mismatch)?;
-    if !comparison_result {
-        std::process::exit(1);
+    let temporary = golden.with_file_name(format!(".{file_name}.{}.tmp", std::process::id()));
+    let write = || -> std::io::Result<()> {
+        let mut file = std::fs::File::create(&temporary)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()
+    };
+    if let Err(e) = write() {
+        let _ = std::fs::remove_file(&temporary);
+        return Err(FatIOError::from_std_io_err(e, temporary).into());
+    }
+    if backup {
+        let backup = golden.with_file_name(format!("{file_name}.bak"));
+        std::fs::copy(golden, &backup).map_err(|e| FatIOError::from_std_io_err(e, backup))?;
     }
+    std::fs::rename(&temporary, golden)
+        .map_err(|e| FatIOError::from_std_io_err(e, golden.to_path_buf()))?;
     Ok(())
 }
 
-pub fn check_diffs(result: Mismatch) -> Result<bool> {
-    let mismatches = result.all_diffs();
-    let is_good = mismatches.is_empty();
-    for (d_type, key) in mismatches {
-        println!("{d_type}: {key}");
+/// Contents of `path` as committed in `rev`. A path missing in the revision is reported like a missing file,
+/// a failing git, e.g. outside of a repository or for an unknown revision, as [`Error::Git`].
+fn read_revision(rev: &str, path: &str) -> Result<Vec<u8>> {
+    // git show resolves paths relative to the repository root unless they start with ./ or ../
+    let relative = match path.starts_with("./") || path.starts_with("../") {
+        true => path.to_string(),
+        false => format!("./{path}"),
+    };
+    let shown = git(&["show", &format!("{rev}:{relative}")])?;
+    if shown.status.success() {
+        return Ok(shown.stdout);
+    }
+    if !git(&["rev-parse", "--git-dir"])?.status.success() {
+        return Err(Error::Git("not inside a git repository".to_string()));
+    }
+    let commit = format!("{rev}^{{commit}}");
+    if !git(&["rev-parse", "--verify", "--quiet", &commit])?
+        .status
+        .success()
+    {
+        return Err(Error::Git(format!("unknown revision {rev}")));
+    }
+    let missing = std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("{path} does not exist in revision {rev}"),
+    );
+    Err(io_error(missing, &format!("{rev}:{path}")))
+}
+
+fn git(args: &[&str]) -> Result<std::process::Output> {
+    std::process::Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|e| Error::Git(format!("git could not be run: {e}")))
+}
+
+/// Contents of the input file `path` of `side`, rejected with [`Error::InputTooLarge`] if it has more than `limit` bytes.
+/// Files reporting no size, like pipes, are read up to the limit. The path `-` reads stdin.
+fn read_input(path: &str, side: Side, limit: u64) -> Result<Vec<u8>> {
+    let too_large = |actual| {
+        let context = ErrorContext::default().file(path).side(side);
+        Error::InputTooLarge { actual, limit }.with_context(context)
+    };
+    let input: Box<dyn Read> = match path {
+        STDIN => Box::new(std::io::stdin()),
+        path => {
+            let file = std::fs::File::open(path).map_err(|e| io_error(e, path))?;
+            let size = file.metadata().map_err(|e| io_error(e, path))?.len();
+            if size > limit {
+                return Err(too_large(size));
+            }
+            Box::new(file)
+        }
+    };
+    let mut contents = Vec::new();
+    input
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut contents)
+        .map_err(|e| io_error(e, path))?;
+    match contents.len() as u64 > limit {
+        true => Err(too_large(contents.len() as u64)),
+        false => Ok(contents),
+    }
+}
+
+/// Splits the two concatenated documents of the `stdin` mode, e.g. `{"a": 1} {"a": 2}` or one per line.
+/// Parse errors name the document that failed, more or less than two documents are an error.
+fn split_documents(input: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    let mut documents = serde_json::Deserializer::from_slice(input).into_iter::<IgnoredAny>();
+    let mut end_of = |side: Side| -> Result<usize> {
+        let error = match documents.next() {
+            Some(Ok(_)) => return Ok(documents.byte_offset()),
+            Some(Err(e)) if side == Side::Left => Error::ParseLeft(e),
+            Some(Err(e)) => Error::ParseRight(e),
+            None => Error::Misc(format!(
+                "stdin holds no {side} document, expected two concatenated json documents"
+            )),
+        };
+        Err(error.with_context(ErrorContext::default().file("<stdin>").side(side)))
+    };
+    let (end_1, end_2) = (end_of(Side::Left)?, end_of(Side::Right)?);
+    if documents.next().is_some() {
+        return Err(Error::Misc(
+            "stdin holds more than two json documents".to_string(),
+        ));
+    }
+    Ok((input[..end_1].to_vec(), input[end_1..end_2].to_vec()))
+}
+
+/// Contents of the input file `path` of `side` like [`read_input`], or the document of a snapshot written by
+/// `prepare` with an empty json, see [`is_snapshot_path`].
+fn read_document(
+    path: &str,
+    side: Side,
+    options: &CompareOptions,
+    limit: u64,
+) -> Result<(Vec<u8>, Option<Value>)> {
+    if !is_snapshot_path(path) {
+        return Ok((read_input(path, side, limit)?, None));
+    }
+    let context = ErrorContext::default().file(path).side(side);
+    let prepared = PreparedDocument::load(path, &options.clone().build()?)
+        .map_err(|e| e.with_context(context))?;
+    Ok((Vec::new(), Some(prepared.into_value())))
+}
+
+fn io_error(e: std::io::Error, file: &str) -> json_diff_ng::Error {
+    FatIOError::from_std_io_err(e, file.into()).into()
+}
+
+/// Error writing to the output of [`run`].
+fn output_error(e: std::io::Error) -> json_diff_ng::Error {
+    io_error(e, "<stdout>")
+}
+
+/// Resolves --color, `auto` colors only output to a terminal, following <https://no-color.org>.
+fn color_mode(color: Color) -> ColorMode {
+    let terminal = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    match color {
+        Color::Always => ColorMode::Always,
+        Color::Auto if terminal => ColorMode::Always,
+        Color::Auto | Color::Never => ColorMode::Never,
+    }
+}
+
+fn check_diffs(result: &Mismatch, report: Report<'_>, out: &mut impl Write) -> Result<bool> {
+    for record in report.records(result) {
+        let expected = if result.is_expected(&record) {
+            " (expected)"
+        } else {
+            ""
+        };
+        let origin = record.origin;
+        let line = report.line(record);
+        match origin {
+            Some(origin) if report.explain => outln!(out, "{line}{expected} [{origin}]"),
+            _ => outln!(out, "{line}{expected}"),
+        }
+    }
+    Ok(result.verdict() == Verdict::Pass)
+}
+
+/// Prints each diff of `left` and `right` as soon as it is found, flushing the output per diff,
+/// and a summary line with their number. Stops after `max_diffs` diffs, if given.
+fn stream_diffs(
+    left: &Value,
+    right: &Value,
+    options: &CompareOptions,
+    report: Report<'_>,
+    max_diffs: Option<u64>,
+    out: &mut impl Write,
+) -> Result<i32> {
+    let (mut count, mut informational) = (0, 0);
+    let mut failed = None;
+    let flow = compare_with_sink(left, right, options, &mut |diff_type, path, values| {
+        let values = match values {
+            Some((Value::String(l), Value::String(r))) if diff_type == DiffType::KeyAlmostEqual => {
+                DiffValues::KeyVariants {
+                    left: l.clone(),
+                    right: r.clone(),
+                }
+            }
+            // one-sided diffs carry their value twice
+            Some((l, r)) if std::ptr::eq(l, r) => DiffValues::Single(l.clone()),
+            Some((l, r)) => DiffValues::Pair(l.clone(), r.clone()),
+            None => DiffValues::Empty,
+        };
+        let record = DiffRecord {
+            diff_type,
+            path: DiffPath(path.iter().map(Into::into).collect()),
+            values,
+            origin: None,
+            context: None,
+            string_diff: None,
+        };
+        informational += u64::from(record.is_informational());
+        if let Err(e) = writeln!(out, "{}", report.line(record)).and_then(|_| out.flush()) {
+            failed = Some(e);
+            return ControlFlow::Break(());
+        }
+        count += 1;
+        match max_diffs.is_some_and(|max| count >= max) {
+            true => ControlFlow::Break(()),
+            false => ControlFlow::Continue(()),
+        }
+    })?;
+    if let Some(e) = failed {
+        return Err(output_error(e));
+    }
+    match flow {
+        ControlFlow::Break(()) => outln!(out, "Stopped after {count} diffs"),
+        ControlFlow::Continue(()) => outln!(out, "{count} diffs"),
+    }
+    Ok(compared(count == informational))
+}
+
+/// Prints the number of diffs per category of `classifier`. With `fail_on` categories, returns whether the
+/// comparison is complete and all diffs of these categories are expected, diffs of other categories pass.
+fn check_categories(
+    mismatch: &Mismatch,
+    classifier: &Classifier,
+    fail_on: Option<&[String]>,
+    report: Report<'_>,
+    out: &mut impl Write,
+) -> Result<Option<bool>> {
+    let classified = mismatch.classified_diffs(classifier);
+    outln!(out, "{}", report.messages.categories);
+    for (category, records) in &classified {
+        let count: usize = records.iter().map(DiffRecord::count).sum();
+        outln!(out, "  {category}: {count}");
+    }
+    Ok(categories_pass(mismatch, &classified, fail_on))
+}
+
+/// With `fail_on` categories, whether the comparison is complete and all `classified` diffs of these categories
+/// are expected.
+fn categories_pass(
+    mismatch: &Mismatch,
+    classified: &BTreeMap<String, Vec<DiffRecord>>,
+    fail_on: Option<&[String]>,
+) -> Option<bool> {
+    fail_on.map(|fail_on| {
+        !mismatch.is_partial()
+            && classified
+                .iter()
+                .filter(|(category, _)| fail_on.contains(category))
+                .flat_map(|(_, records)| records)
+                .all(|record| mismatch.is_expected(record))
+    })
+}
+
+fn check_diffs_grouped(
+    result: &Mismatch,
+    report: Report<'_>,
+    out: &mut impl Write,
+) -> Result<bool> {
+    let groups = result.diffs_by_root_key();
+    let is_good = result.verdict() == Verdict::Pass;
+    for (root_key, diffs) in groups {
+        let section = root_key.as_deref().unwrap_or("<root>");
+        outln!(out, "{section} ({}):", diffs.len());
+        for (d_type, entry) in diffs {
+            outln!(
+                out,
+                "  {}",
+                report.line(DiffRecord::from_entry(d_type, &entry))
+            );
+        }
+    }
+    for record in &result.extra_records {
+        outln!(out, "{}", report.messages.record(record));
     }
     Ok(is_good)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run_direct(flags: &[&str], left: &str, right: &str) -> (Result<i32>, String) {
+        let inputs = ["direct", left, right];
+        let args = ["json_diff_ng"].iter().chain(flags).chain(&inputs);
+        let mut out = Vec::new();
+        let status = run(Args::try_parse_from(args).unwrap(), &mut out);
+        (status, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn run_writes_the_diffs_and_returns_the_status() {
+        let (status, out) = run_direct(&[], r#"{"a": 1}"#, r#"{"a": 2}"#);
+        assert_eq!(status.unwrap(), 1);
+        assert!(
+            out.ends_with("Printing results\nMismatched: .a.(1 != 2)\n"),
+            "{out}"
+        );
+        let (status, out) = run_direct(&["--format", "dot"], "[1]", "[1]");
+        assert_eq!(status.unwrap(), 0);
+        assert!(out.starts_with("digraph diff {"));
+        let (status, _) = run_direct(&[], "{", "{}");
+        assert_eq!(status.unwrap_err().code(), ErrorCode::ParseLeft);
+    }
+
+    #[test]
+    fn color_is_only_used_when_requested_or_on_a_terminal() {
+        let (status, out) = run_direct(&["--color", "always"], r#"{"a": 1}"#, r#"{"a": 2}"#);
+        assert_eq!(status.unwrap(), 1);
+        assert!(out.ends_with("<root>:\x1b[0m\n  \x1b[33m~ a: 1 → 2\x1b[0m\n"), "{out}");
+        // test output is captured, never a terminal
+        let (_, out) = run_direct(&["--color", "auto"], r#"{"a": 1}"#, r#"{"a": 2}"#);
+        assert!(out.ends_with("Mismatched: .a.(1 != 2)\n"), "{out}");
+        assert!(color_mode(Color::Never) == ColorMode::Never);
+    }
+}
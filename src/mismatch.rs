@@ -1,13 +1,741 @@
-use crate::enums::{DiffEntry, DiffType};
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BinaryHeap};
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::{Ordering, Reverse};
+use core::fmt::{Display, Formatter, Write};
+use core::hash::{Hash, Hasher};
+use core::time::Duration;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Value};
+
+use crate::enums::{
+    ArrayContext, DiffEntry, DiffPath, DiffRecord, DiffType, DiffValues, Origin, OwnedPathElement, PathElement,
+    Error, Side, SidePath,
+};
+use crate::process::OptionConflict;
+use crate::report::{Classifier, Labels};
+use crate::string_diff::StringDiff;
 use crate::DiffTreeNode;
 
 /// Structure holding the differences after a compare operation.
 /// For more readable access use the [`Mismatch::all_diffs`] method that yields a [`DiffEntry`] per diff.
-#[derive(Debug, PartialEq)]
+/// Equality only considers the diff trees and extra records, not the [`ResourceUsage`], array frames or origins of the run.
+///
+/// Serializes to an object of its three trees, see [`DiffTreeNode`] for their shape. Everything else, including the
+/// [`Mismatch::extra_records`], is left out and stays empty when deserializing, [`crate::report::to_json`] reports
+/// all records instead.
+/// ```rust
+/// use json_diff_ng::{compare_strs, Mismatch};
+/// use serde_json::json;
+/// let diffs = compare_strs(r#"{"a": 1, "b": [1, 2]}"#, r#"{"a": 2, "b": [1]}"#, false, &[]).unwrap();
+/// let serialized = serde_json::to_value(&diffs).unwrap();
+/// assert_eq!(
+///     serialized,
+///     json!({"left_only": {"b": {"$elements": [[1, [2, 2]]]}}, "right_only": null, "unequal_values": {"a": [1, 2]}})
+/// );
+/// assert_eq!(serde_json::from_value::<Mismatch>(serialized).unwrap(), diffs);
+/// ```
+#[derive(Clone, Debug)]
 pub struct Mismatch {
     pub left_only: DiffTreeNode,
     pub right_only: DiffTreeNode,
     pub unequal_values: DiffTreeNode,
+    /// Diffs not represented in the trees, like summaries of suppressed extras
+    pub extra_records: Vec<DiffRecord>,
+    /// Index bookkeeping of the compared arrays containing diffs, keyed by their reported path
+    pub(crate) array_frames: BTreeMap<DiffPath, ArrayFrame>,
+    /// Comparison pass of each diff, only filled with [`crate::CompareOptions::record_origins`]
+    pub origins: Origins,
+    /// Non-fatal anomalies of the comparison, like identities shared by several elements of an array.
+    /// They do not count as diffs, so [`Mismatch::is_empty`] and equality ignore them
+    pub warnings: Vec<Warning>,
+    /// Names of the compared documents, see [`crate::CompareOptions::labels`]
+    pub labels: Option<Box<Labels>>,
+    /// JSON pointers whose values must differ, diffs at and below them do not fail the [`Mismatch::verdict`],
+    /// see [`crate::Expectation::MustDiffer`]
+    pub expected_changes: Vec<String>,
+    /// Preprocessing of the compared documents that the reported paths reflect, see [`Mismatch::resolve_entry`]
+    pub normalization: Normalization,
+    /// Minimum length of the mismatched strings whose records carry a [`DiffRecord::string_diff`],
+    /// see [`crate::CompareOptions::string_diffs`]
+    pub string_diffs: Option<usize>,
+    pub usage: ResourceUsage,
+}
+
+/// Outcome of a comparison, see [`Mismatch::verdict`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Pass,
+    Fail,
+}
+
+/// Preprocessing the compared documents went through, see [`Mismatch::normalization`].
+/// Paths of a normalized comparison do not necessarily resolve in the original documents.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Normalization {
+    /// Arrays were sorted or filtered, reported indices refer to the compared arrays,
+    /// see [`crate::CompareOptions::sort_arrays`] and [`crate::CompareOptions::filter_array_elements`]
+    pub arrays: bool,
+    /// Only a subdocument of the left input was compared, see [`crate::CompareOptions::unwrap_left`]
+    pub left_unwrapped: bool,
+    /// The left input was edited before comparing, see [`crate::CompareOptions::pre_transform_left`]
+    pub left_transformed: bool,
+}
+
+impl Normalization {
+    /// Whether any preprocessing was applied.
+    pub fn is_applied(&self) -> bool {
+        self.arrays || self.left_unwrapped || self.left_transformed
+    }
+}
+
+/// Non-fatal anomaly noticed during a comparison, see [`Mismatch::warnings`].
+/// Its [`Display`] form is the message, [`Warning::kind`] names the variant in structured reports.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Warning {
+    /// Options that do not work as one might expect, see [`crate::CompareOptions::conflicts`]
+    Options(OptionConflict),
+    /// Several elements of the array at `path` share an identity on `side` and are paired in order,
+    /// see [`crate::CompareOptions::array_identity`]. The identity is `None` without value capture.
+    DuplicateIdentity {
+        path: DiffPath,
+        side: Side,
+        identity: Option<String>,
+        occurrences: usize,
+    },
+    /// The unwrap pointer of `side` does not resolve, so the whole document of the side was compared,
+    /// see [`crate::CompareOptions::unwrap_fallback`]
+    UnwrapFallback { side: Side, pointer: String },
+    /// The object at `path` has several `keys` on `side` of the same canonical form, which are only matched
+    /// exactly, see [`crate::CompareOptions::canonical_keys`]
+    CanonicalKeyCollision {
+        path: DiffPath,
+        side: Side,
+        keys: Vec<String>,
+    },
+    /// The serialized input of `side` had `bytes` before its document that were skipped,
+    /// see [`crate::ParseMode::Tolerant`]
+    SkippedPrefix { side: Side, bytes: usize },
+    /// The `$ref` at `path` on `side` refers to another document, so it was compared as it is,
+    /// see [`crate::CompareOptions::resolve_internal_refs`]
+    ExternalRef {
+        path: DiffPath,
+        side: Side,
+        reference: String,
+    },
+}
+
+impl Warning {
+    /// Path the anomaly was noticed at, the document root for options.
+    pub fn path(&self) -> DiffPath {
+        match self {
+            Warning::Options(_) | Warning::UnwrapFallback { .. } | Warning::SkippedPrefix { .. } => {
+                DiffPath::default()
+            }
+            Warning::DuplicateIdentity { path, .. }
+            | Warning::CanonicalKeyCollision { path, .. }
+            | Warning::ExternalRef { path, .. } => path.clone(),
+        }
+    }
+
+    /// Stable `snake_case` name of the variant.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Warning::Options(_) => "options",
+            Warning::DuplicateIdentity { .. } => "duplicate_identity",
+            Warning::UnwrapFallback { .. } => "unwrap_fallback",
+            Warning::CanonicalKeyCollision { .. } => "canonical_key_collision",
+            Warning::SkippedPrefix { .. } => "skipped_prefix",
+            Warning::ExternalRef { .. } => "external_ref",
+        }
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Warning::Options(conflict) => write!(f, "{conflict}"),
+            Warning::DuplicateIdentity {
+                side,
+                identity,
+                occurrences,
+                ..
+            } => write!(
+                f,
+                "Identity {} occurs {occurrences} times on the {side}, its elements are paired in order",
+                identity.as_deref().unwrap_or("of an element")
+            ),
+            Warning::UnwrapFallback { side, pointer } => write!(
+                f,
+                "JSON pointer {pointer} does not resolve in the {side} document, it is compared as a whole"
+            ),
+            Warning::CanonicalKeyCollision { side, keys, .. } => {
+                write!(f, "Keys ")?;
+                for (i, key) in keys.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { ", " };
+                    write!(f, "{separator}{key:?}")?;
+                }
+                write!(
+                    f,
+                    " on the {side} share a canonical form, they are only matched exactly"
+                )
+            }
+            Warning::SkippedPrefix { side, bytes } => write!(
+                f,
+                "Skipped {bytes} bytes before the document of the {side} input"
+            ),
+            Warning::ExternalRef {
+                side, reference, ..
+            } => write!(
+                f,
+                "$ref {reference} on the {side} refers to another document, it is compared as it is"
+            ),
+        }
+    }
+}
+
+/// Scorer for [`Mismatch::top_diffs`]: the absolute difference of mismatched numbers, `0` for other diffs.
+pub fn numeric_delta(_: &DiffType, entry: &DiffEntry) -> f64 {
+    match entry.values {
+        Some((Value::Number(l), Value::Number(r))) if l != r => match (l.as_f64(), r.as_f64()) {
+            (Some(l), Some(r)) => (l - r).abs(),
+            _ => 0.0,
+        },
+        _ => 0.0,
+    }
+}
+
+/// Scorer for [`Mismatch::top_diffs`]: bytes of the compact json of the larger value of the diff,
+/// e.g. the size of a subtree present on one side only. `0` for diffs without captured values.
+pub fn value_size(_: &DiffType, entry: &DiffEntry) -> f64 {
+    entry
+        .values
+        .map_or(0, |(l, r)| compact_len(l).max(compact_len(r))) as f64
+}
+
+/// Scorer for [`Mismatch::top_diffs`]: the absolute [`crate::SizeDelta::bytes`] of mismatched strings, arrays and objects,
+/// `0` for other diffs.
+pub fn size_change(_: &DiffType, entry: &DiffEntry) -> f64 {
+    entry
+        .size_delta()
+        .map_or(0.0, |delta| delta.bytes.unsigned_abs() as f64)
+}
+
+/// Bytes of the compact json of `value`, counted without serializing it into a buffer.
+pub(crate) fn compact_len(value: &Value) -> usize {
+    let mut counter = ByteCounter(0);
+    // counting cannot fail
+    let _ = write!(counter, "{value}");
+    counter.0
+}
+
+/// Counts the bytes written to it.
+struct ByteCounter(usize);
+
+impl core::fmt::Write for ByteCounter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+/// A diff kept by [`Mismatch::top_diffs`], ordered by score and then by reverse position.
+struct Ranked<'a> {
+    score: f64,
+    position: usize,
+    diff_type: DiffType,
+    entry: DiffEntry<'a>,
+}
+
+impl Ord for Ranked<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then(other.position.cmp(&self.position))
+    }
+}
+
+impl PartialOrd for Ranked<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Ranked<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked<'_> {}
+
+/// Captured containers seen so far by their [`structural_hash`].
+#[derive(Default)]
+pub(crate) struct SharedValues(BTreeMap<u64, Vec<Arc<Value>>>);
+
+impl SharedValues {
+    /// A copy of `value` to capture in a diff, the one captured before if an equal container was,
+    /// so that a subtree found at many paths is only copied once.
+    pub(crate) fn capture(&mut self, value: &Value) -> Arc<Value> {
+        if !matches!(value, Value::Array(_) | Value::Object(_)) {
+            return Arc::new(value.clone());
+        }
+        let candidates = self.0.entry(structural_hash(value)).or_default();
+        match candidates.iter().find(|candidate| candidate.as_ref() == value) {
+            Some(candidate) => candidate.clone(),
+            None => {
+                let value = Arc::new(value.clone());
+                candidates.push(value.clone());
+                value
+            }
+        }
+    }
+
+    fn share(&mut self, node: &mut DiffTreeNode) {
+        match node {
+            DiffTreeNode::Value(l, r) => {
+                let one_sided = Arc::ptr_eq(l, r);
+                *l = self.get(l);
+                *r = match one_sided {
+                    true => l.clone(),
+                    false => self.get(r),
+                };
+            }
+            DiffTreeNode::Node(o) => o.values_mut().for_each(|child| self.share(child)),
+            DiffTreeNode::Array(v) => v.iter_mut().for_each(|(_, child)| self.share(child)),
+            DiffTreeNode::Null | DiffTreeNode::Redacted(_, _) => {}
+        }
+    }
+
+    /// The first seen value equal to `value`, which becomes it if there is none.
+    fn get(&mut self, value: &Arc<Value>) -> Arc<Value> {
+        if !matches!(value.as_ref(), Value::Array(_) | Value::Object(_)) {
+            return value.clone();
+        }
+        let candidates = self.0.entry(structural_hash(value)).or_default();
+        match candidates.iter().find(|candidate| candidate == &value) {
+            Some(candidate) => candidate.clone(),
+            None => {
+                candidates.push(value.clone());
+                value.clone()
+            }
+        }
+    }
+}
+
+/// Hash of `value` that is the same for equal values, regardless of the order of object keys.
+pub(crate) fn structural_hash(value: &Value) -> u64 {
+    let mut hasher = Fnv::default();
+    hash_value(value, &mut hasher);
+    hasher.finish()
+}
+
+fn hash_value(value: &Value, hasher: &mut Fnv) {
+    match value {
+        Value::Null => 0u8.hash(hasher),
+        Value::Bool(b) => (1u8, b).hash(hasher),
+        Value::Number(n) => (2u8, n).hash(hasher),
+        Value::String(s) => (3u8, s).hash(hasher),
+        Value::Array(a) => {
+            (4u8, a.len()).hash(hasher);
+            a.iter().for_each(|v| hash_value(v, hasher));
+        }
+        Value::Object(o) => {
+            // equal objects may list their keys in different order
+            let members = o.iter().fold(0u64, |sum, (k, v)| {
+                let mut member = Fnv::default();
+                k.hash(&mut member);
+                hash_value(v, &mut member);
+                sum.wrapping_add(mix(member.finish()))
+            });
+            (5u8, o.len(), members).hash(hasher);
+        }
+    }
+}
+
+/// Scrambles the hash of an object member before it is summed up, otherwise the same change of the last byte in two
+/// members, e.g. `{"name": "item 1", "n": "1"}` and `{"name": "item 5", "n": "5"}`, can cancel out in the sum.
+fn mix(hash: u64) -> u64 {
+    let hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    let hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
+/// Hash of `value` that is the same across runs and platforms, unlike the one of the `std` hasher.
+#[cfg(feature = "snapshot")]
+pub(crate) fn stable_hash(value: impl Hash) -> u64 {
+    let mut hasher = Fnv::default();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// FNV-1a, which needs no `std` and is fast for the short writes of [`hash_value`].
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// Comparison pass that produced each diff of a [`Mismatch`], keyed by diff type and reported path.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Origins(BTreeMap<(DiffType, DiffPath), Origin>);
+
+impl Origins {
+    pub fn get(&self, diff_type: DiffType, path: &DiffPath) -> Option<Origin> {
+        self.0.get(&(diff_type, path.clone())).copied()
+    }
+
+    /// Records `origin` for a diff directly at `element`.
+    pub(crate) fn insert_at(
+        &mut self,
+        diff_type: DiffType,
+        element: OwnedPathElement,
+        origin: Origin,
+    ) {
+        self.0.insert((diff_type, DiffPath(vec![element])), origin);
+    }
+
+    /// Records `origin` for a diff at `path`, relative to the mismatch holding these origins.
+    pub(crate) fn insert(&mut self, diff_type: DiffType, path: DiffPath, origin: Origin) {
+        self.0.insert((diff_type, path), origin);
+    }
+
+    /// Forgets the origin of a diff directly at `element`.
+    pub(crate) fn remove_at(&mut self, diff_type: DiffType, element: OwnedPathElement) {
+        self.0.remove(&(diff_type, DiffPath(vec![element])));
+    }
+
+    /// Records `origin` for the diff `node` found at `element`, if it is a leaf.
+    pub(crate) fn record(
+        &mut self,
+        diff_type: DiffType,
+        element: &OwnedPathElement,
+        node: &DiffTreeNode,
+        origin: Origin,
+    ) {
+        if let DiffTreeNode::Value(_, _) = node {
+            self.insert_at(diff_type, element.clone(), origin);
+        }
+    }
+
+    /// Moves the origins of a child mismatch found at `element` into these.
+    pub(crate) fn adopt(&mut self, child: Origins, element: &OwnedPathElement) {
+        for ((diff_type, mut path), origin) in child.0 {
+            path.0.insert(0, element.clone());
+            self.0.insert((diff_type, path), origin);
+        }
+    }
+}
+
+/// How the indices of a compared array relate to the original documents, see [`Mismatch::paths_for_side`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct ArrayFrame {
+    /// Original index of each element of the compared left array, `None` if it was compared unchanged
+    pub left_origins: Option<Vec<usize>>,
+    /// Original index of each element of the compared right array, `None` if it was compared unchanged
+    pub right_origins: Option<Vec<usize>>,
+    /// Compared right index of each compared left index with diffs below it
+    pub pairs: BTreeMap<usize, usize>,
+    /// Equal runs of the array, only kept with [`crate::CompareOptions::array_context`]
+    pub anchors: Option<Anchors>,
+}
+
+/// Runs of elements equal on both sides of a compared array, see [`crate::CompareOptions::array_context`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Anchors {
+    /// Number of equal elements noted on each side of a diff
+    pub size: usize,
+    /// Runs of equal elements as compared left index, compared right index and length, in order
+    pub equal: Vec<(usize, usize, usize)>,
+}
+
+impl Anchors {
+    /// The equal elements nearest to the element at compared `index` of `side`, found by a binary search for
+    /// the run at `index` and walking at most [`Anchors::size`] elements to either side of it.
+    pub fn around(&self, side: Side, index: usize) -> ArrayContext {
+        let start = |&(left, right, _): &(usize, usize, usize)| match side {
+            Side::Left => left,
+            Side::Right => right,
+        };
+        // runs before `split` end before `index`, the run at `split` may contain it
+        let split = self
+            .equal
+            .partition_point(|run| start(run) + run.2 <= index);
+        let mut before: Vec<_> = self.equal[..(split + 1).min(self.equal.len())]
+            .iter()
+            .rev()
+            .flat_map(|run @ &(left, right, len)| {
+                let below = index.saturating_sub(start(run)).min(len);
+                (0..below).rev().map(move |i| (left + i, right + i))
+            })
+            .take(self.size)
+            .collect();
+        before.reverse();
+        let after = self.equal[split..]
+            .iter()
+            .flat_map(|run @ &(left, right, len)| {
+                let above = (index + 1).saturating_sub(start(run));
+                (above..len).map(move |i| (left + i, right + i))
+            })
+            .take(self.size)
+            .collect();
+        ArrayContext { before, after }
+    }
+}
+
+impl ArrayFrame {
+    fn origin(origins: &Option<Vec<usize>>, index: usize) -> Option<usize> {
+        match origins {
+            Some(origins) => origins.get(index).copied(),
+            None => Some(index),
+        }
+    }
+}
+
+/// Resources spent by a single comparison run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// Wall-clock time of the comparison, excluding parsing, zero without the `std` feature
+    pub duration: Duration,
+    /// Number of value pairs visited by the comparison
+    pub compared_nodes: usize,
+    /// Deepest nesting level visited, the root being level 1
+    pub max_depth: usize,
+    /// Scalar leaf counts, only collected with [`crate::CompareOptions::collect_counts`]
+    pub leaves: Option<LeafCounts>,
+    /// Array elements and object keys left out by [`crate::CompareOptions::sample`]
+    pub skipped_by_sampling: usize,
+}
+
+/// Number of scalar leaves (null, booleans, numbers and strings) a comparison run covered.
+/// Leaves below ignored keys and filtered array elements are not covered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LeafCounts {
+    /// Leaves of the left document, including those present on the left only
+    pub left: usize,
+    /// Leaves of the right document, including those present on the right only
+    pub right: usize,
+    /// Leaves equal on both sides, counted once per pair
+    pub equal: usize,
+}
+
+/// Bounded rendering of the diffs of a [`Mismatch`], see [`Mismatch::display_summary`].
+pub struct DiffSummary<'a> {
+    mismatch: &'a Mismatch,
+    max_entries: usize,
+}
+
+impl DiffSummary<'_> {
+    /// Order of the groups, the types of the trees first.
+    const GROUPS: [DiffType; 10] = [
+        DiffType::Mismatch,
+        DiffType::TypeChange,
+        DiffType::LeftExtra,
+        DiffType::RightExtra,
+        DiffType::RootMismatch,
+        DiffType::MultiplicityChange,
+        DiffType::DimensionMismatch,
+        DiffType::ExpectedChangeMissing,
+        DiffType::KeyAlmostEqual,
+        DiffType::OrderOnly,
+    ];
+
+    /// Writes the diffs of `diff_type` while the budget of `shown` lasts, with a heading before the first one.
+    fn write_group(
+        &self,
+        f: &mut Formatter<'_>,
+        diff_type: DiffType,
+        shown: &mut usize,
+    ) -> core::fmt::Result {
+        let mismatch = self.mismatch;
+        let mut heading = false;
+        let mut result = Ok(());
+        let mut line = |f: &mut Formatter<'_>, body: &dyn Display| {
+            if *shown >= self.max_entries || result.is_err() {
+                return;
+            }
+            if !heading {
+                heading = true;
+                result = write!(f, "\n{diff_type}:");
+            }
+            *shown += 1;
+            result = result.and_then(|_| write!(f, "\n  {body}"));
+        };
+        let tree = match diff_type {
+            DiffType::Mismatch | DiffType::TypeChange => Some(&mismatch.unequal_values),
+            DiffType::LeftExtra => Some(&mismatch.left_only),
+            DiffType::RightExtra => Some(&mismatch.right_only),
+            _ => None,
+        };
+        if let Some(tree) = tree {
+            tree.visit_diffs(&mut Vec::new(), &mut |entry| {
+                let entry_type = match entry.is_type_change() {
+                    true => DiffType::TypeChange,
+                    false => tree_type(diff_type),
+                };
+                if entry_type == diff_type {
+                    line(f, &entry);
+                }
+            });
+        }
+        for record in &mismatch.extra_records {
+            if record.diff_type == diff_type {
+                line(f, &RecordBody(record));
+            }
+        }
+        result
+    }
+}
+
+/// The type of the tree holding the entries of `diff_type`.
+fn tree_type(diff_type: DiffType) -> DiffType {
+    match diff_type {
+        DiffType::TypeChange => DiffType::Mismatch,
+        diff_type => diff_type,
+    }
+}
+
+/// A record without its type.
+struct RecordBody<'r>(&'r DiffRecord);
+
+impl Display for RecordBody<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        self.0.write_body(f)
+    }
+}
+
+impl Display for DiffSummary<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let mismatch = self.mismatch;
+        let counts = mismatch.count_diffs();
+        write!(
+            f,
+            "{} differences: {} mismatched, {} extra on left, {} extra on right",
+            counts.total(),
+            counts.unequal_values,
+            counts.left_only,
+            counts.right_only
+        )?;
+        let mut shown = 0;
+        for diff_type in Self::GROUPS {
+            if shown >= self.max_entries {
+                break;
+            }
+            self.write_group(f, diff_type, &mut shown)?;
+        }
+        let lines = mismatch.unequal_values.count_diffs()
+            + mismatch.left_only.count_diffs()
+            + mismatch.right_only.count_diffs()
+            + mismatch.extra_records.len();
+        match lines - shown {
+            0 => Ok(()),
+            1 => write!(f, "\n… and 1 more difference"),
+            more => write!(f, "\n… and {more} more differences"),
+        }
+    }
+}
+
+/// Diffs at and below one element of an array, see [`Mismatch::per_element_summary`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ElementSummary {
+    /// Index of the element as reported in the paths of its diffs
+    pub index: usize,
+    /// Index of the element in the original left document, `None` if it only exists on the right
+    pub left_index: Option<usize>,
+    /// Index of the element in the original right document, `None` if it only exists on the left
+    pub right_index: Option<usize>,
+    /// Number of diffs per category at and below the element
+    pub counts: BTreeMap<DiffType, usize>,
+    /// Keys of the element with diffs at or below them, sorted
+    pub changed_fields: Vec<String>,
+}
+
+/// Number of diffs per category, see [`Mismatch::count_diffs`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DiffCounts {
+    pub left_only: usize,
+    pub right_only: usize,
+    pub unequal_values: usize,
+}
+
+impl DiffCounts {
+    pub fn total(&self) -> usize {
+        self.left_only + self.right_only + self.unequal_values
+    }
+}
+
+impl PartialEq for Mismatch {
+    fn eq(&self, other: &Self) -> bool {
+        self.left_only == other.left_only
+            && self.right_only == other.right_only
+            && self.unequal_values == other.unequal_values
+            && self.extra_records == other.extra_records
+    }
+}
+
+impl Default for Mismatch {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+/// Serialized form of a [`Mismatch`], its diff trees.
+#[derive(Serialize, Deserialize)]
+struct Trees<T> {
+    left_only: T,
+    right_only: T,
+    unequal_values: T,
+}
+
+impl Serialize for Mismatch {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        Trees {
+            left_only: &self.left_only,
+            right_only: &self.right_only,
+            unequal_values: &self.unequal_values,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Mismatch {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+        let trees = Trees::<DiffTreeNode>::deserialize(deserializer)?;
+        Ok(Mismatch::new(
+            trees.left_only,
+            trees.right_only,
+            trees.unequal_values,
+        ))
+    }
+}
+
+/// A single tree converts into a [`Mismatch`] holding it as [`Mismatch::unequal_values`],
+/// use [`Mismatch::with_left_only`] and [`Mismatch::with_right_only`] to fill the other slots.
+impl From<DiffTreeNode> for Mismatch {
+    fn from(unequal_values: DiffTreeNode) -> Self {
+        Self::default().with_unequal(unequal_values)
+    }
 }
 
 impl Mismatch {
@@ -16,6 +744,15 @@ impl Mismatch {
             left_only: l,
             right_only: r,
             unequal_values: u,
+            extra_records: Vec::new(),
+            array_frames: BTreeMap::new(),
+            origins: Origins::default(),
+            warnings: Vec::new(),
+            labels: None,
+            expected_changes: Vec::new(),
+            normalization: Normalization::default(),
+            string_diffs: None,
+            usage: ResourceUsage::default(),
         }
     }
 
@@ -24,44 +761,1342 @@ impl Mismatch {
             left_only: DiffTreeNode::Null,
             unequal_values: DiffTreeNode::Null,
             right_only: DiffTreeNode::Null,
+            extra_records: Vec::new(),
+            array_frames: BTreeMap::new(),
+            origins: Origins::default(),
+            warnings: Vec::new(),
+            labels: None,
+            expected_changes: Vec::new(),
+            normalization: Normalization::default(),
+            string_diffs: None,
+            usage: ResourceUsage::default(),
         }
     }
 
+    /// Replaces the tree of values present on the left side only:
+    /// ```rust
+    /// use json_diff_ng::{DiffTreeNode, Mismatch};
+    /// let mismatch = Mismatch::default()
+    ///     .with_left_only(DiffTreeNode::object([("gone", DiffTreeNode::leaf(1, 1))]))
+    ///     .with_unequal(DiffTreeNode::object([("changed", DiffTreeNode::leaf(1, 2))]));
+    /// assert_eq!(mismatch.count_diffs().total(), 2);
+    /// ```
+    pub fn with_left_only(mut self, left_only: DiffTreeNode) -> Self {
+        self.left_only = left_only;
+        self
+    }
+
+    /// Replaces the tree of values present on the right side only.
+    pub fn with_right_only(mut self, right_only: DiffTreeNode) -> Self {
+        self.right_only = right_only;
+        self
+    }
+
+    /// Replaces the tree of values differing between both sides.
+    pub fn with_unequal(mut self, unequal_values: DiffTreeNode) -> Self {
+        self.unequal_values = unequal_values;
+        self
+    }
+
+    /// Names of the compared documents, `left` and `right` unless set with [`crate::CompareOptions::labels`].
+    pub fn labels(&self) -> Labels {
+        self.labels.as_deref().cloned().unwrap_or_default()
+    }
+
+    /// Whether both documents are equal, ignoring informational [`DiffType::OrderOnly`] and [`DiffType::KeyAlmostEqual`] records.
+    /// A [partial](Mismatch::is_partial) result is never empty, see [`Mismatch::is_empty_in_sample`].
     pub fn is_empty(&self) -> bool {
+        !self.is_partial() && self.is_empty_in_sample()
+    }
+
+    /// Whether the compared subset of a [partial](Mismatch::is_partial) result holds no diffs,
+    /// which does not tell whether the documents are equal.
+    pub fn is_empty_in_sample(&self) -> bool {
         self.left_only == DiffTreeNode::Null
             && self.unequal_values == DiffTreeNode::Null
             && self.right_only == DiffTreeNode::Null
+            && self
+                .extra_records
+                .iter()
+                .all(|record| record.is_informational())
+    }
+
+    /// Replaces all values captured in the diffs by their kinds, see [`crate::CompareOptions::no_value_capture`].
+    pub fn redact_values(&mut self) {
+        self.left_only.redact_values();
+        self.right_only.redact_values();
+        self.unequal_values.redact_values();
+        for record in &mut self.extra_records {
+            record.values.redact();
+        }
     }
 
-    pub fn all_diffs(&self) -> Vec<(DiffType, DiffEntry)> {
-        let both = self
-            .unequal_values
-            .get_diffs()
+    /// Makes leaves holding equal arrays or objects share one allocation, e.g. when the same default
+    /// object was added at hundreds of paths. Comparisons share them while capturing the values already,
+    /// this is for mismatches assembled otherwise.
+    pub fn share_values(&mut self) {
+        let mut shared = SharedValues::default();
+        shared.share(&mut self.left_only);
+        shared.share(&mut self.right_only);
+        shared.share(&mut self.unequal_values);
+    }
+
+    /// Whether the comparison skipped parts of the documents, see [`crate::CompareOptions::sample`].
+    pub fn is_partial(&self) -> bool {
+        self.usage.skipped_by_sampling > 0
+    }
+
+    /// Whether `record` lies at or below a path whose values must differ, see [`Mismatch::expected_changes`].
+    pub fn is_expected(&self, record: &DiffRecord) -> bool {
+        record.diff_type != DiffType::ExpectedChangeMissing && {
+            let pointer = record.path.to_pointer();
+            self.expected_changes
+                .iter()
+                .any(|expected| crate::pointer::is_within(&pointer, expected))
+        }
+    }
+
+    /// Whether the comparison passes: a complete comparison without diffs besides informational and
+    /// [expected](Mismatch::is_expected) ones, and no [`DiffType::ExpectedChangeMissing`] record.
+    /// Without expectations this is [`Mismatch::is_empty`].
+    pub fn verdict(&self) -> Verdict {
+        let passed = match self.expected_changes.is_empty() {
+            true => self.is_empty(),
+            false => {
+                !self.is_partial()
+                    && self
+                        .records()
+                        .iter()
+                        .all(|record| record.is_informational() || self.is_expected(record))
+            }
+        };
+        match passed {
+            true => Verdict::Pass,
+            false => Verdict::Fail,
+        }
+    }
+
+    /// Whether any sorted array differs in order, as reported with [`crate::CompareOptions::report_order_differences`].
+    /// Callers treating order as significant check this besides [`Mismatch::is_empty`].
+    pub fn has_order_differences(&self) -> bool {
+        self.extra_records
+            .iter()
+            .any(|record| record.diff_type == DiffType::OrderOnly)
+    }
+
+    /// Counts the diffs per category without collecting them, including diffs only summarized in [`Mismatch::extra_records`].
+    pub fn count_diffs(&self) -> DiffCounts {
+        let mut counts = DiffCounts {
+            left_only: self.left_only.count_diffs(),
+            right_only: self.right_only.count_diffs(),
+            unequal_values: self.unequal_values.count_diffs(),
+        };
+        for record in &self.extra_records {
+            match record.diff_type {
+                DiffType::LeftExtra => counts.left_only += record.count(),
+                DiffType::RightExtra => counts.right_only += record.count(),
+                DiffType::Mismatch
+                | DiffType::RootMismatch
+                | DiffType::MultiplicityChange
+                | DiffType::DimensionMismatch
+                | DiffType::ExpectedChangeMissing
+                | DiffType::TypeChange => counts.unequal_values += record.count(),
+                // lookalike keys are counted as extras, unless they are matched
+                DiffType::OrderOnly | DiffType::KeyAlmostEqual => {}
+            }
+        }
+        counts
+    }
+
+    /// All diffs as owned [`DiffRecord`]s, followed by the [`Mismatch::extra_records`].
+    pub fn records(&self) -> Vec<DiffRecord> {
+        self.all_diffs_with_type_changes()
+            .iter()
+            .map(|(diff_type, entry)| {
+                let mut record = DiffRecord::from_entry(*diff_type, entry);
+                // type changes are leaves of the tree of unequal values
+                let tree_type = match record.diff_type {
+                    DiffType::TypeChange => DiffType::Mismatch,
+                    diff_type => diff_type,
+                };
+                record.origin = self.origins.get(tree_type, &record.path);
+                record.context = self.array_context(&record);
+                record.string_diff = self.string_diff(&record);
+                record
+            })
+            .chain(self.extra_records.iter().cloned())
+            .collect()
+    }
+
+    /// Changed lines of the mismatched strings of `record`, if either is at least [`Mismatch::string_diffs`] long.
+    fn string_diff(&self, record: &DiffRecord) -> Option<StringDiff> {
+        let min_len = self.string_diffs?;
+        match &record.values {
+            DiffValues::Pair(Value::String(l), Value::String(r))
+                if l.len().max(r.len()) >= min_len =>
+            {
+                Some(StringDiff::between(l, r))
+            }
+            _ => None,
+        }
+    }
+
+    /// Equal elements around the element of the innermost array containing `record`, if it has [`Anchors`].
+    fn array_context(&self, record: &DiffRecord) -> Option<ArrayContext> {
+        let elements = &record.path.0;
+        elements.iter().enumerate().rev().find_map(|(n, element)| {
+            let OwnedPathElement::ArrayEntry(index) = element else {
+                return None;
+            };
+            let anchors = self.array_frames.get(&DiffPath(elements[..n].to_vec()))?;
+            // only whole elements extra on the right are indexed into the right array
+            let side = match record.diff_type == DiffType::RightExtra && n + 1 == elements.len() {
+                true => Side::Right,
+                false => Side::Left,
+            };
+            Some(anchors.anchors.as_ref()?.around(side, *index))
+        })
+    }
+
+    /// All diffs like [`Mismatch::records`], with paths translated to the original document of `side`.
+    /// Reported paths index into the compared arrays, which are the sorted or filtered left arrays
+    /// with [`crate::CompareOptions::sort_arrays`] or [`crate::CompareOptions::filter_array_elements`].
+    /// The translated paths resolve against the untouched document of `side` instead.
+    /// Object keys are kept as reported, i.e. in left spelling when keys are normalized.
+    pub fn paths_for_side(&self, side: Side) -> Vec<(DiffType, SidePath)> {
+        self.records()
+            .iter()
+            .map(|record| (record.diff_type, self.path_for_side(record, side)))
+            .collect()
+    }
+
+    /// One summary per element of the array at `array_path` with diffs at or below it, in order of their reported
+    /// index, e.g. to list the changed records of a large array instead of all changed leaves.
+    /// Indices of the elements in the original documents are translated like by [`Mismatch::paths_for_side`].
+    /// ```rust
+    /// use json_diff_ng::prelude::OwnedPathElement;
+    /// use json_diff_ng::{compare_serde_values, DiffPath, DiffType};
+    /// use serde_json::json;
+    /// let left = json!({"items": [{"id": 1, "a": 1}, {"id": 2, "a": 2, "b": 3}]});
+    /// let right = json!({"items": [{"id": 1, "a": 1}, {"id": 2, "a": 4, "c": 5}]});
+    /// let diffs = compare_serde_values(&left, &right, false, &[]).unwrap();
+    /// let items = DiffPath(vec![OwnedPathElement::Object("items".into())]);
+    /// let summaries = diffs.per_element_summary(&items);
+    /// assert_eq!(summaries.len(), 1);
+    /// assert_eq!((summaries[0].left_index, summaries[0].right_index), (Some(1), Some(1)));
+    /// assert_eq!(summaries[0].counts[&DiffType::Mismatch], 1);
+    /// assert_eq!(summaries[0].changed_fields, ["a", "b", "c"]);
+    /// ```
+    pub fn per_element_summary(&self, array_path: &DiffPath) -> Vec<ElementSummary> {
+        let depth = array_path.0.len();
+        let mut summaries: BTreeMap<usize, ElementSummary> = BTreeMap::new();
+        for record in self.records() {
+            let elements = &record.path.0;
+            let Some(OwnedPathElement::ArrayEntry(index)) = elements.get(depth) else {
+                continue;
+            };
+            if !elements.starts_with(&array_path.0) {
+                continue;
+            }
+            let original_index = |side| match self.path_for_side(&record, side).path.0.get(depth) {
+                Some(OwnedPathElement::ArrayEntry(i)) => Some(*i),
+                _ => None,
+            };
+            let summary = summaries.entry(*index).or_insert_with(|| ElementSummary {
+                index: *index,
+                ..ElementSummary::default()
+            });
+            summary.left_index = summary.left_index.or(original_index(Side::Left));
+            summary.right_index = summary.right_index.or(original_index(Side::Right));
+            *summary.counts.entry(record.diff_type).or_default() += record.count();
+            if let Some(OwnedPathElement::Object(field)) = elements.get(depth + 1) {
+                if !summary.changed_fields.contains(field) {
+                    summary.changed_fields.push(field.clone());
+                }
+            }
+        }
+        summaries
+            .into_values()
+            .map(|mut summary| {
+                summary.changed_fields.sort();
+                summary
+            })
+            .collect()
+    }
+
+    /// Resolves `entry` of this mismatch against the untouched left input, where [`DiffEntry::resolve`] needs the
+    /// normalized one. Indices into sorted or filtered arrays are translated like by [`Mismatch::paths_for_side`],
+    /// values missing on the left resolve to `None`. Fails with [`Error::RequiresNormalizedDocument`] if the paths
+    /// cannot be translated, i.e. if only a subdocument of the left input was compared.
+    /// ```rust
+    /// use json_diff_ng::compare_serde_values;
+    /// use serde_json::json;
+    /// let left = json!([{"id": 2, "v": "b"}, {"id": 1, "v": "a"}]);
+    /// let right = json!([{"id": 1, "v": "a"}, {"id": 2, "v": "c"}]);
+    /// let diffs = compare_serde_values(&left, &right, true, &[]).unwrap();
+    /// let (_, entry) = &diffs.all_diffs()[0];
+    /// assert_eq!(entry.to_string(), r#".[1].v.("b" != "c")"#);
+    /// // the reported index refers to the sorted array
+    /// assert_eq!(entry.resolve(&left), Some(&json!("a")));
+    /// assert_eq!(diffs.resolve_entry(entry, &left).unwrap(), Some(&json!("b")));
+    /// ```
+    pub fn resolve_entry<'v>(
+        &self,
+        entry: &DiffEntry,
+        original_left: &'v Value,
+    ) -> Result<Option<&'v Value>, Error> {
+        if self.normalization.left_unwrapped || self.normalization.left_transformed {
+            return Err(Error::RequiresNormalizedDocument);
+        }
+        if !self.normalization.arrays {
+            return Ok(entry.resolve(original_left));
+        }
+        let diff_type = entry.diff_type.unwrap_or(DiffType::Mismatch);
+        let side_path = self.path_for_side(&DiffRecord::from_entry(diff_type, entry), Side::Left);
+        if !side_path.exists {
+            return Ok(None);
+        }
+        Ok(side_path
+            .path
+            .elements()
+            .iter()
+            .try_fold(original_left, |value, element| element.resolve(value)))
+    }
+
+    fn path_for_side(&self, record: &DiffRecord, side: Side) -> SidePath {
+        // only one-sided leaves end in an element missing on the other side
+        let leaf_side = match (&record.values, record.diff_type) {
+            (DiffValues::Single(_) | DiffValues::Redacted(_, _), DiffType::LeftExtra) => {
+                Some(Side::Left)
+            }
+            (DiffValues::Single(_) | DiffValues::Redacted(_, _), DiffType::RightExtra) => {
+                Some(Side::Right)
+            }
+            _ => None,
+        };
+        let mut reported = DiffPath::default();
+        let mut translated = DiffPath::default();
+        let last = record.path.0.len().saturating_sub(1);
+        for (n, element) in record.path.0.iter().enumerate() {
+            let is_leaf = n == last && leaf_side.is_some();
+            if is_leaf && leaf_side != Some(side) {
+                return SidePath {
+                    path: translated,
+                    exists: false,
+                };
+            }
+            let translated_element = match element {
+                OwnedPathElement::Object(key) => Some(OwnedPathElement::Object(key.clone())),
+                OwnedPathElement::ArrayEntry(i) => {
+                    let index = match (self.array_frames.get(&reported), side) {
+                        (None, _) => Some(*i),
+                        (Some(frame), Side::Left) => ArrayFrame::origin(&frame.left_origins, *i),
+                        (Some(frame), Side::Right) if is_leaf => {
+                            ArrayFrame::origin(&frame.right_origins, *i)
+                        }
+                        (Some(frame), Side::Right) => frame
+                            .pairs
+                            .get(i)
+                            .and_then(|j| ArrayFrame::origin(&frame.right_origins, *j)),
+                    };
+                    index.map(OwnedPathElement::ArrayEntry)
+                }
+            };
+            let Some(translated_element) = translated_element else {
+                return SidePath {
+                    path: translated,
+                    exists: false,
+                };
+            };
+            reported.0.push(element.clone());
+            translated.0.push(translated_element);
+        }
+        SidePath {
+            path: translated,
+            exists: true,
+        }
+    }
+
+    pub fn all_diffs(&self) -> Vec<(DiffType, DiffEntry<'_>)> {
+        self.entries()
             .into_iter()
-            .map(|k| (DiffType::Mismatch, k));
-        let left = self
-            .left_only
-            .get_diffs()
+            .filter_map(|entry| Some((entry.diff_type?, entry)))
+            .collect()
+    }
+
+    /// All diffs like [`Mismatch::all_diffs`], with mismatches of an object and an array classified as
+    /// [`DiffType::TypeChange`], like the [`Mismatch::records`] of the reports are.
+    /// ```rust
+    /// use json_diff_ng::{compare_serde_values, DiffType};
+    /// use serde_json::json;
+    /// let diffs = compare_serde_values(&json!({"a": {}, "b": 1}), &json!({"a": [], "b": 2}), false, &[]).unwrap();
+    /// let types: Vec<DiffType> = diffs.all_diffs_with_type_changes().iter().map(|(t, _)| *t).collect();
+    /// assert_eq!(types, [DiffType::TypeChange, DiffType::Mismatch]);
+    /// assert_eq!(diffs.all_diffs()[0].0, DiffType::Mismatch);
+    /// ```
+    pub fn all_diffs_with_type_changes(&self) -> Vec<(DiffType, DiffEntry<'_>)> {
+        let mut diffs = Vec::new();
+        self.visit_entries(true, &mut |diff_type, entry| diffs.push((diff_type, entry)));
+        diffs
+    }
+
+    /// All diffs of the trees with their [`DiffEntry::diff_type`] set: mismatches, then left and right extras.
+    /// ```rust
+    /// use json_diff_ng::{compare_serde_values, DiffType};
+    /// use serde_json::json;
+    /// let diffs = compare_serde_values(&json!({"a": 1, "b": 2}), &json!({"a": 3}), false, &[]).unwrap();
+    /// let entries = diffs.entries();
+    /// assert_eq!(entries[1].diff_type, Some(DiffType::LeftExtra));
+    /// assert_eq!(format!("{:#}", entries[0]), "Mismatched: .a.(1 != 3)");
+    /// ```
+    pub fn entries(&self) -> Vec<DiffEntry<'_>> {
+        let mut entries = Vec::new();
+        self.visit_entries(false, &mut |_, entry| entries.push(entry));
+        entries
+    }
+
+    /// Passes each entry of [`Mismatch::entries`] with its type to `visit`, without collecting them.
+    /// With `type_changes`, see [`Mismatch::all_diffs_with_type_changes`].
+    fn visit_entries<'a>(
+        &'a self,
+        type_changes: bool,
+        visit: &mut dyn FnMut(DiffType, DiffEntry<'a>),
+    ) {
+        let trees = [
+            (&self.unequal_values, DiffType::Mismatch),
+            (&self.left_only, DiffType::LeftExtra),
+            (&self.right_only, DiffType::RightExtra),
+        ];
+        for (tree, tree_type) in trees {
+            tree.visit_diffs(&mut Vec::new(), &mut |entry| {
+                let type_change = type_changes && entry.is_type_change();
+                let diff_type = match tree_type == DiffType::Mismatch && type_change {
+                    true => DiffType::TypeChange,
+                    false => tree_type,
+                };
+                let entry = DiffEntry {
+                    diff_type: Some(diff_type),
+                    ..entry
+                };
+                visit(diff_type, entry);
+            });
+        }
+    }
+
+    /// The `k` diffs of the highest score by `scorer`, highest first, diffs of equal score in the order of
+    /// [`Mismatch::all_diffs`]. Only `k` entries are held at a time, diffs scored `NaN` are skipped and
+    /// [`Mismatch::extra_records`] are not considered. See [`numeric_delta`] and [`value_size`] for built-in scorers.
+    /// ```rust
+    /// use json_diff_ng::compare_serde_values;
+    /// use json_diff_ng::prelude::numeric_delta;
+    /// use serde_json::json;
+    /// let left = json!({"cpu": 1.5, "mem": 512, "disk": 10});
+    /// let right = json!({"cpu": 2.0, "mem": 1024, "disk": 40});
+    /// let diffs = compare_serde_values(&left, &right, false, &[]).unwrap();
+    /// let top: Vec<String> = diffs.top_diffs(2, numeric_delta).iter().map(|(_, _, e)| e.to_string()).collect();
+    /// assert_eq!(top, [".mem.(512 != 1024)", ".disk.(10 != 40)"]);
+    /// ```
+    pub fn top_diffs(
+        &self,
+        k: usize,
+        scorer: impl Fn(&DiffType, &DiffEntry) -> f64,
+    ) -> Vec<(f64, DiffType, DiffEntry<'_>)> {
+        // the lowest ranked of the kept diffs on top, so it is the one replaced
+        let mut kept: BinaryHeap<Reverse<Ranked>> = BinaryHeap::with_capacity(k + 1);
+        let mut position = 0;
+        if k > 0 {
+            self.visit_entries(false, &mut |diff_type, entry| {
+                let score = scorer(&diff_type, &entry);
+                position += 1;
+                if score.is_nan() {
+                    return;
+                }
+                kept.push(Reverse(Ranked {
+                    score,
+                    position,
+                    diff_type,
+                    entry,
+                }));
+                if kept.len() > k {
+                    kept.pop();
+                }
+            });
+        }
+        kept.into_sorted_vec()
             .into_iter()
-            .map(|k| (DiffType::LeftExtra, k));
-        let right = self
-            .right_only
-            .get_diffs()
+            .map(|Reverse(ranked)| (ranked.score, ranked.diff_type, ranked.entry))
+            .collect()
+    }
+
+    /// All diffs like [`Mismatch::all_diffs`], ordered by path and then by [`DiffType`],
+    /// so diffs of all categories below the same key are listed next to each other.
+    pub fn all_diffs_sorted(&self) -> Vec<(DiffType, DiffEntry<'_>)> {
+        let mut diffs = self.all_diffs();
+        diffs.sort_by(|(t1, e1), (t2, e2)| e1.path.cmp(&e2.path).then(t1.cmp(t2)));
+        diffs
+    }
+
+    /// A rendering of at most `max_entries` diffs, one per line and grouped by type, below a line with the counts
+    /// of [`Mismatch::count_diffs`] and above a line with the number of diffs left out, e.g. for panic messages
+    /// of assertions on huge documents. The diffs are visited in place, without collecting them first.
+    /// ```rust
+    /// use json_diff_ng::compare_serde_values;
+    /// use serde_json::json;
+    /// let diffs = compare_serde_values(&json!({"a": 1, "b": 2, "c": 3}), &json!({"a": 2}), false, &[]).unwrap();
+    /// assert_eq!(
+    ///     diffs.display_summary(2).to_string(),
+    ///     "3 differences: 1 mismatched, 2 extra on left, 0 extra on right
+    /// Mismatched:
+    ///   .a.(1 != 2)
+    /// Extra on left:
+    ///   .b.(2)
+    /// … and 1 more difference"
+    /// );
+    /// ```
+    pub fn display_summary(&self, max_entries: usize) -> DiffSummary<'_> {
+        DiffSummary {
+            mismatch: self,
+            max_entries,
+        }
+    }
+
+    /// All records like [`Mismatch::records`], ordered like [`Mismatch::all_diffs_sorted`].
+    pub fn records_sorted(&self) -> Vec<DiffRecord> {
+        let mut records = self.records();
+        records.sort_by(|r1, r2| r1.path.cmp(&r2.path).then(r1.diff_type.cmp(&r2.diff_type)));
+        records
+    }
+
+    /// All records like [`Mismatch::records_sorted`], bucketed by their category of `classifier`.
+    /// Diffs matching no rule are in the [`crate::report::UNCATEGORIZED`] bucket, empty buckets are left out.
+    pub fn classified_diffs(&self, classifier: &Classifier) -> BTreeMap<String, Vec<DiffRecord>> {
+        let mut classified: BTreeMap<String, Vec<DiffRecord>> = BTreeMap::new();
+        for record in self.records_sorted() {
+            let category = classifier.category(&record).to_string();
+            classified.entry(category).or_default().push(record);
+        }
+        classified
+    }
+
+    /// Extracts the parts of `left` and `right` touched by diffs, e.g. to attach a small reproducer to a bug report.
+    /// Objects are reduced to the keys on the path to a diff, while arrays containing diffs are kept as a whole,
+    /// since the alignment of their elements depends on all of them.
+    /// Comparing the returned pair with the same options yields the same diffs as this [`Mismatch`].
+    /// Without any diffs, a pair of nulls is returned.
+    pub fn minimal_repro(&self, left: &Value, right: &Value) -> (Value, Value) {
+        if self.is_empty() {
+            return (Value::Null, Value::Null);
+        }
+        let mut keep = KeepTree::default();
+        for (_, entry) in self.all_diffs() {
+            keep.insert(&entry.path);
+        }
+        (keep.prune(left), keep.prune(right))
+    }
+
+    /// JSON merge patch turning the left document into `right`, the right document of the comparison,
+    /// see [`crate::patch::to_merge_patch`].
+    pub fn to_merge_patch(&self, right: &Value) -> Value {
+        crate::patch::to_merge_patch(self, right)
+    }
+
+    /// Groups all diffs by the top-level object key they are located under.
+    /// Diffs at the document root or below a root array are collected in the `None` bucket.
+    /// Within a group, entries are ordered by path and then by [`DiffType`].
+    pub fn diffs_by_root_key(&self) -> BTreeMap<Option<String>, Vec<(DiffType, DiffEntry<'_>)>> {
+        self.entries_by_root_key()
             .into_iter()
-            .map(|k| (DiffType::RightExtra, k));
+            .map(|(root_key, entries)| {
+                let entries = entries
+                    .into_iter()
+                    .filter_map(|entry| Some((entry.diff_type?, entry)))
+                    .collect();
+                (root_key, entries)
+            })
+            .collect()
+    }
+
+    /// Groups the [`Mismatch::entries`] like [`Mismatch::diffs_by_root_key`], keeping their types in the entries.
+    pub fn entries_by_root_key(&self) -> BTreeMap<Option<String>, Vec<DiffEntry<'_>>> {
+        let mut groups: BTreeMap<Option<String>, Vec<DiffEntry>> = BTreeMap::new();
+        for entry in self.entries() {
+            let root_key = match entry.path.first() {
+                Some(PathElement::Object(key)) => Some(key.to_string()),
+                _ => None,
+            };
+            groups.entry(root_key).or_default().push(entry);
+        }
+        for entries in groups.values_mut() {
+            entries.sort_by(|l, r| {
+                l.path
+                    .cmp(&r.path)
+                    .then_with(|| l.diff_type.cmp(&r.diff_type))
+            });
+        }
+        groups
+    }
+}
+
+/// Changes between two reports of diffs, see [`compare_reports`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReportDelta {
+    /// Records of the new report without counterpart in the old one, e.g. regressions
+    pub appeared: Vec<DiffRecord>,
+    /// Records of the old report without counterpart in the new one, e.g. fixes
+    pub disappeared: Vec<DiffRecord>,
+    /// Records present in both reports with differing values, as old and new record
+    pub changed_values: Vec<(DiffRecord, DiffRecord)>,
+}
+
+impl ReportDelta {
+    /// Whether both reports hold the same diffs.
+    pub fn is_empty(&self) -> bool {
+        self.appeared.is_empty() && self.disappeared.is_empty() && self.changed_values.is_empty()
+    }
+}
+
+/// Compares the records of two reports, e.g. of nightly runs, pairing them by path and [`DiffType`].
+/// Paired records with differing values are changed rather than appeared and disappeared.
+/// All buckets are ordered by path and type, several records at the same path and type are paired in order.
+/// ```rust
+/// use json_diff_ng::prelude::{compare_reports, compare_serde_values};
+/// use serde_json::json;
+/// let old = compare_serde_values(&json!({"a": 1, "b": 1}), &json!({"a": 2, "b": 1}), false, &[]).unwrap();
+/// let new = compare_serde_values(&json!({"a": 1, "b": 1}), &json!({"a": 3, "b": 2}), false, &[]).unwrap();
+/// let delta = compare_reports(&old.records(), &new.records());
+/// assert_eq!(delta.appeared[0].to_string(), "Mismatched: .b.(1 != 2)");
+/// assert_eq!(delta.changed_values[0].1.to_string(), "Mismatched: .a.(1 != 3)");
+/// ```
+pub fn compare_reports(old: &[DiffRecord], new: &[DiffRecord]) -> ReportDelta {
+    // old and new records of each path and type
+    type Sides<'r> = (Vec<&'r DiffRecord>, Vec<&'r DiffRecord>);
+    let mut keyed: BTreeMap<(&DiffPath, DiffType), Sides> = BTreeMap::new();
+    for record in old {
+        let key = (&record.path, record.diff_type);
+        keyed.entry(key).or_default().0.push(record);
+    }
+    for record in new {
+        let key = (&record.path, record.diff_type);
+        keyed.entry(key).or_default().1.push(record);
+    }
+    let mut delta = ReportDelta::default();
+    for (old, new) in keyed.into_values() {
+        for (old, new) in old.iter().zip(&new) {
+            if old.values != new.values {
+                delta.changed_values.push(((*old).clone(), (*new).clone()));
+            }
+        }
+        let paired = old.len().min(new.len());
+        delta
+            .disappeared
+            .extend(old[paired..].iter().map(|r| (*r).clone()));
+        delta
+            .appeared
+            .extend(new[paired..].iter().map(|r| (*r).clone()));
+    }
+    delta
+}
+
+/// Applies the diffs of `mismatch` at the `accepted` paths to a copy of `base`, the left document of the comparison,
+/// and leaves everything else as it is, e.g. for accepting changes one by one in a review. Accepted mismatches take
+/// the right value, left-only values are removed and right-only values added, all diffs at an accepted path apply.
+/// Right-only array elements are inserted next to the elements they follow on the right, so removals and insertions
+/// left out do not shift the accepted ones. Paths are taken as reported, like by [`crate::patch::to_json_patch`].
+/// Fails with [`Error::UnresolvedSelection`] listing the accepted paths that are no diffs of `mismatch`, carry no
+/// values or do not resolve in `base`.
+/// ```rust
+/// use json_diff_ng::prelude::{apply_selected, compare_serde_values, DiffPath};
+/// use serde_json::json;
+/// let left = json!({"name": "a", "tags": ["x", "y"]});
+/// let right = json!({"name": "b", "tags": ["y", "z"]});
+/// let diffs = compare_serde_values(&left, &right, false, &[]).unwrap();
+/// let accepted: Vec<DiffPath> = diffs.records().into_iter().map(|r| r.path).filter(|p| p.to_string() != ".name").collect();
+/// assert_eq!(apply_selected(&left, &diffs, &accepted).unwrap(), json!({"name": "a", "tags": ["y", "z"]}));
+/// ```
+pub fn apply_selected(
+    base: &Value,
+    mismatch: &Mismatch,
+    accepted: &[DiffPath],
+) -> Result<Value, Error> {
+    let records: Vec<DiffRecord> = mismatch
+        .all_diffs()
+        .iter()
+        .map(|(diff_type, entry)| DiffRecord::from_entry(*diff_type, entry))
+        .collect();
+    let mut unresolved: Vec<DiffPath> = accepted
+        .iter()
+        .filter(|path| !records.iter().any(|record| record.path == **path))
+        .cloned()
+        .collect();
+    let is_accepted = |record: &DiffRecord| accepted.contains(&record.path);
+    // changes of values and object keys keep all array indices valid, so they come first
+    let mut operations = Vec::new();
+    let mut arrays: BTreeMap<DiffPath, Vec<&DiffRecord>> = BTreeMap::new();
+    for record in &records {
+        match (record.diff_type, record.path.0.split_last()) {
+            (
+                DiffType::LeftExtra | DiffType::RightExtra,
+                Some((OwnedPathElement::ArrayEntry(_), parent)),
+            ) => arrays
+                .entry(DiffPath(parent.to_vec()))
+                .or_default()
+                .push(record),
+            _ if is_accepted(record) => {
+                operations.push((&record.path, operation(record, record.path.to_pointer())))
+            }
+            _ => {}
+        }
+    }
+    // arrays nested in elements of other arrays are changed first, their paths use indices of the untouched arrays
+    let mut arrays: Vec<(DiffPath, Vec<&DiffRecord>)> = arrays.into_iter().collect();
+    arrays.sort_by_key(|(path, _)| Reverse(path.0.len()));
+    for (path, elements) in arrays {
+        let pointer = path.to_pointer();
+        let indices = |diff_type: DiffType| -> Vec<(usize, &DiffRecord)> {
+            let mut indices: Vec<(usize, &DiffRecord)> = elements
+                .iter()
+                .filter(|record| record.diff_type == diff_type)
+                .filter_map(|record| match record.path.0.last() {
+                    Some(OwnedPathElement::ArrayEntry(index)) => Some((*index, *record)),
+                    _ => None,
+                })
+                .collect();
+            indices.sort_by_key(|(index, _)| *index);
+            indices
+        };
+        let (removed, inserted) = (indices(DiffType::LeftExtra), indices(DiffType::RightExtra));
+        // removing from the back keeps the indices of the elements still to remove valid
+        for (index, record) in removed.iter().rev().filter(|(_, r)| is_accepted(r)) {
+            operations.push((
+                &record.path,
+                operation(record, format!("{pointer}/{index}")),
+            ));
+        }
+        for (index, record) in inserted.iter().filter(|(_, r)| is_accepted(r)) {
+            // elements kept on both sides before the inserted one, with the rejected removals and accepted
+            // insertions between them
+            let kept = index - inserted.iter().filter(|(i, _)| i < index).count();
+            let rejected = removed
+                .iter()
+                .enumerate()
+                .filter(|(n, (i, r))| !is_accepted(r) && i - n <= kept)
+                .count();
+            let accepted_before = inserted
+                .iter()
+                .filter(|(i, r)| i < index && is_accepted(r))
+                .count();
+            let position = kept + rejected + accepted_before;
+            operations.push((
+                &record.path,
+                operation(record, format!("{pointer}/{position}")),
+            ));
+        }
+    }
+    let mut document = base.clone();
+    for (path, operation) in operations {
+        let applied = operation
+            .ok_or(String::new())
+            .and_then(|operation| crate::patch::apply_operation(&mut document, &operation));
+        if applied.is_err() && !unresolved.contains(path) {
+            unresolved.push(path.clone());
+        }
+    }
+    match unresolved.is_empty() {
+        true => Ok(document),
+        false => Err(Error::UnresolvedSelection(unresolved)),
+    }
+}
+
+/// JSON patch operation applying the diff of `record` at `pointer`, `None` if its values were not captured.
+fn operation(record: &DiffRecord, pointer: String) -> Option<Value> {
+    let op = match (record.diff_type, &record.values) {
+        (DiffType::LeftExtra, _) => json!({"op": "remove", "path": pointer}),
+        (DiffType::RightExtra, DiffValues::Single(value)) => {
+            json!({"op": "add", "path": pointer, "value": value})
+        }
+        (_, DiffValues::Pair(_, value)) => {
+            json!({"op": "replace", "path": pointer, "value": value})
+        }
+        _ => return None,
+    };
+    Some(op)
+}
 
-        both.chain(left).chain(right).collect()
+/// Object keys leading to diffs, as collected by [`Mismatch::minimal_repro`].
+#[derive(Default)]
+struct KeepTree {
+    whole: bool,
+    children: BTreeMap<String, KeepTree>,
+}
+
+impl KeepTree {
+    fn insert(&mut self, path: &[PathElement]) {
+        if self.whole {
+            return;
+        }
+        match path.first() {
+            Some(PathElement::Object(key)) => self
+                .children
+                .entry(key.to_string())
+                .or_default()
+                .insert(&path[1..]),
+            Some(PathElement::ArrayEntry(_)) | None => {
+                self.whole = true;
+                self.children.clear();
+            }
+        }
+    }
+
+    fn prune(&self, value: &Value) -> Value {
+        match value {
+            Value::Object(map) if !self.whole => Value::Object(
+                map.iter()
+                    .filter_map(|(k, v)| self.children.get(k).map(|c| (k.clone(), c.prune(v))))
+                    .collect(),
+            ),
+            _ => value.clone(),
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
+    use serde_json::{json, Map};
+
+    use crate::{compare_serde_values, compare_strs, ErrorCode, ValueKind};
+
     use super::*;
 
+    fn leaf_values(node: &DiffTreeNode) -> Vec<(Arc<Value>, Arc<Value>)> {
+        match node {
+            DiffTreeNode::Value(l, r) => vec![(l.clone(), r.clone())],
+            DiffTreeNode::Node(o) => o.values().flat_map(leaf_values).collect(),
+            DiffTreeNode::Array(v) => v.iter().flat_map(|(_, child)| leaf_values(child)).collect(),
+            DiffTreeNode::Null | DiffTreeNode::Redacted(_, _) => vec![],
+        }
+    }
+
+    #[test]
+    fn top_diffs_keep_the_highest_scores() {
+        let left = r#"{"a": 10, "b": {"c": -5, "d": 1}, "e": [1, 2.5], "f": "x", "gone": {"big": [1, 2, 3]}}"#;
+        let right = r#"{"a": 12, "b": {"c": 20, "d": 1.25}, "e": [1, 100], "f": "y", "new": 7}"#;
+        let diffs = compare_strs(left, right, false, &[]).unwrap();
+        let top = |k, scorer: fn(&DiffType, &DiffEntry) -> f64| -> Vec<(f64, String)> {
+            diffs
+                .top_diffs(k, scorer)
+                .iter()
+                .map(|(score, diff_type, entry)| (*score, format!("{diff_type}: {entry}")))
+                .collect()
+        };
+        assert_eq!(
+            top(2, numeric_delta),
+            vec![
+                (97.5, "Mismatched: .e.[1].(2.5 != 100)".to_string()),
+                (25.0, "Mismatched: .b.c.(-5 != 20)".to_string()),
+            ]
+        );
+        assert_eq!(
+            top(1, value_size),
+            vec![(
+                15.0,
+                r#"Extra on left: .gone.({"big":[1,2,3]})"#.to_string()
+            )]
+        );
+        assert!(top(0, numeric_delta).is_empty());
+        // ties keep the order of all_diffs
+        let all = diffs.top_diffs(10, |_, _| 1.0);
+        assert_eq!(all.len(), diffs.all_diffs().len());
+        assert!(all
+            .iter()
+            .zip(diffs.all_diffs())
+            .all(|((_, _, ranked), (_, entry))| *ranked == entry));
+        assert!(diffs.top_diffs(3, |_, _| f64::NAN).is_empty());
+    }
+
+    #[test]
+    fn equal_captured_subtrees_are_shared() {
+        let default = json!({"retries": 3, "backoff": {"kind": "linear", "steps": [1, 2, 4]}});
+        let keys = (0..100).map(|n| format!("service{n}"));
+        let left: Map<String, Value> = keys.clone().map(|k| (k, Value::Null)).collect();
+        let right: Map<String, Value> = keys.map(|k| (k, default.clone())).collect();
+        let (left, right) = (Value::Object(left), Value::Object(right));
+        let diffs = crate::compare_serde_values(&left, &right, false, &[]).unwrap();
+        let leaves = leaf_values(&diffs.unequal_values);
+        assert_eq!(leaves.len(), 100);
+        let first = leaves[0].1.clone();
+        assert!(leaves.iter().all(|(_, r)| Arc::ptr_eq(r, &first)));
+        drop(leaves);
+        // one reference per leaf of the tree besides `first`, the default was copied once while comparing
+        assert_eq!(Arc::strong_count(&first), 101);
+        // one-sided leaves hold their value twice
+        let extra =
+            crate::compare_serde_values(&left, &Value::Object(Map::new()), false, &[]).unwrap();
+        let added =
+            crate::compare_serde_values(&Value::Object(Map::new()), &right, false, &[]).unwrap();
+        assert!(leaf_values(&extra.left_only)
+            .iter()
+            .all(|(l, r)| Arc::ptr_eq(l, r)));
+        let added = leaf_values(&added.right_only);
+        assert!(added
+            .iter()
+            .all(|(l, r)| Arc::ptr_eq(l, &added[0].0) && Arc::ptr_eq(r, l)));
+        // scalars and differing subtrees are kept apart
+        let mut mixed = Mismatch::new(
+            DiffTreeNode::Null,
+            DiffTreeNode::Null,
+            DiffTreeNode::array([
+                (0, DiffTreeNode::leaf(default.clone(), 1)),
+                (1, DiffTreeNode::leaf(1, [1])),
+            ]),
+        );
+        mixed.share_values();
+        let leaves = leaf_values(&mixed.unequal_values);
+        assert_eq!(Arc::strong_count(&leaves[0].0), 2);
+        assert!(!Arc::ptr_eq(&leaves[0].1, &leaves[1].0));
+        assert_eq!(
+            structural_hash(&json!({"a": 1, "b": 2})),
+            structural_hash(&json!({"b": 2, "a": 1}))
+        );
+        assert_ne!(
+            structural_hash(&json!({"name": "item 1", "n": "1"})),
+            structural_hash(&json!({"name": "item 5", "n": "5"}))
+        );
+    }
+
+    #[test]
+    fn default_and_builders() {
+        assert_eq!(Mismatch::default(), Mismatch::empty());
+        let tree = DiffTreeNode::array([(0, DiffTreeNode::leaf("a", "b"))]);
+        let built = Mismatch::default()
+            .with_left_only(DiffTreeNode::object([("x", DiffTreeNode::leaf(1, 1))]))
+            .with_unequal(DiffTreeNode::array([(0, DiffTreeNode::leaf("a", "b"))]));
+        let expected = Mismatch::new(
+            DiffTreeNode::object([("x", DiffTreeNode::leaf(1, 1))]),
+            DiffTreeNode::Null,
+            tree,
+        );
+        assert_eq!(built, expected);
+        let from_tree = Mismatch::from(DiffTreeNode::leaf(1, 2));
+        assert_eq!(from_tree.unequal_values, DiffTreeNode::leaf(1, 2));
+        assert!(from_tree.left_only == DiffTreeNode::Null);
+    }
+
+    #[test]
+    fn entries_know_their_tree() {
+        let diffs = compare_strs(
+            r#"{"a": 1, "b": {"x": 1}, "c": [1, 2]}"#,
+            r#"{"a": 2, "b": {"y": 1}, "c": [1]}"#,
+            false,
+            &[],
+        )
+        .unwrap();
+        let mut typed: Vec<String> = diffs.entries().iter().map(|e| format!("{e:#}")).collect();
+        typed.sort();
+        assert_eq!(
+            typed,
+            [
+                "Extra on left: .b.x.(1)",
+                "Extra on left: .c.[1].(2)",
+                "Extra on right: .b.y.(1)",
+                "Mismatched: .a.(1 != 2)",
+            ]
+        );
+        for (diff_type, entry) in diffs.all_diffs() {
+            assert_eq!(entry.diff_type, Some(diff_type));
+            let record = DiffRecord::from_typed_entry(&entry).unwrap();
+            assert_eq!(record, DiffRecord::from_entry(diff_type, &entry));
+        }
+        let untyped = diffs.left_only.get_diffs();
+        assert!(untyped.iter().all(|e| e.diff_type.is_none()));
+        assert_eq!(format!("{:#}", untyped[0]), format!("{}", untyped[0]));
+        let groups = diffs.entries_by_root_key();
+        assert_eq!(
+            groups[&Some("b".to_string())][1].diff_type,
+            Some(DiffType::RightExtra)
+        );
+    }
+
+    #[test]
+    fn sorted_diffs_interleave_categories_by_path() {
+        let diffs = compare_strs(
+            r#"{"a": 1, "b": {"x": 1, "y": 1}, "c": [1, 2], "e": 1}"#,
+            r#"{"a": 2, "b": {"x": 2, "z": 1}, "c": [1], "d": 1}"#,
+            false,
+            &[],
+        )
+        .unwrap();
+        let sorted: Vec<String> = diffs
+            .all_diffs_sorted()
+            .iter()
+            .map(|(diff_type, entry)| format!("{diff_type}: {entry}"))
+            .collect();
+        assert_eq!(
+            sorted,
+            [
+                "Mismatched: .a.(1 != 2)",
+                "Mismatched: .b.x.(1 != 2)",
+                "Extra on left: .b.y.(1)",
+                "Extra on right: .b.z.(1)",
+                "Extra on left: .c.[1].(2)",
+                "Extra on right: .d.(1)",
+                "Extra on left: .e.(1)",
+            ]
+        );
+        let records: Vec<String> = diffs
+            .records_sorted()
+            .iter()
+            .map(|r| r.to_string())
+            .collect();
+        assert_eq!(records, sorted);
+    }
+
+    fn records(left: &str, right: &str) -> Vec<DiffRecord> {
+        compare_strs(left, right, false, &[]).unwrap().records()
+    }
+
+    #[test]
+    fn report_delta_of_overlapping_reports() {
+        let old = records(r#"{"a": 1, "b": 1, "c": 1}"#, r#"{"a": 2, "b": 2}"#);
+        let new = records(
+            r#"{"a": 1, "b": 1, "c": 1}"#,
+            r#"{"a": 2, "b": 3, "c": 1, "d": 1}"#,
+        );
+        let delta = compare_reports(&old, &new);
+        let strings = |records: &[DiffRecord]| -> Vec<String> {
+            records.iter().map(|r| r.to_string()).collect()
+        };
+        assert_eq!(strings(&delta.appeared), ["Extra on right: .d.(1)"]);
+        assert_eq!(strings(&delta.disappeared), ["Extra on left: .c.(1)"]);
+        assert_eq!(delta.changed_values.len(), 1);
+        let (before, after) = &delta.changed_values[0];
+        assert_eq!(before.to_string(), "Mismatched: .b.(1 != 2)");
+        assert_eq!(after.to_string(), "Mismatched: .b.(1 != 3)");
+        assert!(compare_reports(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn report_delta_of_disjoint_reports() {
+        let old = records(r#"{"a": 1}"#, r#"{"a": 2}"#);
+        let new = records(r#"{"b": [1]}"#, r#"{"b": [1, 2]}"#);
+        let delta = compare_reports(&old, &new);
+        assert_eq!(delta.appeared, new);
+        assert_eq!(delta.disappeared, old);
+        assert!(delta.changed_values.is_empty());
+        // a value turning into an extra is a change of type, not of value
+        let new = records(r#"{"a": 1}"#, r#"{}"#);
+        let delta = compare_reports(&old, &new);
+        assert_eq!((delta.appeared.len(), delta.disappeared.len()), (1, 1));
+    }
+
     #[test]
     fn empty_diffs() {
         let empty = Mismatch::empty();
         let all_diffs = empty.all_diffs();
         assert!(all_diffs.is_empty());
     }
+
+    #[test]
+    fn display_summary_stays_within_its_budget() {
+        let diffs = compare_strs(
+            r#"{"a": 1, "b": {"c": [1, 2]}, "d": true}"#,
+            r#"{"a": 2, "b": [1], "e": null}"#,
+            false,
+            &[],
+        )
+        .unwrap();
+        let all = "\
+4 differences: 2 mismatched, 1 extra on left, 1 extra on right
+Mismatched:
+  .a.(1 != 2)
+Type changed:
+  .b.(object != array)
+Extra on left:
+  .d.(true)
+Extra on right:
+  .e.(null)";
+        assert_eq!(diffs.display_summary(10).to_string(), all);
+        assert_eq!(diffs.display_summary(4).to_string(), all);
+        assert_eq!(
+            diffs.display_summary(3).to_string(),
+            "\
+4 differences: 2 mismatched, 1 extra on left, 1 extra on right
+Mismatched:
+  .a.(1 != 2)
+Type changed:
+  .b.(object != array)
+Extra on left:
+  .d.(true)
+… and 1 more difference"
+        );
+        assert_eq!(
+            diffs.display_summary(0).to_string(),
+            "\
+4 differences: 2 mismatched, 1 extra on left, 1 extra on right
+… and 4 more differences"
+        );
+        assert_eq!(
+            Mismatch::empty().display_summary(3).to_string(),
+            "0 differences: 0 mismatched, 0 extra on left, 0 extra on right"
+        );
+    }
+
+    #[test]
+    fn count_diffs_matches_all_diffs() {
+        let data1 = r#"{"a": [1, 2, 3], "b": {"c": 1, "d": 2}, "e": null, "g": "x"}"#;
+        let data2 = r#"{"a": [1, 2], "b": {"c": 2}, "f": 1, "g": "y"}"#;
+        let diffs = compare_strs(data1, data2, false, &[]).unwrap();
+        let counts = diffs.count_diffs();
+        assert_eq!(counts.total(), diffs.all_diffs().len());
+        assert_eq!(
+            counts,
+            DiffCounts {
+                left_only: 3,
+                right_only: 1,
+                unequal_values: 2
+            }
+        );
+    }
+
+    #[test]
+    fn diffs_grouped_by_root_key() {
+        let data1 = r#"{"a": {"x": 1, "y": 2}, "b": [1, 2], "c": "same", "d": true, "e": 1}"#;
+        let data2 = r#"{"a": {"x": 2}, "b": [1, 3], "c": "same", "d": false, "f": 1}"#;
+        let diffs = compare_strs(data1, data2, false, &[]).unwrap();
+        let groups = diffs.diffs_by_root_key();
+
+        let keys: Vec<_> = groups.keys().cloned().collect();
+        assert_eq!(
+            keys,
+            vec![
+                Some("a".to_string()),
+                Some("b".to_string()),
+                Some("d".to_string()),
+                Some("e".to_string()),
+                Some("f".to_string())
+            ]
+        );
+        let a_group: Vec<_> = groups[&Some("a".to_string())]
+            .iter()
+            .map(|(t, e)| format!("{t}: {e}"))
+            .collect();
+        assert_eq!(
+            a_group,
+            vec!["Mismatched: .a.x.(1 != 2)", "Extra on left: .a.y.(2)"]
+        );
+        assert_eq!(groups[&Some("b".to_string())].len(), 1);
+        assert_eq!(groups[&Some("d".to_string())].len(), 1);
+        assert!(!groups.contains_key(&None));
+    }
+
+    #[test]
+    fn root_mismatch_grouped_in_none_bucket() {
+        let diffs = compare_strs(r#"{"a": 1}"#, r#"[1]"#, false, &[]).unwrap();
+        let groups = diffs.diffs_by_root_key();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[&None].len(), 1);
+
+        let diffs = compare_strs(r#"[1, 2]"#, r#"[1, 3]"#, false, &[]).unwrap();
+        let groups = diffs.diffs_by_root_key();
+        assert_eq!(groups[&None].len(), 1);
+    }
+
+    #[test]
+    fn serialized_results_have_a_stable_shape() {
+        let left = r#"{"id": 1, "tags": ["a", "b", "c"], "meta": {"owner": "x"}}"#;
+        let right = r#"{"id": "1", "tags": ["a", "c"], "meta": {}, "new": null}"#;
+        let diffs = compare_strs(left, right, false, &[]).unwrap();
+        assert_eq!(
+            serde_json::to_string(&diffs).unwrap(),
+            concat!(
+                r#"{"left_only":{"meta":{"owner":["x","x"]},"tags":{"$elements":[[1,["b","b"]]]}},"#,
+                r#""right_only":{"new":[null,null]},"unequal_values":{"id":[1,"1"]}}"#
+            )
+        );
+        let entries = serde_json::to_value(diffs.entries()).unwrap();
+        assert_eq!(
+            entries[2],
+            json!({"path": ["tags", 1], "values": ["b", "b"], "redacted": null, "diff_type": "left_extra"})
+        );
+        let redacted = DiffTreeNode::array([(
+            0,
+            DiffTreeNode::Redacted(ValueKind::Array(2), ValueKind::Null),
+        )]);
+        let serialized = serde_json::to_value(Mismatch::from(redacted.clone())).unwrap();
+        assert_eq!(
+            serialized["unequal_values"],
+            json!({"$elements": [[0, {"$redacted": [{"array": 2}, "null"]}]]})
+        );
+        let read: Mismatch = serde_json::from_value(serialized).unwrap();
+        assert_eq!(read.unequal_values, redacted);
+        let read: Mismatch = serde_json::from_str(&serde_json::to_string(&diffs).unwrap()).unwrap();
+        assert_eq!(read, diffs);
+        let invalid = r#"{"left_only": [1], "right_only": null, "unequal_values": null}"#;
+        assert!(serde_json::from_str::<Mismatch>(invalid).is_err());
+    }
+
+    #[test]
+    fn keys_looking_reserved_round_trip() {
+        let left = r#"{"$elements": 1, "$redacted": {"$$x": [1]}, "$ref": "a", "plain": 1}"#;
+        let right = r#"{"$elements": 2, "$redacted": {"$$x": [2]}, "plain": 2}"#;
+        let diffs = compare_strs(left, right, false, &[]).unwrap();
+        let serialized = serde_json::to_value(&diffs).unwrap();
+        assert_eq!(
+            serialized["unequal_values"],
+            json!({
+                "$$elements": [1, 2],
+                "$$redacted": {"$$$x": {"$elements": [[0, [1, 2]]]}},
+                "plain": [1, 2]
+            })
+        );
+        assert_eq!(serialized["left_only"], json!({"$$ref": ["a", "a"]}));
+        let read: Mismatch = serde_json::from_value(serialized).unwrap();
+        assert_eq!(read, diffs);
+    }
+
+    /// Paths of the diffs of `diffs` rendered as one of `accepted`.
+    fn selection(diffs: &Mismatch, accepted: &[&str]) -> Vec<DiffPath> {
+        diffs
+            .records()
+            .into_iter()
+            .map(|record| record.path)
+            .filter(|path| accepted.contains(&path.to_string().as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn selected_diffs_apply_alone() {
+        let left = json!({"a": 1, "b": [1, 2], "gone": true});
+        let right = json!({"a": 2, "b": [1, 2, 3]});
+        let diffs = compare_serde_values(&left, &right, false, &[]).unwrap();
+        assert_eq!(diffs.records().len(), 3);
+        let accepted = selection(&diffs, &[".b.[2]"]);
+        assert_eq!(
+            apply_selected(&left, &diffs, &accepted).unwrap(),
+            json!({"a": 1, "b": [1, 2, 3], "gone": true})
+        );
+        assert_eq!(apply_selected(&left, &diffs, &[]).unwrap(), left);
+
+        let mut accepted = selection(&diffs, &[".a", ".gone"]);
+        accepted.push(DiffPath(vec![OwnedPathElement::Object("c".into())]));
+        let err = apply_selected(&json!({"a": 1}), &diffs, &accepted).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::UnresolvedSelection);
+        assert_eq!(err.to_string(), "Selected diffs do not resolve: .c, .gone");
+    }
+
+    #[test]
+    fn selected_array_diffs_keep_their_positions() {
+        let left = json!([{"id": 1}, {"id": 2, "v": 2}, "x", {"id": 3}]);
+        let right = json!([{"id": 2, "v": 5}, {"id": 3}, "y", "z"]);
+        let diffs = compare_serde_values(&left, &right, false, &[]).unwrap();
+        let rendered: Vec<String> = diffs.records().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            rendered,
+            vec![
+                "Mismatched: .[1].v.(2 != 5)",
+                "Extra on left: .[0].({\"id\":1})",
+                "Extra on left: .[2].(\"x\")",
+                "Extra on right: .[2].(\"y\")",
+                "Extra on right: .[3].(\"z\")",
+            ]
+        );
+        let accepted = selection(&diffs, &[".[0]", ".[1].v"]);
+        assert_eq!(
+            apply_selected(&left, &diffs, &accepted).unwrap(),
+            json!([{"id": 2, "v": 5}, "x", {"id": 3}])
+        );
+        // the insertion follows the kept element and the rejected removal before it
+        let accepted: Vec<DiffPath> = diffs
+            .records()
+            .into_iter()
+            .filter(|record| record.to_string() == "Extra on right: .[3].(\"z\")")
+            .map(|record| record.path)
+            .collect();
+        assert_eq!(
+            apply_selected(&left, &diffs, &accepted).unwrap(),
+            json!([{"id": 1}, {"id": 2, "v": 2}, "x", {"id": 3}, "z"])
+        );
+        let all: Vec<DiffPath> = diffs.records().into_iter().map(|r| r.path).collect();
+        assert_eq!(apply_selected(&left, &diffs, &all).unwrap(), right);
+    }
+
+    #[test]
+    fn elements_of_differing_shapes_are_summarized_separately() {
+        let left = json!({"items": [
+            {"id": 1, "name": "a", "tags": ["x"]},
+            {"id": 2, "price": {"net": 1}},
+            {"id": 3}
+        ]});
+        let right = json!({"items": [
+            {"id": 3},
+            {"id": 2, "price": {"net": 2}, "stock": 4},
+            {"id": 1, "name": "b", "tags": ["x", "y"]}
+        ]});
+        let options = crate::CompareOptions::default().sort_arrays(true);
+        let diffs = crate::compare_serde_values_with_options(&left, &right, &options).unwrap();
+        let items = DiffPath(vec![OwnedPathElement::Object("items".into())]);
+        let summaries = diffs.per_element_summary(&items);
+        assert_eq!(summaries.len(), 2);
+        let name_and_tags = &summaries[0];
+        assert_eq!(
+            (name_and_tags.left_index, name_and_tags.right_index),
+            (Some(0), Some(2))
+        );
+        assert_eq!(
+            name_and_tags.counts,
+            BTreeMap::from([(DiffType::RightExtra, 1), (DiffType::Mismatch, 1)])
+        );
+        assert_eq!(name_and_tags.changed_fields, ["name", "tags"]);
+        let price = &summaries[1];
+        assert_eq!((price.left_index, price.right_index), (Some(1), Some(1)));
+        assert_eq!(
+            price.counts,
+            BTreeMap::from([(DiffType::RightExtra, 1), (DiffType::Mismatch, 1)])
+        );
+        assert_eq!(price.changed_fields, ["price", "stock"]);
+        let removed = compare_serde_values(&json!([1, {"a": 1}]), &json!([1]), false, &[]).unwrap();
+        let summaries = removed.per_element_summary(&DiffPath::ROOT);
+        assert_eq!((summaries[0].left_index, summaries[0].right_index), (Some(1), None));
+        assert!(summaries[0].changed_fields.is_empty());
+        assert!(removed.per_element_summary(&items).is_empty());
+    }
+
+    #[test]
+    fn anchors_around_match_a_walk_over_all_equal_elements() {
+        let anchors = Anchors {
+            size: 2,
+            equal: vec![(0, 0, 3), (5, 4, 1), (7, 8, 4), (12, 14, 1)],
+        };
+        for side in [Side::Left, Side::Right] {
+            let position = |(left, right): &(usize, usize)| match side {
+                Side::Left => *left,
+                Side::Right => *right,
+            };
+            let pairs: Vec<(usize, usize)> = anchors
+                .equal
+                .iter()
+                .flat_map(|&(left, right, len)| (0..len).map(move |i| (left + i, right + i)))
+                .collect();
+            for index in 0..18 {
+                let mut before: Vec<_> = pairs
+                    .iter()
+                    .rev()
+                    .filter(|pair| position(pair) < index)
+                    .take(2)
+                    .copied()
+                    .collect();
+                before.reverse();
+                let after = pairs
+                    .iter()
+                    .filter(|pair| position(pair) > index)
+                    .take(2)
+                    .copied()
+                    .collect();
+                assert_eq!(
+                    anchors.around(side, index),
+                    ArrayContext { before, after },
+                    "{side:?} {index}"
+                );
+            }
+        }
+    }
 }
@@ -0,0 +1,259 @@
+//! Myers' diff of two slices, following the linear space variant of the `diffs` crate so that arrays are split
+//! into the same equal, replaced, deleted and inserted ranges. Kept in-house as that crate requires `std`.
+use alloc::vec;
+use core::cmp::{max, min};
+
+/// Receives the ranges of a diff in order of the old slice.
+#[allow(unused_variables)]
+pub(crate) trait Diff {
+    /// `len` elements starting at `old` in the old slice equal those starting at `new` in the new slice.
+    fn equal(&mut self, old: usize, new: usize, len: usize) {}
+    /// `len` elements starting at `old` are only in the old slice, `new` is the position in the new slice.
+    fn delete(&mut self, old: usize, len: usize, new: usize) {}
+    /// `new_len` elements starting at `new` are only in the new slice, inserted at `old` of the old slice.
+    fn insert(&mut self, old: usize, new: usize, new_len: usize) {}
+    /// `old_len` elements starting at `old` are replaced by `new_len` elements starting at `new`.
+    fn replace(&mut self, old: usize, old_len: usize, new: usize, new_len: usize) {
+        self.delete(old, old_len, new);
+        self.insert(old, new, new_len);
+    }
+    /// Called once after all ranges.
+    fn finish(&mut self) {}
+}
+
+/// Combines adjacent deletions and insertions into blocks of maximal length and into replacements.
+pub(crate) struct Replace<D: Diff> {
+    d: D,
+    del: Option<(usize, usize, usize)>,
+    ins: Option<(usize, usize, usize)>,
+    eq: Option<(usize, usize, usize)>,
+}
+
+impl<D: Diff> Replace<D> {
+    pub(crate) fn new(d: D) -> Self {
+        Self {
+            d,
+            del: None,
+            ins: None,
+            eq: None,
+        }
+    }
+
+    pub(crate) fn into_inner(self) -> D {
+        self.d
+    }
+
+    fn flush_equal(&mut self) {
+        if let Some((old, new, len)) = self.eq.take() {
+            self.d.equal(old, new, len);
+        }
+    }
+
+    fn flush_changes(&mut self) {
+        if let Some((old, len, new)) = self.del.take() {
+            match self.ins.take() {
+                Some((_, new, new_len)) => self.d.replace(old, len, new, new_len),
+                None => self.d.delete(old, len, new),
+            }
+        } else if let Some((old, new, new_len)) = self.ins.take() {
+            self.d.insert(old, new, new_len);
+        }
+    }
+}
+
+impl<D: Diff> Diff for Replace<D> {
+    fn equal(&mut self, old: usize, new: usize, len: usize) {
+        self.flush_changes();
+        self.eq = match self.eq.take() {
+            Some((old, new, len0)) => Some((old, new, len0 + len)),
+            None => Some((old, new, len)),
+        };
+    }
+
+    fn delete(&mut self, old: usize, len: usize, new: usize) {
+        self.flush_equal();
+        self.del = match self.del.take() {
+            Some((old0, len0, new0)) => {
+                debug_assert_eq!(old, old0 + len0);
+                Some((old0, len0 + len, new0))
+            }
+            None => Some((old, len, new)),
+        };
+    }
+
+    fn insert(&mut self, old: usize, new: usize, new_len: usize) {
+        self.flush_equal();
+        self.ins = match self.ins.take() {
+            Some((old0, new0, new_len0)) => {
+                debug_assert_eq!(new0 + new_len0, new);
+                Some((old0, new0, new_len0 + new_len))
+            }
+            None => Some((old, new, new_len)),
+        };
+    }
+
+    fn replace(&mut self, old: usize, old_len: usize, new: usize, new_len: usize) {
+        self.flush_equal();
+        self.d.replace(old, old_len, new, new_len);
+    }
+
+    fn finish(&mut self) {
+        self.flush_equal();
+        self.flush_changes();
+        self.d.finish();
+    }
+}
+
+/// Reports the ranges of `e` and `f` to `d`, then calls [`Diff::finish`].
+pub(crate) fn diff<T: PartialEq>(d: &mut impl Diff, e: &[T], f: &[T]) {
+    diff_offsets(d, e, 0, e.len(), f, 0, f.len());
+    d.finish();
+}
+
+fn modulo(a: isize, b: usize) -> usize {
+    a.rem_euclid(b as isize) as usize
+}
+
+/// Diffs `e[i..i_]` against `f[j..j_]` by searching the middle snake from both ends and recursing around it.
+fn diff_offsets<T: PartialEq>(
+    diff: &mut impl Diff,
+    e: &[T],
+    i: usize,
+    i_: usize,
+    f: &[T],
+    j: usize,
+    j_: usize,
+) {
+    if i_ <= i || j_ <= j {
+        if i_ > i {
+            diff.delete(i, i_ - i, j);
+        } else if j_ > j {
+            diff.insert(i, j, j_ - j);
+        }
+        return;
+    }
+    let (n, m) = (i_ - i, j_ - j);
+    let l = (n + m) as isize;
+    let z = 2 * min(n, m) + 2;
+    let w = n as isize - m as isize;
+    // furthest reaching offsets of the forward and the backward search per diagonal
+    let mut forward = vec![0; z];
+    let mut backward = vec![0; z];
+    for h in 0..=(l / 2 + l % 2) {
+        for is_forward in [true, false] {
+            let (c, d) = match is_forward {
+                true => (&mut forward, &backward),
+                false => (&mut backward, &forward),
+            };
+            let (k0, k1) = {
+                let (m, n) = (m as isize, n as isize);
+                (-(h - 2 * max(0, h - m)), h - 2 * max(0, h - n) + 1)
+            };
+            for k in (k0..k1).step_by(2) {
+                let mut a = if k == -h || k != h && c[modulo(k - 1, z)] < c[modulo(k + 1, z)] {
+                    c[modulo(k + 1, z)]
+                } else {
+                    c[modulo(k - 1, z)] + 1
+                };
+                let mut b = (a as isize - k) as usize;
+                let (s, t) = (a, b);
+                while a < n && b < m && {
+                    let (e_i, f_i) = match is_forward {
+                        true => (a, b),
+                        false => (n - a - 1, m - b - 1),
+                    };
+                    f[j + f_i] == e[i + e_i]
+                } {
+                    a += 1;
+                    b += 1;
+                }
+                c[modulo(k, z)] = a;
+                let bound = if is_forward { h - 1 } else { h };
+                if (l % 2 == 1) == is_forward
+                    && w - k >= -bound
+                    && w - k <= bound
+                    && c[modulo(k, z)] + d[modulo(w - k, z)] >= n
+                {
+                    let (x, y, u, v) = match is_forward {
+                        true => (s, t, a, b),
+                        false => (n - a, m - b, n - s, m - t),
+                    };
+                    if h + bound > 1 || (x != u && y != v) {
+                        diff_offsets(diff, e, i, i + x, f, j, j + y);
+                        if x != u {
+                            diff.equal(i + x, j + y, u - x);
+                        }
+                        diff_offsets(diff, e, i + u, i_, f, j + v, j_);
+                    } else if m > n {
+                        diff.equal(i, j, n);
+                        diff.insert(i + n, j + n, m - n);
+                    } else if m < n {
+                        diff.equal(i, j, m);
+                        diff.delete(i + m, n - m, j + m);
+                    }
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Ranges(Vec<(&'static str, usize, usize, usize, usize)>);
+
+    impl Diff for Ranges {
+        fn equal(&mut self, old: usize, new: usize, len: usize) {
+            self.0.push(("equal", old, len, new, len));
+        }
+        fn delete(&mut self, old: usize, len: usize, new: usize) {
+            self.0.push(("delete", old, len, new, 0));
+        }
+        fn insert(&mut self, old: usize, new: usize, new_len: usize) {
+            self.0.push(("insert", old, 0, new, new_len));
+        }
+        fn replace(&mut self, old: usize, old_len: usize, new: usize, new_len: usize) {
+            self.0.push(("replace", old, old_len, new, new_len));
+        }
+    }
+
+    fn ranges(a: &str, b: &str) -> Vec<(&'static str, usize, usize, usize, usize)> {
+        let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+        let mut diff = Replace::new(Ranges::default());
+        super::diff(&mut diff, &a, &b);
+        diff.into_inner().0
+    }
+
+    #[test]
+    fn ranges_cover_both_slices_in_order() {
+        assert_eq!(
+            ranges(">abc=def<", ">xbc=yef<"),
+            vec![
+                ("equal", 0, 1, 0, 1),
+                ("replace", 1, 1, 1, 1),
+                ("equal", 2, 3, 2, 3),
+                ("replace", 5, 1, 5, 1),
+                ("equal", 6, 3, 6, 3),
+            ]
+        );
+        assert_eq!(
+            ranges("abc", "abcde"),
+            vec![("equal", 0, 3, 0, 3), ("insert", 3, 0, 3, 2)]
+        );
+        assert_eq!(ranges("abc", ""), vec![("delete", 0, 3, 0, 0)]);
+        assert_eq!(ranges("", ""), vec![]);
+        for (a, b) in [("abcabba", "cbabac"), ("xaxbx", "ab"), ("ab", "bxa")] {
+            let (mut old, mut new) = (0, 0);
+            for (_, o, ol, n, nl) in ranges(a, b) {
+                assert_eq!((o, n), (old, new));
+                (old, new) = (o + ol, n + nl);
+            }
+            assert_eq!((old, new), (a.len(), b.len()));
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Built-in key normalizers for [`crate::CompareOptions::key_normalizer`], and helpers for keys that only look alike.
+//! ```rust
+//! use json_diff_ng::{compare_serde_values_with_options, normalize, CompareOptions};
+//! use serde_json::json;
+//! let options = CompareOptions::default().key_normalizer(normalize::snake_case);
+//! let diffs = compare_serde_values_with_options(&json!({"userName": 1}), &json!({"user_name": 1}), &options).unwrap();
+//! assert!(diffs.is_empty());
+//! ```
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Lowercases `key`, matching keys that only differ in case, e.g. `CustomerId` and `customerId`.
+pub fn lowercase(key: &str) -> String {
+    key.to_lowercase()
+}
+
+/// Converts `camelCase`, `PascalCase` and `kebab-case` keys to `snake_case`, e.g. `userID` becomes `user_id`.
+pub fn snake_case(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    let mut result = String::with_capacity(key.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '-' {
+            result.push('_');
+            continue;
+        }
+        if c.is_uppercase() && i > 0 {
+            let previous = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            let starts_word = previous.is_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_uppercase() && next_is_lower);
+            if starts_word && !result.ends_with('_') {
+                result.push('_');
+            }
+        }
+        result.extend(c.to_lowercase());
+    }
+    result
+}
+
+/// Converts `snake_case` and `kebab-case` keys to `camelCase`, e.g. `user_name` becomes `userName`.
+pub fn camel_case(key: &str) -> String {
+    let mut result = String::with_capacity(key.len());
+    let mut upper_next = false;
+    for c in key.chars() {
+        if c == '_' || c == '-' {
+            upper_next = !result.is_empty();
+        } else if upper_next {
+            result.extend(c.to_uppercase());
+            upper_next = false;
+        } else if result.is_empty() {
+            result.extend(c.to_lowercase());
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Normalizes keys that look alike to the same form: applies Unicode NFKC normalization, which e.g. composes
+/// combining characters and maps a non-breaking space to a space, and drops invisible characters like zero-width spaces.
+/// Used to detect [`crate::DiffType::KeyAlmostEqual`] keys, see [`crate::CompareOptions::match_lookalike_keys`].
+pub fn lookalike(key: &str) -> String {
+    key.nfkc().filter(|c| !is_zero_width(*c)).collect()
+}
+
+/// Lowercases `key` and drops `_` and `-`, so `max_connections`, `maxConnections` and `MAX-CONNECTIONS` share the
+/// form `maxconnections`. Used to match config keys, see [`crate::CompareOptions::canonical_keys`].
+pub fn canonical_key(key: &str) -> String {
+    key.chars()
+        .filter(|c| !matches!(c, '_' | '-'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Escapes characters of `key` that do not show up or look like others when printed, e.g. `name\u{200b}`.
+/// These are control and format characters, whitespace other than a space, and combining characters.
+pub fn escape_invisible(key: &str) -> String {
+    let mut escaped = String::with_capacity(key.len());
+    // writing to a string cannot fail
+    let _ = write_visible(&mut escaped, key);
+    escaped
+}
+
+/// Writes `key` like [`escape_invisible`] without allocating.
+pub(crate) fn write_visible(f: &mut impl Write, key: &str) -> core::fmt::Result {
+    for c in key.chars() {
+        if is_invisible(c) {
+            write!(f, "\\u{{{:x}}}", c as u32)?;
+        } else {
+            f.write_char(c)?;
+        }
+    }
+    Ok(())
+}
+
+fn is_invisible(c: char) -> bool {
+    c.is_control()
+        || (c.is_whitespace() && c != ' ')
+        || is_zero_width(c)
+        || matches!(c, '\u{300}'..='\u{36f}' | '\u{1ab0}'..='\u{1aff}' | '\u{1dc0}'..='\u{1dff}' | '\u{20d0}'..='\u{20ff}' | '\u{fe20}'..='\u{fe2f}')
+}
+
+/// Format characters and fillers that render without width.
+fn is_zero_width(c: char) -> bool {
+    matches!(
+        c,
+        '\u{ad}'
+            | '\u{34f}'
+            | '\u{61c}'
+            | '\u{115f}'
+            | '\u{1160}'
+            | '\u{17b4}'
+            | '\u{17b5}'
+            | '\u{180b}'..='\u{180f}'
+            | '\u{200b}'..='\u{200f}'
+            | '\u{202a}'..='\u{202e}'
+            | '\u{2060}'..='\u{206f}'
+            | '\u{3164}'
+            | '\u{fe00}'..='\u{fe0f}'
+            | '\u{feff}'
+            | '\u{ffa0}'
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn to_snake_case() {
+        assert_eq!(snake_case("userName"), "user_name");
+        assert_eq!(snake_case("UserName"), "user_name");
+        assert_eq!(snake_case("user_name"), "user_name");
+        assert_eq!(snake_case("user-name"), "user_name");
+        assert_eq!(snake_case("userID"), "user_id");
+        assert_eq!(snake_case("HTTPServer"), "http_server");
+        assert_eq!(snake_case("address2Line"), "address2_line");
+    }
+
+    #[test]
+    fn to_camel_case() {
+        assert_eq!(camel_case("user_name"), "userName");
+        assert_eq!(camel_case("userName"), "userName");
+        assert_eq!(camel_case("user-name"), "userName");
+        assert_eq!(camel_case("_private_key"), "privateKey");
+    }
+
+    #[test]
+    fn config_keys_share_a_canonical_form() {
+        for key in [
+            "max_connections",
+            "maxConnections",
+            "MAX_CONNECTIONS",
+            "max-connections",
+        ] {
+            assert_eq!(canonical_key(key), "maxconnections");
+        }
+        assert_ne!(canonical_key("max_connection"), "maxconnections");
+    }
+
+    #[test]
+    fn lookalike_keys_share_a_form() {
+        assert_eq!(lookalike("name\u{200b}"), "name");
+        assert_eq!(lookalike("first\u{a0}name"), "first name");
+        assert_eq!(lookalike("cafe\u{301}"), "caf\u{e9}");
+        assert_ne!(lookalike("cafe"), lookalike("caf\u{e9}"));
+    }
+
+    #[test]
+    fn invisible_characters_are_escaped() {
+        assert_eq!(escape_invisible("name\u{200b}"), "name\\u{200b}");
+        assert_eq!(escape_invisible("first\u{a0}name"), "first\\u{a0}name");
+        assert_eq!(escape_invisible("cafe\u{301}"), "cafe\\u{301}");
+        assert_eq!(escape_invisible("caf\u{e9} name"), "caf\u{e9} name");
+    }
+}
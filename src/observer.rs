@@ -0,0 +1,127 @@
+//! Hooks for observing comparisons, e.g. to export metrics.
+//! Set an [`Observer`] on the [`crate::CompareOptions`] to receive a callback when a comparison starts,
+//! finishes or fails. When no observer is set, the comparison skips all of this.
+use alloc::string::String;
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+
+use crate::mismatch::{DiffCounts, ResourceUsage};
+use crate::Error;
+
+/// Receives lifecycle callbacks of comparisons. All methods default to doing nothing.
+pub trait Observer: Send + Sync {
+    /// Called before the inputs are parsed or compared.
+    fn on_start(&self) {}
+    /// Called after a successful comparison.
+    fn on_finish(&self, _usage: &ResourceUsage, _counts: &DiffCounts) {}
+    /// Called instead of [`Observer::on_finish`] when the comparison failed.
+    fn on_error(&self, _error: &Error) {}
+}
+
+/// Observer ignoring all callbacks.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoopObserver;
+
+impl Observer for NoopObserver {}
+
+/// A single callback as recorded by [`RecordingObserver`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObservedEvent {
+    Start,
+    Finish(ResourceUsage, DiffCounts),
+    Error(String),
+}
+
+/// Observer keeping all callbacks in memory, mostly useful for tests.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct RecordingObserver {
+    events: Mutex<Vec<ObservedEvent>>,
+}
+
+#[cfg(feature = "std")]
+impl RecordingObserver {
+    /// Returns a copy of all events recorded so far.
+    pub fn events(&self) -> Vec<ObservedEvent> {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<ObservedEvent>> {
+        self.events
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Observer for RecordingObserver {
+    fn on_start(&self) {
+        self.lock().push(ObservedEvent::Start);
+    }
+
+    fn on_finish(&self, usage: &ResourceUsage, counts: &DiffCounts) {
+        self.lock()
+            .push(ObservedEvent::Finish(usage.clone(), *counts));
+    }
+
+    fn on_error(&self, error: &Error) {
+        self.lock().push(ObservedEvent::Error(error.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::sync::Arc;
+
+    use serde_json::json;
+
+    use crate::{compare_serde_values_with_options, compare_strs_with_options, CompareOptions};
+
+    use super::*;
+
+    #[test]
+    fn observer_sees_start_and_finish() {
+        let observer = Arc::new(RecordingObserver::default());
+        let options = CompareOptions::default().observer(observer.clone());
+        let mismatch = compare_serde_values_with_options(
+            &json!({"a": [1, 2], "b": {"c": true}}),
+            &json!({"a": [1, 3], "b": {"c": true}, "d": 1}),
+            &options,
+        )
+        .unwrap();
+
+        let events = observer.events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0], ObservedEvent::Start);
+        let ObservedEvent::Finish(usage, counts) = &events[1] else {
+            panic!("expected finish event, got {:?}", events[1]);
+        };
+        assert_eq!(counts, &mismatch.count_diffs());
+        assert_eq!(counts.unequal_values, 1);
+        assert_eq!(counts.right_only, 1);
+        // root, "a", "a"[1], "b", "b"."c"
+        assert_eq!(usage.compared_nodes, 5);
+        assert_eq!(usage.max_depth, 3);
+    }
+
+    #[test]
+    fn observer_sees_parse_errors() {
+        let observer = Arc::new(RecordingObserver::default());
+        let options = CompareOptions::default().observer(observer.clone());
+        compare_strs_with_options("{invalid", "{}", &options).unwrap_err();
+
+        let events = observer.events();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[1], ObservedEvent::Error(_)));
+    }
+
+    #[test]
+    fn noop_observer_does_not_change_results() {
+        let data1 = json!({"a": [1, 2]});
+        let data2 = json!({"a": [2, 1]});
+        let plain = compare_serde_values_with_options(&data1, &data2, &CompareOptions::default());
+        let options = CompareOptions::default().observer(Arc::new(NoopObserver));
+        let observed = compare_serde_values_with_options(&data1, &data2, &options);
+        assert_eq!(plain.unwrap(), observed.unwrap());
+    }
+}
@@ -0,0 +1,457 @@
+//! JSON Patch ([RFC 6902](https://www.rfc-editor.org/rfc/rfc6902)) and JSON Merge Patch
+//! ([RFC 7386](https://www.rfc-editor.org/rfc/rfc7386)) generation and application.
+//! ```rust
+//! use json_diff_ng::{compare_serde_values, patch};
+//! use serde_json::json;
+//! let left = json!({"a": {"b": [1, 2, 3]}, "gone": true});
+//! let right = json!({"a": {"b": [1, 3, 4]}});
+//! let diffs = compare_serde_values(&left, &right, false, &[]).unwrap();
+//! let patch = patch::to_json_patch(&diffs);
+//! assert_eq!(patch::apply(&left, &patch).unwrap(), right);
+//! ```
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use serde_json::{json, Map, Value};
+
+use crate::pointer::{array_index, escape, unescape};
+use crate::{DiffTreeNode, DiffType, Error, Mismatch, PathElement, Result};
+
+/// Converts a [`Mismatch`] into a JSON patch transforming the left document into the right one.
+/// Left-only entries become `remove`, right-only entries `add` and unequal values `replace` operations.
+/// Array indices are taken as reported, so a comparison with [`crate::CompareOptions::sort_arrays`]
+/// or an array element filter yields a patch for the sorted or filtered arrays,
+/// and keys excluded from the comparison are not patched. Elements paired by [`crate::CompareOptions::array_identity`]
+/// are patched in place, so their reordering is not part of the patch.
+/// Leaves of a comparison with [`crate::CompareOptions::no_value_capture`] carry no values,
+/// their `add` and `replace` operations hold `null` instead. Records of [`Mismatch::extra_records`], like matrices
+/// of differing dimensions or suppressed diffs, are not part of the patch.
+pub fn to_json_patch(mismatch: &Mismatch) -> Value {
+    let mut operations = Vec::new();
+    collect(
+        &mut operations,
+        "",
+        &mismatch.left_only,
+        &mismatch.right_only,
+        &mismatch.unequal_values,
+    );
+    Value::Array(operations)
+}
+
+/// Stands in for values not captured by the comparison.
+static NULL: Value = Value::Null;
+
+/// Children of the three diff trees found at one path.
+#[derive(Default)]
+struct Children<'a> {
+    left: Option<&'a DiffTreeNode>,
+    right: Option<&'a DiffTreeNode>,
+    unequal: Option<&'a DiffTreeNode>,
+}
+
+/// Appends the operations for the diffs below `pointer`. Changes within kept elements of an array come first,
+/// as they use left indices, followed by removals from the back and insertions at their right indices.
+fn collect(
+    operations: &mut Vec<Value>,
+    pointer: &str,
+    left: &DiffTreeNode,
+    right: &DiffTreeNode,
+    unequal: &DiffTreeNode,
+) {
+    match unequal {
+        DiffTreeNode::Value(_, r) => {
+            operations.push(json!({"op": "replace", "path": pointer, "value": r.as_ref()}));
+            return;
+        }
+        DiffTreeNode::Redacted(_, _) => {
+            operations.push(json!({"op": "replace", "path": pointer, "value": null}));
+            return;
+        }
+        _ => {}
+    }
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    let mut nested: BTreeMap<String, Children> = BTreeMap::new();
+    let mut indices: BTreeMap<usize, Children> = BTreeMap::new();
+    for (node, diff_type) in [
+        (left, DiffType::LeftExtra),
+        (right, DiffType::RightExtra),
+        (unequal, DiffType::Mismatch),
+    ] {
+        for (element, child) in node.children() {
+            let children = match element {
+                PathElement::Object(key) => nested.entry(escape(key)).or_default(),
+                PathElement::ArrayEntry(index) => indices.entry(index).or_default(),
+            };
+            match (diff_type, child) {
+                (DiffType::LeftExtra, DiffTreeNode::Value(v, _)) => {
+                    removed.push((element, v.as_ref()))
+                }
+                (DiffType::RightExtra, DiffTreeNode::Value(v, _)) => {
+                    added.push((element, v.as_ref()))
+                }
+                (DiffType::LeftExtra, DiffTreeNode::Redacted(_, _)) => {
+                    removed.push((element, &NULL))
+                }
+                (DiffType::RightExtra, DiffTreeNode::Redacted(_, _)) => {
+                    added.push((element, &NULL))
+                }
+                (DiffType::LeftExtra, _) => children.left = Some(child),
+                (DiffType::RightExtra, _) => children.right = Some(child),
+                _ => children.unequal = Some(child),
+            }
+        }
+    }
+    let tokens = nested
+        .into_iter()
+        .chain(indices.into_iter().map(|(i, c)| (i.to_string(), c)));
+    for (token, children) in tokens {
+        let null = DiffTreeNode::Null;
+        collect(
+            operations,
+            &format!("{pointer}/{token}"),
+            children.left.unwrap_or(&null),
+            children.right.unwrap_or(&null),
+            children.unequal.unwrap_or(&null),
+        );
+    }
+    // removing from the back keeps the indices of the elements still to remove valid
+    removed.sort_by_key(|(element, _)| core::cmp::Reverse(array_position(element)));
+    for (element, _) in removed {
+        let path = format!("{pointer}/{}", token(&element));
+        operations.push(json!({"op": "remove", "path": path}));
+    }
+    added.sort_by_key(|(element, _)| array_position(element));
+    for (element, value) in added {
+        let path = format!("{pointer}/{}", token(&element));
+        operations.push(json!({"op": "add", "path": path, "value": value}));
+    }
+}
+
+fn array_position(element: &PathElement) -> Option<usize> {
+    match element {
+        PathElement::ArrayEntry(index) => Some(*index),
+        PathElement::Object(_) => None,
+    }
+}
+
+fn token(element: &PathElement) -> String {
+    match element {
+        PathElement::Object(key) => escape(key),
+        PathElement::ArrayEntry(index) => index.to_string(),
+    }
+}
+
+/// Applies the `add`, `remove` and `replace` operations of a JSON patch to a copy of `document`.
+pub fn apply(document: &Value, patch: &Value) -> Result<Value> {
+    let Value::Array(operations) = patch else {
+        return Err(Error::InvalidPatch("a patch must be an array".to_string()));
+    };
+    let mut document = document.clone();
+    for (index, operation) in operations.iter().enumerate() {
+        apply_operation(&mut document, operation)
+            .map_err(|message| Error::InvalidPatch(format!("operation {index}: {message}")))?;
+    }
+    Ok(document)
+}
+
+/// Whether applying `patch` to `left` yields `right`.
+pub fn check(left: &Value, right: &Value, patch: &Value) -> Result<bool> {
+    Ok(apply(left, patch)? == *right)
+}
+
+pub(crate) fn apply_operation(
+    document: &mut Value,
+    operation: &Value,
+) -> core::result::Result<(), String> {
+    let op = operation.get("op").and_then(Value::as_str);
+    let path = operation
+        .get("path")
+        .and_then(Value::as_str)
+        .ok_or("missing path")?;
+    let value = operation.get("value");
+    let value = || value.cloned().ok_or("missing value");
+    let Some((parent, last)) = path.rsplit_once('/') else {
+        if !path.is_empty() {
+            return Err(format!("invalid pointer {path}"));
+        }
+        return match op {
+            Some("add" | "replace") => {
+                *document = value()?;
+                Ok(())
+            }
+            _ => Err("only add and replace apply to the document root".to_string()),
+        };
+    };
+    let mut target = &mut *document;
+    for token in parent.split('/').skip(1) {
+        let key = unescape(token);
+        target = match target {
+            Value::Object(o) => o.get_mut(&key),
+            Value::Array(a) => array_index(&key).and_then(|i| a.get_mut(i)),
+            _ => None,
+        }
+        .ok_or_else(|| format!("{path} does not resolve"))?;
+    }
+    let key = unescape(last);
+    let missing = || format!("{path} does not resolve");
+    match (target, op) {
+        (Value::Object(o), Some("add")) => {
+            o.insert(key, value()?);
+        }
+        (Value::Object(o), Some("remove")) => {
+            o.shift_remove(&key).ok_or_else(missing)?;
+        }
+        (Value::Object(o), Some("replace")) => {
+            *o.get_mut(&key).ok_or_else(missing)? = value()?;
+        }
+        (Value::Array(a), Some("add")) => {
+            let index = match key.as_str() {
+                "-" => a.len(),
+                key => array_index(key)
+                    .filter(|i| *i <= a.len())
+                    .ok_or_else(missing)?,
+            };
+            a.insert(index, value()?);
+        }
+        (Value::Array(a), Some("remove")) => {
+            let index = array_index(&key)
+                .filter(|i| *i < a.len())
+                .ok_or_else(missing)?;
+            a.remove(index);
+        }
+        (Value::Array(a), Some("replace")) => {
+            let index = array_index(&key)
+                .filter(|i| *i < a.len())
+                .ok_or_else(missing)?;
+            a[index] = value()?;
+        }
+        (_, Some("add" | "remove" | "replace")) => return Err(missing()),
+        (_, op) => return Err(format!("unsupported operation {op:?}")),
+    }
+    Ok(())
+}
+
+/// Converts a [`Mismatch`] into a JSON merge patch turning the left document into `right`, the right document of
+/// the comparison. Changed and right-only values map to their right value and left-only keys to `null`, objects
+/// containing diffs recurse. Merge patches cannot change parts of arrays, so arrays containing diffs are replaced
+/// by the complete right array. Neither can they set a value to `null`, so a value changed to `null` is removed.
+/// Without any diffs the patch is an empty object. A merge patch that is no object replaces the whole document, so
+/// for a `right` root that is no object the patch is `right` itself.
+/// ```rust
+/// use json_diff_ng::{compare_serde_values, patch};
+/// use serde_json::json;
+/// let left = json!({"a": {"b": [1, 2], "c": 1}, "gone": true});
+/// let right = json!({"a": {"b": [1, 3], "c": 1}, "new": "x"});
+/// let diffs = compare_serde_values(&left, &right, false, &[]).unwrap();
+/// let patch = patch::to_merge_patch(&diffs, &right);
+/// assert_eq!(patch, json!({"a": {"b": [1, 3]}, "gone": null, "new": "x"}));
+/// assert_eq!(patch::apply_merge_patch(&left, &patch), right);
+/// ```
+pub fn to_merge_patch(mismatch: &Mismatch, right: &Value) -> Value {
+    if !right.is_object() {
+        return right.clone();
+    }
+    let children = Children {
+        left: Some(&mismatch.left_only),
+        right: Some(&mismatch.right_only),
+        unequal: Some(&mismatch.unequal_values),
+    };
+    merge_patch(children, Some(right)).unwrap_or_else(|| Value::Object(Map::new()))
+}
+
+/// Merge patch for the diffs of `children`, whose right value is `right`. `None` without diffs.
+fn merge_patch(children: Children, right: Option<&Value>) -> Option<Value> {
+    let mut keys: BTreeMap<String, Children> = BTreeMap::new();
+    let mut changed = false;
+    for (node, diff_type) in [
+        (children.left, DiffType::LeftExtra),
+        (children.right, DiffType::RightExtra),
+        (children.unequal, DiffType::Mismatch),
+    ] {
+        match node {
+            None | Some(DiffTreeNode::Null) => {}
+            Some(
+                DiffTreeNode::Value(_, _) | DiffTreeNode::Redacted(_, _) | DiffTreeNode::Array(_),
+            ) => changed = true,
+            Some(DiffTreeNode::Node(map)) => {
+                for (key, child) in map {
+                    let entry = keys.entry(key.clone()).or_default();
+                    match diff_type {
+                        DiffType::LeftExtra => entry.left = Some(child),
+                        DiffType::RightExtra => entry.right = Some(child),
+                        _ => entry.unequal = Some(child),
+                    }
+                }
+            }
+        }
+    }
+    if changed {
+        // the value is changed or removed as a whole, or an array containing diffs
+        return Some(right.cloned().unwrap_or(Value::Null));
+    }
+    let patch: Map<String, Value> = keys
+        .into_iter()
+        .filter_map(|(key, children)| {
+            let right = right.and_then(|right| right.get(&key));
+            Some((key, merge_patch(children, right)?))
+        })
+        .collect();
+    (!patch.is_empty()).then_some(Value::Object(patch))
+}
+
+/// Applies the JSON merge patch `patch` to a copy of `document`, following RFC 7386.
+pub fn apply_merge_patch(document: &Value, patch: &Value) -> Value {
+    let mut document = document.clone();
+    merge(&mut document, patch);
+    document
+}
+
+fn merge(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(target) = target else {
+        return;
+    };
+    for (key, value) in patch {
+        match value {
+            Value::Null => {
+                target.shift_remove(key);
+            }
+            value => merge(target.entry(key.clone()).or_insert(Value::Null), value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use crate::{compare_serde_values, compare_serde_values_with_options, CompareOptions};
+
+    use super::*;
+
+    fn assert_patch_applies(left: &Value, right: &Value) -> Value {
+        let diffs = compare_serde_values(left, right, false, &[]).unwrap();
+        let patch = to_json_patch(&diffs);
+        assert_eq!(&apply(left, &patch).unwrap(), right, "patch {patch}");
+        patch
+    }
+
+    #[test]
+    fn nested_change_applies_cleanly() {
+        let left = json!({"spec": {"replicas": 1, "labels": {"a/b": "x", "m~n": 1}}, "old": [1]});
+        let right = json!({"spec": {"replicas": 2, "labels": {"a/b": "y"}}, "new": {"k": true}});
+        let patch = assert_patch_applies(&left, &right);
+        assert!(patch
+            .as_array()
+            .unwrap()
+            .contains(&json!({"op": "replace", "path": "/spec/labels/a~1b", "value": "y"})));
+        assert!(patch
+            .as_array()
+            .unwrap()
+            .contains(&json!({"op": "remove", "path": "/spec/labels/m~0n"})));
+    }
+
+    #[test]
+    fn array_changes_apply_in_order() {
+        assert_patch_applies(&json!([1, 2, 3, 4, 5]), &json!([0, 2, 4, 6, 7]));
+        assert_patch_applies(
+            &json!({"items": [{"id": 1, "v": [1, 2]}, {"id": 2}, {"id": 3, "v": 1}]}),
+            &json!({"items": [{"id": 0}, {"id": 1, "v": [2, 3]}, {"id": 3, "v": 2}, {"id": 4}]}),
+        );
+        assert_patch_applies(&json!([]), &json!([[1], {"a": 1}]));
+        assert_patch_applies(&json!({"a": 1}), &json!([1]));
+    }
+
+    #[test]
+    fn equal_documents_yield_an_empty_patch() {
+        let value = json!({"a": [1, {"b": 2}]});
+        assert_eq!(assert_patch_applies(&value, &value), json!([]));
+    }
+
+    #[test]
+    fn check_catches_a_corrupted_patch() {
+        let left = json!({"a": {"b": 1}, "c": [1, 2]});
+        let right = json!({"a": {"b": 2}, "c": [1]});
+        let options = CompareOptions::default();
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        let mut patch = to_json_patch(&diffs);
+        assert!(check(&left, &right, &patch).unwrap());
+
+        patch.as_array_mut().unwrap().pop();
+        assert!(!check(&left, &right, &patch).unwrap());
+
+        let unresolvable = json!([{"op": "remove", "path": "/c/5"}]);
+        let err = check(&left, &right, &unresolvable).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid JSON patch: operation 0: /c/5 does not resolve"
+        );
+    }
+
+    #[test]
+    fn merge_patches_reproduce_the_right_document() {
+        let left = json!({
+            "name": "svc",
+            "replicas": 1,
+            "ports": [80, 443],
+            "meta": {"owner": "ops", "build": 7, "tier": "web"},
+            "legacy": {"enabled": true}
+        });
+        let right = json!({
+            "name": "svc",
+            "replicas": 3,
+            "ports": [80, 8443, 9000],
+            "meta": {"build": 8, "tier": "web"}
+        });
+        let diffs = compare_serde_values(&left, &right, false, &[]).unwrap();
+        let patch = diffs.to_merge_patch(&right);
+        assert_eq!(
+            patch,
+            json!({
+                "replicas": 3,
+                "ports": [80, 8443, 9000],
+                "meta": {"owner": null, "build": 8},
+                "legacy": null
+            })
+        );
+        assert_eq!(apply_merge_patch(&left, &patch), right);
+
+        // arrays are taken from the right document as they are, whatever the comparison did to them
+        let sorted =
+            compare_serde_values(&json!({"a": [3, 1]}), &json!({"a": [2, 1]}), true, &[]).unwrap();
+        assert_eq!(
+            sorted.to_merge_patch(&json!({"a": [2, 1]})),
+            json!({"a": [2, 1]})
+        );
+        let retyped = compare_serde_values(&json!({"a": 1}), &json!([1]), false, &[]).unwrap();
+        assert_eq!(retyped.to_merge_patch(&json!([1])), json!([1]));
+        let equal = compare_serde_values(&left, &left, false, &[]).unwrap();
+        assert_eq!(equal.to_merge_patch(&left), json!({}));
+    }
+
+    #[test]
+    fn merge_patches_of_roots_other_than_objects_replace_the_document() {
+        for (left, right) in [
+            (json!(true), json!(true)),
+            (json!(1), json!("one")),
+            (json!([1]), json!([1])),
+            (json!([1, 2]), json!([1, 3])),
+            (json!({"a": 1}), json!([1])),
+        ] {
+            let diffs = compare_serde_values(&left, &right, false, &[]).unwrap();
+            let patch = diffs.to_merge_patch(&right);
+            assert_eq!(patch, right);
+            assert_eq!(apply_merge_patch(&left, &patch), right);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! JSON Pointer ([RFC 6901](https://www.rfc-editor.org/rfc/rfc6901)) helpers.
+use alloc::string::String;
+use alloc::string::ToString;
+
+use serde_json::Value;
+
+use crate::enums::{DiffPath, OwnedPathElement};
+use crate::{Error, Result};
+
+/// Resolves `pointer` in `value`, e.g. `/spec/items/0` or `/a~1b` for the key `a/b`.
+/// The empty pointer refers to `value` itself.
+/// ```rust
+/// use json_diff_ng::pointer::extract;
+/// use serde_json::json;
+/// let value = json!({"spec": {"items": [1, 2]}});
+/// assert_eq!(extract(&value, "/spec/items/1").unwrap(), &json!(2));
+/// ```
+pub fn extract<'a>(value: &'a Value, pointer: &str) -> Result<&'a Value> {
+    if pointer.is_empty() {
+        return Ok(value);
+    }
+    let Some(tokens) = pointer.strip_prefix('/') else {
+        return Err(Error::InvalidPointer(pointer.to_string()));
+    };
+    let mut current = value;
+    let mut resolved = String::new();
+    for token in tokens.split('/') {
+        let key = unescape(token);
+        let next = match current {
+            Value::Object(o) => o.get(&key),
+            Value::Array(a) => array_index(&key).and_then(|i| a.get(i)),
+            _ => None,
+        };
+        let Some(next) = next else {
+            return Err(Error::PointerNotFound(pointer.to_string(), resolved));
+        };
+        resolved.push('/');
+        resolved.push_str(token);
+        current = next;
+    }
+    Ok(current)
+}
+
+/// Path of the value `pointer` refers to in `value`, tokens indexing into arrays of `value` become array entries.
+pub(crate) fn to_path(value: &Value, pointer: &str) -> DiffPath {
+    let mut current = Some(value);
+    let tokens = pointer.strip_prefix('/').map(|tokens| tokens.split('/'));
+    DiffPath(
+        tokens
+            .into_iter()
+            .flatten()
+            .map(|token| {
+                let key = unescape(token);
+                match (current, array_index(&key)) {
+                    (Some(Value::Array(a)), Some(index)) => {
+                        current = a.get(index);
+                        OwnedPathElement::ArrayEntry(index)
+                    }
+                    _ => {
+                        current = current.and_then(|v| v.get(&key));
+                        OwnedPathElement::Object(key)
+                    }
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Escapes an object key for use as a reference token, e.g. `a/b` becomes `a~1b`.
+pub fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Whether `pointer` refers to the value at `ancestor` or one of its descendants.
+pub(crate) fn is_within(pointer: &str, ancestor: &str) -> bool {
+    pointer
+        .strip_prefix(ancestor)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Array indices are decimal without leading zeros, see section 4 of RFC 6901.
+pub(crate) fn array_index(token: &str) -> Option<usize> {
+    if token.is_empty()
+        || (token.len() > 1 && token.starts_with('0'))
+        || !token.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    token.parse().ok()
+}
+
+pub(crate) fn unescape(token: &str) -> String {
+    token.replace("~1", "/").replace("~0", "~")
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn extract_valid_pointers() {
+        let value = json!({"spec": {"template": {"a": 1}}, "a/b": {"m~n": true}, "": 0});
+        assert_eq!(extract(&value, "").unwrap(), &value);
+        assert_eq!(extract(&value, "/spec/template").unwrap(), &json!({"a": 1}));
+        assert_eq!(extract(&value, "/a~1b/m~0n").unwrap(), &json!(true));
+        assert_eq!(extract(&value, "/").unwrap(), &json!(0));
+        let pointer = format!("/{}/{}", escape("a/b"), escape("m~n"));
+        assert_eq!(extract(&value, &pointer).unwrap(), &json!(true));
+    }
+
+    #[test]
+    fn extract_array_indices() {
+        let value = json!({"items": [{"id": 1}, {"id": 2}]});
+        assert_eq!(extract(&value, "/items/1/id").unwrap(), &json!(2));
+        assert!(extract(&value, "/items/01").is_err());
+        assert!(extract(&value, "/items/-").is_err());
+    }
+
+    #[test]
+    fn missing_pointer_names_nearest_ancestor() {
+        let value = json!({"spec": {"items": [1]}});
+        let err = extract(&value, "/spec/items/3/id").unwrap_err();
+        assert!(
+            matches!(&err, Error::PointerNotFound(p, a) if p == "/spec/items/3/id" && a == "/spec/items")
+        );
+        assert_eq!(
+            err.to_string(),
+            "JSON pointer /spec/items/3/id does not resolve, nearest existing ancestor is /spec/items"
+        );
+        let err = extract(&value, "/nope").unwrap_err();
+        assert!(err.to_string().ends_with("ancestor is the document root"));
+        assert!(matches!(
+            extract(&value, "spec"),
+            Err(Error::InvalidPointer(_))
+        ));
+    }
+}
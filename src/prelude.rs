@@ -0,0 +1,43 @@
+//! The supported API in one import. Items reached through other paths may move between releases,
+//! the names re-exported here stay.
+//! ```rust
+//! use json_diff_ng::prelude::*;
+//! use serde_json::json;
+//! let options = CompareOptions::default().sort_arrays(true).labels("old", "new");
+//! let diffs = compare_serde_values_with_options(&json!([2, 1]), &json!([1, 3]), &options).unwrap();
+//! assert_eq!(diffs.verdict(), Verdict::Fail);
+//! assert!(report::to_markdown(&diffs).contains("| Mismatched | `.[1]` | 2 | 3 |"));
+//! ```
+//! Paths through the modules implementing the comparison still resolve until the next release:
+//! ```rust
+//! let _: json_diff_ng::mismatch::Verdict = json_diff_ng::prelude::Verdict::Pass;
+//! ```
+pub use crate::cookbook::{ci_gate, resolve_all, GateOutcome, GatePolicy};
+pub use crate::enums::{
+    ArrayContext, DiffEntry, DiffPath, DiffRecord, DiffTreeNode, DiffType, DiffValues, Error, ErrorCode,
+    ErrorContext, Origin, OwnedPathElement, PathElement, Side, SidePath, SizeDelta, ValueKind,
+};
+pub use crate::keys::KeyMatcher;
+pub use crate::mismatch::{
+    apply_selected, compare_reports, numeric_delta, size_change, value_size, DiffCounts, DiffSummary, ElementSummary,
+    LeafCounts, Mismatch, Normalization, Origins, ReportDelta, ResourceUsage, Verdict, Warning,
+};
+pub use crate::observer::Observer;
+pub use crate::process::{
+    compare_multi_document_strs, compare_serde_values, compare_serde_values_with_options, compare_slices,
+    array_edit_script, compare_strs, compare_strs_with_options, diff_at, ArrayMode, CompareMode, CompareOptions,
+    Comparator, EditOp, Expectation, OptionConflict, ParseMode, SampleSpec, ValueRule, DEFAULT_MAX_DEPTH,
+    DEFAULT_MAX_RESOLVED_VALUES,
+};
+#[cfg(feature = "std")]
+pub use crate::process::compare_files;
+pub use crate::report::{JsonOptions, Labels, Messages, Summary, ValueFormat};
+pub use crate::{canonical, normalize, observer, patch, pointer, raw, report, sink, sort, string_diff, transform, Result};
+#[cfg(feature = "std")]
+pub use crate::{cache, dirs};
+#[cfg(feature = "snapshot")]
+pub use crate::snapshot;
+#[cfg(feature = "tabular")]
+pub use crate::tabular;
+/// Key patterns of [`CompareOptions::ignore_keys`] and [`compare_serde_values`]
+pub use regex::Regex;
@@ -1,665 +1,6617 @@
-use std::collections::HashMap;
-use std::collections::HashSet;
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::collections::BTreeSet;
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{Debug, Display, Formatter};
 
-use diffs::{Diff, myers, Replace};
 use regex::Regex;
 use serde_json::Map;
+use serde_json::Number;
 use serde_json::Value;
 
+use crate::canonical::canonicalize;
+use crate::decimal;
+use crate::enums::{
+    DiffPath, DiffRecord, DiffType, DiffValues, ErrorContext, Origin, OwnedPathElement, Side,
+    ValueKind,
+};
 use crate::DiffTreeNode;
+use crate::Error;
+use crate::PathElement;
 use crate::Mismatch;
+use crate::mismatch::{LeafCounts, Normalization, ResourceUsage, Warning};
+use crate::normalize;
+use crate::pointer;
+use crate::observer::Observer;
+use crate::report::Labels;
 use crate::Result;
-use crate::sort::preprocess_array;
+use crate::mismatch::{structural_hash, Anchors, ArrayFrame, Origins, SharedValues};
+use crate::myers::{self, Diff, Replace};
+use crate::keys::{KeyMatcher, SideKeyMatchers};
+use crate::sort::{preprocess_array_with_origins, sort_value, sort_value_filtered, type_rank};
+use crate::template;
+use crate::transform::{apply_transform, resolve_internal_refs, Transform};
 
-/// Compares two string slices containing serialized json with each other, returns an error or a [`Mismatch`] structure holding all differences.
-/// Internally this calls into [`compare_serde_values`] after deserializing the string slices into [`serde_json::Value`].
-/// Arguments are the string slices, a bool to trigger deep sorting of arrays and ignored_keys as a list of regex to match keys against.
-/// Ignoring a regex from comparison will also ignore the key from having an impact on sorting arrays.
-pub fn compare_strs(
-    a: &str,
-    b: &str,
-    sort_arrays: bool,
-    ignore_keys: &[Regex],
-) -> Result<Mismatch> {
-    let value1 = serde_json::from_str(a)?;
-    let value2 = serde_json::from_str(b)?;
-    compare_serde_values(&value1, &value2, sort_arrays, ignore_keys)
+/// Options for a comparison, assembled builder-style:
+/// ```rust
+/// use json_diff_ng::CompareOptions;
+/// let options = CompareOptions::default()
+///     .sort_arrays(true)
+///     .ignore_keys(vec![regex::Regex::new("^_id$").unwrap()]);
+/// assert!(options.sort_arrays);
+/// ```
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct CompareOptions {
+    /// Deep-sort arrays before comparing them
+    pub sort_arrays: bool,
+    /// Object keys matching any of these regexes are excluded from comparison and from sorting
+    pub ignore_keys: Vec<Regex>,
+    /// Replaces [`CompareOptions::ignore_keys`] for the left document. A key ignored on one side only
+    /// is treated as absent on that side, so it is reported as extra if the other side has it
+    pub ignore_keys_left: Option<Vec<Regex>>,
+    /// Replaces [`CompareOptions::ignore_keys`] for the right document, see [`CompareOptions::ignore_keys_left`]
+    pub ignore_keys_right: Option<Vec<Regex>>,
+    /// Object keys matching any of these regexes are compared even if they match [`CompareOptions::ignore_keys`].
+    /// Precedence: a key is ignored only if it matches an ignore regex and no override, keys matching no ignore regex are always compared.
+    pub include_keys_override: Vec<Regex>,
+    /// Regex sources compiled into [`CompareOptions::ignore_keys`] by [`CompareOptions::build`]
+    pub ignore_key_patterns: Vec<String>,
+    /// Regex sources compiled into [`CompareOptions::include_keys_override`] by [`CompareOptions::build`]
+    pub include_key_patterns: Vec<String>,
+    /// Values at paths whose rendering, e.g. `.items.[0].id`, matches any of these regexes are excluded from
+    /// comparison with everything below them, see [`CompareOptions::ignore_pointer`]
+    pub ignore_paths: Vec<Regex>,
+    /// Regex sources compiled into [`CompareOptions::ignore_paths`] by [`CompareOptions::build`]
+    pub ignore_path_patterns: Vec<String>,
+    /// Values at and below these JSON pointers are excluded from comparison, see [`CompareOptions::ignore_pointer`]
+    pub ignore_pointers: Vec<String>,
+    /// Receives lifecycle callbacks of each comparison run with these options
+    pub observer: Option<ObserverHandle>,
+    /// Inputs nested deeper than this are rejected with [`Error::DepthLimitExceeded`] instead of risking a stack overflow
+    pub max_depth: usize,
+    /// Serialized inputs of [`Comparator::compare_strs`], [`Comparator::compare_slices`] and files larger than this
+    /// many bytes are rejected with [`Error::InputTooLarge`] before they are read or parsed
+    pub max_input_bytes: Option<u64>,
+    /// How serialized inputs of [`Comparator::compare_strs`], [`Comparator::compare_slices`] and files are parsed
+    pub parse_mode: ParseMode,
+    /// Object keys are matched by their normalized form, see [`crate::normalize`] for built-in normalizers
+    pub key_normalizer: Option<KeyNormalizer>,
+    /// How the left document is interpreted
+    pub mode: CompareMode,
+    /// Reports at most this many keys present on one side only per object, in key order,
+    /// and a [`DiffValues::Summary`] record with the number of the others
+    pub max_extras_per_object: Option<usize>,
+    /// Array elements for which this returns `false` are dropped before the array is compared, see
+    /// [`CompareOptions::filter_array_elements`]
+    pub array_element_filter: Option<ElementFilter>,
+    /// With [`CompareOptions::sort_arrays`], additionally compares how often each element occurs in both arrays,
+    /// ignoring [`CompareOptions::ignore_keys`], and reports [`DiffType::MultiplicityChange`] records for differing counts
+    pub verify_multiplicity: bool,
+    /// Records which comparison pass produced each diff, reported as [`DiffRecord::origin`] by [`Mismatch::records`]
+    pub record_origins: bool,
+    /// Placeholder values like `"TODO"` that equal anything on the other side, when found on either side.
+    /// With [`CompareOptions::sort_arrays`] wildcard elements are excluded from ordering and moved behind the sorted
+    /// elements, keeping their relative order. Each of them then matches one element otherwise missing on its side,
+    /// wildcards without such a counterpart are reported as extras.
+    pub wildcard_values: Vec<Value>,
+    /// Pairs of strings matching one of these rules are equal even if the strings differ, e.g. two UUIDs,
+    /// see [`CompareOptions::ignore_values`]
+    pub ignore_values: Vec<ValueRule>,
+    /// Regex sources compiled into rules of [`CompareOptions::ignore_values`] matching both sides by [`CompareOptions::build`]
+    pub ignore_value_patterns: Vec<String>,
+    /// Counts the scalar leaves on both sides and the equal ones into [`ResourceUsage::leaves`]
+    pub collect_counts: bool,
+    /// With [`CompareOptions::sort_arrays`], additionally reports a [`DiffType::OrderOnly`] record for every array
+    /// whose matched elements appear in a different order on both sides. These records are informational and
+    /// ignored by [`Mismatch::is_empty`], see [`Mismatch::has_order_differences`]
+    pub report_order_differences: bool,
+    /// Pairs array elements by identity instead of position, see [`CompareOptions::array_identity`]
+    pub array_identity: Option<ElementIdentity>,
+    /// Members identifying the elements of all arrays, see [`CompareOptions::identity_keys`]
+    pub identity_keys: Vec<String>,
+    /// Members identifying the elements of the arrays at JSON pointers, see [`CompareOptions::identity_key_at`]
+    pub identity_keys_at: Vec<(String, String)>,
+    /// Keeps the compared values out of the result: diff leaves only hold the kinds of their values and render as
+    /// `(values differ)`, warnings do not quote values either. Errors never contain values, regardless of this option
+    pub no_value_capture: bool,
+    /// Names of the compared documents used in reports, see [`crate::report`]
+    pub labels: Option<Labels>,
+    /// Object keys missing on one side whose counterpart on the other side looks alike, see [`crate::normalize::lookalike`],
+    /// are always reported as [`DiffType::KeyAlmostEqual`] records. With this option their values are also compared
+    /// with each other, instead of reporting both keys as extras
+    pub match_lookalike_keys: bool,
+    /// Object keys missing on one side are matched with a key of the other side of the same
+    /// [`crate::normalize::canonical_key`], see [`CompareOptions::canonical_keys`]
+    pub canonical_keys: bool,
+    /// Object members holding `null` on one side count as equal to missing members on the other side,
+    /// see [`CompareOptions::null_equals_missing`]
+    pub null_equals_missing: bool,
+    /// Comparison modes of the arrays at these JSON pointers, see [`CompareOptions::array_mode`]
+    pub array_modes: Vec<(String, ArrayMode)>,
+    /// Compares only a deterministic subset of large documents, see [`CompareOptions::sample`]
+    pub sample: Option<SampleSpec>,
+    /// Numbers are equal if they round to the same value at this many fractional digits, see
+    /// [`CompareOptions::decimal_places`]
+    pub decimal_places: Option<u32>,
+    /// Replaces [`CompareOptions::decimal_places`] for the numbers at and below these JSON pointers,
+    /// see [`CompareOptions::decimal_places_at`]
+    pub decimal_places_at: Vec<(String, u32)>,
+    /// Floats differing by at most this are equal, see [`CompareOptions::float_tolerance`]
+    pub absolute_tolerance: f64,
+    /// Floats differing by at most this fraction of the larger magnitude are equal, see [`CompareOptions::float_tolerance`]
+    pub relative_tolerance: f64,
+    /// Expected outcomes of the comparison at these JSON pointers, see [`CompareOptions::expect`]
+    pub expectations: Vec<(String, Expectation)>,
+    /// Number of equal elements noted before and after each array diff, see [`CompareOptions::array_context`]
+    pub array_context: Option<usize>,
+    /// Mismatched strings of at least this many bytes on either side are diffed line by line, see
+    /// [`crate::string_diff`]
+    pub string_diffs: Option<usize>,
+    /// JSON pointer to the part of the left document that is compared, see [`CompareOptions::unwrap_left`]
+    pub unwrap_left: Option<String>,
+    /// JSON pointer to the part of the right document that is compared, see [`CompareOptions::unwrap_left`]
+    pub unwrap_right: Option<String>,
+    /// Compares the whole document of a side whose unwrap pointer does not resolve, instead of failing
+    pub unwrap_fallback: bool,
+    /// Edits applied to the left document before comparing it, see [`CompareOptions::pre_transform_left`]
+    pub pre_transform_left: Option<Transform>,
+    /// Edits applied to the right document before comparing it, see [`CompareOptions::pre_transform_left`]
+    pub pre_transform_right: Option<Transform>,
+    /// Internal `$ref` pointers of both documents are expanded, see [`CompareOptions::resolve_internal_refs`]
+    pub resolve_internal_refs: bool,
+    /// Expanding internal `$ref` pointers fails with [`Error::RefExpansionTooLarge`] once a document grows past this
+    /// many values, see [`CompareOptions::max_resolved_values`]
+    pub max_resolved_values: usize,
+    /// Fails comparisons of documents whose root is not an object or array, see [`CompareOptions::require_container_roots`]
+    pub require_container_roots: bool,
+    /// Objects at these JSON pointers encoding sets are compared as arrays of their keys,
+    /// see [`CompareOptions::coerce_object_set`]
+    pub object_sets: Vec<String>,
 }
 
-/// Compares two [`serde_json::Value`] items with each other, returns an error or a [`Mismatch`] structure holding all differences.
-/// Arguments are the values, a bool to trigger deep sorting of arrays and ignored_keys as a list of regex to match keys against.
-/// Ignoring a regex from comparison will also ignore the key from having an impact on sorting arrays.
-pub fn compare_serde_values(
-    a: &Value,
-    b: &Value,
-    sort_arrays: bool,
-    ignore_keys: &[Regex],
-) -> Result<Mismatch> {
-    match_json(a, b, sort_arrays, ignore_keys)
+/// Interpretation of the left document of a comparison.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CompareMode {
+    /// Both documents are data and compared for equality
+    #[default]
+    Exact,
+    /// The left document is a template: string leaves naming a json type (`"integer"`, `"number"`, `"string"`,
+    /// `"boolean"`, `"object"`, `"array"`, `"null"` or `"any"`) only check the type of the right value,
+    /// and an array with a single element checks every element of the right array against it.
+    Template,
 }
 
-fn values_to_node(vec: Vec<(usize, &Value)>) -> DiffTreeNode {
-    if vec.is_empty() {
-        DiffTreeNode::Null
-    } else {
-        DiffTreeNode::Array(
-            vec.into_iter()
-                .map(|(l, v)| (l, DiffTreeNode::Value(v.clone(), v.clone())))
-                .collect(),
-        )
+/// How serialized inputs are parsed, see [`CompareOptions::parse_mode`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ParseMode {
+    /// Inputs hold exactly one json document, surrounded by whitespace at most
+    #[default]
+    Strict,
+    /// A UTF-8 byte order mark and whitespace before the document are skipped. With `skip_prefix`, so is everything
+    /// before the first `{` or `[`, e.g. the timestamp of a log line, and reported as a [`Warning::SkippedPrefix`].
+    /// Documents that are no object or array are then only found if nothing precedes them
+    Tolerant { skip_prefix: bool },
+}
+
+impl ParseMode {
+    /// The part of `input` parsed in this mode and the number of bytes of a prefix skipped before it,
+    /// not counting a byte order mark and whitespace.
+    fn document<'i>(&self, input: &'i [u8]) -> (&'i [u8], usize) {
+        let ParseMode::Tolerant { skip_prefix } = *self else {
+            return (input, 0);
+        };
+        let input = input.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(input);
+        let input = input.trim_ascii_start();
+        let starts_document = |byte: &u8| matches!(byte, b'{' | b'[');
+        if !skip_prefix || input.first().is_none_or(starts_document) {
+            return (input, 0);
+        }
+        match input.iter().position(starts_document) {
+            Some(start) => (&input[start..], start),
+            None => (input, 0),
+        }
     }
 }
 
-struct ListDiffHandler<'a> {
-    replaced: &'a mut Vec<(usize, usize, usize, usize)>,
-    deletion: &'a mut Vec<(usize, usize)>,
-    insertion: &'a mut Vec<(usize, usize)>,
+/// How the arrays at a path are compared, see [`CompareOptions::array_mode`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ArrayMode {
+    /// Elements are paired by a sequence diff, or by [`CompareOptions::sort_arrays`] and
+    /// [`CompareOptions::array_identity`] if set
+    #[default]
+    Sequence,
+    /// Arrays of equally long arrays are compared cell by cell, ignoring [`CompareOptions::sort_arrays`].
+    /// Matrices of differing dimensions are reported as a single [`DiffType::DimensionMismatch`] record,
+    /// arrays that are no matrix on either side are compared as [`ArrayMode::Sequence`].
+    Matrix {
+        /// Numeric cells differing by at most this much are equal
+        tolerance: f64,
+        /// Reports at most this many differing cells, in row-major order,
+        /// and a [`DiffValues::Summary`] record with the number of the others
+        max_cells: Option<usize>,
+    },
+    /// Elements that are equal, ignoring the [ignored keys](CompareOptions::ignore_keys) and with keys normalized by
+    /// the [`CompareOptions::key_normalizer`], pair up first regardless of their positions, the elements left over
+    /// are paired by similarity or position. Suits large arrays of mostly unchanged elements without identities,
+    /// moved elements are not reported. Ignores [`CompareOptions::array_identity`] and [`CompareOptions::sample`].
+    FingerprintFirst,
 }
-impl<'a> ListDiffHandler<'a> {
-    pub fn new(
-        replaced: &'a mut Vec<(usize, usize, usize, usize)>,
-        deletion: &'a mut Vec<(usize, usize)>,
-        insertion: &'a mut Vec<(usize, usize)>,
-    ) -> Self {
-        Self {
-            replaced,
-            deletion,
-            insertion,
-        }
+
+/// Expected outcome of the comparison at a path, see [`CompareOptions::expect`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Expectation {
+    /// The values at the path must differ. Diffs at and below the path are expected and do not fail
+    /// the [`Mismatch::verdict`], equal values or a path missing on both sides are reported as a
+    /// [`DiffType::ExpectedChangeMissing`] record
+    MustDiffer,
+}
+
+/// Subset of the documents compared with [`CompareOptions::sample`], assembled builder-style from [`SampleSpec::new`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SampleSpec {
+    /// Selects the sampled object keys, the same seed samples the same keys
+    pub seed: u64,
+    /// Compares only every this many elements of arrays, by position. Values up to 1 compare all elements
+    pub array_stride: usize,
+    /// Compares only this many keys of larger objects, a pseudo-random subset chosen by [`SampleSpec::seed`].
+    /// With 0, no keys of non-empty objects are compared
+    pub max_keys_per_object: Option<usize>,
+}
+
+/// Combination of [`CompareOptions`] that does not work as one might expect, see [`CompareOptions::conflicts`].
+/// Errors make [`CompareOptions::build`] fail, warnings are reported in [`Mismatch::warnings`] of each comparison.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum OptionConflict {
+    /// Warning: [`CompareOptions::verify_multiplicity`] only applies to sorted arrays
+    MultiplicityWithoutSorting,
+    /// Warning: [`CompareOptions::report_order_differences`] only applies to sorted arrays
+    OrderWithoutSorting,
+    /// Warning: [`CompareOptions::collect_counts`] only counts the leaves inside the [`CompareOptions::sample`]
+    CountsOfSample,
+    /// Error: sampled arrays are compared by position, so the multiplicity of their elements cannot be verified
+    SampledMultiplicity,
+    /// Error: sampled arrays are compared by position, so their order cannot be compared
+    SampledOrder,
+    /// Error: the pointer of an [`CompareOptions::array_mode`] is neither empty nor starts with `/`
+    InvalidArrayModePointer(String),
+    /// Error: the same pointer was given different [`ArrayMode`]s
+    AmbiguousArrayMode(String),
+    /// Error: the [`ArrayMode::Matrix`] tolerance at this pointer is negative or not a number
+    InvalidTolerance(String),
+    /// Error: a [`CompareOptions::float_tolerance`] is negative or not a number
+    InvalidFloatTolerance,
+    /// Error: the pointer of a [`CompareOptions::decimal_places_at`] rule is neither empty nor starts with `/`
+    InvalidDecimalPlacesPointer(String),
+    /// Error: the pointer of a [`CompareOptions::expect`] rule is neither empty nor starts with `/`
+    InvalidExpectationPointer(String),
+    /// Error: the pointer of [`CompareOptions::unwrap_left`] or [`CompareOptions::unwrap_right`] is neither empty nor starts with `/`
+    InvalidUnwrapPointer(String),
+    /// Error: the pointer of a [`CompareOptions::coerce_object_set`] rule is neither empty nor starts with `/`
+    InvalidObjectSetPointer(String),
+    /// Error: a pointer of [`CompareOptions::ignore_pointer`] is neither empty nor starts with `/`
+    InvalidIgnorePointer(String),
+    /// Error: the pointer of a [`CompareOptions::identity_key_at`] rule is neither empty nor starts with `/`
+    InvalidIdentityPointer(String),
+    /// Error: a [`CompareOptions::max_depth`] of 0 rejects every input
+    ZeroMaxDepth,
+}
+
+impl OptionConflict {
+    /// Whether this conflict makes the options unusable, instead of just being reported.
+    pub fn is_error(&self) -> bool {
+        !matches!(
+            self,
+            OptionConflict::MultiplicityWithoutSorting
+                | OptionConflict::OrderWithoutSorting
+                | OptionConflict::CountsOfSample
+        )
     }
 }
-impl<'a> Diff for ListDiffHandler<'a> {
-    type Error = ();
-    fn delete(&mut self, old: usize, len: usize, _new: usize) -> std::result::Result<(), ()> {
-        self.deletion.push((old, len));
-        Ok(())
+
+impl Display for OptionConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            OptionConflict::MultiplicityWithoutSorting => {
+                write!(f, "verify_multiplicity has no effect without sort_arrays")
+            }
+            OptionConflict::OrderWithoutSorting => {
+                write!(
+                    f,
+                    "report_order_differences has no effect without sort_arrays"
+                )
+            }
+            OptionConflict::CountsOfSample => {
+                write!(f, "collect_counts only counts the sampled leaves")
+            }
+            OptionConflict::SampledMultiplicity => {
+                write!(
+                    f,
+                    "verify_multiplicity cannot be combined with sampled arrays"
+                )
+            }
+            OptionConflict::SampledOrder => write!(
+                f,
+                "report_order_differences cannot be combined with sampled arrays"
+            ),
+            OptionConflict::InvalidArrayModePointer(pointer) => write!(
+                f,
+                "array mode pointer {pointer} must be empty or start with '/'"
+            ),
+            OptionConflict::AmbiguousArrayMode(pointer) => {
+                write!(f, "array mode pointer {pointer} is given different modes")
+            }
+            OptionConflict::InvalidTolerance(pointer) => write!(
+                f,
+                "matrix tolerance at {pointer} must be a non-negative number"
+            ),
+            OptionConflict::InvalidFloatTolerance => {
+                write!(f, "float tolerances must be non-negative numbers")
+            }
+            OptionConflict::InvalidDecimalPlacesPointer(pointer) => write!(
+                f,
+                "decimal places pointer {pointer} must be empty or start with '/'"
+            ),
+            OptionConflict::InvalidExpectationPointer(pointer) => write!(
+                f,
+                "expectation pointer {pointer} must be empty or start with '/'"
+            ),
+            OptionConflict::InvalidUnwrapPointer(pointer) => write!(
+                f,
+                "unwrap pointer {pointer} must be empty or start with '/'"
+            ),
+            OptionConflict::InvalidObjectSetPointer(pointer) => write!(
+                f,
+                "object set pointer {pointer} must be empty or start with '/'"
+            ),
+            OptionConflict::InvalidIgnorePointer(pointer) => write!(
+                f,
+                "ignored pointer {pointer} must be empty or start with '/'"
+            ),
+            OptionConflict::InvalidIdentityPointer(pointer) => write!(
+                f,
+                "identity key pointer {pointer} must be empty or start with '/'"
+            ),
+            OptionConflict::ZeroMaxDepth => write!(f, "max_depth 0 rejects every input"),
+        }
     }
-    fn insert(&mut self, _o: usize, new: usize, len: usize) -> std::result::Result<(), ()> {
-        self.insertion.push((new, len));
-        Ok(())
+}
+
+impl SampleSpec {
+    /// Samples object keys by `seed`, without limiting the keys per object or skipping array elements.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            array_stride: 1,
+            max_keys_per_object: None,
+        }
     }
-    fn replace(
-        &mut self,
-        old: usize,
-        len: usize,
-        new: usize,
-        new_len: usize,
-    ) -> std::result::Result<(), ()> {
-        self.replaced.push((old, len, new, new_len));
-        Ok(())
+
+    pub fn array_stride(mut self, array_stride: usize) -> Self {
+        self.array_stride = array_stride;
+        self
     }
-}
 
-fn match_json(
-    value1: &Value,
-    value2: &Value,
-    sort_arrays: bool,
-    ignore_keys: &[Regex],
-) -> Result<Mismatch> {
-    match (value1, value2) {
-        (Value::Object(a), Value::Object(b)) => process_objects(a, b, ignore_keys, sort_arrays),
-        (Value::Array(a), Value::Array(b)) => process_arrays(sort_arrays, a, ignore_keys, b),
-        (a, b) => process_values(a, b),
+    pub fn max_keys_per_object(mut self, max_keys_per_object: Option<usize>) -> Self {
+        self.max_keys_per_object = max_keys_per_object;
+        self
     }
 }
 
-fn process_values(a: &Value, b: &Value) -> Result<Mismatch> {
-    if a == b {
-        Ok(Mismatch::empty())
-    } else {
-        Ok(Mismatch::new(
-            DiffTreeNode::Null,
-            DiffTreeNode::Null,
-            DiffTreeNode::Value(a.clone(), b.clone()),
-        ))
+/// Default for [`CompareOptions::max_depth`], the same nesting `serde_json` parses by default.
+/// Deeper inputs need the `unbounded_depth` feature to be parsed.
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Default for [`CompareOptions::max_resolved_values`]
+pub const DEFAULT_MAX_RESOLVED_VALUES: usize = 1 << 20;
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        Self {
+            sort_arrays: false,
+            ignore_keys: Vec::new(),
+            ignore_keys_left: None,
+            ignore_keys_right: None,
+            include_keys_override: Vec::new(),
+            ignore_key_patterns: Vec::new(),
+            include_key_patterns: Vec::new(),
+            ignore_paths: Vec::new(),
+            ignore_path_patterns: Vec::new(),
+            ignore_pointers: Vec::new(),
+            observer: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_input_bytes: None,
+            parse_mode: ParseMode::Strict,
+            key_normalizer: None,
+            mode: CompareMode::Exact,
+            max_extras_per_object: None,
+            array_element_filter: None,
+            verify_multiplicity: false,
+            record_origins: false,
+            wildcard_values: Vec::new(),
+            ignore_values: Vec::new(),
+            ignore_value_patterns: Vec::new(),
+            collect_counts: false,
+            report_order_differences: false,
+            array_identity: None,
+            identity_keys: Vec::new(),
+            identity_keys_at: Vec::new(),
+            no_value_capture: false,
+            labels: None,
+            match_lookalike_keys: false,
+            canonical_keys: false,
+            null_equals_missing: false,
+            array_modes: Vec::new(),
+            sample: None,
+            decimal_places: None,
+            decimal_places_at: Vec::new(),
+            absolute_tolerance: 0.0,
+            relative_tolerance: 0.0,
+            expectations: Vec::new(),
+            array_context: None,
+            string_diffs: None,
+            unwrap_left: None,
+            unwrap_right: None,
+            unwrap_fallback: false,
+            pre_transform_left: None,
+            pre_transform_right: None,
+            resolve_internal_refs: false,
+            max_resolved_values: DEFAULT_MAX_RESOLVED_VALUES,
+            require_container_roots: false,
+            object_sets: Vec::new(),
+        }
     }
 }
 
-fn process_objects(
-    a: &Map<String, Value>,
-    b: &Map<String, Value>,
-    ignore_keys: &[Regex],
-    sort_arrays: bool,
-) -> Result<Mismatch> {
-    let diff = intersect_maps(a, b, ignore_keys);
-    let mut left_only_keys = get_map_of_keys(diff.left_only);
-    let mut right_only_keys = get_map_of_keys(diff.right_only);
-    let intersection_keys = diff.intersection;
+impl CompareOptions {
+    /// Deep-sort arrays before comparing them. Elements equal apart from ignored keys are ordered by their
+    /// complete values, elements equal in all keys keep their order.
+    pub fn sort_arrays(mut self, sort_arrays: bool) -> Self {
+        self.sort_arrays = sort_arrays;
+        self
+    }
 
-    let mut unequal_keys = DiffTreeNode::Null;
+    pub fn ignore_keys(mut self, ignore_keys: Vec<Regex>) -> Self {
+        self.ignore_keys = ignore_keys;
+        self
+    }
 
-    for key in intersection_keys {
-        let Mismatch {
-            left_only: l,
-            right_only: r,
-            unequal_values: u,
-        } = match_json(
-            a.get(&key).unwrap(),
-            b.get(&key).unwrap(),
-            sort_arrays,
-            ignore_keys,
-        )?;
-        left_only_keys = insert_child_key_map(left_only_keys, l, &key)?;
-        right_only_keys = insert_child_key_map(right_only_keys, r, &key)?;
-        unequal_keys = insert_child_key_map(unequal_keys, u, &key)?;
+    pub fn ignore_keys_left(mut self, ignore_keys_left: Vec<Regex>) -> Self {
+        self.ignore_keys_left = Some(ignore_keys_left);
+        self
     }
 
-    Ok(Mismatch::new(left_only_keys, right_only_keys, unequal_keys))
-}
+    pub fn ignore_keys_right(mut self, ignore_keys_right: Vec<Regex>) -> Self {
+        self.ignore_keys_right = Some(ignore_keys_right);
+        self
+    }
 
-fn process_arrays(
-    sort_arrays: bool,
-    a: &Vec<Value>,
-    ignore_keys: &[Regex],
-    b: &Vec<Value>,
-) -> Result<Mismatch> {
-    let a = preprocess_array(sort_arrays, a, ignore_keys);
-    let b = preprocess_array(sort_arrays, b, ignore_keys);
-
-    let mut replaced = Vec::new();
-    let mut deleted = Vec::new();
-    let mut inserted = Vec::new();
-
-    let mut diff = Replace::new(ListDiffHandler::new(
-        &mut replaced,
-        &mut deleted,
-        &mut inserted,
-    ));
-    myers::diff(
-        &mut diff,
-        a.as_slice(),
-        0,
-        a.len(),
-        b.as_slice(),
-        0,
-        b.len(),
-    )
-    .unwrap();
+    pub fn include_keys_override(mut self, include_keys_override: Vec<Regex>) -> Self {
+        self.include_keys_override = include_keys_override;
+        self
+    }
 
-    fn extract_one_sided_values(v: Vec<(usize, usize)>, vals: &[Value]) -> Vec<(usize, &Value)> {
-        v.into_iter()
-            .flat_map(|(o, ol)| (o..o + ol).map(|i| (i, &vals[i])))
-            .collect::<Vec<(usize, &Value)>>()
+    pub fn ignore_key_patterns<S: Into<String>>(
+        mut self,
+        patterns: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.ignore_key_patterns = patterns.into_iter().map(Into::into).collect();
+        self
     }
 
-    let left_only_values: Vec<_> = extract_one_sided_values(deleted, a.as_slice());
-    let right_only_values: Vec<_> = extract_one_sided_values(inserted, b.as_slice());
+    pub fn include_key_patterns<S: Into<String>>(
+        mut self,
+        patterns: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.include_key_patterns = patterns.into_iter().map(Into::into).collect();
+        self
+    }
 
-    let mut left_only_nodes = values_to_node(left_only_values);
-    let mut right_only_nodes = values_to_node(right_only_values);
-    let mut diff = DiffTreeNode::Null;
+    pub fn ignore_paths(mut self, ignore_paths: Vec<Regex>) -> Self {
+        self.ignore_paths = ignore_paths;
+        self
+    }
 
-    for (o, ol, n, nl) in replaced {
-        let max_length = ol.max(nl);
-        for i in 0..max_length {
-            let inner_a = a.get(o + i).unwrap_or(&Value::Null);
-            let inner_b = b.get(n + i).unwrap_or(&Value::Null);
-            let cdiff = match_json(inner_a, inner_b, sort_arrays, ignore_keys)?;
-            let position = o + i;
-            let Mismatch {
-                left_only: l,
-                right_only: r,
-                unequal_values: u,
-            } = cdiff;
-            left_only_nodes = insert_child_key_diff(left_only_nodes, l, position)?;
-            right_only_nodes = insert_child_key_diff(right_only_nodes, r, position)?;
-            diff = insert_child_key_diff(diff, u, position)?;
-        }
-    }
-
-    Ok(Mismatch::new(left_only_nodes, right_only_nodes, diff))
-}
-
-fn get_map_of_keys(set: HashSet<String>) -> DiffTreeNode {
-    if !set.is_empty() {
-        DiffTreeNode::Node(
-            set.iter()
-                .map(|key| (String::from(key), DiffTreeNode::Null))
-                .collect(),
-        )
-    } else {
-        DiffTreeNode::Null
+    pub fn ignore_path_patterns<S: Into<String>>(
+        mut self,
+        patterns: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.ignore_path_patterns = patterns.into_iter().map(Into::into).collect();
+        self
     }
-}
 
-fn insert_child_key_diff(
-    parent: DiffTreeNode,
-    child: DiffTreeNode,
-    line: usize,
-) -> Result<DiffTreeNode> {
-    if child == DiffTreeNode::Null {
-        return Ok(parent);
+    /// Excludes the values at and below the JSON pointer `pointer` from comparison, e.g. `/metadata/timestamps`.
+    /// Unlike [`CompareOptions::ignore_keys`] this only excludes keys under a specific parent, and both can be combined.
+    /// Paths are the ones diffs are reported at, so indices of sorted arrays refer to the sorted elements.
+    /// ```rust
+    /// use json_diff_ng::{compare_serde_values_with_options, CompareOptions};
+    /// use serde_json::json;
+    /// let options = CompareOptions::default()
+    ///     .ignore_pointer("/metadata/id")
+    ///     .ignore_path_patterns([r"^\.items\.\[\d+\]\.id$"])
+    ///     .ignore_key_patterns(["^etag$"]);
+    /// let left = json!({"id": 1, "metadata": {"id": 2, "etag": "a"}, "items": [{"id": 3}]});
+    /// let right = json!({"id": 4, "metadata": {"id": 5, "etag": "b"}, "items": [{"id": 6}]});
+    /// let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+    /// assert_eq!(diffs.all_diffs().len(), 1);
+    /// assert_eq!(diffs.all_diffs()[0].1.to_string(), ".id.(1 != 4)");
+    /// ```
+    pub fn ignore_pointer(mut self, pointer: impl Into<String>) -> Self {
+        self.ignore_pointers.push(pointer.into());
+        self
     }
-    if let DiffTreeNode::Array(mut array) = parent {
-        array.push((line, child));
-        Ok(DiffTreeNode::Array(array))
-    } else if let DiffTreeNode::Null = parent {
-        Ok(DiffTreeNode::Array(vec![(line, child)]))
-    } else {
-        Err(format!("Tried to insert child: {child:?} into parent {parent:?} - structure incoherent, expected a parent array - somehow json structure seems broken").into())
+
+    pub fn observer(mut self, observer: Arc<dyn Observer>) -> Self {
+        self.observer = Some(ObserverHandle(observer));
+        self
     }
-}
 
-fn insert_child_key_map(
-    parent: DiffTreeNode,
-    child: DiffTreeNode,
-    key: &String,
-) -> Result<DiffTreeNode> {
-    if child == DiffTreeNode::Null {
-        return Ok(parent);
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
     }
-    if let DiffTreeNode::Node(mut map) = parent {
-        map.insert(String::from(key), child);
-        Ok(DiffTreeNode::Node(map))
-    } else if let DiffTreeNode::Null = parent {
-        let mut map = HashMap::new();
-        map.insert(String::from(key), child);
-        Ok(DiffTreeNode::Node(map))
-    } else {
-        Err(format!("Tried to insert child: {child:?} into parent {parent:?} - structure incoherent, expected a parent object - somehow json structure seems broken").into())
+
+    pub fn max_input_bytes(mut self, max_input_bytes: Option<u64>) -> Self {
+        self.max_input_bytes = max_input_bytes;
+        self
     }
-}
 
-struct MapDifference {
-    left_only: HashSet<String>,
-    right_only: HashSet<String>,
-    intersection: HashSet<String>,
-}
+    /// Accepts serialized inputs with a byte order mark or a prefix before the document, see [`ParseMode::Tolerant`].
+    /// ```rust
+    /// use json_diff_ng::{compare_strs_with_options, CompareOptions, ParseMode};
+    /// let options = CompareOptions::default().parse_mode(ParseMode::Tolerant { skip_prefix: true });
+    /// let diffs = compare_strs_with_options("\u{feff}{\"a\": 1}", "INFO got {\"a\": 1}", &options).unwrap();
+    /// assert!(diffs.is_empty());
+    /// assert_eq!(diffs.warnings[0].to_string(), "Skipped 9 bytes before the document of the right input");
+    /// ```
+    pub fn parse_mode(mut self, parse_mode: ParseMode) -> Self {
+        self.parse_mode = parse_mode;
+        self
+    }
 
-impl MapDifference {
-    pub fn new(
-        left_only: HashSet<String>,
-        right_only: HashSet<String>,
-        intersection: HashSet<String>,
+    pub fn key_normalizer(
+        mut self,
+        normalizer: impl Fn(&str) -> String + Send + Sync + 'static,
     ) -> Self {
-        Self {
-            right_only,
-            left_only,
-            intersection,
-        }
+        self.key_normalizer = Some(KeyNormalizer(Arc::new(normalizer)));
+        self
     }
-}
 
-fn intersect_maps(
-    a: &Map<String, Value>,
-    b: &Map<String, Value>,
-    ignore_keys: &[Regex],
-) -> MapDifference {
-    let mut intersection = HashSet::new();
-    let mut left = HashSet::new();
+    /// Compares the arrays at the JSON pointer `pointer` in `mode`, e.g. `/weights` or `/layers/0/kernel`.
+    /// ```rust
+    /// use json_diff_ng::{compare_serde_values_with_options, ArrayMode, CompareOptions};
+    /// use serde_json::json;
+    /// let mode = ArrayMode::Matrix { tolerance: 0.01, max_cells: None };
+    /// let options = CompareOptions::default().array_mode("/m", mode);
+    /// let left = json!({"m": [[1.0, 2.0], [3.0, 4.0]]});
+    /// let right = json!({"m": [[1.0, 2.001], [3.0, 5.0]]});
+    /// let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+    /// assert_eq!(diffs.all_diffs()[0].1.to_string(), ".m.[1].[1].(4.0 != 5.0)");
+    /// ```
+    pub fn array_mode(mut self, pointer: impl Into<String>, mode: ArrayMode) -> Self {
+        self.array_modes.push((pointer.into(), mode));
+        self
+    }
 
-    let mut right = HashSet::new();
-    for a_key in a
-        .keys()
-        .filter(|k| ignore_keys.iter().all(|r| !r.is_match(k.as_str())))
-    {
-        if b.contains_key(a_key) {
-            intersection.insert(String::from(a_key));
-        } else {
-            left.insert(String::from(a_key));
-        }
+    /// Compares only a deterministic subset of the documents, for quick checks of documents too large to compare fully.
+    /// Sampled arrays are compared by position, after sorting or filtering them if configured, while arrays compared
+    /// by [`CompareOptions::array_identity`], in an [`ArrayMode::Matrix`] or [`ArrayMode::FingerprintFirst`] are compared fully.
+    /// A result that skipped anything is partial, see [`Mismatch::is_partial`].
+    /// ```rust
+    /// use json_diff_ng::prelude::{compare_serde_values_with_options, CompareOptions, SampleSpec};
+    /// use serde_json::json;
+    /// let spec = SampleSpec::new(1).array_stride(2);
+    /// let options = CompareOptions::default().sample(spec);
+    /// let diffs = compare_serde_values_with_options(&json!([1, 2, 3]), &json!([1, 0, 3]), &options).unwrap();
+    /// assert!(diffs.is_partial() && diffs.is_empty_in_sample());
+    /// assert!(!diffs.is_empty());
+    /// ```
+    pub fn sample(mut self, spec: SampleSpec) -> Self {
+        self.sample = Some(spec);
+        self
     }
-    for b_key in b
-        .keys()
-        .filter(|k| ignore_keys.iter().all(|r| !r.is_match(k.as_str())))
-    {
-        if !a.contains_key(b_key) {
-            right.insert(String::from(b_key));
-        }
+
+    /// Compares numbers by rounding their decimal representations half away from zero to `places` fractional digits.
+    /// The rounding works on the digits, not on `f64`, so `0.1 + 0.2` equals `0.3` at any precision up to 16 places.
+    /// Numbers still differing are reported with their original values. Strings are never compared as numbers.
+    /// Numbers parsed by `serde_json` are rounded to `f64` precision first, unless the `arbitrary_precision` feature of
+    /// this crate keeps all their digits.
+    /// ```rust
+    /// use json_diff_ng::{compare_serde_values_with_options, CompareOptions};
+    /// use serde_json::json;
+    /// let options = CompareOptions::default().decimal_places(2);
+    /// let left = json!({"net": 10.004, "tax": 1.904});
+    /// let right = json!({"net": 10.0049, "tax": 1.906});
+    /// let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+    /// assert_eq!(diffs.all_diffs()[0].1.to_string(), ".tax.(1.904 != 1.906)");
+    /// ```
+    pub fn decimal_places(mut self, places: u32) -> Self {
+        self.decimal_places = Some(places);
+        self
     }
 
-    MapDifference::new(left, right, intersection)
-}
+    /// Compares the numbers at and below the JSON pointer `pointer` at `places` fractional digits,
+    /// like [`CompareOptions::decimal_places`]. The rule with the longest matching pointer applies.
+    pub fn decimal_places_at(mut self, pointer: impl Into<String>, places: u32) -> Self {
+        self.decimal_places_at.push((pointer.into(), places));
+        self
+    }
 
-#[cfg(test)]
-mod tests {
-    use maplit::hashmap;
-    use serde_json::json;
+    /// Compares numbers as equal if they differ by at most `absolute`, or by at most `relative` times the larger
+    /// of their magnitudes, e.g. results of two numeric pipelines. Pairs of integers are still compared exactly,
+    /// as are all numbers with both tolerances 0. Numbers not within tolerance are reported with their original values.
+    /// `serde_json` has no NaN, non-finite floats are serialized as `null` and compare like any other `null`.
+    /// ```rust
+    /// use json_diff_ng::{compare_serde_values_with_options, CompareOptions};
+    /// use serde_json::json;
+    /// let options = CompareOptions::default().float_tolerance(1e-5, 0.0);
+    /// let diffs = compare_serde_values_with_options(&json!({"a": 1.0000001}), &json!({"a": 1.0}), &options).unwrap();
+    /// assert!(diffs.is_empty());
+    /// let diffs = compare_serde_values_with_options(&json!({"a": 0.1, "n": 1}), &json!({"a": 0.2, "n": 2}), &options).unwrap();
+    /// assert_eq!(diffs.count_diffs().unequal_values, 2);
+    /// ```
+    pub fn float_tolerance(mut self, absolute: f64, relative: f64) -> Self {
+        self.absolute_tolerance = absolute;
+        self.relative_tolerance = relative;
+        self
+    }
 
-    use super::*;
+    /// Asserts `expectation` for the values at the JSON pointer `pointer`, e.g. that a migration changes the version.
+    /// ```rust
+    /// use json_diff_ng::prelude::{compare_serde_values_with_options, CompareOptions, Expectation, Verdict};
+    /// use serde_json::json;
+    /// let options = CompareOptions::default().expect("/version", Expectation::MustDiffer);
+    /// let migrated = compare_serde_values_with_options(
+    ///     &json!({"version": 1, "data": [1]}),
+    ///     &json!({"version": 2, "data": [1]}),
+    ///     &options,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(migrated.verdict(), Verdict::Pass);
+    /// let unchanged = compare_serde_values_with_options(&json!({"version": 1}), &json!({"version": 1}), &options)
+    ///     .unwrap();
+    /// assert_eq!(unchanged.records()[0].to_string(), "Expected change missing: .version.(1 != 1)");
+    /// assert_eq!(unchanged.verdict(), Verdict::Fail);
+    /// ```
+    pub fn expect(mut self, pointer: impl Into<String>, expectation: Expectation) -> Self {
+        self.expectations.push((pointer.into(), expectation));
+        self
+    }
 
-    #[test]
-    fn sorting_ignores_ignored_keys() {
-        let data1: Value =
-            serde_json::from_str(r#"[{"a": 1, "b":2 }, { "a": 2, "b" : 1 }]"#).unwrap();
-        let ignore = [Regex::new("a").unwrap()];
-        let sorted_ignores = preprocess_array(true, data1.as_array().unwrap(), &ignore);
-        let sorted_no_ignores = preprocess_array(true, data1.as_array().unwrap(), &[]);
+    /// Notes the indices of up to `elements` equal elements before and after each diff inside an array
+    /// in [`DiffRecord::context`], to find the spot of a change in long lists. The anchors come from the
+    /// sequence diff of the array, so arrays compared by identity, as matrix or sampled have no context.
+    /// Indices refer to the compared arrays, like the indices of the paths.
+    /// ```rust
+    /// use json_diff_ng::{compare_serde_values_with_options, CompareOptions};
+    /// use serde_json::json;
+    /// let options = CompareOptions::default().array_context(2);
+    /// let left = json!({"log": ["boot", "login", "read", "write", "logout"]});
+    /// let right = json!({"log": ["boot", "login", "read", "delete", "logout"]});
+    /// let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+    /// let context = diffs.records()[0].context.clone().unwrap();
+    /// assert_eq!(context.before, vec![(1, 1), (2, 2)]);
+    /// assert_eq!(context.after, vec![(4, 4)]);
+    /// ```
+    pub fn array_context(mut self, elements: usize) -> Self {
+        self.array_context = Some(elements);
+        self
+    }
 
-        assert_eq!(
-            sorted_ignores
-                .first()
-                .unwrap()
-                .as_object()
-                .unwrap()
-                .get("b")
-                .unwrap()
-                .as_i64()
-                .unwrap(),
-            1
-        );
-        assert_eq!(
-            sorted_no_ignores
-                .first()
-                .unwrap()
-                .as_object()
-                .unwrap()
-                .get("b")
-                .unwrap()
-                .as_i64()
-                .unwrap(),
-            2
-        );
+    /// Attaches a line diff to the records of mismatched strings of at least `min_len` bytes on either side,
+    /// see [`crate::string_diff`].
+    pub fn string_diffs(mut self, min_len: usize) -> Self {
+        self.string_diffs = Some(min_len);
+        self
     }
 
-    #[test]
-    fn test_arrays_sorted_objects_ignored() {
-        let data1 = r#"[{"c": {"d": "e"} },"b","c"]"#;
-        let data2 = r#"["b","c",{"c": {"d": "f"} }]"#;
-        let ignore = Regex::new("d").unwrap();
-        let diff = compare_strs(data1, data2, true, &[ignore]).unwrap();
-        assert!(diff.is_empty());
+    /// Compares only the value at `pointer` of the left document, e.g. to compare a payload wrapped as
+    /// `{"data": ...}` with a bare one. Paths of the diffs and all other pointers of the options are relative to
+    /// the unwrapped values. A pointer that does not resolve fails the comparison with [`Error::UnwrapNotFound`],
+    /// unless [`CompareOptions::unwrap_fallback`] is set.
+    /// ```rust
+    /// use json_diff_ng::{compare_serde_values_with_options, CompareOptions};
+    /// use serde_json::json;
+    /// let options = CompareOptions::default().unwrap_left("/data");
+    /// let wrapped = json!({"data": {"id": 1}, "meta": {"page": 1}});
+    /// let diffs = compare_serde_values_with_options(&wrapped, &json!({"id": 1}), &options).unwrap();
+    /// assert!(diffs.is_empty());
+    /// ```
+    pub fn unwrap_left(mut self, pointer: impl Into<String>) -> Self {
+        self.unwrap_left = Some(pointer.into());
+        self
     }
 
-    #[test]
-    fn test_arrays_sorted_simple() {
-        let data1 = r#"["a","b","c"]"#;
-        let data2 = r#"["b","c","a"]"#;
-        let diff = compare_strs(data1, data2, true, &[]).unwrap();
-        assert!(diff.is_empty());
+    /// Compares only the value at `pointer` of the right document, see [`CompareOptions::unwrap_left`].
+    pub fn unwrap_right(mut self, pointer: impl Into<String>) -> Self {
+        self.unwrap_right = Some(pointer.into());
+        self
     }
 
-    #[test]
-    fn test_arrays_sorted_objects() {
-        let data1 = r#"[{"c": {"d": "e"} },"b","c"]"#;
-        let data2 = r#"["b","c",{"c": {"d": "e"} }]"#;
-        let diff = compare_strs(data1, data2, true, &[]).unwrap();
-        assert!(diff.is_empty());
+    /// Compares the whole document of a side whose unwrap pointer does not resolve, noting a
+    /// [`Warning::UnwrapFallback`] instead of failing the comparison.
+    pub fn unwrap_fallback(mut self, unwrap_fallback: bool) -> Self {
+        self.unwrap_fallback = unwrap_fallback;
+        self
     }
 
-    #[test]
-    fn test_arrays_deep_sorted_objects() {
-        let data1 = r#"[{"c": ["d","e"] },"b","c"]"#;
-        let data2 = r#"["b","c",{"c": ["e", "d"] }]"#;
-        let diff = compare_strs(data1, data2, true, &[]).unwrap();
-        assert!(diff.is_empty());
+    /// Applies `transform` to the left document before anything else, e.g. to drop a debug subtree only one side has.
+    /// Diffs are reported at the paths of the transformed document. A failing operation fails the comparison with
+    /// [`Error::InvalidTransform`] in the context of its side.
+    /// ```rust
+    /// use json_diff_ng::transform::Transform;
+    /// use json_diff_ng::{compare_serde_values_with_options, CompareOptions};
+    /// use serde_json::json;
+    /// let transform = Transform::from_json(&json!([{"op": "remove", "path": "/debug"}])).unwrap();
+    /// let options = CompareOptions::default().pre_transform_left(transform);
+    /// let diffs = compare_serde_values_with_options(&json!({"a": 1, "debug": {}}), &json!({"a": 1}), &options).unwrap();
+    /// assert!(diffs.is_empty());
+    /// ```
+    pub fn pre_transform_left(mut self, transform: Transform) -> Self {
+        self.pre_transform_left = Some(transform);
+        self
     }
 
-    #[test]
-    fn test_arrays_deep_sorted_objects_with_arrays() {
-        let data1 = r#"[{"a": [{"b": ["3", "1"]}] }, {"a": [{"b": ["2", "3"]}] }]"#;
-        let data2 = r#"[{"a": [{"b": ["2", "3"]}] }, {"a": [{"b": ["1", "3"]}] }]"#;
-        let diff = compare_strs(data1, data2, true, &[]).unwrap();
-        assert!(diff.is_empty());
+    /// Applies `transform` to the right document before anything else, see [`CompareOptions::pre_transform_left`].
+    pub fn pre_transform_right(mut self, transform: Transform) -> Self {
+        self.pre_transform_right = Some(transform);
+        self
     }
 
-    #[test]
-    fn test_arrays_deep_sorted_objects_with_outer_diff() {
-        let data1 = r#"[{"c": ["d","e"] },"b"]"#;
-        let data2 = r#"["b","c",{"c": ["e", "d"] }]"#;
-        let diff = compare_strs(data1, data2, true, &[]).unwrap();
-        assert!(!diff.is_empty());
-        let insertions = diff.right_only.get_diffs();
-        assert_eq!(insertions.len(), 1);
-        assert_eq!(insertions.first().unwrap().to_string(), r#".[2].("c")"#);
+    /// Replaces internal references like `{"$ref": "#/components/schemas/Foo"}` in both documents by the values they
+    /// point to, after the [`CompareOptions::pre_transform_left`] and [`CompareOptions::pre_transform_right`], so a
+    /// referenced subtree equals an inlined one. References to other documents are compared as they are and reported
+    /// as [`Warning::ExternalRef`]. Cyclic references fail the comparison with [`Error::RefCycle`], expansions are
+    /// bounded by [`CompareOptions::max_depth`] and [`CompareOptions::max_resolved_values`].
+    /// See [`crate::transform::resolve_internal_refs`].
+    /// ```rust
+    /// use json_diff_ng::{compare_serde_values_with_options, CompareOptions};
+    /// use serde_json::json;
+    /// let referenced = json!({"pet": {"$ref": "#/schemas/Pet"}, "schemas": {"Pet": {"type": "object"}}});
+    /// let inlined = json!({"pet": {"type": "object"}, "schemas": {"Pet": {"type": "object"}}});
+    /// let options = CompareOptions::default().resolve_internal_refs(true);
+    /// assert!(compare_serde_values_with_options(&referenced, &inlined, &options).unwrap().is_empty());
+    /// ```
+    pub fn resolve_internal_refs(mut self, resolve_internal_refs: bool) -> Self {
+        self.resolve_internal_refs = resolve_internal_refs;
+        self
     }
 
-    #[test]
-    fn test_arrays_deep_sorted_objects_with_inner_diff() {
-        let data1 = r#"["a",{"c": ["d","e", "f"] },"b"]"#;
-        let data2 = r#"["b",{"c": ["e","d"] },"a"]"#;
-        let diff = compare_strs(data1, data2, true, &[]).unwrap();
-        assert!(!diff.is_empty());
-        let deletions = diff.left_only.get_diffs();
+    /// References used many times expand into a copy each, so a small document of references to references can grow
+    /// exponentially. Expansions of a document into more than `max_resolved_values` values fail with
+    /// [`Error::RefExpansionTooLarge`], by default [`DEFAULT_MAX_RESOLVED_VALUES`].
+    /// ```rust
+    /// use json_diff_ng::{compare_serde_values_with_options, CompareOptions, ErrorCode};
+    /// use serde_json::json;
+    /// let doubled = json!({"a": [1, 2], "b": [{"$ref": "#/a"}, {"$ref": "#/a"}]});
+    /// let options = CompareOptions::default().resolve_internal_refs(true).max_resolved_values(8);
+    /// let err = compare_serde_values_with_options(&doubled, &doubled, &options).unwrap_err();
+    /// assert_eq!(err.code(), ErrorCode::LimitExceeded);
+    /// ```
+    pub fn max_resolved_values(mut self, max_resolved_values: usize) -> Self {
+        self.max_resolved_values = max_resolved_values;
+        self
+    }
 
-        assert_eq!(deletions.len(), 1);
-        assert_eq!(
-            deletions.first().unwrap().to_string(),
-            r#".[0].c.[2].("f")"#
-        );
+    /// Both documents with their internal references expanded if [`CompareOptions::resolve_internal_refs`] is set,
+    /// together with warnings about external references.
+    pub(crate) fn refs_resolved<'v>(
+        &self,
+        a: Cow<'v, Value>,
+        b: Cow<'v, Value>,
+    ) -> Result<(Cow<'v, Value>, Cow<'v, Value>, Vec<Warning>)> {
+        if !self.resolve_internal_refs {
+            return Ok((a, b, Vec::new()));
+        }
+        let mut warnings = Vec::new();
+        let mut resolve = |side: Side, value: &Value| {
+            let resolved = resolve_internal_refs(value, self.max_depth, self.max_resolved_values);
+            let (resolved, external) = resolved
+                .map_err(|e| e.with_context(ErrorContext::default().side(side)))?;
+            warnings.extend(external.into_iter().map(|(path, reference)| Warning::ExternalRef {
+                side,
+                path,
+                reference,
+            }));
+            Ok::<_, Error>(Cow::Owned(resolved))
+        };
+        let (a, b) = (resolve(Side::Left, &a)?, resolve(Side::Right, &b)?);
+        Ok((a, b, warnings))
     }
 
-    #[test]
-    fn test_arrays_deep_sorted_objects_with_inner_diff_mutation() {
-        let data1 = r#"["a",{"c": ["d", "f"] },"b"]"#;
-        let data2 = r#"["b",{"c": ["e","d"] },"a"]"#;
+    /// Both documents with their [`CompareOptions::pre_transform_left`] and [`CompareOptions::pre_transform_right`]
+    /// applied, borrowed if a side has none.
+    pub(crate) fn transformed<'v>(
+        &self,
+        a: &'v Value,
+        b: &'v Value,
+    ) -> Result<(Cow<'v, Value>, Cow<'v, Value>)> {
+        let transform = |side: Side, value: &'v Value, transform: &Option<Transform>| {
+            let Some(transform) = transform else {
+                return Ok(Cow::Borrowed(value));
+            };
+            apply_transform(value, transform)
+                .map(Cow::Owned)
+                .map_err(|e| e.with_context(ErrorContext::default().side(side)))
+        };
+        Ok((
+            transform(Side::Left, a, &self.pre_transform_left)?,
+            transform(Side::Right, b, &self.pre_transform_right)?,
+        ))
+    }
+
+    /// Compares an object at the JSON pointer `pointer` whose values are all `true`, or all `null`, as the sorted array
+    /// of its keys, so a set encoded as `{"a": true, "b": true}` equals one encoded as `["a", "b"]`. Members present
+    /// on one side only are reported like array elements, with indices into the sorted keys, and with the origin
+    /// [`Origin::ObjectSet`]. Objects with any other value are compared as objects. Arrays are compared as they are,
+    /// unsorted ones equal a coerced object only with [`CompareOptions::sort_arrays`].
+    /// ```rust
+    /// use json_diff_ng::{compare_serde_values_with_options, CompareOptions};
+    /// use serde_json::json;
+    /// let options = CompareOptions::default().coerce_object_set("/roles");
+    /// let left = json!({"roles": {"admin": true, "dev": true}});
+    /// let diffs = compare_serde_values_with_options(&left, &json!({"roles": ["admin", "dev"]}), &options).unwrap();
+    /// assert!(diffs.is_empty());
+    /// let diffs = compare_serde_values_with_options(&left, &json!({"roles": ["admin"]}), &options).unwrap();
+    /// assert_eq!(diffs.all_diffs()[0].1.to_string(), r#".roles.[1].("dev")"#);
+    /// ```
+    pub fn coerce_object_set(mut self, pointer: impl Into<String>) -> Self {
+        self.object_sets.push(pointer.into());
+        self
+    }
+
+    /// Fails comparisons with [`Error::UnexpectedRootType`] if the root of either document is not an object or array,
+    /// as a bare scalar document like `"null"` or an error message usually hints at a broken producer. The roots of
+    /// the whole documents are checked, before unwrapping them.
+    /// ```rust
+    /// use json_diff_ng::{compare_serde_values_with_options, CompareOptions, Error, Side};
+    /// use serde_json::json;
+    /// let options = CompareOptions::default().require_container_roots(true);
+    /// let err = compare_serde_values_with_options(&json!([1]), &json!("error"), &options).unwrap_err();
+    /// assert_eq!(err.side(), Some(Side::Right));
+    /// assert_eq!(err.to_string(), "The right document is a string, not an object or array");
+    /// ```
+    pub fn require_container_roots(mut self, require_container_roots: bool) -> Self {
+        self.require_container_roots = require_container_roots;
+        self
+    }
+
+    /// The values of `a` and `b` at the unwrap pointers, with warnings for sides that fell back to the whole document.
+    /// Fails for scalar roots with [`CompareOptions::require_container_roots`].
+    pub(crate) fn unwrapped<'v>(
+        &self,
+        a: &'v Value,
+        b: &'v Value,
+    ) -> Result<(&'v Value, &'v Value, Vec<Warning>)> {
+        if self.require_container_roots {
+            for (side, root) in [(Side::Left, a), (Side::Right, b)] {
+                let found = ValueKind::of(root);
+                if !found.is_container() {
+                    return Err(Error::UnexpectedRootType { side, found });
+                }
+            }
+        }
+        let mut warnings = Vec::new();
+        let mut unwrap = |side: Side, value: &'v Value, pointer: &Option<String>| {
+            let Some(pointer) = pointer else {
+                return Ok(value);
+            };
+            match pointer::extract(value, pointer) {
+                Ok(inner) => Ok(inner),
+                Err(_) if self.unwrap_fallback => {
+                    let pointer = pointer.clone();
+                    warnings.push(Warning::UnwrapFallback { side, pointer });
+                    Ok(value)
+                }
+                Err(Error::PointerNotFound(pointer, ancestor)) => {
+                    let context = ErrorContext::default().side(side).pointer(&pointer);
+                    Err(Error::UnwrapNotFound(side, pointer, ancestor).with_context(context))
+                }
+                Err(e) => Err(e),
+            }
+        };
+        let a = unwrap(Side::Left, a, &self.unwrap_left)?;
+        let b = unwrap(Side::Right, b, &self.unwrap_right)?;
+        Ok((a, b, warnings))
+    }
+
+    /// Fractional digits the numbers at `pointer` are compared at, `None` for exact comparison.
+    pub(crate) fn decimal_places_for(&self, pointer: &str) -> Option<u32> {
+        self.decimal_places_at
+            .iter()
+            .filter(|(rule, _)| pointer::is_within(pointer, rule))
+            .max_by_key(|(rule, _)| rule.len())
+            .map(|(_, places)| *places)
+            .or(self.decimal_places)
+    }
+
+    pub fn match_lookalike_keys(mut self, match_lookalike_keys: bool) -> Self {
+        self.match_lookalike_keys = match_lookalike_keys;
+        self
+    }
+
+    /// Matches object keys spelled differently, like `max_connections` and `maxConnections`, by their
+    /// [`crate::normalize::canonical_key`] and compares their values. Each such pair is reported as a
+    /// [`DiffType::KeyAlmostEqual`] record with both spellings. Keys sharing a canonical form with another key
+    /// of their object are only matched exactly and reported as a [`Warning::CanonicalKeyCollision`].
+    /// ```rust
+    /// use json_diff_ng::{compare_serde_values_with_options, CompareOptions};
+    /// use serde_json::json;
+    /// let options = CompareOptions::default().canonical_keys(true);
+    /// let left = json!({"max_connections": 10, "timeout": 5});
+    /// let right = json!({"MAX_CONNECTIONS": 20, "timeout": 5});
+    /// let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+    /// assert_eq!(diffs.all_diffs()[0].1.to_string(), ".max_connections.(10 != 20)");
+    /// assert_eq!(
+    ///     diffs.extra_records[0].to_string(),
+    ///     r#"Key almost equal: .("max_connections" ≈ "MAX_CONNECTIONS")"#
+    /// );
+    /// ```
+    pub fn canonical_keys(mut self, canonical_keys: bool) -> Self {
+        self.canonical_keys = canonical_keys;
+        self
+    }
+
+    /// Treats an object member holding `null` like a missing member, in objects at any depth including objects within
+    /// arrays. A member missing on one side is not reported if it is `null` on the other side, `null` array
+    /// elements are still compared as values.
+    /// ```rust
+    /// use json_diff_ng::{compare_serde_values_with_options, CompareOptions};
+    /// use serde_json::json;
+    /// let options = CompareOptions::default().null_equals_missing(true);
+    /// let left = json!({"a": null, "items": [{"b": null, "c": 1}]});
+    /// let right = json!({"items": [{"c": 1}]});
+    /// assert!(compare_serde_values_with_options(&left, &right, &options).unwrap().is_empty());
+    /// ```
+    pub fn null_equals_missing(mut self, null_equals_missing: bool) -> Self {
+        self.null_equals_missing = null_equals_missing;
+        self
+    }
+
+    pub fn mode(mut self, mode: CompareMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn max_extras_per_object(mut self, max_extras_per_object: Option<usize>) -> Self {
+        self.max_extras_per_object = max_extras_per_object;
+        self
+    }
+
+    pub fn verify_multiplicity(mut self, verify_multiplicity: bool) -> Self {
+        self.verify_multiplicity = verify_multiplicity;
+        self
+    }
+
+    pub fn record_origins(mut self, record_origins: bool) -> Self {
+        self.record_origins = record_origins;
+        self
+    }
+
+    pub fn wildcard_values(mut self, wildcard_values: Vec<Value>) -> Self {
+        self.wildcard_values = wildcard_values;
+        self
+    }
+
+    /// Treats values matching one of `rules` as equal whatever their contents, regardless of the key they are found under,
+    /// e.g. generated ids or timestamps. Unlike [`CompareOptions::ignore_keys`] the values are still required to be
+    /// present on both sides, and both can be combined.
+    /// ```rust
+    /// use json_diff_ng::{compare_serde_values_with_options, CompareOptions, ValueRule};
+    /// use regex::Regex;
+    /// use serde_json::json;
+    /// let timestamp = Regex::new(r"^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}").unwrap();
+    /// let options = CompareOptions::default().ignore_values(vec![ValueRule::either_side(timestamp)]);
+    /// let left = json!({"created_at": "2024-01-02T03:04:05Z", "deleted_at": null, "id": 1});
+    /// let right = json!({"created_at": "2024-06-07T08:09:10Z", "deleted_at": "2024-06-08T00:00:00Z", "id": 2});
+    /// let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+    /// assert_eq!(diffs.all_diffs()[0].1.to_string(), ".id.(1 != 2)");
+    /// assert_eq!(diffs.all_diffs().len(), 1);
+    /// ```
+    pub fn ignore_values(mut self, rules: Vec<ValueRule>) -> Self {
+        self.ignore_values = rules;
+        self
+    }
+
+    pub fn ignore_value_patterns<S: Into<String>>(
+        mut self,
+        patterns: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.ignore_value_patterns = patterns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn collect_counts(mut self, collect_counts: bool) -> Self {
+        self.collect_counts = collect_counts;
+        self
+    }
+
+    pub fn report_order_differences(mut self, report_order_differences: bool) -> Self {
+        self.report_order_differences = report_order_differences;
+        self
+    }
+
+    /// Names the left and right document in reports, e.g. by file path or environment.
+    pub fn labels(mut self, left: impl Into<String>, right: impl Into<String>) -> Self {
+        self.labels = Some(Labels::new(left, right));
+        self
+    }
+
+    pub fn no_value_capture(mut self, no_value_capture: bool) -> Self {
+        self.no_value_capture = no_value_capture;
+        self
+    }
+
+    pub(crate) fn has_decimal_places(&self) -> bool {
+        self.decimal_places.is_some() || !self.decimal_places_at.is_empty()
+    }
+
+    pub(crate) fn has_float_tolerance(&self) -> bool {
+        self.absolute_tolerance > 0.0 || self.relative_tolerance > 0.0
+    }
+
+    pub(crate) fn has_ignored_paths(&self) -> bool {
+        !self.ignore_paths.is_empty() || !self.ignore_pointers.is_empty()
+    }
+
+    /// Whether the value at `path` is excluded by [`CompareOptions::ignore_paths`] or [`CompareOptions::ignore_pointer`].
+    pub(crate) fn is_ignored_path(&self, path: &DiffPath) -> bool {
+        if !self.ignore_pointers.is_empty() {
+            let pointer = path.to_pointer();
+            let within = |ignored: &String| pointer::is_within(&pointer, ignored);
+            if self.ignore_pointers.iter().any(within) {
+                return true;
+            }
+        }
+        if self.ignore_paths.is_empty() {
+            return false;
+        }
+        let rendered = path.to_string();
+        self.ignore_paths.iter().any(|r| r.is_match(&rendered))
+    }
+
+    pub(crate) fn is_wildcard(&self, value: &Value) -> bool {
+        self.wildcard_values.contains(value)
+    }
+
+    /// Whether `a` and `b` are equal by one of the [`CompareOptions::ignore_values`] rules.
+    pub(crate) fn is_ignored_value_pair(&self, a: &Value, b: &Value) -> bool {
+        self.ignore_values.iter().any(|rule| rule.matches(a, b))
+    }
+
+    /// Drops array elements for which `filter` returns `false` on either side before comparing (and sorting) the arrays,
+    /// e.g. to skip tombstones. The filter receives the path of the array and the element.
+    /// Indices of diffs within filtered arrays refer to positions in the filtered arrays.
+    pub fn filter_array_elements(
+        mut self,
+        filter: impl Fn(&[PathElement], &Value) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.array_element_filter = Some(ElementFilter(Arc::new(filter)));
+        self
+    }
+
+    /// Pairs the elements of arrays by the identity `identity` returns for them, e.g. a composite of several fields,
+    /// so reordered elements are compared with their counterparts. The function receives the path of the array
+    /// and an element, elements without identity are paired by position among each other.
+    /// Elements sharing an identity on one side are paired in order and reported in [`Mismatch::warnings`].
+    /// Arrays without any identified element are compared as usual.
+    /// ```rust
+    /// use json_diff_ng::{compare_serde_values_with_options, CompareOptions};
+    /// use serde_json::json;
+    /// let options = CompareOptions::default().array_identity(|_path, element| {
+    ///     Some(format!("{}/{}", element.get("type")?, element.get("name")?))
+    /// });
+    /// let left = json!([{"type": "a", "name": "x", "v": 1}, {"type": "b", "name": "x", "v": 2}]);
+    /// let right = json!([{"type": "b", "name": "x", "v": 2}, {"type": "a", "name": "x", "v": 3}]);
+    /// let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+    /// assert_eq!(diffs.all_diffs()[0].1.to_string(), ".[0].v.(1 != 3)");
+    /// ```
+    pub fn array_identity(
+        mut self,
+        identity: impl Fn(&[PathElement], &Value) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.array_identity = Some(ElementIdentity(Arc::new(identity)));
+        self
+    }
+
+    /// Pairs the elements of all arrays by the value of the first of `keys` they have, like
+    /// [`CompareOptions::array_identity`] does. Their identity is rendered like `id=2`, elements having none of the
+    /// keys are paired by position among each other. Identities of [`CompareOptions::identity_key_at`] and
+    /// [`CompareOptions::array_identity`] take precedence.
+    /// ```rust
+    /// use json_diff_ng::{compare_serde_values_with_options, CompareOptions};
+    /// use serde_json::json;
+    /// let options = CompareOptions::default().identity_keys(["id"]);
+    /// let left = json!({"users": [{"id": 1, "v": 10}, {"id": 2, "v": 20}]});
+    /// let right = json!({"users": [{"id": 2, "v": 21}, {"id": 1, "v": 10}]});
+    /// let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+    /// assert_eq!(diffs.all_diffs()[0].1.to_string(), ".users.[1].v.(20 != 21)");
+    /// ```
+    pub fn identity_keys(mut self, keys: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.identity_keys = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Pairs the elements of the arrays at the JSON pointer `pointer` by the value of their member `key`,
+    /// e.g. `("/users", "email")`, see [`CompareOptions::identity_keys`]. Elements without the key are paired by
+    /// position among each other.
+    pub fn identity_key_at(mut self, pointer: impl Into<String>, key: impl Into<String>) -> Self {
+        self.identity_keys_at.push((pointer.into(), key.into()));
+        self
+    }
+
+    /// Whether array elements are paired by identity, see [`CompareOptions::array_identity`].
+    pub(crate) fn has_array_identity(&self) -> bool {
+        self.array_identity.is_some()
+            || !self.identity_keys.is_empty()
+            || !self.identity_keys_at.is_empty()
+    }
+
+    /// Identity of `element` of the array at `path`, whose JSON pointer is `pointer`.
+    fn element_identity(
+        &self,
+        path: &[PathElement],
+        pointer: &str,
+        element: &Value,
+    ) -> Option<String> {
+        let keyed = |key: &str| Some(format!("{key}={}", element.get(key)?));
+        if let Some((_, key)) = self.identity_keys_at.iter().find(|(p, _)| p == pointer) {
+            return keyed(key);
+        }
+        if let Some(ElementIdentity(identity)) = &self.array_identity {
+            return identity(path, element);
+        }
+        self.identity_keys.iter().find_map(|key| keyed(key))
+    }
+
+    pub(crate) fn key_matchers(&self) -> SideKeyMatchers<'_> {
+        let left = self.ignore_keys_left.as_deref();
+        let right = self.ignore_keys_right.as_deref();
+        SideKeyMatchers {
+            left: KeyMatcher::new(
+                left.unwrap_or(&self.ignore_keys),
+                &self.include_keys_override,
+            ),
+            right: KeyMatcher::new(
+                right.unwrap_or(&self.ignore_keys),
+                &self.include_keys_override,
+            ),
+        }
+    }
+
+    /// All combinations of these options that are errors or worth a warning, see [`OptionConflict`].
+    pub fn conflicts(&self) -> Vec<OptionConflict> {
+        let mut conflicts = Vec::new();
+        let sampled_arrays = self.sample.is_some_and(|s| s.array_stride > 1);
+        if self.verify_multiplicity && !self.sort_arrays {
+            conflicts.push(OptionConflict::MultiplicityWithoutSorting);
+        }
+        if self.report_order_differences && !self.sort_arrays {
+            conflicts.push(OptionConflict::OrderWithoutSorting);
+        }
+        if self.collect_counts && self.sample.is_some() {
+            conflicts.push(OptionConflict::CountsOfSample);
+        }
+        if sampled_arrays && self.verify_multiplicity {
+            conflicts.push(OptionConflict::SampledMultiplicity);
+        }
+        if sampled_arrays && self.report_order_differences {
+            conflicts.push(OptionConflict::SampledOrder);
+        }
+        for (n, (pointer, mode)) in self.array_modes.iter().enumerate() {
+            if !pointer.is_empty() && !pointer.starts_with('/') {
+                conflicts.push(OptionConflict::InvalidArrayModePointer(pointer.clone()));
+            }
+            let ambiguous = OptionConflict::AmbiguousArrayMode(pointer.clone());
+            let differs = |(p, m): &(String, ArrayMode)| p == pointer && m != mode;
+            if self.array_modes[..n].iter().any(differs) && !conflicts.contains(&ambiguous) {
+                conflicts.push(ambiguous);
+            }
+            if let ArrayMode::Matrix { tolerance, .. } = mode {
+                if tolerance.is_nan() || *tolerance < 0.0 {
+                    conflicts.push(OptionConflict::InvalidTolerance(pointer.clone()));
+                }
+            }
+        }
+        let valid_tolerance = |tolerance: f64| tolerance >= 0.0;
+        if !valid_tolerance(self.absolute_tolerance) || !valid_tolerance(self.relative_tolerance) {
+            conflicts.push(OptionConflict::InvalidFloatTolerance);
+        }
+        for (pointer, _) in &self.decimal_places_at {
+            if !pointer.is_empty() && !pointer.starts_with('/') {
+                conflicts.push(OptionConflict::InvalidDecimalPlacesPointer(pointer.clone()));
+            }
+        }
+        for (pointer, _) in &self.expectations {
+            if !pointer.is_empty() && !pointer.starts_with('/') {
+                conflicts.push(OptionConflict::InvalidExpectationPointer(pointer.clone()));
+            }
+        }
+        for pointer in self.unwrap_left.iter().chain(&self.unwrap_right) {
+            if !pointer.is_empty() && !pointer.starts_with('/') {
+                conflicts.push(OptionConflict::InvalidUnwrapPointer(pointer.clone()));
+            }
+        }
+        for pointer in &self.object_sets {
+            if !pointer.is_empty() && !pointer.starts_with('/') {
+                conflicts.push(OptionConflict::InvalidObjectSetPointer(pointer.clone()));
+            }
+        }
+        for pointer in &self.ignore_pointers {
+            if !pointer.is_empty() && !pointer.starts_with('/') {
+                conflicts.push(OptionConflict::InvalidIgnorePointer(pointer.clone()));
+            }
+        }
+        for (pointer, _) in &self.identity_keys_at {
+            if !pointer.is_empty() && !pointer.starts_with('/') {
+                conflicts.push(OptionConflict::InvalidIdentityPointer(pointer.clone()));
+            }
+        }
+        if self.max_depth == 0 {
+            conflicts.push(OptionConflict::ZeroMaxDepth);
+        }
+        conflicts
+    }
+
+    /// Checks these options for conflicting combinations, failing with all [`OptionConflict`]s if any is an error.
+    /// Called by [`CompareOptions::build`] and thus by all comparison functions.
+    /// ```rust
+    /// use json_diff_ng::prelude::{CompareOptions, OptionConflict, SampleSpec};
+    /// let options = CompareOptions::default().verify_multiplicity(true);
+    /// assert_eq!(options.conflicts(), vec![OptionConflict::MultiplicityWithoutSorting]);
+    /// assert!(options.validate().is_ok());
+    /// let spec = SampleSpec::new(1).array_stride(2);
+    /// let conflicts = options.sort_arrays(true).sample(spec).validate().unwrap_err();
+    /// assert_eq!(conflicts, vec![OptionConflict::SampledMultiplicity]);
+    /// ```
+    pub fn validate(&self) -> core::result::Result<(), Vec<OptionConflict>> {
+        let conflicts = self.conflicts();
+        if conflicts.iter().any(OptionConflict::is_error) {
+            return Err(conflicts);
+        }
+        Ok(())
+    }
+
+    /// Validates and compiles these options into a [`Comparator`] for repeated comparisons.
+    /// Invalid patterns and conflicting options are reported here instead of on each comparison.
+    pub fn build(&self) -> Result<Comparator> {
+        self.validate().map_err(Error::InvalidOptions)?;
+        let mut options = self.clone();
+        for pattern in core::mem::take(&mut options.ignore_key_patterns) {
+            options.ignore_keys.push(Regex::new(&pattern)?);
+        }
+        for pattern in core::mem::take(&mut options.include_key_patterns) {
+            options.include_keys_override.push(Regex::new(&pattern)?);
+        }
+        for pattern in core::mem::take(&mut options.ignore_path_patterns) {
+            options.ignore_paths.push(Regex::new(&pattern)?);
+        }
+        for pattern in core::mem::take(&mut options.ignore_value_patterns) {
+            options.ignore_values.push(ValueRule::both_sides(Regex::new(&pattern)?));
+        }
+        Ok(Comparator { options })
+    }
+}
+
+/// Compiled [`CompareOptions`], created by [`CompareOptions::build`].
+/// A comparator is `Send + Sync`, so one instance can serve a whole thread pool:
+/// ```rust
+/// use json_diff_ng::CompareOptions;
+/// use serde_json::json;
+/// let comparator = CompareOptions::default().ignore_key_patterns(["^_id$"]).build().unwrap();
+/// let diffs = comparator.compare(&json!({"_id": 1, "a": 2}), &json!({"_id": 3, "a": 2})).unwrap();
+/// assert!(diffs.is_empty());
+/// ```
+#[derive(Clone, Debug)]
+pub struct Comparator {
+    options: CompareOptions,
+}
+
+impl Comparator {
+    /// The compiled options, with all patterns moved into [`CompareOptions::ignore_keys`], [`CompareOptions::include_keys_override`],
+    /// [`CompareOptions::ignore_paths`] and [`CompareOptions::ignore_values`].
+    pub fn options(&self) -> &CompareOptions {
+        &self.options
+    }
+
+    /// Which keys of the document of `side` are compared, with per-side ignores and include overrides applied.
+    pub fn key_matcher(&self, side: Side) -> KeyMatcher<'_> {
+        let keys = self.options.key_matchers();
+        match side {
+            Side::Left => keys.left,
+            Side::Right => keys.right,
+        }
+    }
+
+    /// Compares two [`serde_json::Value`] items, see [`compare_serde_values_with_options`].
+    pub fn compare(&self, a: &Value, b: &Value) -> Result<Mismatch> {
+        observed(&self.options, || compare_observed(a, b, &self.options))
+    }
+
+    /// Compares two string slices containing serialized json, see [`compare_strs_with_options`].
+    pub fn compare_strs(&self, a: &str, b: &str) -> Result<Mismatch> {
+        self.compare_slices(a.as_bytes(), b.as_bytes())
+    }
+
+    /// Compares two byte slices containing serialized json, see [`compare_slices`].
+    pub fn compare_slices(&self, a: &[u8], b: &[u8]) -> Result<Mismatch> {
+        self.check_input_sizes(a.len() as u64, b.len() as u64)?;
+        let ((a, skipped_a), (b, skipped_b)) = (self.document(a), self.document(b));
+        let mut mismatch = observed(&self.options, || {
+            let value1 = parse_input(a, Side::Left, self.options.max_depth)?;
+            let value2 = parse_input(b, Side::Right, self.options.max_depth)?;
+            compare_observed(&value1, &value2, &self.options)
+        })?;
+        mismatch.warnings.extend(skipped_prefixes(skipped_a, skipped_b));
+        Ok(mismatch)
+    }
+
+    /// Parses the serialized input of `side` the way [`Comparator::compare_slices`] does, following
+    /// [`CompareOptions::parse_mode`] and [`CompareOptions::max_depth`], with the warning about a skipped prefix.
+    /// ```rust
+    /// use json_diff_ng::{CompareOptions, ParseMode, Side};
+    /// use serde_json::json;
+    /// let comparator = CompareOptions::default()
+    ///     .parse_mode(ParseMode::Tolerant { skip_prefix: false })
+    ///     .build()
+    ///     .unwrap();
+    /// let (value, skipped) = comparator.parse(b"\xEF\xBB\xBF{\"a\": 1}", Side::Left).unwrap();
+    /// assert_eq!(value, json!({"a": 1}));
+    /// assert!(skipped.is_none());
+    /// ```
+    pub fn parse(&self, input: &[u8], side: Side) -> Result<(Value, Option<Warning>)> {
+        let (input, skipped) = self.document(input);
+        let value = parse_input(input, side, self.options.max_depth)?;
+        let skipped = (skipped > 0).then_some(Warning::SkippedPrefix {
+            side,
+            bytes: skipped,
+        });
+        Ok((value, skipped))
+    }
+
+    /// Compares two files containing serialized json, see [`compare_files`].
+    #[cfg(feature = "std")]
+    pub fn compare_files(
+        &self,
+        a: impl AsRef<std::path::Path>,
+        b: impl AsRef<std::path::Path>,
+    ) -> Result<Mismatch> {
+        let (a, b) = (a.as_ref(), b.as_ref());
+        let context = |side: Side| {
+            let path = match side {
+                Side::Left => a,
+                Side::Right => b,
+            };
+            ErrorContext::default()
+                .file(path.display().to_string())
+                .side(side)
+        };
+        let io_error = |e, side: Side, path: &std::path::Path| {
+            Error::from(vg_errortools::FatIOError::from_std_io_err(e, path.into()))
+                .with_context(context(side))
+        };
+        let size = |side: Side, path: &std::path::Path| {
+            std::fs::metadata(path)
+                .map(|metadata| metadata.len())
+                .map_err(|e| io_error(e, side, path))
+        };
+        self.check_input_sizes(size(Side::Left, a)?, size(Side::Right, b)?)
+            .map_err(|e| match e.side() {
+                Some(side) => e.with_context(context(side)),
+                None => e,
+            })?;
+        let read = |side: Side, path: &std::path::Path| {
+            std::fs::read(path).map_err(|e| io_error(e, side, path))
+        };
+        let (a_bytes, b_bytes) = (read(Side::Left, a)?, read(Side::Right, b)?);
+        self.compare_slices(&a_bytes, &b_bytes)
+            .map_err(|e| match e.side() {
+                Some(side) => e.with_context(context(side)),
+                None => e,
+            })
+    }
+
+    /// Compares two streams of whitespace-separated json documents, see [`compare_multi_document_strs`].
+    pub fn compare_multi_document_strs(&self, a: &str, b: &str) -> Result<Vec<Mismatch>> {
+        self.compare_multi_document_slices(a.as_bytes(), b.as_bytes())
+    }
+
+    /// Same as [`Comparator::compare_multi_document_strs`] for byte slices.
+    pub fn compare_multi_document_slices(&self, a: &[u8], b: &[u8]) -> Result<Vec<Mismatch>> {
+        self.check_input_sizes(a.len() as u64, b.len() as u64)?;
+        let ((a, skipped_a), (b, skipped_b)) = (self.document(a), self.document(b));
+        let documents_a = parse_documents(a, Side::Left, self.options.max_depth)?;
+        let documents_b = parse_documents(b, Side::Right, self.options.max_depth)?;
+        let length = documents_a.len().max(documents_b.len());
+        let mut mismatches = (0..length)
+            .map(|i| match (documents_a.get(i), documents_b.get(i)) {
+                (Some(a), Some(b)) => self.compare(a, b),
+                (Some(a), None) => Ok(self.one_sided_document(Side::Left, a)),
+                (None, Some(b)) => Ok(self.one_sided_document(Side::Right, b)),
+                (None, None) => Ok(Mismatch::default()),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // prefixes are skipped before the first document only
+        if let Some(first) = mismatches.first_mut() {
+            first.warnings.extend(skipped_prefixes(skipped_a, skipped_b));
+        }
+        Ok(mismatches)
+    }
+
+    /// The document of a serialized input and the bytes skipped before it, see [`CompareOptions::parse_mode`].
+    fn document<'i>(&self, input: &'i [u8]) -> (&'i [u8], usize) {
+        self.options.parse_mode.document(input)
+    }
+
+    /// Rejects inputs of `left` and `right` bytes exceeding [`CompareOptions::max_input_bytes`], naming their side.
+    fn check_input_sizes(&self, left: u64, right: u64) -> Result<()> {
+        let Some(limit) = self.options.max_input_bytes else {
+            return Ok(());
+        };
+        for (side, actual) in [(Side::Left, left), (Side::Right, right)] {
+            if actual > limit {
+                let context = ErrorContext::default().side(side);
+                return Err(Error::InputTooLarge { actual, limit }.with_context(context));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reports a document without counterpart as extra at the root.
+    fn one_sided_document(&self, side: Side, document: &Value) -> Mismatch {
+        let mut node = DiffTreeNode::one_sided(document.clone());
+        if self.options.no_value_capture {
+            node.redact_values();
+        }
+        let mut mismatch = match side {
+            Side::Left => Mismatch::default().with_left_only(node),
+            Side::Right => Mismatch::default().with_right_only(node),
+        };
+        mismatch.labels = self.options.labels.clone().map(Box::new);
+        mismatch
+    }
+}
+
+/// Parses the json `input` of `side`, failures are [`Error::ParseLeft`] or [`Error::ParseRight`].
+/// `serde_json` rejects inputs nested deeper than 128 levels. With the `unbounded_depth` feature inputs are
+/// parsed up to `max_depth` instead, see [`CompareOptions::max_depth`], deeper ones are rejected with
+/// [`Error::DepthLimitExceeded`] before parsing, so they cannot overflow the stack. Parsing and comparing
+/// need stack in proportion to the depth, raise `max_depth` only as far as the stack of the calling thread allows.
+pub(crate) fn parse_input(input: &[u8], side: Side, max_depth: usize) -> Result<Value> {
+    let error = |e| match side {
+        Side::Left => Error::ParseLeft(e),
+        Side::Right => Error::ParseRight(e),
+    };
+    #[cfg(feature = "unbounded_depth")]
+    {
+        use serde::Deserialize;
+        if crate::raw::raw_depth(input) > max_depth {
+            return Err(Error::DepthLimitExceeded(max_depth));
+        }
+        let mut deserializer = serde_json::Deserializer::from_slice(input);
+        deserializer.disable_recursion_limit();
+        let value = Value::deserialize(&mut deserializer).map_err(error)?;
+        deserializer.end().map_err(error)?;
+        Ok(value)
+    }
+    #[cfg(not(feature = "unbounded_depth"))]
+    {
+        let _ = max_depth;
+        serde_json::from_slice(input).map_err(error)
+    }
+}
+
+fn parse_documents(input: &[u8], side: Side, max_depth: usize) -> Result<Vec<Value>> {
+    #[allow(unused_mut)]
+    let mut deserializer = serde_json::Deserializer::from_slice(input);
+    #[cfg(feature = "unbounded_depth")]
+    {
+        if crate::raw::raw_depth(input) > max_depth {
+            return Err(Error::DepthLimitExceeded(max_depth));
+        }
+        deserializer.disable_recursion_limit();
+    }
+    #[cfg(not(feature = "unbounded_depth"))]
+    let _ = max_depth;
+    deserializer
+        .into_iter::<Value>()
+        .enumerate()
+        .map(|(index, document)| document.map_err(|e| Error::ParseDocument(side, index, e)))
+        .collect()
+}
+
+/// Warnings about the prefixes of `left` and `right` bytes skipped by [`ParseMode::Tolerant`].
+fn skipped_prefixes(left: usize, right: usize) -> impl Iterator<Item = Warning> {
+    [(Side::Left, left), (Side::Right, right)]
+        .into_iter()
+        .filter(|(_, bytes)| *bytes > 0)
+        .map(|(side, bytes)| Warning::SkippedPrefix { side, bytes })
+}
+
+/// Shared handle to an [`Observer`], needed to keep [`CompareOptions`] `Clone` and `Debug`.
+#[derive(Clone)]
+pub struct ObserverHandle(pub(crate) Arc<dyn Observer>);
+
+impl Debug for ObserverHandle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "ObserverHandle")
+    }
+}
+
+/// Rule of [`CompareOptions::ignore_values`]: a pair of strings both matching `pattern` is equal. With `either_side`,
+/// a string matching `pattern` equals any value on the other side, e.g. a timestamp that is `null` on one side.
+/// Patterns match anywhere in the string unless they are anchored with `^` and `$`.
+#[derive(Clone, Debug)]
+pub struct ValueRule {
+    pub pattern: Regex,
+    pub either_side: bool,
+}
+
+impl ValueRule {
+    /// Pairs of strings both matching `pattern` are equal.
+    pub fn both_sides(pattern: Regex) -> Self {
+        Self {
+            pattern,
+            either_side: false,
+        }
+    }
+
+    /// A string matching `pattern` equals any value on the other side.
+    pub fn either_side(pattern: Regex) -> Self {
+        Self {
+            pattern,
+            either_side: true,
+        }
+    }
+
+    fn matches(&self, a: &Value, b: &Value) -> bool {
+        let is_match = |v: &Value| v.as_str().is_some_and(|s| self.pattern.is_match(s));
+        match self.either_side {
+            true => is_match(a) || is_match(b),
+            false => is_match(a) && is_match(b),
+        }
+    }
+}
+
+/// Shared key normalization function, see [`CompareOptions::key_normalizer`].
+#[derive(Clone)]
+pub struct KeyNormalizer(pub(crate) Arc<dyn Fn(&str) -> String + Send + Sync>);
+
+impl Debug for KeyNormalizer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "KeyNormalizer")
+    }
+}
+
+/// Shared array element filter, see [`CompareOptions::filter_array_elements`].
+#[derive(Clone)]
+pub struct ElementFilter(pub(crate) Arc<ElementFilterFn>);
+
+/// Signature of an [`ElementFilter`]: path of the array and an element, `false` drops the element.
+pub type ElementFilterFn = dyn Fn(&[PathElement], &Value) -> bool + Send + Sync;
+
+impl Debug for ElementFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "ElementFilter")
+    }
+}
+
+/// Shared array element identity, see [`CompareOptions::array_identity`].
+#[derive(Clone)]
+pub struct ElementIdentity(pub(crate) Arc<ElementIdentityFn>);
+
+/// Signature of an [`ElementIdentity`]: path of the array and an element, `None` pairs the element by position.
+pub type ElementIdentityFn = dyn Fn(&[PathElement], &Value) -> Option<String> + Send + Sync;
+
+impl Debug for ElementIdentity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "ElementIdentity")
+    }
+}
+
+/// State of a single comparison run, threaded through the recursion.
+struct Context<'o> {
+    options: &'o CompareOptions,
+    usage: ResourceUsage,
+    depth: usize,
+    /// Path of the values currently compared, only tracked when an option depending on paths needs it
+    path: DiffPath,
+    /// Anomalies noticed along the way, see [`Mismatch::warnings`]
+    warnings: Vec<Warning>,
+    /// Containers captured in diffs so far, equal ones are stored once
+    captured: SharedValues,
+}
+
+impl<'o> Context<'o> {
+    fn new(options: &'o CompareOptions) -> Self {
+        Self {
+            options,
+            usage: ResourceUsage {
+                leaves: options.collect_counts.then(LeafCounts::default),
+                ..Default::default()
+            },
+            depth: 0,
+            path: DiffPath::default(),
+            warnings: Vec::new(),
+            captured: SharedValues::default(),
+        }
+    }
+
+    /// A copy of `value` to capture in a diff, shared with an equal one captured before.
+    fn capture(&mut self, value: &Value) -> Arc<Value> {
+        self.captured.capture(value)
+    }
+
+    /// Leaf of the differing values `a` and `b`.
+    fn leaf(&mut self, a: &Value, b: &Value) -> DiffTreeNode {
+        DiffTreeNode::Value(self.capture(a), self.capture(b))
+    }
+
+    /// Adds the scalar leaves of `left` and `right` to the leaf counts, if collected,
+    /// and those of `left` as equal ones if `equal`.
+    fn count(&mut self, left: Option<&Value>, right: Option<&Value>, equal: bool) {
+        let Some(counts) = &mut self.usage.leaves else {
+            return;
+        };
+        let keys = self.options.key_matchers();
+        let left = left.map_or(0, |v| scalar_leaves(v, keys.left));
+        counts.left += left;
+        counts.right += right.map_or(0, |v| scalar_leaves(v, keys.right));
+        if equal {
+            counts.equal += left;
+        }
+    }
+
+    /// Compares the children `a` and `b` found at `element` of the current path.
+    fn match_child(&mut self, a: &Value, b: &Value, element: OwnedPathElement) -> Result<Mismatch> {
+        if self.options.array_element_filter.is_none()
+            && !self.options.has_array_identity()
+            && self.options.array_modes.is_empty()
+            && self.options.decimal_places_at.is_empty()
+            && self.options.object_sets.is_empty()
+            && !self.options.canonical_keys
+            && !self.options.has_ignored_paths()
+        {
+            return match_json(a, b, self);
+        }
+        self.path.0.push(element);
+        let result = match self.options.is_ignored_path(&self.path) {
+            true => Ok(Mismatch::empty()),
+            false => match_json(a, b, self),
+        };
+        self.path.0.pop();
+        result
+    }
+
+    /// Whether the child at `element` of the current path is excluded, see [`CompareOptions::ignore_pointer`].
+    fn is_ignored_child(&mut self, element: OwnedPathElement) -> bool {
+        if !self.options.has_ignored_paths() {
+            return false;
+        }
+        self.path.0.push(element);
+        let ignored = self.options.is_ignored_path(&self.path);
+        self.path.0.pop();
+        ignored
+    }
+}
+
+/// Compares two string slices containing serialized json with each other, returns an error or a [`Mismatch`] structure holding all differences.
+/// Internally this calls into [`compare_serde_values`] after deserializing the string slices into [`serde_json::Value`].
+/// Arguments are the string slices, a bool to trigger deep sorting of arrays and ignored_keys as a list of regex to match keys against.
+/// Ignoring a regex from comparison will also ignore the key from having an impact on sorting arrays.
+pub fn compare_strs(
+    a: &str,
+    b: &str,
+    sort_arrays: bool,
+    ignore_keys: &[Regex],
+) -> Result<Mismatch> {
+    let options = CompareOptions::default()
+        .sort_arrays(sort_arrays)
+        .ignore_keys(ignore_keys.to_vec());
+    compare_strs_with_options(a, b, &options)
+}
+
+/// Compares two [`serde_json::Value`] items with each other, returns an error or a [`Mismatch`] structure holding all differences.
+/// Arguments are the values, a bool to trigger deep sorting of arrays and ignored_keys as a list of regex to match keys against.
+/// Ignoring a regex from comparison will also ignore the key from having an impact on sorting arrays.
+pub fn compare_serde_values(
+    a: &Value,
+    b: &Value,
+    sort_arrays: bool,
+    ignore_keys: &[Regex],
+) -> Result<Mismatch> {
+    let options = CompareOptions::default()
+        .sort_arrays(sort_arrays)
+        .ignore_keys(ignore_keys.to_vec());
+    compare_serde_values_with_options(a, b, &options)
+}
+
+/// Same as [`compare_strs`] but configured by [`CompareOptions`].
+/// Prefer [`CompareOptions::build`] when comparing many pairs with the same options.
+pub fn compare_strs_with_options(a: &str, b: &str, options: &CompareOptions) -> Result<Mismatch> {
+    with_comparator(options, |comparator| comparator.compare_strs(a, b))
+}
+
+/// Compares two byte slices containing serialized json, e.g. file contents, without converting them to strings first.
+/// This saves the separate UTF-8 validation pass and the copy of [`compare_strs`] for large inputs.
+pub fn compare_slices(a: &[u8], b: &[u8], options: &CompareOptions) -> Result<Mismatch> {
+    with_comparator(options, |comparator| comparator.compare_slices(a, b))
+}
+
+/// Compares the json files at the paths `a` and `b`. Errors reading or parsing a file, or unwrapping its document,
+/// carry the file and its side as [`Error::context`].
+/// ```rust
+/// use json_diff_ng::{compare_files, CompareOptions, Side};
+/// let err = compare_files("missing.json", "Cargo.toml", &CompareOptions::default()).unwrap_err();
+/// assert_eq!((err.file(), err.side()), (Some("missing.json"), Some(Side::Left)));
+/// ```
+#[cfg(feature = "std")]
+pub fn compare_files(
+    a: impl AsRef<std::path::Path>,
+    b: impl AsRef<std::path::Path>,
+    options: &CompareOptions,
+) -> Result<Mismatch> {
+    with_comparator(options, |comparator| comparator.compare_files(a, b))
+}
+
+/// Same as [`compare_serde_values`] but configured by [`CompareOptions`].
+/// This never panics for any pair of values and any options: inputs nested deeper than [`CompareOptions::max_depth`]
+/// are rejected with [`Error::DepthLimitExceeded`], everything else is compared.
+pub fn compare_serde_values_with_options(
+    a: &Value,
+    b: &Value,
+    options: &CompareOptions,
+) -> Result<Mismatch> {
+    with_comparator(options, |comparator| comparator.compare(a, b))
+}
+
+/// Compares only the values at the JSON pointer `pointer` of `a` and `b`, e.g. to check whether `/spec/replicas` changed
+/// without comparing the whole documents. Returns `None` if the values are equal or missing on both sides, and a
+/// one-sided record if the pointer resolves on one side only. Values differing in one place return that diff, values
+/// differing in several places a [`DiffType::Mismatch`] record of both values at the pointer. Paths of the records start
+/// at the roots of the documents, path based options like [`CompareOptions::ignore_pointer`] apply to the values at
+/// the pointer as roots.
+/// ```rust
+/// use json_diff_ng::{diff_at, CompareOptions, DiffValues};
+/// use serde_json::json;
+/// let a = json!({"spec": {"replicas": 2, "image": "app:1"}});
+/// let b = json!({"spec": {"replicas": 3, "image": "app:2"}});
+/// let options = CompareOptions::default();
+/// let record = diff_at(&a, &b, "/spec/replicas", &options).unwrap().unwrap();
+/// assert_eq!(record.to_string(), "Mismatched: .spec.replicas.(2 != 3)");
+/// assert_eq!(record.values, DiffValues::Pair(json!(2), json!(3)));
+/// assert!(diff_at(&a, &a, "/spec/replicas", &options).unwrap().is_none());
+/// ```
+pub fn diff_at(
+    a: &Value,
+    b: &Value,
+    pointer: &str,
+    options: &CompareOptions,
+) -> Result<Option<DiffRecord>> {
+    let resolve = |document| match pointer::extract(document, pointer) {
+        Ok(value) => Ok(Some(value)),
+        Err(Error::PointerNotFound(..)) => Ok(None),
+        Err(e) => Err(e),
+    };
+    let (diff_type, values, document) = match (resolve(a)?, resolve(b)?) {
+        (None, None) => return Ok(None),
+        (Some(left), None) => (DiffType::LeftExtra, DiffValues::Single(left.clone()), a),
+        (None, Some(right)) => (DiffType::RightExtra, DiffValues::Single(right.clone()), b),
+        (Some(left), Some(right)) => {
+            let mismatch = compare_serde_values_with_options(left, right, options)?;
+            let mut records: Vec<DiffRecord> = mismatch
+                .records()
+                .into_iter()
+                .filter(|record| !record.is_informational())
+                .collect();
+            if records.is_empty() {
+                return Ok(None);
+            }
+            if let [record] = records.as_mut_slice() {
+                let mut path = pointer::to_path(a, pointer).0;
+                path.append(&mut record.path.0);
+                record.path = DiffPath(path);
+                return Ok(records.pop());
+            }
+            let pair = DiffValues::Pair(left.clone(), right.clone());
+            (DiffType::Mismatch, pair, a)
+        }
+    };
+    let mut record = DiffRecord {
+        diff_type,
+        path: pointer::to_path(document, pointer),
+        values,
+        origin: None,
+        context: None,
+        string_diff: None,
+    };
+    if options.no_value_capture {
+        record.values.redact();
+    }
+    Ok(Some(record))
+}
+
+/// Builds `options` for a single comparison, build errors are reported to the observer like comparison errors.
+fn with_comparator(
+    options: &CompareOptions,
+    comparison: impl FnOnce(&Comparator) -> Result<Mismatch>,
+) -> Result<Mismatch> {
+    match options.build() {
+        Ok(comparator) => comparison(&comparator),
+        Err(e) => observed(options, || Err(e)),
+    }
+}
+
+/// Compares two streams of whitespace-separated json documents, e.g. `{"a": 1} {"a": 2}`, pairing them by position.
+/// Returns one [`Mismatch`] per position. A document without counterpart on the other side is reported as extra at the root.
+/// ```rust
+/// use json_diff_ng::{compare_multi_document_strs, CompareOptions};
+/// let diffs = compare_multi_document_strs("1 [2]", "1 [3] {}", &CompareOptions::default()).unwrap();
+/// assert_eq!(diffs.len(), 3);
+/// assert!(diffs[0].is_empty());
+/// assert_eq!(diffs[2].all_diffs()[0].1.to_string(), ".({})");
+/// ```
+pub fn compare_multi_document_strs(
+    a: &str,
+    b: &str,
+    options: &CompareOptions,
+) -> Result<Vec<Mismatch>> {
+    options.build()?.compare_multi_document_strs(a, b)
+}
+
+/// Runs `comparison` and reports its start, outcome and resource usage to the observer, if one is set.
+fn observed(
+    options: &CompareOptions,
+    comparison: impl FnOnce() -> Result<Mismatch>,
+) -> Result<Mismatch> {
+    let Some(ObserverHandle(observer)) = &options.observer else {
+        return comparison();
+    };
+    observer.on_start();
+    let result = comparison();
+    match &result {
+        Ok(mismatch) => observer.on_finish(&mismatch.usage, &mismatch.count_diffs()),
+        Err(e) => observer.on_error(e),
+    }
+    result
+}
+
+fn compare_observed(a: &Value, b: &Value, options: &CompareOptions) -> Result<Mismatch> {
+    let (a, b) = options.transformed(a, b)?;
+    let (a, b, ref_warnings) = options.refs_resolved(a, b)?;
+    let (a, b) = (a.as_ref(), b.as_ref());
+    let original_left = a;
+    let (a, b, unwrap_warnings) = options.unwrapped(a, b)?;
+    // all passes over the values recurse, so the depth is checked up front
+    if exceeds_depth(a, options.max_depth) || exceeds_depth(b, options.max_depth) {
+        return Err(Error::DepthLimitExceeded(options.max_depth));
+    }
+    #[cfg(feature = "std")]
+    let start = std::time::Instant::now();
+    let mut context = Context::new(options);
+    let mut mismatch = match_json(a, b, &mut context)?;
+    mismatch.usage = context.usage;
+    mismatch.warnings = options
+        .conflicts()
+        .into_iter()
+        .map(Warning::Options)
+        .chain(ref_warnings)
+        .chain(unwrap_warnings)
+        .chain(context.warnings)
+        .collect();
+    mismatch.labels = options.labels.clone().map(Box::new);
+    mismatch.string_diffs = options.string_diffs;
+    mismatch.normalization = Normalization {
+        arrays: options.sort_arrays || options.array_element_filter.is_some(),
+        left_unwrapped: !core::ptr::eq(a, original_left),
+        left_transformed: options.pre_transform_left.is_some() || options.resolve_internal_refs,
+    };
+    check_expectations(a, b, options, &mut mismatch);
+    if options.no_value_capture {
+        mismatch.redact_values();
+    }
+    #[cfg(feature = "std")]
+    {
+        mismatch.usage.duration = start.elapsed();
+    }
+    Ok(mismatch)
+}
+
+/// Reports [`Expectation::MustDiffer`] paths without diffs at or below them, and stores the paths in the result.
+fn check_expectations(a: &Value, b: &Value, options: &CompareOptions, mismatch: &mut Mismatch) {
+    if options.expectations.is_empty() {
+        return;
+    }
+    let diff_pointers: Vec<String> = mismatch
+        .records()
+        .iter()
+        .map(|record| record.path.to_pointer())
+        .collect();
+    for (pointer, Expectation::MustDiffer) in &options.expectations {
+        mismatch.expected_changes.push(pointer.clone());
+        if diff_pointers.iter().any(|p| pointer::is_within(p, pointer)) {
+            continue;
+        }
+        let values = match (a.pointer(pointer), b.pointer(pointer)) {
+            (Some(l), Some(r)) => DiffValues::Pair(l.clone(), r.clone()),
+            _ => DiffValues::Empty,
+        };
+        mismatch.extra_records.push(DiffRecord {
+            diff_type: DiffType::ExpectedChangeMissing,
+            path: pointer_path(a, b, pointer),
+            values,
+            origin: None,
+            context: None,
+            string_diff: None,
+        });
+    }
+}
+
+/// The path `pointer` refers to, tokens are array indices where either document holds an array.
+fn pointer_path(a: &Value, b: &Value, pointer: &str) -> DiffPath {
+    fn child<'v>(value: Option<&'v Value>, element: &OwnedPathElement) -> Option<&'v Value> {
+        match (value?, element) {
+            (Value::Array(array), OwnedPathElement::ArrayEntry(i)) => array.get(*i),
+            (Value::Object(object), OwnedPathElement::Object(key)) => object.get(key),
+            _ => None,
+        }
+    }
+    let mut path = DiffPath::default();
+    let (mut a, mut b) = (Some(a), Some(b));
+    for token in pointer.split('/').skip(1).map(pointer::unescape) {
+        let is_array = |v: Option<&Value>| v.is_some_and(Value::is_array);
+        let element = match pointer::array_index(&token) {
+            Some(index) if is_array(a) || is_array(b) => OwnedPathElement::ArrayEntry(index),
+            _ => OwnedPathElement::Object(token),
+        };
+        (a, b) = (child(a, &element), child(b, &element));
+        path.0.push(element);
+    }
+    path
+}
+
+/// Checks the nesting depth of `value` without recursion, so arbitrarily deep values can be checked.
+pub(crate) fn exceeds_depth(value: &Value, limit: usize) -> bool {
+    let mut stack = vec![(value, 1)];
+    while let Some((value, depth)) = stack.pop() {
+        if depth > limit {
+            return true;
+        }
+        match value {
+            Value::Array(a) => stack.extend(a.iter().map(|v| (v, depth + 1))),
+            Value::Object(o) => stack.extend(o.values().map(|v| (v, depth + 1))),
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Ranges reported by the myers diff of two arrays.
+#[derive(Default)]
+pub(crate) struct ListDiffHandler {
+    /// Runs of equal elements as offset in both arrays and length
+    pub equal: Vec<(usize, usize, usize)>,
+    pub replaced: Vec<ReplacedBlock>,
+    pub deletion: Vec<Range>,
+    pub insertion: Vec<Range>,
+}
+impl Diff for ListDiffHandler {
+    fn equal(&mut self, old: usize, new: usize, len: usize) {
+        self.equal.push((old, new, len));
+    }
+    fn delete(&mut self, old: usize, len: usize, _new: usize) {
+        self.deletion.push((old, len));
+    }
+    fn insert(&mut self, _o: usize, new: usize, len: usize) {
+        self.insertion.push((new, len));
+    }
+    fn replace(&mut self, old: usize, len: usize, new: usize, new_len: usize) {
+        self.replaced.push((old, len, new, new_len));
+    }
+}
+
+fn match_json(value1: &Value, value2: &Value, context: &mut Context) -> Result<Mismatch> {
+    context.usage.compared_nodes += 1;
+    context.depth += 1;
+    context.usage.max_depth = context.usage.max_depth.max(context.depth);
+    let sets = match context.options.object_sets.is_empty() {
+        true => None,
+        false => coerced_object_sets(value1, value2, context),
+    };
+    let (value1, value2) = match &sets {
+        Some((a, b)) => (a, b),
+        None => (value1, value2),
+    };
+    let template = context.options.mode == CompareMode::Template;
+    let wildcard = context.options.is_wildcard(value1)
+        || context.options.is_wildcard(value2)
+        || context.options.is_ignored_value_pair(value1, value2);
+    let result = match (value1, value2) {
+        _ if wildcard => {
+            let scalars = !is_container(value1) && !is_container(value2);
+            context.count(Some(value1), Some(value2), scalars);
+            Ok(Mismatch::empty())
+        }
+        (Value::String(name), b) if template && template::is_type_name(name) => {
+            let result = process_template_type(name, b, context);
+            let matched = result.as_ref().is_ok_and(|m| m.is_empty()) && !is_container(b);
+            context.count(Some(value1), Some(b), matched);
+            result
+        }
+        (Value::Array(a), Value::Array(b)) if template && a.len() == 1 => {
+            process_template_array(&a[0], b, context)
+        }
+        (Value::Object(a), Value::Object(b)) => process_objects(a, b, context),
+        (Value::Array(a), Value::Array(b)) if !context.options.array_modes.is_empty() => {
+            process_arrays_in_mode(a, b, context)
+        }
+        (Value::Array(a), Value::Array(b)) if context.options.has_array_identity() => {
+            process_keyed_arrays(a, b, context)
+        }
+        (Value::Array(a), Value::Array(b)) => process_arrays(a, b, context),
+        (Value::Number(a), Value::Number(b))
+            if context.options.has_decimal_places() || context.options.has_float_tolerance() =>
+        {
+            process_numbers(a, b, context)
+        }
+        (a, b) => {
+            context.count(Some(a), Some(b), a == b);
+            process_values(a, b, context)
+        }
+    };
+    let result = match (value1, value2) {
+        (Value::Array(_), Value::Array(_)) if context.options.has_ignored_paths() => {
+            result.map(|mismatch| drop_ignored_elements(mismatch, context))
+        }
+        _ => result,
+    };
+    context.depth -= 1;
+    match sets {
+        Some(_) if context.options.record_origins => result.map(as_object_set),
+        _ => result,
+    }
+}
+
+/// Drops the array elements present on one side only at excluded paths, see [`CompareOptions::ignore_pointer`].
+/// Excluded pairs of elements are skipped by [`Context::match_child`] already.
+fn drop_ignored_elements(mut mismatch: Mismatch, context: &mut Context) -> Mismatch {
+    for diff_type in [DiffType::LeftExtra, DiffType::RightExtra] {
+        let node = match diff_type {
+            DiffType::LeftExtra => &mut mismatch.left_only,
+            _ => &mut mismatch.right_only,
+        };
+        let DiffTreeNode::Array(elements) = node else {
+            continue;
+        };
+        let mut ignored = Vec::new();
+        elements.retain(|(index, _)| {
+            let element = OwnedPathElement::ArrayEntry(*index);
+            let keep = !context.is_ignored_child(element.clone());
+            if !keep {
+                ignored.push(element);
+            }
+            keep
+        });
+        if elements.is_empty() {
+            *node = DiffTreeNode::Null;
+        }
+        for element in ignored {
+            mismatch.origins.remove_at(diff_type, element);
+        }
+    }
+    if mismatch.is_empty() && !mismatch.has_order_differences() {
+        mismatch.array_frames.remove(&DiffPath::default());
+    }
+    mismatch
+}
+
+/// Both values with the sets among them as sorted arrays of their keys, if the current path is a
+/// [`CompareOptions::coerce_object_set`] rule and either value is a set.
+fn coerced_object_sets(a: &Value, b: &Value, context: &Context) -> Option<(Value, Value)> {
+    let pointer = context.path.to_pointer();
+    if !context.options.object_sets.contains(&pointer) {
+        return None;
+    }
+    let (set_a, set_b) = (object_set(a), object_set(b));
+    if set_a.is_none() && set_b.is_none() {
+        return None;
+    }
+    Some((
+        set_a.unwrap_or_else(|| a.clone()),
+        set_b.unwrap_or_else(|| b.clone()),
+    ))
+}
+
+/// The sorted keys of `value` if it is an object whose values are all `true` or all `null`.
+fn object_set(value: &Value) -> Option<Value> {
+    let Value::Object(object) = value else {
+        return None;
+    };
+    let all = |member: &Value| object.values().all(|v| v == member);
+    if !all(&Value::Bool(true)) && !all(&Value::Null) {
+        return None;
+    }
+    // keys are only sorted without the preserve_order feature of serde_json
+    let mut keys: Vec<&String> = object.keys().collect();
+    keys.sort();
+    Some(Value::Array(
+        keys.into_iter().cloned().map(Value::String).collect(),
+    ))
+}
+
+/// Attributes every diff of `mismatch`, compared after coercing a set, to the coercion.
+fn as_object_set(mut mismatch: Mismatch) -> Mismatch {
+    for record in mismatch.records() {
+        mismatch
+            .origins
+            .insert(record.diff_type, record.path, Origin::ObjectSet);
+    }
+    mismatch
+}
+
+fn is_container(value: &Value) -> bool {
+    matches!(value, Value::Array(_) | Value::Object(_))
+}
+
+/// Number of null, boolean, number and string values in `value`, disregarding ignored keys.
+fn scalar_leaves(value: &Value, keys: KeyMatcher) -> usize {
+    match value {
+        Value::Array(a) => a.iter().map(|v| scalar_leaves(v, keys)).sum(),
+        Value::Object(o) => o
+            .iter()
+            .filter(|(k, _)| keys.is_compared(k))
+            .map(|(_, v)| scalar_leaves(v, keys))
+            .sum(),
+        _ => 1,
+    }
+}
+
+fn process_values(a: &Value, b: &Value, context: &mut Context) -> Result<Mismatch> {
+    if a == b {
+        Ok(Mismatch::empty())
+    } else {
+        Ok(Mismatch::new(
+            DiffTreeNode::Null,
+            DiffTreeNode::Null,
+            context.leaf(a, b),
+        ))
+    }
+}
+
+/// Compares numbers at their [`CompareOptions::decimal_places`] and within their [`CompareOptions::float_tolerance`],
+/// they are equal if either says so.
+fn process_numbers(a: &Number, b: &Number, context: &mut Context) -> Result<Mismatch> {
+    let options = context.options;
+    let places = match options.decimal_places_at.is_empty() {
+        true => options.decimal_places,
+        false => options.decimal_places_for(&context.path.to_pointer()),
+    };
+    let equal = match places {
+        Some(places) => decimal::equal_at(a, b, places),
+        None => a == b,
+    } || (options.has_float_tolerance()
+        && decimal::within_tolerance(a, b, options.absolute_tolerance, options.relative_tolerance));
+    let (a, b) = (Value::Number(a.clone()), Value::Number(b.clone()));
+    context.count(Some(&a), Some(&b), equal);
+    match equal {
+        true => Ok(Mismatch::empty()),
+        false => process_values(&a, &b, context),
+    }
+}
+
+fn process_template_type(name: &str, b: &Value, context: &mut Context) -> Result<Mismatch> {
+    if template::matches_type(name, b) {
+        Ok(Mismatch::empty())
+    } else {
+        Ok(Mismatch::new(
+            DiffTreeNode::Null,
+            DiffTreeNode::Null,
+            DiffTreeNode::Value(Arc::new(Value::from(name)), context.capture(b)),
+        ))
+    }
+}
+
+/// Checks every element of `b` against the single `element` of a template array.
+fn process_template_array(element: &Value, b: &[Value], context: &mut Context) -> Result<Mismatch> {
+    let mut left_only_nodes = DiffTreeNode::Null;
+    let mut right_only_nodes = DiffTreeNode::Null;
+    let mut diff = DiffTreeNode::Null;
+    let mut extra_records = Vec::new();
+    let mut origins = Origins::default();
+    for (position, value) in b.iter().enumerate() {
+        let Mismatch {
+            left_only: l,
+            right_only: r,
+            unequal_values: u,
+            extra_records: e,
+            origins: o,
+            ..
+        } = context.match_child(element, value, OwnedPathElement::ArrayEntry(position))?;
+        let element = OwnedPathElement::ArrayEntry(position);
+        if context.options.record_origins {
+            origins.record(DiffType::Mismatch, &element, &u, Origin::ArrayPositional);
+            origins.adopt(o, &element);
+        }
+        left_only_nodes = insert_child_key_diff(left_only_nodes, l, position)?;
+        right_only_nodes = insert_child_key_diff(right_only_nodes, r, position)?;
+        diff = insert_child_key_diff(diff, u, position)?;
+        adopt_records(&mut extra_records, e, element);
+    }
+    let mut mismatch = Mismatch::new(left_only_nodes, right_only_nodes, diff);
+    mismatch.extra_records = extra_records;
+    mismatch.origins = origins;
+    Ok(mismatch)
+}
+
+fn process_objects(
+    a: &Map<String, Value>,
+    b: &Map<String, Value>,
+    context: &mut Context,
+) -> Result<Mismatch> {
+    let mut diff = intersect_maps(a, b, context.options)?;
+    if context.options.has_ignored_paths() {
+        let mut ignored =
+            |key: &String| context.is_ignored_child(OwnedPathElement::Object(key.clone()));
+        diff.left_only.retain(|key| !ignored(key));
+        diff.right_only.retain(|key| !ignored(key));
+        diff.lookalikes
+            .retain(|(left, right)| !ignored(left) && !ignored(right));
+    }
+    for (side, keys) in core::mem::take(&mut diff.collisions) {
+        context.warnings.push(Warning::CanonicalKeyCollision {
+            path: context.path.clone(),
+            side,
+            keys,
+        });
+    }
+    if let Some(SampleSpec {
+        seed,
+        max_keys_per_object: Some(max_keys),
+        ..
+    }) = context.options.sample
+    {
+        context.usage.skipped_by_sampling += diff.sample_keys(seed, max_keys);
+    }
+    for key in &diff.left_only {
+        context.count(a.get(key), None, false);
+    }
+    for key in &diff.right_only {
+        context.count(None, b.get(key), false);
+    }
+    let max_extras = context.options.max_extras_per_object;
+    let captured = &mut context.captured;
+    let (mut left_only_keys, left_summary) =
+        get_map_of_keys(diff.left_only, a, max_extras, captured);
+    let (mut right_only_keys, right_summary) =
+        get_map_of_keys(diff.right_only, b, max_extras, captured);
+    let intersection_keys = diff.intersection;
+
+    let mut unequal_keys = DiffTreeNode::Null;
+    let mut array_frames = BTreeMap::new();
+    let mut origins = Origins::default();
+    if context.options.record_origins {
+        for (diff_type, node) in [
+            (DiffType::LeftExtra, &left_only_keys),
+            (DiffType::RightExtra, &right_only_keys),
+        ] {
+            for (element, child) in node.children() {
+                origins.record(diff_type, &(&element).into(), child, Origin::ObjectKey);
+            }
+        }
+    }
+    let mut extra_records: Vec<DiffRecord> = [
+        (DiffType::LeftExtra, left_summary),
+        (DiffType::RightExtra, right_summary),
+    ]
+    .into_iter()
+    .filter_map(|(diff_type, suppressed)| {
+        Some(DiffRecord {
+            diff_type,
+            path: DiffPath::default(),
+            values: DiffValues::Summary {
+                suppressed: suppressed?,
+            },
+            origin: None,
+            context: None,
+            string_diff: None,
+        })
+    })
+    .collect();
+    extra_records.extend(diff.lookalikes.into_iter().map(|(left, right)| DiffRecord {
+        diff_type: DiffType::KeyAlmostEqual,
+        path: DiffPath::default(),
+        values: DiffValues::KeyVariants { left, right },
+        origin: None,
+        context: None,
+        string_diff: None,
+    }));
+
+    for (key, b_key) in intersection_keys {
+        let (Some(a_value), Some(b_value)) = (a.get(&key), b.get(&b_key)) else {
+            return Err(Error::Structure(format!(
+                "Key {key} of the key intersection is missing in an object"
+            )));
+        };
+        let Mismatch {
+            left_only: l,
+            right_only: r,
+            unequal_values: u,
+            extra_records: e,
+            array_frames: f,
+            origins: o,
+            ..
+        } = context.match_child(a_value, b_value, OwnedPathElement::Object(key.clone()))?;
+        let element = OwnedPathElement::Object(key.clone());
+        if context.options.record_origins {
+            origins.record(DiffType::Mismatch, &element, &u, Origin::ObjectKey);
+            origins.adopt(o, &element);
+        }
+        left_only_keys = insert_child_key_map(left_only_keys, l, &key)?;
+        right_only_keys = insert_child_key_map(right_only_keys, r, &key)?;
+        unequal_keys = insert_child_key_map(unequal_keys, u, &key)?;
+        adopt_frames(&mut array_frames, f, &element);
+        adopt_records(&mut extra_records, e, element);
+    }
+
+    let mut mismatch = Mismatch::new(left_only_keys, right_only_keys, unequal_keys);
+    mismatch.extra_records = extra_records;
+    mismatch.array_frames = array_frames;
+    mismatch.origins = origins;
+    Ok(mismatch)
+}
+
+/// Compares arrays in the [`ArrayMode`] configured for the current path.
+fn process_arrays_in_mode(a: &[Value], b: &[Value], context: &mut Context) -> Result<Mismatch> {
+    let pointer = context.path.to_pointer();
+    let mode = context
+        .options
+        .array_modes
+        .iter()
+        .find_map(|(p, mode)| (*p == pointer).then_some(mode));
+    match mode {
+        Some(ArrayMode::Matrix {
+            tolerance,
+            max_cells,
+        }) => {
+            if let (Some(left), Some(right)) = (matrix_shape(a), matrix_shape(b)) {
+                return Ok(process_matrix(
+                    a,
+                    b,
+                    (left, right),
+                    *tolerance,
+                    *max_cells,
+                    context,
+                ));
+            }
+        }
+        Some(ArrayMode::FingerprintFirst) => return process_fingerprinted_arrays(a, b, context),
+        Some(ArrayMode::Sequence) | None => {}
+    }
+    match context.options.has_array_identity() {
+        true => process_keyed_arrays(a, b, context),
+        false => process_arrays(a, b, context),
+    }
+}
+
+/// Rows and columns of `rows`, if all of them are arrays of the same length.
+fn matrix_shape(rows: &[Value]) -> Option<(usize, usize)> {
+    let columns = match rows.first() {
+        Some(Value::Array(first)) => first.len(),
+        Some(_) => return None,
+        None => 0,
+    };
+    rows.iter()
+        .all(|row| matches!(row, Value::Array(row) if row.len() == columns))
+        .then_some((rows.len(), columns))
+}
+
+/// Compares two matrices cell by cell, see [`ArrayMode::Matrix`].
+fn process_matrix(
+    a: &[Value],
+    b: &[Value],
+    (left_shape, right_shape): ((usize, usize), (usize, usize)),
+    tolerance: f64,
+    max_cells: Option<usize>,
+    context: &mut Context,
+) -> Mismatch {
+    let mut mismatch = Mismatch::empty();
+    if left_shape != right_shape {
+        let shape = |(rows, columns): (usize, usize)| Value::from(vec![rows, columns]);
+        mismatch.extra_records.push(DiffRecord {
+            diff_type: DiffType::DimensionMismatch,
+            path: DiffPath::default(),
+            values: DiffValues::Pair(shape(left_shape), shape(right_shape)),
+            origin: None,
+            context: None,
+            string_diff: None,
+        });
+        return mismatch;
+    }
+    let cells_equal = |x: &Value, y: &Value| match (x.as_f64(), y.as_f64()) {
+        (Some(x), Some(y)) => (x - y).abs() <= tolerance,
+        _ => x == y,
+    };
+    let mut rows = Vec::new();
+    let (mut reported, mut suppressed) = (0, 0);
+    for (row, (left_row, right_row)) in a.iter().zip(b).enumerate() {
+        let (Value::Array(left_row), Value::Array(right_row)) = (left_row, right_row) else {
+            continue;
+        };
+        let mut cells = Vec::new();
+        for (column, (x, y)) in left_row.iter().zip(right_row).enumerate() {
+            let equal = cells_equal(x, y);
+            context.count(Some(x), Some(y), equal);
+            if equal {
+                continue;
+            }
+            if max_cells.is_some_and(|max| reported >= max) {
+                suppressed += 1;
+                continue;
+            }
+            reported += 1;
+            if context.options.record_origins {
+                let path = DiffPath(vec![
+                    OwnedPathElement::ArrayEntry(row),
+                    OwnedPathElement::ArrayEntry(column),
+                ]);
+                mismatch
+                    .origins
+                    .insert(DiffType::Mismatch, path, Origin::MatrixCell);
+            }
+            cells.push((column, context.leaf(x, y)));
+        }
+        if !cells.is_empty() {
+            rows.push((row, DiffTreeNode::Array(cells)));
+        }
+    }
+    if !rows.is_empty() {
+        mismatch.unequal_values = DiffTreeNode::Array(rows);
+    }
+    if suppressed > 0 {
+        mismatch.extra_records.push(DiffRecord {
+            diff_type: DiffType::Mismatch,
+            path: DiffPath::default(),
+            values: DiffValues::Summary { suppressed },
+            origin: None,
+            context: None,
+            string_diff: None,
+        });
+    }
+    mismatch
+}
+
+/// Compares arrays by pairing their elements by [`CompareOptions::array_identity`] or identity keys. Elements without
+/// identity are paired by position among each other, arrays without any identified element are compared by
+/// [`process_arrays`].
+fn process_keyed_arrays(a: &[Value], b: &[Value], context: &mut Context) -> Result<Mismatch> {
+    if !context.options.has_array_identity() {
+        return process_arrays(a, b, context);
+    }
+    let ((prepared_a, left_origins), (prepared_b, right_origins)) =
+        prepare_arrays(a, b, context.options, &context.path.elements());
+    let (left_ids, right_ids) = {
+        let path = context.path.elements();
+        let pointer = context.path.to_pointer();
+        let ids = |values: &[Value]| -> Vec<_> {
+            values
+                .iter()
+                .map(|v| context.options.element_identity(&path, &pointer, v))
+                .collect()
+        };
+        (ids(&prepared_a), ids(&prepared_b))
+    };
+    if left_ids.iter().chain(&right_ids).all(Option::is_none) {
+        return process_arrays(a, b, context);
+    }
+    let (a, b) = (prepared_a, prepared_b);
+    let left_groups = group_by_identity(&left_ids);
+    let right_groups = group_by_identity(&right_ids);
+    for (side, groups) in [(Side::Left, &left_groups), (Side::Right, &right_groups)] {
+        for (id, indices) in groups.iter().filter(|(_, indices)| indices.len() > 1) {
+            context.warnings.push(Warning::DuplicateIdentity {
+                path: context.path.clone(),
+                side,
+                // identities may be derived from values
+                identity: (!context.options.no_value_capture).then(|| id.to_string()),
+                occurrences: indices.len(),
+            });
+        }
+    }
+
+    // identified elements pair up by identity, the others by position among each other
+    let mut matched = Vec::new();
+    let (mut left_extra, mut right_extra) = (Vec::new(), Vec::new());
+    let unidentified = |ids: &[Option<String>]| -> Vec<usize> {
+        (0..ids.len()).filter(|i| ids[*i].is_none()).collect()
+    };
+    let no_indices = Vec::new();
+    let pools = left_groups
+        .iter()
+        .map(|(id, left)| {
+            (
+                left,
+                right_groups.get(id).unwrap_or(&no_indices),
+                Origin::ArrayKeyed,
+            )
+        })
+        .chain(right_groups.iter().filter_map(|(id, right)| {
+            (!left_groups.contains_key(id)).then_some((&no_indices, right, Origin::ArrayKeyed))
+        }))
+        .collect::<Vec<_>>();
+    let (left_pool, right_pool) = (unidentified(&left_ids), unidentified(&right_ids));
+    for (left, right, origin) in pools.into_iter().chain(core::iter::once((
+        &left_pool,
+        &right_pool,
+        Origin::ArrayPositional,
+    ))) {
+        let paired = left.len().min(right.len());
+        matched.extend(left.iter().zip(right).map(|(i, j)| (*i, *j, origin)));
+        left_extra.extend(left[paired..].iter().map(|i| (*i, origin)));
+        right_extra.extend(right[paired..].iter().map(|j| (*j, origin)));
+    }
+
+    let mut nodes = ArrayNodes::new(context.options.record_origins);
+    for (index, origin) in left_extra {
+        context.count(Some(&a[index]), None, false);
+        nodes.push_one_sided(DiffType::LeftExtra, index, context.capture(&a[index]), origin)?;
+    }
+    for (index, origin) in right_extra {
+        context.count(None, Some(&b[index]), false);
+        nodes.push_one_sided(DiffType::RightExtra, index, context.capture(&b[index]), origin)?;
+    }
+    let mut array_frames = BTreeMap::new();
+    let mut extra_records = Vec::new();
+    let mut pairs = BTreeMap::new();
+    for (i, j, origin) in matched {
+        let element = OwnedPathElement::ArrayEntry(i);
+        let child = context.match_child(&a[i], &b[j], element.clone())?;
+        if !child.is_empty() || child.has_order_differences() {
+            pairs.insert(i, j);
+        }
+        let (f, e) = nodes.adopt_child(child, i, origin)?;
+        adopt_frames(&mut array_frames, f, &element);
+        adopt_records(&mut extra_records, e, element);
+    }
+
+    let mut mismatch = Mismatch::new(nodes.left_only, nodes.right_only, nodes.unequal);
+    mismatch.extra_records = extra_records;
+    mismatch.origins = nodes.origins;
+    if !mismatch.is_empty() || mismatch.has_order_differences() {
+        let frame = ArrayFrame {
+            left_origins,
+            right_origins,
+            pairs,
+            anchors: None,
+        };
+        array_frames.insert(DiffPath::default(), frame);
+    }
+    mismatch.array_frames = array_frames;
+    Ok(mismatch)
+}
+
+/// Indices of the identified elements per identity, in order.
+fn group_by_identity(ids: &[Option<String>]) -> BTreeMap<&str, Vec<usize>> {
+    let mut groups: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (index, id) in ids.iter().enumerate() {
+        if let Some(id) = id {
+            groups.entry(id).or_default().push(index);
+        }
+    }
+    groups
+}
+
+/// Compares arrays in [`ArrayMode::FingerprintFirst`]: elements of equal [`fingerprint`]s pair up in order,
+/// the others are aligned by [`align_block`]. Fingerprint pairs are compared as well, as their hashes may collide.
+fn process_fingerprinted_arrays(
+    a: &[Value],
+    b: &[Value],
+    context: &mut Context,
+) -> Result<Mismatch> {
+    let keys = context.options.key_matchers();
+    let normalizer = context.options.key_normalizer.as_ref();
+    let ((a, left_origins), (b, right_origins)) =
+        prepare_arrays(a, b, context.options, &context.path.elements());
+    let mut unmatched: BTreeMap<u64, VecDeque<usize>> = BTreeMap::new();
+    for (index, value) in a.iter().enumerate() {
+        let fingerprint = fingerprint(value, keys.left, normalizer);
+        unmatched.entry(fingerprint).or_default().push_back(index);
+    }
+    let mut matched = Vec::new();
+    let mut right_rest = Vec::new();
+    for (index, value) in b.iter().enumerate() {
+        let fingerprint = fingerprint(value, keys.right, normalizer);
+        match unmatched
+            .get_mut(&fingerprint)
+            .and_then(VecDeque::pop_front)
+        {
+            Some(left) => matched.push((left, index)),
+            None => right_rest.push(index),
+        }
+    }
+    let mut left_rest: Vec<usize> = unmatched.into_values().flatten().collect();
+    left_rest.sort_unstable();
+
+    let origin = Origin::ArrayFingerprint;
+    let mut nodes = ArrayNodes::new(context.options.record_origins);
+    let rest = |values: &[Value], indices: &[usize]| -> Vec<Value> {
+        indices.iter().map(|i| values[*i].clone()).collect()
+    };
+    for pair in align_block(&rest(&a, &left_rest), &rest(&b, &right_rest), keys) {
+        match pair {
+            BlockPair::Both(i, j) => matched.push((left_rest[i], right_rest[j])),
+            BlockPair::Left(i) => {
+                let index = left_rest[i];
+                context.count(Some(&a[index]), None, false);
+                let value = context.capture(&a[index]);
+                nodes.push_one_sided(DiffType::LeftExtra, index, value, origin)?;
+            }
+            BlockPair::Right(j) => {
+                let index = right_rest[j];
+                context.count(None, Some(&b[index]), false);
+                let value = context.capture(&b[index]);
+                nodes.push_one_sided(DiffType::RightExtra, index, value, origin)?;
+            }
+        }
+    }
+    matched.sort_unstable();
+    let mut array_frames = BTreeMap::new();
+    let mut extra_records = Vec::new();
+    let mut pairs = BTreeMap::new();
+    for (i, j) in matched {
+        let element = OwnedPathElement::ArrayEntry(i);
+        let child = context.match_child(&a[i], &b[j], element.clone())?;
+        if !child.is_empty() || child.has_order_differences() {
+            pairs.insert(i, j);
+        }
+        let (f, e) = nodes.adopt_child(child, i, origin)?;
+        adopt_frames(&mut array_frames, f, &element);
+        adopt_records(&mut extra_records, e, element);
+    }
+
+    let mut mismatch = Mismatch::new(nodes.left_only, nodes.right_only, nodes.unequal);
+    mismatch.extra_records = extra_records;
+    mismatch.origins = nodes.origins;
+    if !mismatch.is_empty() || mismatch.has_order_differences() {
+        let frame = ArrayFrame {
+            left_origins,
+            right_origins,
+            pairs,
+            anchors: None,
+        };
+        array_frames.insert(DiffPath::default(), frame);
+    }
+    mismatch.array_frames = array_frames;
+    Ok(mismatch)
+}
+
+/// [`structural_hash`] of `value` without the keys ignored by `keys` and with keys normalized by `normalizer`.
+fn fingerprint(value: &Value, keys: KeyMatcher, normalizer: Option<&KeyNormalizer>) -> u64 {
+    match (keys.ignores_any(), normalizer) {
+        (false, None) => structural_hash(value),
+        _ => structural_hash(&fingerprinted_form(value, keys, normalizer)),
+    }
+}
+
+fn fingerprinted_form(
+    value: &Value,
+    keys: KeyMatcher,
+    normalizer: Option<&KeyNormalizer>,
+) -> Value {
+    match value {
+        Value::Array(a) => Value::Array(
+            a.iter()
+                .map(|v| fingerprinted_form(v, keys, normalizer))
+                .collect(),
+        ),
+        Value::Object(o) => Value::Object(
+            o.iter()
+                .filter(|(k, _)| keys.is_compared(k))
+                .map(|(k, v)| {
+                    let key = match normalizer {
+                        Some(KeyNormalizer(normalize)) => normalize(k),
+                        None => k.clone(),
+                    };
+                    (key, fingerprinted_form(v, keys, normalizer))
+                })
+                .collect(),
+        ),
+        v => v.clone(),
+    }
+}
+
+fn process_arrays(a: &[Value], b: &[Value], context: &mut Context) -> Result<Mismatch> {
+    if let Some(stride) = context.options.sample.map(|s| s.array_stride) {
+        if stride > 1 {
+            return process_sampled_arrays(a, b, stride, context);
+        }
+    }
+    let sort_arrays = context.options.sort_arrays;
+    let keys = context.options.key_matchers();
+    let ((a, left_origins), (b, right_origins)) =
+        prepare_arrays(a, b, context.options, &context.path.elements());
+    // wildcards of sorted arrays are kept out of the sequence diff and stand in for extras afterwards
+    let count_wildcards = |values: &[Value]| match sort_arrays {
+        true => values
+            .iter()
+            .filter(|v| context.options.is_wildcard(v))
+            .count(),
+        false => 0,
+    };
+    let (a_compared, b_compared) = (a.len() - count_wildcards(&a), b.len() - count_wildcards(&b));
+    let mut extra_records = Vec::new();
+    if sort_arrays && context.options.verify_multiplicity {
+        extra_records = multiplicity_changes(&a, &b, keys);
+    }
+    // elements are aligned by their deep-sorted forms and compared by their original ones
+    let (a_forms, b_forms) = match sort_arrays {
+        true => (sorted_forms(&a, keys.left), sorted_forms(&b, keys.right)),
+        false => (Cow::Borrowed(&a[..]), Cow::Borrowed(&b[..])),
+    };
+    let ListDiffHandler {
+        equal,
+        replaced,
+        deletion: deleted,
+        insertion: inserted,
+    } = sequence_diff(&a_forms[..a_compared], &b_forms[..b_compared]);
+    if context.usage.leaves.is_some() {
+        count_unvisited_elements(context, &equal, (&a, a_compared), (&b, b_compared));
+    }
+
+    let (positional, replaced_block) = if sort_arrays {
+        (Origin::ArraySorted, Origin::ArraySorted)
+    } else {
+        (Origin::ArrayPositional, Origin::ArrayReplacedBlock)
+    };
+    let mut nodes = ArrayNodes::new(context.options.record_origins);
+    for (o, ol) in deleted {
+        for index in o..o + ol {
+            context.count(Some(&a[index]), None, false);
+            let value = context.capture(&a[index]);
+            nodes.push_one_sided(DiffType::LeftExtra, index, value, positional)?;
+        }
+    }
+    for (n, nl) in inserted {
+        for index in n..n + nl {
+            context.count(None, Some(&b[index]), false);
+            let value = context.capture(&b[index]);
+            nodes.push_one_sided(DiffType::RightExtra, index, value, positional)?;
+        }
+    }
+
+    let mut array_frames = BTreeMap::new();
+    let mut pairs = BTreeMap::new();
+    let report_order = sort_arrays && context.options.report_order_differences;
+    let mut matched = Vec::new();
+    for (o, ol, n, nl) in replaced {
+        let (Some(block_a), Some(block_b)) = (a.get(o..o + ol), b.get(n..n + nl)) else {
+            return Err(Error::Structure(format!(
+                "Replaced block {o}+{ol} / {n}+{nl} exceeds the arrays"
+            )));
+        };
+        let block_pairs = match sort_arrays {
+            true => align_sorted_block(&a_forms[o..o + ol], &b_forms[n..n + nl], keys),
+            false => align_block(block_a, block_b, keys),
+        };
+        for pair in block_pairs {
+            match pair {
+                BlockPair::Both(i, j) => {
+                    let position = o + i;
+                    let element = OwnedPathElement::ArrayEntry(position);
+                    let child = context.match_child(&block_a[i], &block_b[j], element.clone())?;
+                    if !child.is_empty() || child.has_order_differences() {
+                        pairs.insert(position, n + j);
+                    }
+                    if report_order {
+                        matched.push((position, n + j));
+                    }
+                    let (f, e) = nodes.adopt_child(child, position, replaced_block)?;
+                    adopt_frames(&mut array_frames, f, &element);
+                    adopt_records(&mut extra_records, e, element);
+                }
+                BlockPair::Left(i) => {
+                    context.count(Some(&block_a[i]), None, false);
+                    let diff_type = DiffType::LeftExtra;
+                    let value = context.capture(&block_a[i]);
+                    nodes.push_one_sided(diff_type, o + i, value, replaced_block)?;
+                }
+                BlockPair::Right(j) => {
+                    context.count(None, Some(&block_b[j]), false);
+                    let diff_type = DiffType::RightExtra;
+                    let value = context.capture(&block_b[j]);
+                    nodes.push_one_sided(diff_type, n + j, value, replaced_block)?;
+                }
+            }
+        }
+    }
+    if a_compared < a.len() || b_compared < b.len() {
+        nodes.absorb_by_wildcards((&a, a_compared), (&b, b_compared), &mut context.captured)?;
+    }
+    if report_order && order_differs(&equal, matched, &left_origins, &right_origins) {
+        extra_records.push(DiffRecord {
+            diff_type: DiffType::OrderOnly,
+            path: DiffPath::default(),
+            values: DiffValues::Empty,
+            origin: None,
+            context: None,
+            string_diff: None,
+        });
+    }
+
+    let mut mismatch = Mismatch::new(nodes.left_only, nodes.right_only, nodes.unequal);
+    mismatch.extra_records = extra_records;
+    mismatch.origins = nodes.origins;
+    if !mismatch.is_empty() || mismatch.has_order_differences() {
+        let frame = ArrayFrame {
+            left_origins,
+            right_origins,
+            pairs,
+            anchors: context
+                .options
+                .array_context
+                .map(|size| Anchors { size, equal }),
+        };
+        array_frames.insert(DiffPath::default(), frame);
+    }
+    mismatch.array_frames = array_frames;
+    Ok(mismatch)
+}
+
+/// Deep-sorted copies of the elements of a sorted array, so elements only differing in the order of nested arrays
+/// are equal when aligning both arrays. Borrows the elements if none of them holds an array.
+fn sorted_forms<'a>(values: &'a [Value], keys: KeyMatcher) -> Cow<'a, [Value]> {
+    fn holds_array(value: &Value) -> bool {
+        match value {
+            Value::Array(_) => true,
+            Value::Object(o) => o.values().any(holds_array),
+            _ => false,
+        }
+    }
+    match values.iter().any(holds_array) {
+        true => values
+            .iter()
+            .map(|v| sort_value_filtered(v, keys))
+            .collect(),
+        false => Cow::Borrowed(values),
+    }
+}
+
+/// Compares every `stride`th element of both arrays by position, see [`CompareOptions::sample`].
+fn process_sampled_arrays(
+    a: &[Value],
+    b: &[Value],
+    stride: usize,
+    context: &mut Context,
+) -> Result<Mismatch> {
+    let ((a, left_origins), (b, right_origins)) =
+        prepare_arrays(a, b, context.options, &context.path.elements());
+    let origin = Origin::ArrayPositional;
+    let mut nodes = ArrayNodes::new(context.options.record_origins);
+    let mut array_frames = BTreeMap::new();
+    let mut extra_records = Vec::new();
+    let mut pairs = BTreeMap::new();
+    let len = a.len().max(b.len());
+    for index in (0..len).step_by(stride) {
+        match (a.get(index), b.get(index)) {
+            (Some(x), Some(y)) => {
+                let element = OwnedPathElement::ArrayEntry(index);
+                let child = context.match_child(x, y, element.clone())?;
+                if !child.is_empty() {
+                    pairs.insert(index, index);
+                }
+                let (f, e) = nodes.adopt_child(child, index, origin)?;
+                adopt_frames(&mut array_frames, f, &element);
+                adopt_records(&mut extra_records, e, element);
+            }
+            (Some(x), None) => {
+                context.count(Some(x), None, false);
+                nodes.push_one_sided(DiffType::LeftExtra, index, context.capture(x), origin)?;
+            }
+            (None, Some(y)) => {
+                context.count(None, Some(y), false);
+                nodes.push_one_sided(DiffType::RightExtra, index, context.capture(y), origin)?;
+            }
+            (None, None) => {}
+        }
+    }
+    context.usage.skipped_by_sampling += len - len.div_ceil(stride);
+
+    let mut mismatch = Mismatch::new(nodes.left_only, nodes.right_only, nodes.unequal);
+    mismatch.extra_records = extra_records;
+    mismatch.origins = nodes.origins;
+    if !mismatch.is_empty() {
+        let frame = ArrayFrame {
+            left_origins,
+            right_origins,
+            pairs,
+            anchors: None,
+        };
+        array_frames.insert(DiffPath::default(), frame);
+    }
+    mismatch.array_frames = array_frames;
+    Ok(mismatch)
+}
+
+/// Whether the elements matched between both sorted arrays, given by the `equal` runs and the `matched` pairs
+/// of replaced blocks, appear in a different order in the original arrays.
+fn order_differs(
+    equal: &[(usize, usize, usize)],
+    mut matched: Vec<(usize, usize)>,
+    left_origins: &Option<Vec<usize>>,
+    right_origins: &Option<Vec<usize>>,
+) -> bool {
+    matched.extend(
+        equal
+            .iter()
+            .flat_map(|(o, n, len)| (0..*len).map(move |k| (o + k, n + k))),
+    );
+    let origin = |origins: &Option<Vec<usize>>, i: usize| match origins {
+        Some(origins) => origins[i],
+        None => i,
+    };
+    let mut original: Vec<(usize, usize)> = matched
+        .into_iter()
+        .map(|(i, j)| (origin(left_origins, i), origin(right_origins, j)))
+        .collect();
+    original.sort_unstable();
+    original.windows(2).any(|pair| pair[0].1 > pair[1].1)
+}
+
+/// Arrays as compared with their original indices, see [`preprocess_array_with_origins`].
+pub(crate) type PreparedArray<'a> = (Cow<'a, [Value]>, Option<Vec<usize>>);
+
+/// Filters and sorts both arrays as configured, with wildcards moved behind the sorted elements.
+/// `path` leads to the arrays and is handed to the [`CompareOptions::array_element_filter`].
+pub(crate) fn prepare_arrays<'a>(
+    a: &'a [Value],
+    b: &'a [Value],
+    options: &CompareOptions,
+    path: &[PathElement],
+) -> (PreparedArray<'a>, PreparedArray<'a>) {
+    let keys = options.key_matchers();
+    // ignoring keys on either side sorts both arrays, so they are ordered alike
+    let sort = options.sort_arrays || keys.ignores_any();
+    let (a, b) = match &options.array_element_filter {
+        Some(ElementFilter(filter)) => {
+            let keep = |value: &Value| filter(path, value);
+            (
+                preprocess_array_with_origins(sort, a, keys.left, Some(&keep)),
+                preprocess_array_with_origins(sort, b, keys.right, Some(&keep)),
+            )
+        }
+        None => (
+            preprocess_array_with_origins(sort, a, keys.left, None),
+            preprocess_array_with_origins(sort, b, keys.right, None),
+        ),
+    };
+    if !options.sort_arrays {
+        return (a, b);
+    }
+    (
+        move_wildcards_last(a.0, a.1, options),
+        move_wildcards_last(b.0, b.1, options),
+    )
+}
+
+/// A range of `a` replaced by a range of `b` as offset and length in each.
+pub(crate) type ReplacedBlock = (usize, usize, usize, usize);
+/// A range as offset and length.
+pub(crate) type Range = (usize, usize);
+
+/// Equal runs, replaced blocks, deleted and inserted ranges of the myers diff of `a` and `b`.
+pub(crate) fn sequence_diff(a: &[Value], b: &[Value]) -> ListDiffHandler {
+    let mut diff = Replace::new(ListDiffHandler::default());
+    myers::diff(&mut diff, a, b);
+    let ranges = diff.into_inner();
+    // myers only reports ranges within the compared slices
+    debug_assert!(ranges.deletion.iter().all(|(o, ol)| o + ol <= a.len()));
+    debug_assert!(ranges.insertion.iter().all(|(n, nl)| n + nl <= b.len()));
+    ranges
+}
+
+/// Run of an edit script turning one array into another, see [`array_edit_script`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditOp {
+    /// `len` elements are equal on both sides
+    Equal {
+        a_start: usize,
+        b_start: usize,
+        len: usize,
+    },
+    /// `len` elements of `b` are missing in `a`
+    Insert { b_start: usize, len: usize },
+    /// `len` elements of `a` are missing in `b`
+    Delete { a_start: usize, len: usize },
+    /// `a_len` elements of `a` are replaced by `b_len` elements of `b`
+    Replace {
+        a_start: usize,
+        a_len: usize,
+        b_start: usize,
+        b_len: usize,
+    },
+}
+
+/// Collects the runs of a myers diff in order.
+#[derive(Default)]
+struct EditScript(Vec<EditOp>);
+
+impl Diff for EditScript {
+    fn equal(&mut self, a_start: usize, b_start: usize, len: usize) {
+        self.0.push(EditOp::Equal {
+            a_start,
+            b_start,
+            len,
+        });
+    }
+    fn delete(&mut self, a_start: usize, len: usize, _new: usize) {
+        self.0.push(EditOp::Delete { a_start, len });
+    }
+    fn insert(&mut self, _old: usize, b_start: usize, len: usize) {
+        self.0.push(EditOp::Insert { b_start, len });
+    }
+    fn replace(&mut self, a_start: usize, a_len: usize, b_start: usize, b_len: usize) {
+        self.0.push(EditOp::Replace {
+            a_start,
+            a_len,
+            b_start,
+            b_len,
+        });
+    }
+}
+
+/// The runs of the sequence diff of `a` and `b` in order, e.g. for side-by-side views of both arrays.
+/// They cover both arrays completely, adjacent deletions and insertions are combined into replacements.
+/// The arrays are filtered and sorted as configured in `options` before they are compared, offsets refer to the
+/// arrays as compared, see [`CompareOptions::filter_array_elements`] and [`CompareOptions::sort_arrays`].
+/// ```rust
+/// use json_diff_ng::prelude::{array_edit_script, EditOp};
+/// use json_diff_ng::CompareOptions;
+/// use serde_json::json;
+/// let (a, b) = (json!([1, 2, 3]), json!([1, 4, 3, 5]));
+/// let script = array_edit_script(a.as_array().unwrap(), b.as_array().unwrap(), &CompareOptions::default());
+/// assert_eq!(script[1], EditOp::Replace { a_start: 1, a_len: 1, b_start: 1, b_len: 1 });
+/// assert_eq!(script[3], EditOp::Insert { b_start: 3, len: 1 });
+/// ```
+pub fn array_edit_script(a: &[Value], b: &[Value], options: &CompareOptions) -> Vec<EditOp> {
+    let ((a, _), (b, _)) = prepare_arrays(a, b, options, &[]);
+    let mut script = Replace::new(EditScript::default());
+    myers::diff(&mut script, &a, &b);
+    script.into_inner().0
+}
+
+/// Diff trees and origins of an array comparison under construction.
+struct ArrayNodes {
+    left_only: DiffTreeNode,
+    right_only: DiffTreeNode,
+    unequal: DiffTreeNode,
+    origins: Origins,
+    record_origins: bool,
+}
+
+impl ArrayNodes {
+    fn new(record_origins: bool) -> Self {
+        Self {
+            left_only: DiffTreeNode::Null,
+            right_only: DiffTreeNode::Null,
+            unequal: DiffTreeNode::Null,
+            origins: Origins::default(),
+            record_origins,
+        }
+    }
+
+    /// Reports the whole element `value` at `index` as present on one side only.
+    fn push_one_sided(
+        &mut self,
+        diff_type: DiffType,
+        index: usize,
+        value: Arc<Value>,
+        origin: Origin,
+    ) -> Result<()> {
+        let node = DiffTreeNode::one_sided_shared(value);
+        let nodes = match diff_type {
+            DiffType::LeftExtra => &mut self.left_only,
+            _ => &mut self.right_only,
+        };
+        *nodes = insert_child_key_diff(core::mem::replace(nodes, DiffTreeNode::Null), node, index)?;
+        if self.record_origins {
+            let element = OwnedPathElement::ArrayEntry(index);
+            self.origins.insert_at(diff_type, element, origin);
+        }
+        Ok(())
+    }
+
+    /// Inserts the trees of the comparison of a pair of elements found at `position`,
+    /// returning the array frames and extra records of the child for the caller to adopt.
+    fn adopt_child(
+        &mut self,
+        child: Mismatch,
+        position: usize,
+        origin: Origin,
+    ) -> Result<(BTreeMap<DiffPath, ArrayFrame>, Vec<DiffRecord>)> {
+        let element = OwnedPathElement::ArrayEntry(position);
+        let Mismatch {
+            left_only: l,
+            right_only: r,
+            unequal_values: u,
+            extra_records: e,
+            array_frames: f,
+            origins: o,
+            ..
+        } = child;
+        if self.record_origins {
+            self.origins
+                .record(DiffType::Mismatch, &element, &u, origin);
+            self.origins.adopt(o, &element);
+        }
+        self.left_only = insert_child_key_diff(
+            core::mem::replace(&mut self.left_only, DiffTreeNode::Null),
+            l,
+            position,
+        )?;
+        self.right_only = insert_child_key_diff(
+            core::mem::replace(&mut self.right_only, DiffTreeNode::Null),
+            r,
+            position,
+        )?;
+        self.unequal = insert_child_key_diff(
+            core::mem::replace(&mut self.unequal, DiffTreeNode::Null),
+            u,
+            position,
+        )?;
+        Ok((f, e))
+    }
+
+    /// Lets the wildcards behind the first `compared` elements of each side stand in for whole elements
+    /// present on the other side only. Wildcards left over on both sides match each other,
+    /// the remaining ones are reported as present on their side only.
+    fn absorb_by_wildcards(
+        &mut self,
+        (a, a_compared): (&[Value], usize),
+        (b, b_compared): (&[Value], usize),
+        captured: &mut SharedValues,
+    ) -> Result<()> {
+        let (left_wildcards, right_wildcards) = (a.len() - a_compared, b.len() - b_compared);
+        let (nodes, absorbed_right) = take_whole_elements(
+            core::mem::replace(&mut self.right_only, DiffTreeNode::Null),
+            left_wildcards,
+        );
+        self.right_only = nodes;
+        let (nodes, absorbed_left) = take_whole_elements(
+            core::mem::replace(&mut self.left_only, DiffTreeNode::Null),
+            right_wildcards,
+        );
+        self.left_only = nodes;
+        for (diff_type, absorbed) in [
+            (DiffType::RightExtra, &absorbed_right),
+            (DiffType::LeftExtra, &absorbed_left),
+        ] {
+            for index in absorbed {
+                self.origins
+                    .remove_at(diff_type, OwnedPathElement::ArrayEntry(*index));
+            }
+        }
+        let surplus_left = left_wildcards - absorbed_right.len();
+        let surplus_right = right_wildcards - absorbed_left.len();
+        let paired = surplus_left.min(surplus_right);
+        for (index, value) in a.iter().enumerate().skip(a.len() - surplus_left + paired) {
+            let value = captured.capture(value);
+            self.push_one_sided(DiffType::LeftExtra, index, value, Origin::ArraySorted)?;
+        }
+        for (index, value) in b.iter().enumerate().skip(b.len() - surplus_right + paired) {
+            let value = captured.capture(value);
+            self.push_one_sided(DiffType::RightExtra, index, value, Origin::ArraySorted)?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts the leaves of the array elements the comparison does not visit: the `equal` runs
+/// and the wildcards behind the first `compared` elements.
+fn count_unvisited_elements(
+    context: &mut Context,
+    equal: &[(usize, usize, usize)],
+    (a, a_compared): (&[Value], usize),
+    (b, b_compared): (&[Value], usize),
+) {
+    for (o, _, len) in equal {
+        for value in &a[*o..o + len] {
+            context.count(Some(value), Some(value), true);
+        }
+    }
+    for value in &a[a_compared..] {
+        context.count(Some(value), None, false);
+    }
+    for value in &b[b_compared..] {
+        context.count(None, Some(value), false);
+    }
+}
+
+/// Moves the wildcard elements of a sorted array behind the other elements, keeping the order within both groups.
+fn move_wildcards_last<'a>(
+    values: Cow<'a, [Value]>,
+    origins: Option<Vec<usize>>,
+    options: &CompareOptions,
+) -> (Cow<'a, [Value]>, Option<Vec<usize>>) {
+    if !values.iter().any(|v| options.is_wildcard(v)) {
+        return (values, origins);
+    }
+    let origins = origins.unwrap_or_else(|| (0..values.len()).collect());
+    let (mut moved, wildcards): (Vec<_>, Vec<_>) = values
+        .iter()
+        .cloned()
+        .zip(origins)
+        .partition(|(v, _)| !options.is_wildcard(v));
+    moved.extend(wildcards);
+    let (values, origins) = moved.into_iter().unzip();
+    (Cow::Owned(values), Some(origins))
+}
+
+/// Removes the first `count` whole elements from a node of one-sided array elements, returning their indices.
+fn take_whole_elements(node: DiffTreeNode, count: usize) -> (DiffTreeNode, Vec<usize>) {
+    let DiffTreeNode::Array(children) = node else {
+        return (node, Vec::new());
+    };
+    let mut taken = Vec::new();
+    let remaining: Vec<_> = children
+        .into_iter()
+        .filter(|(index, child)| {
+            let take = taken.len() < count && matches!(child, DiffTreeNode::Value(_, _));
+            if take {
+                taken.push(*index);
+            }
+            !take
+        })
+        .collect();
+    let node = match remaining.is_empty() {
+        true => DiffTreeNode::Null,
+        false => DiffTreeNode::Array(remaining),
+    };
+    (node, taken)
+}
+
+/// Counts the occurrences of each element in `a` and `b`, disregarding ignored keys and the order of nested arrays,
+/// and reports the elements with differing counts.
+fn multiplicity_changes(a: &[Value], b: &[Value], keys: SideKeyMatchers) -> Vec<DiffRecord> {
+    let mut counts: BTreeMap<String, (&Value, usize, usize)> = BTreeMap::new();
+    for (value, is_left) in a
+        .iter()
+        .map(|v| (v, true))
+        .chain(b.iter().map(|v| (v, false)))
+    {
+        let side_keys = if is_left { keys.left } else { keys.right };
+        // nested arrays are compared sorted as well, so their order must not tell elements apart
+        let key =
+            canonicalize(&sort_value(&without_ignored_keys(value, side_keys), &[])).to_string();
+        let entry = counts.entry(key).or_insert((value, 0, 0));
+        if is_left {
+            entry.1 += 1;
+        } else {
+            entry.2 += 1;
+        }
+    }
+    counts
+        .into_values()
+        .filter(|(_, left, right)| left != right)
+        .map(|(element, left, right)| DiffRecord {
+            diff_type: DiffType::MultiplicityChange,
+            path: DiffPath::default(),
+            values: DiffValues::Multiplicity {
+                element: element.clone(),
+                left,
+                right,
+            },
+            origin: None,
+            context: None,
+            string_diff: None,
+        })
+        .collect()
+}
+
+fn without_ignored_keys(value: &Value, keys: KeyMatcher) -> Value {
+    match value {
+        Value::Array(a) => Value::Array(a.iter().map(|v| without_ignored_keys(v, keys)).collect()),
+        Value::Object(o) => Value::Object(
+            o.iter()
+                .filter(|(k, _)| keys.is_compared(k))
+                .map(|(k, v)| (k.clone(), without_ignored_keys(v, keys)))
+                .collect(),
+        ),
+        v => v.clone(),
+    }
+}
+
+/// Pairing of elements within a replaced block, indices are relative to the block.
+#[derive(Debug, PartialEq)]
+pub(crate) enum BlockPair {
+    Both(usize, usize),
+    Left(usize),
+    Right(usize),
+}
+
+/// Elements at least this similar are aligned with each other inside a replaced block.
+const SIMILARITY_THRESHOLD: f64 = 0.5;
+/// Blocks with more element pairs than this skip the similarity alignment.
+const MAX_ALIGNMENT_CELLS: usize = 250_000;
+
+/// Aligns the elements of a replaced block: a second pass pairs elements that are similar to each other,
+/// so that e.g. one element removed from a block of modified elements does not shift all later pairings.
+/// Stretches between similar elements are paired by position, surplus elements are one-sided.
+pub(crate) fn align_block(a: &[Value], b: &[Value], keys: SideKeyMatchers) -> Vec<BlockPair> {
+    let anchors = if a.len() * b.len() <= MAX_ALIGNMENT_CELLS {
+        similar_pairs(a, b, keys)
+    } else {
+        Vec::new()
+    };
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (anchor_i, anchor_j) in anchors
+        .into_iter()
+        .chain(core::iter::once((a.len(), b.len())))
+    {
+        let common = (anchor_i - i).min(anchor_j - j);
+        pairs.extend((0..common).map(|k| BlockPair::Both(i + k, j + k)));
+        pairs.extend((i + common..anchor_i).map(BlockPair::Left));
+        pairs.extend((j + common..anchor_j).map(BlockPair::Right));
+        if anchor_i < a.len() {
+            pairs.push(BlockPair::Both(anchor_i, anchor_j));
+        }
+        (i, j) = (anchor_i + 1, anchor_j + 1);
+    }
+    pairs
+}
+
+/// Aligns a replaced block of sorted arrays like [`align_block`], but pairs elements only with elements of their own
+/// type. Sorted arrays are grouped by type, see [`crate::sort`], so each type forms one run on either side.
+pub(crate) fn align_sorted_block(
+    a: &[Value],
+    b: &[Value],
+    keys: SideKeyMatchers,
+) -> Vec<BlockPair> {
+    let run_end = |values: &[Value], start: usize| {
+        let rank = type_rank(&values[start]);
+        start
+            + values[start..]
+                .iter()
+                .take_while(|v| type_rank(v) == rank)
+                .count()
+    };
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        match (a.get(i).map(type_rank), b.get(j).map(type_rank)) {
+            (Some(left), Some(right)) if left == right => {
+                let (end_a, end_b) = (run_end(a, i), run_end(b, j));
+                pairs.extend(
+                    align_block(&a[i..end_a], &b[j..end_b], keys)
+                        .into_iter()
+                        .map(|pair| match pair {
+                            BlockPair::Both(x, y) => BlockPair::Both(i + x, j + y),
+                            BlockPair::Left(x) => BlockPair::Left(i + x),
+                            BlockPair::Right(y) => BlockPair::Right(j + y),
+                        }),
+                );
+                (i, j) = (end_a, end_b);
+            }
+            (Some(left), right) if right.is_none_or(|right| left < right) => {
+                let end = run_end(a, i);
+                pairs.extend((i..end).map(BlockPair::Left));
+                i = end;
+            }
+            _ => {
+                let end = run_end(b, j);
+                pairs.extend((j..end).map(BlockPair::Right));
+                j = end;
+            }
+        }
+    }
+    pairs
+}
+
+/// Finds the order-preserving pairing of similar elements with the highest total similarity.
+fn similar_pairs(a: &[Value], b: &[Value], keys: SideKeyMatchers) -> Vec<(usize, usize)> {
+    let width = b.len() + 1;
+    let similarity: Vec<f64> = a
+        .iter()
+        .flat_map(|x| b.iter().map(move |y| (x, y)))
+        .map(|(x, y)| similarity(x, y, keys))
+        .collect();
+    // best[i * width + j] is the best total similarity achievable for a[i..] and b[j..]
+    let mut best = vec![0.0_f64; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            let skip = best[(i + 1) * width + j].max(best[i * width + j + 1]);
+            let sim = similarity[i * b.len() + j];
+            best[i * width + j] = if sim >= SIMILARITY_THRESHOLD {
+                skip.max(sim + best[(i + 1) * width + j + 1])
+            } else {
+                skip
+            };
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let sim = similarity[i * b.len() + j];
+        if sim >= SIMILARITY_THRESHOLD && best[i * width + j] == sim + best[(i + 1) * width + j + 1]
+        {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if best[i * width + j] == best[(i + 1) * width + j] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// Rough similarity of two values between 0 (unrelated) and 1 (equal):
+/// the share of equal entries for objects and arrays, otherwise plain equality.
+fn similarity(a: &Value, b: &Value, keys: SideKeyMatchers) -> f64 {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            let compared: BTreeSet<&String> = a
+                .keys()
+                .filter(|k| keys.left.is_compared(k))
+                .chain(b.keys().filter(|k| keys.right.is_compared(k)))
+                .collect();
+            if compared.is_empty() {
+                return 1.0;
+            }
+            // a key ignored on one side only counts as absent there
+            let equal = compared
+                .iter()
+                .filter(|k| keys.left.is_compared(k) && keys.right.is_compared(k))
+                .filter(|k| {
+                    a.get(k.as_str())
+                        .is_some_and(|v| b.get(k.as_str()) == Some(v))
+                })
+                .count();
+            equal as f64 / compared.len() as f64
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            let length = a.len().max(b.len());
+            if length == 0 {
+                return 1.0;
+            }
+            let equal = a.iter().zip(b.iter()).filter(|(x, y)| x == y).count();
+            equal as f64 / length as f64
+        }
+        (a, b) => f64::from(u8::from(a == b)),
+    }
+}
+
+/// Builds the node of keys present in one object only, reporting at most `max_extras` of them.
+/// Returns the number of suppressed keys alongside, if any.
+fn get_map_of_keys(
+    set: BTreeSet<String>,
+    map: &Map<String, Value>,
+    max_extras: Option<usize>,
+    captured: &mut SharedValues,
+) -> (DiffTreeNode, Option<usize>) {
+    let mut keys: Vec<String> = set.into_iter().collect();
+    let mut suppressed = None;
+    if let Some(max_extras) = max_extras.filter(|max| keys.len() > *max) {
+        keys.sort();
+        suppressed = Some(keys.len() - max_extras);
+        keys.truncate(max_extras);
+    }
+    if keys.is_empty() {
+        return (DiffTreeNode::Null, suppressed);
+    }
+    let node = DiffTreeNode::Node(
+        keys.into_iter()
+            .filter_map(|key| {
+                let value = map.get(&key)?;
+                Some((key, DiffTreeNode::one_sided_shared(captured.capture(value))))
+            })
+            .collect(),
+    );
+    (node, suppressed)
+}
+
+/// Moves the array frames of a child mismatch found at `element` into `frames`.
+fn adopt_frames(
+    frames: &mut BTreeMap<DiffPath, ArrayFrame>,
+    child: BTreeMap<DiffPath, ArrayFrame>,
+    element: &OwnedPathElement,
+) {
+    for (mut path, frame) in child {
+        path.0.insert(0, element.clone());
+        frames.insert(path, frame);
+    }
+}
+
+/// Moves the extra records of a child mismatch found at `element` into `records`.
+fn adopt_records(records: &mut Vec<DiffRecord>, child: Vec<DiffRecord>, element: OwnedPathElement) {
+    for mut record in child {
+        record.path.0.insert(0, element.clone());
+        records.push(record);
+    }
+}
+
+fn insert_child_key_diff(
+    parent: DiffTreeNode,
+    child: DiffTreeNode,
+    line: usize,
+) -> Result<DiffTreeNode> {
+    if child == DiffTreeNode::Null {
+        return Ok(parent);
+    }
+    if let DiffTreeNode::Array(mut array) = parent {
+        let position = array.partition_point(|(index, _)| *index <= line);
+        array.insert(position, (line, child));
+        Ok(DiffTreeNode::Array(array))
+    } else if let DiffTreeNode::Null = parent {
+        Ok(DiffTreeNode::Array(vec![(line, child)]))
+    } else {
+        Err(Error::Structure(format!("Tried to insert child at [{line}] into a parent {} - structure incoherent, expected a parent array - somehow json structure seems broken", node_shape(&parent))))
+    }
+}
+
+fn insert_child_key_map(
+    parent: DiffTreeNode,
+    child: DiffTreeNode,
+    key: &String,
+) -> Result<DiffTreeNode> {
+    if child == DiffTreeNode::Null {
+        return Ok(parent);
+    }
+    if let DiffTreeNode::Node(mut map) = parent {
+        map.insert(String::from(key), child);
+        Ok(DiffTreeNode::Node(map))
+    } else if let DiffTreeNode::Null = parent {
+        let mut map = BTreeMap::new();
+        map.insert(String::from(key), child);
+        Ok(DiffTreeNode::Node(map))
+    } else {
+        Err(Error::Structure(format!("Tried to insert child at {key} into a parent {} - structure incoherent, expected a parent object - somehow json structure seems broken", node_shape(&parent))))
+    }
+}
+
+/// Describes the variant of `node` without its values, which must not end up in errors.
+fn node_shape(node: &DiffTreeNode) -> &'static str {
+    match node {
+        DiffTreeNode::Null => "without diffs",
+        DiffTreeNode::Value(_, _) | DiffTreeNode::Redacted(_, _) => "leaf",
+        DiffTreeNode::Node(_) => "object",
+        DiffTreeNode::Array(_) => "array",
+    }
+}
+
+pub(crate) struct MapDifference {
+    pub left_only: BTreeSet<String>,
+    pub right_only: BTreeSet<String>,
+    /// Pairs of matching keys, spelled as in the left and the right object
+    pub intersection: Vec<(String, String)>,
+    /// Pairs of keys that look alike, spelled as in the left and the right object
+    pub lookalikes: Vec<(String, String)>,
+    /// Keys of one side sharing a canonical form, see [`CompareOptions::canonical_keys`]
+    pub collisions: Vec<(Side, Vec<String>)>,
+}
+
+impl MapDifference {
+    pub fn new(
+        left_only: BTreeSet<String>,
+        right_only: BTreeSet<String>,
+        intersection: Vec<(String, String)>,
+    ) -> Self {
+        Self {
+            right_only,
+            left_only,
+            intersection,
+            lookalikes: Vec::new(),
+            collisions: Vec::new(),
+        }
+    }
+
+    /// Keeps the `max_keys` keys of the lowest [`sample_rank`] for `seed`, returns the number of keys dropped.
+    fn sample_keys(&mut self, seed: u64, max_keys: usize) -> usize {
+        let total = self.intersection.len() + self.left_only.len() + self.right_only.len();
+        if total <= max_keys {
+            return 0;
+        }
+        let keep = max_keys.checked_sub(1).map(|last| {
+            let mut ranks: Vec<u64> = self
+                .intersection
+                .iter()
+                .map(|(key, _)| key)
+                .chain(&self.left_only)
+                .chain(&self.right_only)
+                .map(|key| sample_rank(seed, key))
+                .collect();
+            ranks.select_nth_unstable(last);
+            ranks[last]
+        });
+        // without any key to keep, all are dropped
+        let sampled = |key: &String| keep.is_some_and(|keep| sample_rank(seed, key) <= keep);
+        self.intersection.retain(|(key, _)| sampled(key));
+        self.left_only.retain(sampled);
+        self.right_only.retain(sampled);
+        self.lookalikes
+            .retain(|(left, right)| sampled(left) && sampled(right));
+        total - self.intersection.len() - self.left_only.len() - self.right_only.len()
+    }
+
+    /// Pairs up left-only and right-only keys that look alike, with `matching` moves them into the intersection.
+    fn pair_lookalikes(mut self, matching: bool) -> Self {
+        if self.left_only.is_empty() || self.right_only.is_empty() {
+            return self;
+        }
+        let mut right: BTreeMap<String, &String> = self
+            .right_only
+            .iter()
+            .map(|key| (normalize::lookalike(key), key))
+            .collect();
+        let lookalikes: Vec<(String, String)> = self
+            .left_only
+            .iter()
+            .filter_map(|key| {
+                Some((
+                    key.clone(),
+                    right.remove(&normalize::lookalike(key))?.clone(),
+                ))
+            })
+            .collect();
+        if matching {
+            for (left, right) in &lookalikes {
+                self.left_only.remove(left);
+                self.right_only.remove(right);
+                self.intersection.push((left.clone(), right.clone()));
+            }
+        }
+        self.lookalikes.extend(lookalikes);
+        self.lookalikes.sort();
+        self
+    }
+
+    /// Moves left-only and right-only keys of the same [`normalize::canonical_key`] into the intersection and
+    /// reports them as lookalikes. Forms shared by several compared keys of one side are collisions instead.
+    fn pair_canonical<'k>(
+        mut self,
+        left_keys: impl Iterator<Item = &'k String>,
+        right_keys: impl Iterator<Item = &'k String>,
+    ) -> Self {
+        let (left, mut right) = (canonical_forms(left_keys), canonical_forms(right_keys));
+        for (side, forms) in [(Side::Left, &left), (Side::Right, &right)] {
+            self.collisions.extend(
+                forms
+                    .values()
+                    .filter(|keys| keys.len() > 1)
+                    .map(|keys| (side, keys.iter().map(|key| (*key).clone()).collect())),
+            );
+        }
+        for (form, keys) in left {
+            let counterparts = right.remove(&form).unwrap_or_default();
+            let ([left], [right]) = (keys.as_slice(), counterparts.as_slice()) else {
+                continue;
+            };
+            if self.left_only.contains(*left) && self.right_only.contains(*right) {
+                self.left_only.remove(*left);
+                self.right_only.remove(*right);
+                self.intersection.push(((*left).clone(), (*right).clone()));
+                self.lookalikes.push(((*left).clone(), (*right).clone()));
+            }
+        }
+        self
+    }
+}
+
+/// Keys by their [`normalize::canonical_key`].
+fn canonical_forms<'k>(
+    keys: impl Iterator<Item = &'k String>,
+) -> BTreeMap<String, Vec<&'k String>> {
+    let mut forms: BTreeMap<String, Vec<&String>> = BTreeMap::new();
+    for key in keys {
+        forms
+            .entry(normalize::canonical_key(key))
+            .or_default()
+            .push(key);
+    }
+    forms
+}
+
+/// Deterministic pseudo-random rank of `key` for `seed`, an FNV-1a hash mixed by the splitmix64 finalizer.
+fn sample_rank(seed: u64, key: &str) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325 ^ seed;
+    for byte in key.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    let mut z = hash.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+pub(crate) fn intersect_maps(
+    a: &Map<String, Value>,
+    b: &Map<String, Value>,
+    options: &CompareOptions,
+) -> Result<MapDifference> {
+    // keys ignored on one side only are absent on that side
+    let keys = options.key_matchers();
+    let in_a = |k: &String| a.contains_key(k) && keys.left.is_compared(k);
+    let in_b = |k: &String| b.contains_key(k) && keys.right.is_compared(k);
+    let Some(KeyNormalizer(normalizer)) = &options.key_normalizer else {
+        let mut intersection = Vec::new();
+        let mut left = BTreeSet::new();
+        let mut right = BTreeSet::new();
+        for a_key in a.keys().filter(|k| keys.left.is_compared(k)) {
+            if in_b(a_key) {
+                intersection.push((a_key.clone(), a_key.clone()));
+            } else {
+                left.insert(String::from(a_key));
+            }
+        }
+        for b_key in b.keys().filter(|k| keys.right.is_compared(k)) {
+            if !in_a(b_key) {
+                right.insert(String::from(b_key));
+            }
+        }
+        let diff = MapDifference::new(left, right, intersection);
+        return Ok(pair_keys(diff, a, b, &keys, options));
+    };
+
+    let normalized_keys =
+        |map: &Map<String, Value>, side: KeyMatcher| -> Result<BTreeMap<String, String>> {
+            let mut keys = BTreeMap::new();
+            for key in map.keys().filter(|k| side.is_compared(k)) {
+                let normalized = normalizer(key);
+                if let Some(previous) = keys.insert(normalized.clone(), key.clone()) {
+                    return Err(Error::KeyCollision(previous, key.clone(), normalized));
+                }
+            }
+            Ok(keys)
+        };
+    let a_keys = normalized_keys(a, keys.left)?;
+    let mut b_keys = normalized_keys(b, keys.right)?;
+
+    let mut intersection = Vec::new();
+    let mut left = BTreeSet::new();
+    for (normalized, a_key) in a_keys {
+        match b_keys.remove(&normalized) {
+            Some(b_key) => intersection.push((a_key, b_key)),
+            None => {
+                left.insert(a_key);
+            }
+        }
+    }
+    let right = b_keys.into_values().collect();
+
+    let diff = MapDifference::new(left, right, intersection);
+    Ok(pair_keys(diff, a, b, &keys, options))
+}
+
+/// Pairs the keys of `diff` that only match by their canonical form or look alike, as configured, and drops
+/// one-sided keys holding `null` with [`CompareOptions::null_equals_missing`].
+fn pair_keys(
+    diff: MapDifference,
+    a: &Map<String, Value>,
+    b: &Map<String, Value>,
+    keys: &SideKeyMatchers,
+    options: &CompareOptions,
+) -> MapDifference {
+    let diff = match options.canonical_keys {
+        true => diff.pair_canonical(
+            a.keys().filter(|k| keys.left.is_compared(k)),
+            b.keys().filter(|k| keys.right.is_compared(k)),
+        ),
+        false => diff,
+    };
+    let mut diff = diff.pair_lookalikes(options.match_lookalike_keys);
+    if options.null_equals_missing {
+        diff.left_only
+            .retain(|key| !a.get(key).is_some_and(Value::is_null));
+        diff.right_only
+            .retain(|key| !b.get(key).is_some_and(Value::is_null));
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use serde_json::json;
+
+    use crate::sort::preprocess_array;
+    use crate::PathElement;
+    use crate::{ArrayContext, ErrorCode, ValueKind};
+
+    use super::*;
+
+    #[test]
+    fn sorting_ignores_ignored_keys() {
+        let data1: Value =
+            serde_json::from_str(r#"[{"a": 1, "b":2 }, { "a": 2, "b" : 1 }]"#).unwrap();
+        let ignore = [Regex::new("a").unwrap()];
+        let sorted_ignores = preprocess_array(
+            true,
+            data1.as_array().unwrap(),
+            KeyMatcher::new(&ignore, &[]),
+            None,
+        );
+        let sorted_no_ignores = preprocess_array(
+            true,
+            data1.as_array().unwrap(),
+            KeyMatcher::new(&[], &[]),
+            None,
+        );
+
+        assert_eq!(
+            sorted_ignores
+                .first()
+                .unwrap()
+                .as_object()
+                .unwrap()
+                .get("b")
+                .unwrap()
+                .as_i64()
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            sorted_no_ignores
+                .first()
+                .unwrap()
+                .as_object()
+                .unwrap()
+                .get("b")
+                .unwrap()
+                .as_i64()
+                .unwrap(),
+            2
+        );
+    }
+
+    #[test]
+    fn elements_equal_after_ignore_sort_by_their_ignored_keys() {
+        let ignore = [Regex::new("^ts$").unwrap()];
+        let elements = [
+            json!({"id": 1, "ts": 3}),
+            json!({"id": 1, "ts": 1}),
+            json!({"id": 0, "ts": 9}),
+            json!({"id": 1, "ts": 2}),
+        ];
+        let sorted = |order: [usize; 4]| {
+            let array: Vec<Value> = order.iter().map(|i| elements[*i].clone()).collect();
+            preprocess_array(true, &array, KeyMatcher::new(&ignore, &[]), None).into_owned()
+        };
+        let expected = sorted([0, 1, 2, 3]);
+        assert_eq!(
+            expected,
+            vec![
+                elements[2].clone(),
+                elements[1].clone(),
+                elements[3].clone(),
+                elements[0].clone()
+            ]
+        );
+        for order in [[3, 2, 1, 0], [1, 3, 0, 2], [2, 0, 3, 1]] {
+            assert_eq!(sorted(order), expected);
+        }
+
+        // the reported elements and indices do not depend on the input order of either side
+        let options = CompareOptions::default()
+            .sort_arrays(true)
+            .ignore_keys(ignore.to_vec());
+        let diffs = |left: Value, right: Value| -> Vec<String> {
+            compare_serde_values_with_options(&left, &right, &options)
+                .unwrap()
+                .all_diffs()
+                .iter()
+                .map(|(_, entry)| entry.to_string())
+                .collect()
+        };
+        let reported = diffs(
+            json!([elements[0], elements[1], elements[3]]),
+            json!([{"id": 1, "ts": 2}, {"id": 1, "ts": 3}, {"id": 4, "ts": 1}]),
+        );
+        assert_eq!(
+            reported,
+            vec![r#".[0].({"id":1,"ts":1})"#, r#".[2].({"id":4,"ts":1})"#]
+        );
+        let reordered = diffs(
+            json!([elements[3], elements[0], elements[1]]),
+            json!([{"id": 4, "ts": 1}, {"id": 1, "ts": 3}, {"id": 1, "ts": 2}]),
+        );
+        assert_eq!(reordered, reported);
+    }
+
+    #[test]
+    fn test_arrays_sorted_objects_ignored() {
+        let data1 = r#"[{"c": {"d": "e"} },"b","c"]"#;
+        let data2 = r#"["b","c",{"c": {"d": "f"} }]"#;
+        let ignore = Regex::new("d").unwrap();
+        let diff = compare_strs(data1, data2, true, &[ignore]).unwrap();
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn test_arrays_sorted_simple() {
+        let data1 = r#"["a","b","c"]"#;
+        let data2 = r#"["b","c","a"]"#;
+        let diff = compare_strs(data1, data2, true, &[]).unwrap();
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn test_arrays_sorted_objects() {
+        let data1 = r#"[{"c": {"d": "e"} },"b","c"]"#;
+        let data2 = r#"["b","c",{"c": {"d": "e"} }]"#;
+        let diff = compare_strs(data1, data2, true, &[]).unwrap();
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn test_arrays_deep_sorted_objects() {
+        let data1 = r#"[{"c": ["d","e"] },"b","c"]"#;
+        let data2 = r#"["b","c",{"c": ["e", "d"] }]"#;
+        let diff = compare_strs(data1, data2, true, &[]).unwrap();
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn test_arrays_deep_sorted_objects_with_arrays() {
+        let data1 = r#"[{"a": [{"b": ["3", "1"]}] }, {"a": [{"b": ["2", "3"]}] }]"#;
+        let data2 = r#"[{"a": [{"b": ["2", "3"]}] }, {"a": [{"b": ["1", "3"]}] }]"#;
+        let diff = compare_strs(data1, data2, true, &[]).unwrap();
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn test_arrays_deep_sorted_objects_with_outer_diff() {
+        let data1 = r#"[{"c": ["d","e"] },"b"]"#;
+        let data2 = r#"["b","c",{"c": ["e", "d"] }]"#;
+        let diff = compare_strs(data1, data2, true, &[]).unwrap();
+        assert!(!diff.is_empty());
+        let insertions = diff.right_only.get_diffs();
+        assert_eq!(insertions.len(), 1);
+        assert_eq!(insertions.first().unwrap().to_string(), r#".[1].("c")"#);
+    }
+
+    #[test]
+    fn test_arrays_deep_sorted_objects_with_inner_diff() {
+        let data1 = r#"["a",{"c": ["d","e", "f"] },"b"]"#;
+        let data2 = r#"["b",{"c": ["e","d"] },"a"]"#;
+        let diff = compare_strs(data1, data2, true, &[]).unwrap();
+        assert!(!diff.is_empty());
+        let deletions = diff.left_only.get_diffs();
+
+        assert_eq!(deletions.len(), 1);
+        assert_eq!(
+            deletions.first().unwrap().to_string(),
+            r#".[2].c.[2].("f")"#
+        );
+    }
+
+    #[test]
+    fn test_arrays_deep_sorted_objects_with_inner_diff_mutation() {
+        let data1 = r#"["a",{"c": ["d", "f"] },"b"]"#;
+        let data2 = r#"["b",{"c": ["e","d"] },"a"]"#;
         let diffs = compare_strs(data1, data2, true, &[]).unwrap();
         assert!(!diffs.is_empty());
-        let diffs = diffs.unequal_values.get_diffs();
+        let diffs = diffs.unequal_values.get_diffs();
+
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(
+            diffs.first().unwrap().to_string(),
+            r#".[2].c.[1].("f" != "e")"#
+        );
+    }
+
+    #[test]
+    fn test_arrays_simple_diff() {
+        let data1 = r#"["a","b","c"]"#;
+        let data2 = r#"["a","b","d"]"#;
+        let diff = compare_strs(data1, data2, false, &[]).unwrap();
+        assert_eq!(diff.left_only, DiffTreeNode::Null);
+        assert_eq!(diff.right_only, DiffTreeNode::Null);
+        let diff = diff.unequal_values.get_diffs();
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff.first().unwrap().to_string(), r#".[2].("c" != "d")"#);
+    }
+
+    #[test]
+    fn test_arrays_more_complex_diff() {
+        let data1 = r#"["a","b","c"]"#;
+        let data2 = r#"["a","a","b","d"]"#;
+        let diff = compare_strs(data1, data2, false, &[]).unwrap();
+
+        let changes_diff = diff.unequal_values.get_diffs();
+        assert_eq!(diff.left_only, DiffTreeNode::Null);
+
+        assert_eq!(changes_diff.len(), 1);
+        assert_eq!(
+            changes_diff.first().unwrap().to_string(),
+            r#".[2].("c" != "d")"#
+        );
+        let insertions = diff.right_only.get_diffs();
+        assert_eq!(insertions.len(), 1);
+        assert_eq!(insertions.first().unwrap().to_string(), r#".[0].("a")"#);
+    }
+
+    #[test]
+    fn test_arrays_extra_left() {
+        let data1 = r#"["a","b","c"]"#;
+        let data2 = r#"["a","b"]"#;
+        let diff = compare_strs(data1, data2, false, &[]).unwrap();
+
+        let diffs = diff.left_only.get_diffs();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs.first().unwrap().to_string(), r#".[2].("c")"#);
+        assert_eq!(diff.unequal_values, DiffTreeNode::Null);
+        assert_eq!(diff.right_only, DiffTreeNode::Null);
+    }
+
+    #[test]
+    fn test_arrays_extra_right() {
+        let data1 = r#"["a","b"]"#;
+        let data2 = r#"["a","b","c"]"#;
+        let diff = compare_strs(data1, data2, false, &[]).unwrap();
+
+        let diffs = diff.right_only.get_diffs();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs.first().unwrap().to_string(), r#".[2].("c")"#);
+        assert_eq!(diff.unequal_values, DiffTreeNode::Null);
+        assert_eq!(diff.left_only, DiffTreeNode::Null);
+    }
+
+    #[test]
+    fn long_insertion_modification() {
+        let data1 = r#"["a","b","a"]"#;
+        let data2 = r#"["a","c","c","c","a"]"#;
+        let diff = compare_strs(data1, data2, false, &[]).unwrap();
+        let diffs = diff.unequal_values.get_diffs();
+
+        // "b" is replaced by the first "c", the other two are insertions - the trailing "a" is equal
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].to_string(), r#".[1].("b" != "c")"#);
+        let insertions: Vec<_> = diff
+            .right_only
+            .get_diffs()
+            .into_iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(insertions, vec![r#".[2].("c")"#, r#".[3].("c")"#]);
+        assert_eq!(diff.left_only, DiffTreeNode::Null);
+    }
+
+    fn records(count: usize) -> Vec<Value> {
+        (0..count)
+            .map(|i| json!({"id": i, "name": format!("item {i}"), "updated": "2024-01-01"}))
+            .collect()
+    }
+
+    #[test]
+    fn interior_removal_in_replaced_block() {
+        let left = records(10);
+        // every element was touched, one was removed from the middle and another one changed
+        let mut right = records(10);
+        right
+            .iter_mut()
+            .for_each(|r| r["updated"] = json!("2024-02-02"));
+        right.remove(4);
+        right[6]["name"] = json!("renamed");
+
+        let diff = compare_serde_values(&json!(left), &json!(right), false, &[]).unwrap();
+        let deletions = diff.left_only.get_diffs();
+        assert_eq!(deletions.len(), 1);
+        assert_eq!(deletions[0].path, vec![PathElement::ArrayEntry(4)]);
+        assert_eq!(diff.right_only, DiffTreeNode::Null);
+
+        let mutations: Vec<_> = diff
+            .unequal_values
+            .get_diffs()
+            .into_iter()
+            .map(|d| d.to_string())
+            .collect();
+        // 9 remaining "updated" changes plus the rename, all paired with the right element
+        assert_eq!(mutations.len(), 10);
+        assert!(mutations.contains(&r#".[7].name.("item 7" != "renamed")"#.to_string()));
+        assert!(mutations.iter().all(|m| !m.contains(".id.")));
+    }
+
+    #[test]
+    fn interior_insertion_in_replaced_block() {
+        let left = records(10);
+        let mut right = records(10);
+        right
+            .iter_mut()
+            .for_each(|r| r["updated"] = json!("2024-02-02"));
+        right.insert(
+            3,
+            json!({"id": 100, "name": "new", "updated": "2024-02-02"}),
+        );
+
+        let diff = compare_serde_values(&json!(left), &json!(right), false, &[]).unwrap();
+        let insertions = diff.right_only.get_diffs();
+        assert_eq!(insertions.len(), 1);
+        assert_eq!(insertions[0].path, vec![PathElement::ArrayEntry(3)]);
+        assert_eq!(diff.left_only, DiffTreeNode::Null);
+        assert_eq!(diff.unequal_values.count_diffs(), 10);
+    }
+
+    #[test]
+    fn dissimilar_block_is_paired_by_position() {
+        let keys = CompareOptions::default();
+        let pairs = align_block(&[json!(1), json!(2)], &[json!(3)], keys.key_matchers());
+        assert_eq!(pairs, vec![BlockPair::Both(0, 0), BlockPair::Left(1)]);
+    }
+
+    #[test]
+    fn test_arrays_object_extra() {
+        let data1 = r#"["a","b"]"#;
+        let data2 = r#"["a","b", {"c": {"d": "e"} }]"#;
+        let diff = compare_strs(data1, data2, false, &[]).unwrap();
+
+        let diffs = diff.right_only.get_diffs();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(
+            diffs.first().unwrap().to_string(),
+            r#".[2].({"c":{"d":"e"}})"#
+        );
+        assert_eq!(diff.unequal_values, DiffTreeNode::Null);
+        assert_eq!(diff.left_only, DiffTreeNode::Null);
+    }
+
+    const NESTED_LEFT: &str = r#"{
+            "a":"b",
+            "b":{
+                "c":{
+                    "d":true,
+                    "e":5,
+                    "f":9,
+                    "h":{
+                        "i":true,
+                        "j":false
+                    }
+                }
+            }
+        }"#;
+    const NESTED_RIGHT: &str = r#"{
+            "a":"b",
+            "b":{
+                "c":{
+                    "d":true,
+                    "e":6,
+                    "g":0,
+                    "h":{
+                        "i":false,
+                        "k":false
+                    }
+                }
+            }
+        }"#;
+
+    #[test]
+    fn nested_diff() {
+        let data1 = NESTED_LEFT;
+        let data2 = NESTED_RIGHT;
+
+        let expected_left = DiffTreeNode::object([(
+            "b",
+            DiffTreeNode::object([(
+                "c",
+                DiffTreeNode::object([
+                    ("f", DiffTreeNode::leaf(9, 9)),
+                    (
+                        "h",
+                        DiffTreeNode::object([("j", DiffTreeNode::leaf(false, false))]),
+                    ),
+                ]),
+            )]),
+        )]);
+        let expected_right = DiffTreeNode::object([(
+            "b",
+            DiffTreeNode::object([(
+                "c",
+                DiffTreeNode::object([
+                    ("g", DiffTreeNode::leaf(0, 0)),
+                    (
+                        "h",
+                        DiffTreeNode::object([("k", DiffTreeNode::leaf(false, false))]),
+                    ),
+                ]),
+            )]),
+        )]);
+        let expected_uneq = DiffTreeNode::object([(
+            "b",
+            DiffTreeNode::object([(
+                "c",
+                DiffTreeNode::object([
+                    ("e", DiffTreeNode::leaf(5, 6)),
+                    (
+                        "h",
+                        DiffTreeNode::object([("i", DiffTreeNode::leaf(true, false))]),
+                    ),
+                ]),
+            )]),
+        )]);
+        let expected = Mismatch::new(expected_left, expected_right, expected_uneq);
+
+        let mismatch = compare_strs(data1, data2, false, &[]).unwrap();
+        assert_eq!(mismatch, expected, "Diff was incorrect.");
+    }
+
+    #[test]
+    fn no_diff() {
+        let data1 = r#"{
+            "a":"b",
+            "b":{
+                "c":{
+                    "d":true,
+                    "e":5,
+                    "f":9,
+                    "h":{
+                        "i":true,
+                        "j":false
+                    }
+                }
+            }
+        }"#;
+        let data2 = r#"{
+            "a":"b",
+            "b":{
+                "c":{
+                    "d":true,
+                    "e":5,
+                    "f":9,
+                    "h":{
+                        "i":true,
+                        "j":false
+                    }
+                }
+            }
+        }"#;
+
+        assert_eq!(
+            compare_strs(data1, data2, false, &[]).unwrap(),
+            Mismatch::new(DiffTreeNode::Null, DiffTreeNode::Null, DiffTreeNode::Null)
+        );
+    }
+
+    #[test]
+    fn no_json() {
+        let data1 = r#"{}"#;
+        let data2 = r#"{}"#;
+
+        assert_eq!(
+            compare_strs(data1, data2, false, &[]).unwrap(),
+            Mismatch::empty()
+        );
+    }
+
+    #[test]
+    fn parse_err_source_one() {
+        let invalid_json1 = r#"{invalid: json}"#;
+        let valid_json2 = r#"{"a":"b"}"#;
+        let err = compare_strs(invalid_json1, valid_json2, false, &[])
+            .expect_err("Parsing invalid JSON didn't throw an error");
+        assert!(matches!(err, Error::ParseLeft(_)));
+    }
+
+    #[test]
+    fn parse_err_source_two() {
+        let valid_json1 = r#"{"a":"b"}"#;
+        let invalid_json2 = r#"{invalid: json}"#;
+        let err = compare_strs(valid_json1, invalid_json2, false, &[])
+            .expect_err("Parsing invalid JSON didn't throw an err");
+        assert!(matches!(err, Error::ParseRight(_)));
+    }
+
+    #[test]
+    fn slices_compare_like_strs() {
+        let options = CompareOptions::default().sort_arrays(true);
+        for (a, b) in [
+            (NESTED_LEFT, NESTED_RIGHT),
+            (
+                r#"["a",{"c": ["d","f"] },"b"]"#,
+                r#"["b",{"c": ["e","d"] },"a"]"#,
+            ),
+            (r#"{"ü": "ä"}"#, r#"{"ü": "ö"}"#),
+        ] {
+            let from_strs = compare_strs_with_options(a, b, &options).unwrap();
+            let from_slices = compare_slices(a.as_bytes(), b.as_bytes(), &options).unwrap();
+            assert_eq!(from_strs, from_slices);
+        }
+    }
+
+    #[test]
+    fn slices_report_parse_error_side() {
+        let options = CompareOptions::default();
+        let err = compare_slices(b"{", b"{}", &options).unwrap_err();
+        assert!(matches!(err, Error::ParseLeft(_)));
+        let err = compare_slices(b"{}", b"{\"a\": \"\xff\"}", &options).unwrap_err();
+        assert!(matches!(err, Error::ParseRight(_)));
+    }
+
+    #[test]
+    fn normalized_keys_compare_clean() {
+        let left = json!({"userName": 1, "address": {"zipCode": "1234"}});
+        let right = json!({"user_name": 1, "address": {"zip_code": "1234"}});
+        let options = CompareOptions::default().key_normalizer(crate::normalize::snake_case);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert!(diffs.is_empty());
+
+        let diffs = compare_serde_values(&left, &right, false, &[]).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![
+                "Extra on left: .address.zipCode.(\"1234\")",
+                "Extra on left: .userName.(1)",
+                "Extra on right: .address.zip_code.(\"1234\")",
+                "Extra on right: .user_name.(1)",
+            ]
+        );
+    }
+
+    #[test]
+    fn normalized_keys_report_original_spelling() {
+        let left = json!({"userName": 1, "leftOnly": true});
+        let right = json!({"user_name": 2, "right_only": true});
+        let options = CompareOptions::default().key_normalizer(crate::normalize::snake_case);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![
+                "Extra on left: .leftOnly.(true)",
+                "Extra on right: .right_only.(true)",
+                "Mismatched: .userName.(1 != 2)",
+            ]
+        );
+    }
+
+    #[test]
+    fn lowercased_keys_pair_across_case() {
+        let left = json!({"CustomerId": 7, "Address": {"ZipCode": "1234", "City": "Bern"}});
+        let right = json!({"customerId": 7, "address": {"zipcode": "1234", "city": "Basel"}});
+        let options = CompareOptions::default().key_normalizer(crate::normalize::lowercase);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![r#"Mismatched: .Address.City.("Bern" != "Basel")"#]
+        );
+        let (_, entry) = diffs.all_diffs().pop().unwrap();
+        assert_eq!(entry.resolve(&left), Some(&json!("Bern")));
+
+        let left = json!({"customerId": 1, "CustomerID": 2});
+        let err = compare_serde_values_with_options(&left, &right, &options).unwrap_err();
+        assert!(matches!(err, Error::KeyCollision(_, _, normalized) if normalized == "customerid"));
+    }
+
+    #[test]
+    fn normalized_key_collision_errors() {
+        let left = json!({"userName": 1, "user_name": 2});
+        let right = json!({"user_name": 1});
+        let options = CompareOptions::default().key_normalizer(crate::normalize::snake_case);
+        let err = compare_serde_values_with_options(&left, &right, &options).unwrap_err();
+        assert!(matches!(err, Error::KeyCollision(_, _, normalized) if normalized == "user_name"));
+    }
+
+    #[test]
+    fn comparator_matches_per_call_api() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Comparator>();
+
+        let options = CompareOptions::default()
+            .sort_arrays(true)
+            .ignore_key_patterns(["^_"]);
+        let comparator = options.build().unwrap();
+        for i in 0..50 {
+            let left = json!({"_rev": i, "items": [i, i + 1, {"v": i % 3}]});
+            let right = json!({"_rev": i + 1, "items": [i + 1, i, {"v": i % 2}]});
+            let built = comparator.compare(&left, &right).unwrap();
+            let per_call = compare_serde_values_with_options(&left, &right, &options).unwrap();
+            assert_eq!(built, per_call);
+            assert!(!built
+                .all_diffs()
+                .iter()
+                .any(|(_, d)| d.to_string().contains("_rev")));
+        }
+    }
+
+    #[test]
+    fn invalid_pattern_fails_at_build() {
+        let options = CompareOptions::default().ignore_key_patterns(["("]);
+        assert!(matches!(options.build(), Err(Error::Regex(_))));
+        let err = compare_strs_with_options("{}", "{}", &options).unwrap_err();
+        assert!(matches!(err, Error::Regex(_)));
+    }
+
+    #[test]
+    fn template_array_validates_every_element() {
+        let options = CompareOptions::default().mode(CompareMode::Template);
+        let template = json!({"id": "integer", "tags": ["integer"]});
+        let data = json!({"id": 7, "tags": [1, 2, 3]});
+        let diffs = compare_serde_values_with_options(&template, &data, &options).unwrap();
+        assert!(diffs.is_empty());
+
+        let data = json!({"id": 7, "tags": [1, "two", 3]});
+        let diffs = compare_serde_values_with_options(&template, &data, &options).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![r#"Mismatched: .tags.[1].("integer" != "two")"#]
+        );
+    }
+
+    #[test]
+    fn template_reports_missing_keys_and_literals() {
+        let options = CompareOptions::default().mode(CompareMode::Template);
+        let template = json!({"id": "any", "kind": "user", "name": "string"});
+        let data = json!({"id": null, "kind": "admin", "extra": true});
+        let diffs = compare_serde_values_with_options(&template, &data, &options).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![
+                r#"Extra on left: .name.("string")"#,
+                "Extra on right: .extra.(true)",
+                r#"Mismatched: .kind.("user" != "admin")"#,
+            ]
+        );
+        // without template mode the type names are plain strings
+        let diffs = compare_serde_values(&template, &data, false, &[]).unwrap();
+        assert_eq!(diffs.count_diffs().unequal_values, 2);
+    }
+
+    #[test]
+    fn wide_objects_cap_reported_extras() {
+        let wide: Map<String, Value> = (0..5000)
+            .map(|i| (format!("key{i:05}"), json!(i)))
+            .collect();
+        let left = json!({"nested": {"map": wide, "same": 1}});
+        let right = json!({"nested": {"map": {"other": true}, "same": 1}});
+        let options = CompareOptions::default().max_extras_per_object(Some(3));
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+
+        let mut reported: Vec<_> = diffs.records().iter().map(ToString::to_string).collect();
+        reported.sort();
+        assert_eq!(
+            reported,
+            vec![
+                "Extra on left: .nested.map.(... 4997 more)",
+                "Extra on left: .nested.map.key00000.(0)",
+                "Extra on left: .nested.map.key00001.(1)",
+                "Extra on left: .nested.map.key00002.(2)",
+                "Extra on right: .nested.map.other.(true)",
+            ]
+        );
+        let summary = &diffs.extra_records[0];
+        assert_eq!(summary.values, DiffValues::Summary { suppressed: 4997 });
+        assert_eq!(summary.count(), 4997);
+
+        let counts = diffs.count_diffs();
+        assert_eq!(counts.left_only, 5000);
+        assert_eq!(counts.right_only, 1);
+        let uncapped = compare_serde_values(&left, &right, false, &[]).unwrap();
+        assert_eq!(uncapped.count_diffs(), counts);
+    }
+
+    #[test]
+    fn zero_extras_still_reports_a_summary() {
+        let options = CompareOptions::default().max_extras_per_object(Some(0));
+        let diffs =
+            compare_serde_values_with_options(&json!([{"a": 1}]), &json!([{}]), &options).unwrap();
+        assert!(!diffs.is_empty());
+        assert_eq!(
+            diffs.records()[0].to_string(),
+            "Extra on left: .[0].(... 1 more)"
+        );
+    }
+
+    fn without_tombstones() -> CompareOptions {
+        CompareOptions::default().filter_array_elements(|path, value| {
+            path.first() != Some(&PathElement::Object("items")) || value["deleted"] != json!(true)
+        })
+    }
+
+    #[test]
+    fn filtered_tombstones_compare_clean() {
+        let left = json!({"items": [{"id": 1}, {"id": 2, "deleted": true}, {"id": 3}]});
+        let right = json!({"items": [{"id": 1}, {"id": 3}, {"id": 4, "deleted": true}]});
+        let diffs =
+            compare_serde_values_with_options(&left, &right, &without_tombstones()).unwrap();
+        assert!(diffs.is_empty());
+        let sorted = without_tombstones().sort_arrays(true);
+        let diffs = compare_serde_values_with_options(&right, &left, &sorted).unwrap();
+        assert!(diffs.is_empty());
+        // the filter only applies below "items"
+        let left = json!({"other": [{"deleted": true}]});
+        let diffs =
+            compare_serde_values_with_options(&left, &json!({"other": []}), &without_tombstones())
+                .unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![r#"Extra on left: .other.[0].({"deleted":true})"#]
+        );
+    }
+
+    #[test]
+    fn filtered_arrays_still_report_differences() {
+        let left = json!({"items": [{"id": 1, "deleted": true}, {"id": 2}, {"id": 3}]});
+        let right = json!({"items": [{"id": 2}, {"id": 5}]});
+        let diffs =
+            compare_serde_values_with_options(&left, &right, &without_tombstones()).unwrap();
+        // indices refer to the filtered arrays
+        assert_eq!(
+            diff_strings(&diffs),
+            vec!["Mismatched: .items.[1].id.(3 != 5)"]
+        );
+    }
+
+    #[test]
+    fn multiplicity_of_duplicated_scalars() {
+        let options = CompareOptions::default()
+            .sort_arrays(true)
+            .verify_multiplicity(true);
+        let diffs = compare_serde_values_with_options(
+            &json!({"tags": ["a", "a", "b"]}),
+            &json!({"tags": ["a", "b", "b"]}),
+            &options,
+        )
+        .unwrap();
+        let records: Vec<_> = diffs.records().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            records,
+            vec![
+                r#"Extra on left: .tags.[0].("a")"#,
+                r#"Extra on right: .tags.[2].("b")"#,
+                r#"Multiplicity changed: .tags.("a": 2 → 1)"#,
+                r#"Multiplicity changed: .tags.("b": 1 → 2)"#,
+            ]
+        );
+        assert_eq!(diffs.count_diffs().unequal_values, 2);
+    }
+
+    #[test]
+    fn multiplicity_of_duplicated_objects_with_ignored_keys() {
+        let ignore = vec![Regex::new("^ts$").unwrap()];
+        let left = json!([{"id": 1, "ts": 1}, {"id": 1, "ts": 2}, {"id": 2, "ts": 3}]);
+        let right = json!([{"id": 1, "ts": 4}, {"id": 2, "ts": 5}, {"id": 2, "ts": 6}]);
+        let options = CompareOptions::default()
+            .sort_arrays(true)
+            .ignore_keys(ignore);
+        let positional = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        let verified =
+            compare_serde_values_with_options(&left, &right, &options.verify_multiplicity(true))
+                .unwrap();
+        assert_eq!(
+            verified.extra_records[0].values,
+            DiffValues::Multiplicity {
+                element: json!({"id": 1, "ts": 1}),
+                left: 2,
+                right: 1
+            }
+        );
+        assert_eq!(verified.extra_records.len(), 2);
+        assert_eq!(
+            verified.count_diffs().total(),
+            positional.count_diffs().total() + 2
+        );
+
+        // equal multisets add nothing
+        let verified = compare_serde_values_with_options(
+            &json!([1, 2, 1]),
+            &json!([1, 1, 2]),
+            &CompareOptions::default()
+                .sort_arrays(true)
+                .verify_multiplicity(true),
+        )
+        .unwrap();
+        assert!(verified.is_empty());
+    }
+
+    fn resolve_side<'v>(value: &'v Value, path: &crate::SidePath) -> Option<&'v Value> {
+        path.path
+            .elements()
+            .iter()
+            .try_fold(value, |v, element| element.resolve(v))
+    }
+
+    #[test]
+    fn paths_for_side_resolve_in_shuffled_arrays() {
+        let left =
+            json!({"items": [{"id": 3, "v": "c"}, {"id": 1, "v": "a"}, {"id": 2, "v": "b"}, 7]});
+        let right =
+            json!({"items": [8, {"id": 2, "v": "B"}, {"id": 1, "v": "a"}, {"id": 3, "v": "c"}]});
+        let options = CompareOptions::default().sort_arrays(true);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![
+                "Mismatched: .items.[0].(7 != 8)",
+                r#"Mismatched: .items.[2].v.("b" != "B")"#,
+            ]
+        );
+
+        for (diff_type, path) in diffs.paths_for_side(crate::Side::Right) {
+            assert!(path.exists, "{diff_type}: {}", path.path);
+            let value = resolve_side(&right, &path).unwrap();
+            assert!(value == &json!("B") || value == &json!(8), "{}", path.path);
+        }
+        for (_, path) in diffs.paths_for_side(crate::Side::Left) {
+            let value = resolve_side(&left, &path).unwrap();
+            assert!(value == &json!("b") || value == &json!(7), "{}", path.path);
+        }
+    }
+
+    #[test]
+    fn paths_for_side_mark_one_sided_entries() {
+        let left = json!([{"id": 2, "x": [1]}, {"id": 1}]);
+        let right = json!([{"id": 0}, {"id": 1}, {"id": 2, "x": [1, 2]}]);
+        let options = CompareOptions::default().sort_arrays(true);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+
+        let right_paths = diffs.paths_for_side(crate::Side::Right);
+        let left_paths = diffs.paths_for_side(crate::Side::Left);
+        for ((diff_type, right_path), (_, left_path)) in right_paths.iter().zip(&left_paths) {
+            match diff_type {
+                DiffType::RightExtra => {
+                    assert!(right_path.exists);
+                    assert!(!left_path.exists);
+                    let value = resolve_side(&right, right_path).unwrap();
+                    assert!(value == &json!({"id": 0}) || value == &json!(2));
+                    assert!(resolve_side(&left, left_path).unwrap().is_array());
+                }
+                _ => panic!("unexpected {diff_type}"),
+            }
+        }
+        assert_eq!(right_paths.len(), 2);
+    }
+
+    #[test]
+    fn resolve_entry_translates_sorted_paths() {
+        let left = json!({"items": [{"id": 3, "v": "c"}, {"id": 1, "v": "a"}, 7], "gone": [2, 1]});
+        let right = json!({"items": [8, {"id": 1, "v": "a"}, {"id": 3, "v": "C"}]});
+        let diffs = compare_serde_values(&left, &right, true, &[]).unwrap();
+        assert!(diffs.normalization.arrays);
+        let resolved: Vec<(String, Option<&Value>, Option<&Value>)> = diffs
+            .all_diffs()
+            .iter()
+            .map(|(_, entry)| {
+                let translated = diffs.resolve_entry(entry, &left).unwrap();
+                (entry.to_string(), entry.resolve(&left), translated)
+            })
+            .collect();
+        assert_eq!(
+            resolved,
+            vec![
+                // resolving directly silently picks the wrong elements of the original arrays
+                (
+                    ".items.[0].(7 != 8)".to_string(),
+                    Some(&json!({"id": 3, "v": "c"})),
+                    Some(&json!(7))
+                ),
+                (
+                    r#".items.[2].v.("c" != "C")"#.to_string(),
+                    None,
+                    Some(&json!("c"))
+                ),
+                (
+                    ".gone.([2,1])".to_string(),
+                    Some(&json!([2, 1])),
+                    Some(&json!([2, 1]))
+                ),
+            ]
+        );
+        let unsorted = compare_serde_values(&left, &right, false, &[]).unwrap();
+        assert!(!unsorted.normalization.is_applied());
+        let (_, entry) = &unsorted.all_diffs()[0];
+        assert_eq!(
+            unsorted.resolve_entry(entry, &left).unwrap(),
+            entry.resolve(&left)
+        );
+    }
+
+    #[test]
+    fn transforms_edit_their_side_before_comparing() {
+        let left = json!({"debug": {"trace": [1]}, "user": "ada", "items": [1]});
+        let right = json!({"owner": "ada", "items": [1, 2], "retries": 0});
+        let transform = |operations| crate::transform::Transform::from_json(&operations).unwrap();
+        let options = CompareOptions::default()
+            .pre_transform_left(transform(json!([
+                {"op": "remove", "path": "/debug"},
+                {"op": "move", "from": "/user", "path": "/owner"},
+                {"op": "default", "path": "/retries", "value": 0}
+            ])))
+            .pre_transform_right(transform(json!([{"op": "remove", "path": "/items/1"}])));
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert!(diffs.is_empty());
+        assert!(diffs.normalization.left_transformed);
+
+        let missing = transform(json!([{"op": "remove", "path": "/debug"}]));
+        let options = options.pre_transform_right(missing);
+        let err = compare_serde_values_with_options(&left, &right, &options).unwrap_err();
+        assert_eq!(err.side(), Some(Side::Right));
+        assert_eq!(err.code(), ErrorCode::InvalidTransform);
+    }
+
+    #[test]
+    fn resolve_entry_rejects_unwrapped_comparisons() {
+        let left = json!({"data": [2, 1]});
+        let right = json!([1, 3]);
+        let options = CompareOptions::default()
+            .sort_arrays(true)
+            .unwrap_left("/data");
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        let (_, entry) = &diffs.all_diffs()[0];
+        let err = diffs.resolve_entry(entry, &left).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::RequiresNormalizedDocument);
+        // resolved against the compared subdocument, the entry refers to the sorted array
+        assert_eq!(entry.resolve(&json!([1, 2])), Some(&json!(2)));
+        let fallback = options.unwrap_left("/missing").unwrap_fallback(true);
+        let diffs = compare_serde_values_with_options(&json!([2, 1]), &right, &fallback).unwrap();
+        assert!(!diffs.normalization.left_unwrapped);
+        let (_, entry) = &diffs.all_diffs()[0];
+        assert_eq!(
+            diffs.resolve_entry(entry, &json!([2, 1])).unwrap(),
+            Some(&json!(2))
+        );
+    }
+
+    #[test]
+    fn multi_document_pairs_by_position() {
+        let options = CompareOptions::default();
+        let diffs =
+            compare_multi_document_strs(r#"{"a": 1} {"a": 2}"#, "{\"a\": 1}\n{\"a\": 3}", &options)
+                .unwrap();
+        assert_eq!(diffs.len(), 2);
+        assert!(diffs[0].is_empty());
+        assert_eq!(diff_strings(&diffs[1]), vec!["Mismatched: .a.(2 != 3)"]);
+
+        let diffs = compare_multi_document_strs("[1] [2]", "[1] [2] [3]", &options).unwrap();
+        assert_eq!(diffs.len(), 3);
+        assert!(diffs[0].is_empty() && diffs[1].is_empty());
+        assert_eq!(diff_strings(&diffs[2]), vec!["Extra on right: .([3])"]);
+    }
+
+    #[test]
+    fn multi_document_parse_error_names_side_and_index() {
+        let options = CompareOptions::default();
+        let err = compare_multi_document_strs("{} {", "{} {}", &options).unwrap_err();
+        assert!(matches!(err, Error::ParseDocument(Side::Left, 1, _)));
+        assert!(err
+            .to_string()
+            .starts_with("Error parsing left document 1:"));
+        let err = compare_multi_document_strs("{} {}", "{} x", &options).unwrap_err();
+        assert!(matches!(err, Error::ParseDocument(Side::Right, 1, _)));
+    }
+
+    #[test]
+    fn include_overrides_take_precedence_over_ignores() {
+        let left = json!({"user_id": 1, "order_id": 2, "name": "a"});
+        let right = json!({"user_id": 3, "order_id": 4, "name": "b"});
+        let ignore = vec![Regex::new(".*_id$").unwrap()];
+        let excluded = CompareOptions::default().ignore_keys(ignore.clone());
+        let diffs = compare_serde_values_with_options(&left, &right, &excluded).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![r#"Mismatched: .name.("a" != "b")"#]
+        );
+
+        let overridden = excluded.include_keys_override(vec![Regex::new("^order_id$").unwrap()]);
+        let diffs = compare_serde_values_with_options(&left, &right, &overridden).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![
+                r#"Mismatched: .name.("a" != "b")"#,
+                "Mismatched: .order_id.(2 != 4)",
+            ]
+        );
+
+        // an override without a matching ignore changes nothing
+        let untouched =
+            CompareOptions::default().include_keys_override(vec![Regex::new("^name$").unwrap()]);
+        let diffs = compare_serde_values_with_options(&left, &right, &untouched).unwrap();
+        assert_eq!(diffs.count_diffs().unequal_values, 3);
+    }
+
+    #[test]
+    fn include_overrides_apply_inside_sorted_arrays() {
+        let ignore = vec![Regex::new("_id$").unwrap()];
+        let overrides = vec![Regex::new("^order_id$").unwrap()];
+        let left = json!([{"order_id": 2, "user_id": 9}, {"order_id": 1, "user_id": 8}]);
+        let right = json!([{"order_id": 1, "user_id": 7}, {"order_id": 2, "user_id": 6}]);
+        let options = CompareOptions::default()
+            .sort_arrays(true)
+            .ignore_keys(ignore)
+            .include_keys_override(overrides);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert!(diffs.is_empty());
+
+        let right = json!([{"order_id": 1, "user_id": 7}, {"order_id": 3, "user_id": 6}]);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec!["Mismatched: .[1].order_id.(2 != 3)"]
+        );
+    }
+
+    #[test]
+    fn array_children_are_inserted_in_index_order() {
+        let mut node = DiffTreeNode::Null;
+        for index in [5, 1, 9, 3] {
+            node = insert_child_key_diff(node, DiffTreeNode::leaf(index, 0), index).unwrap();
+        }
+        let indices: Vec<_> = node.children().map(|(element, _)| element).collect();
+        assert_eq!(indices, [1, 3, 5, 9].map(PathElement::ArrayEntry).to_vec());
+        assert_eq!(node.get_index(9), Some(&DiffTreeNode::leaf(9, 0)));
+        assert_eq!(node.get_index(4), None);
+    }
+
+    fn origins_of(
+        left: Value,
+        right: Value,
+        options: CompareOptions,
+    ) -> Vec<(String, Option<Origin>)> {
+        let diffs = compare_serde_values_with_options(&left, &right, &options.record_origins(true))
+            .unwrap();
+        let mut origins: Vec<_> = diffs
+            .records()
+            .into_iter()
+            .map(|record| (record.to_string(), record.origin))
+            .collect();
+        origins.sort_by(|a, b| a.0.cmp(&b.0));
+        origins
+    }
+
+    #[test]
+    fn origins_name_the_comparison_pass() {
+        let object = origins_of(
+            json!({"a": 1, "gone": 2}),
+            json!({"a": 3}),
+            CompareOptions::default(),
+        );
+        assert_eq!(
+            object,
+            vec![
+                (
+                    "Extra on left: .gone.(2)".to_string(),
+                    Some(Origin::ObjectKey)
+                ),
+                (
+                    "Mismatched: .a.(1 != 3)".to_string(),
+                    Some(Origin::ObjectKey)
+                ),
+            ]
+        );
+
+        let positional = origins_of(json!([1, 2]), json!([1, 2, 3]), CompareOptions::default());
+        assert_eq!(positional[0].1, Some(Origin::ArrayPositional));
+
+        let replaced = origins_of(json!([1, 2]), json!([1, 5]), CompareOptions::default());
+        assert_eq!(
+            replaced,
+            vec![(
+                "Mismatched: .[1].(2 != 5)".to_string(),
+                Some(Origin::ArrayReplacedBlock)
+            )]
+        );
+
+        let sorted = origins_of(
+            json!([3, 1]),
+            json!([1, 4]),
+            CompareOptions::default().sort_arrays(true),
+        );
+        assert!(sorted
+            .iter()
+            .all(|(_, origin)| *origin == Some(Origin::ArraySorted)));
+
+        let nested = origins_of(
+            json!({"a": [{"b": 1}]}),
+            json!({"a": [{"b": 2}]}),
+            CompareOptions::default(),
+        );
+        assert_eq!(nested[0].1, Some(Origin::ObjectKey));
+    }
+
+    #[test]
+    fn origins_are_not_recorded_by_default() {
+        let diffs =
+            compare_serde_values(&json!({"a": [1]}), &json!({"a": [2]}), false, &[]).unwrap();
+        assert!(diffs.records().iter().all(|record| record.origin.is_none()));
+        assert_eq!(diffs.origins, Default::default());
+    }
+
+    #[test]
+    fn wildcard_values_match_anything() {
+        let options = CompareOptions::default().wildcard_values(vec![json!("TODO"), json!(-1)]);
+        let left = json!({"name": "TODO", "id": -1, "tags": "TODO", "kind": "a"});
+        let right = json!({"name": "Jane", "id": 17, "tags": ["x"], "kind": "a"});
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert!(diffs.is_empty());
+        let diffs = compare_serde_values_with_options(&right, &left, &options).unwrap();
+        assert!(diffs.is_empty());
+
+        let right = json!({"name": "Jane", "id": 17, "tags": [], "kind": "b"});
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![r#"Mismatched: .kind.("a" != "b")"#]
+        );
+    }
+
+    #[test]
+    fn values_matching_ignored_patterns_compare_equal() {
+        let iso_8601 = r"^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}(\.\d+)?(Z|[+-]\d{2}:\d{2})$";
+        let options = CompareOptions::default()
+            .ignore_value_patterns([iso_8601])
+            .ignore_key_patterns(["^etag$"]);
+        let left = json!({"created_at": "2024-01-02T03:04:05Z", "etag": "a", "items": [{"at": "2024-01-02T03:04:05.123+01:00"}]});
+        let right = json!({"created_at": "2024-06-07T08:09:10Z", "etag": "b", "items": [{"at": "2025-01-01T00:00:00Z"}]});
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert!(diffs.is_empty());
+
+        // both sides have to match, values of other types and strings not matching still differ
+        let right = json!({"created_at": "yesterday", "etag": "b", "items": [{"at": null}]});
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![
+                r#"Mismatched: .created_at.("2024-01-02T03:04:05Z" != "yesterday")"#,
+                r#"Mismatched: .items.[0].at.("2024-01-02T03:04:05.123+01:00" != null)"#,
+            ]
+        );
+        let either = ValueRule::either_side(Regex::new(iso_8601).unwrap());
+        let options = options.ignore_values(vec![either]);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert!(diffs.is_empty());
+    }
+
+    #[test]
+    fn wildcards_are_sorted_last() {
+        let options = CompareOptions::default()
+            .sort_arrays(true)
+            .wildcard_values(vec![json!("TODO")]);
+        let left = json!([3, "TODO", 1, "b"]);
+        let diffs =
+            compare_serde_values_with_options(&left, &json!([1, 3, "b", 7]), &options).unwrap();
+        assert!(diffs.is_empty());
+
+        let (sorted, origins) = move_wildcards_last(
+            Cow::Owned(vec![json!("TODO"), json!(1), json!("TODO"), json!(2)]),
+            Some(vec![3, 0, 2, 1]),
+            &options,
+        );
+        assert_eq!(*sorted, [json!(1), json!(2), json!("TODO"), json!("TODO")]);
+        assert_eq!(origins, Some(vec![0, 1, 3, 2]));
+
+        let diffs =
+            compare_serde_values_with_options(&left, &json!([1, 4, "b", 7]), &options).unwrap();
+        assert_eq!(diff_strings(&diffs), vec!["Mismatched: .[1].(3 != 4)"]);
+
+        let diffs = compare_serde_values_with_options(
+            &json!([1, "TODO", "TODO"]),
+            &json!([2, 1]),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![r#"Extra on left: .[2].("TODO")"#]
+        );
+    }
+
+    #[test]
+    fn leaf_counts_of_a_small_document() {
+        let left = json!({"a": 1, "b": [1, 2, 3], "c": {"d": "x"}, "gone": [true, null], "_id": 5});
+        let right = json!({"a": 2, "b": [1, 3], "c": {"d": "x", "e": 1}, "_id": 6});
+        let options = CompareOptions::default()
+            .collect_counts(true)
+            .ignore_keys(vec![Regex::new("^_id$").unwrap()]);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        let expected = LeafCounts {
+            left: 7,
+            right: 5,
+            equal: 3,
+        };
+        assert_eq!(diffs.usage.leaves, Some(expected));
+
+        let diffs = compare_serde_values(&left, &right, false, &[]).unwrap();
+        assert_eq!(diffs.usage.leaves, None);
+    }
+
+    #[test]
+    fn reordered_arrays_report_order_only() {
+        let options = CompareOptions::default()
+            .sort_arrays(true)
+            .report_order_differences(true);
+        let left = json!({"a": [1, 2, 3], "b": [1, 2]});
+        let right = json!({"a": [3, 1, 2], "b": [1, 2]});
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert!(diffs.is_empty());
+        assert!(diffs.has_order_differences());
+        assert_eq!(diffs.count_diffs().total(), 0);
+        let records = diffs.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].to_string(), "Order differs: .a");
+
+        let unsorted = CompareOptions::default().report_order_differences(true);
+        let diffs = compare_serde_values_with_options(&left, &right, &unsorted).unwrap();
+        assert!(!diffs.is_empty());
+        assert!(!diffs.has_order_differences());
+    }
+
+    #[test]
+    fn reorder_and_mutation_report_both() {
+        let options = CompareOptions::default()
+            .sort_arrays(true)
+            .report_order_differences(true);
+        let left = json!([{"id": 1, "v": 1}, {"id": 2, "v": 2}, "x"]);
+        let right = json!(["x", {"id": 2, "v": 3}, {"id": 1, "v": 1}]);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert!(!diffs.is_empty());
+        let mut records: Vec<_> = diffs.records().iter().map(|r| r.to_string()).collect();
+        records.sort();
+        assert_eq!(
+            records,
+            vec!["Mismatched: .[2].v.(2 != 3)", "Order differs: "]
+        );
+
+        let right = json!([{"id": 1, "v": 1}, {"id": 2, "v": 3}, "x"]);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert!(!diffs.has_order_differences());
+    }
+
+    fn composite_identity() -> CompareOptions {
+        CompareOptions::default()
+            .record_origins(true)
+            .array_identity(|_path, element| {
+                Some(format!("{}/{}", element.get("type")?, element.get("name")?))
+            })
+    }
+
+    #[test]
+    fn composite_identity_aligns_reordered_elements() {
+        let left = json!({"items": [
+            {"type": "a", "name": "x", "v": 1},
+            {"type": "b", "name": "x", "v": 2},
+            {"type": "a", "name": "y", "v": 3}
+        ]});
+        let right = json!({"items": [
+            {"type": "a", "name": "y", "v": 3},
+            {"type": "c", "name": "x", "v": 4},
+            {"type": "a", "name": "x", "v": 5}
+        ]});
+        let diffs =
+            compare_serde_values_with_options(&left, &right, &composite_identity()).unwrap();
+        let mut records: Vec<_> = diffs
+            .records()
+            .iter()
+            .map(|r| format!("{r} [{}]", r.origin.unwrap()))
+            .collect();
+        records.sort();
+        assert_eq!(
+            records,
+            vec![
+                r#"Extra on left: .items.[1].({"type":"b","name":"x","v":2}) [array identity]"#,
+                r#"Extra on right: .items.[1].({"type":"c","name":"x","v":4}) [array identity]"#,
+                "Mismatched: .items.[0].v.(1 != 5) [object key]",
+            ]
+        );
+        let right_paths = diffs.paths_for_side(Side::Right);
+        assert!(right_paths
+            .iter()
+            .any(|(_, p)| p.path.to_string() == ".items.[2].v" && p.exists));
+        assert!(diffs.warnings.is_empty());
+    }
+
+    #[test]
+    fn unidentified_elements_pair_by_position() {
+        let left = json!([{"type": "a", "name": "x"}, 1, {"v": 1}]);
+        let right = json!([{"v": 2}, {"type": "a", "name": "x"}, 1, 7]);
+        let diffs =
+            compare_serde_values_with_options(&left, &right, &composite_identity()).unwrap();
+        let mut records: Vec<_> = diffs
+            .records()
+            .iter()
+            .map(|r| format!("{r} [{}]", r.origin.unwrap()))
+            .collect();
+        records.sort();
+        assert_eq!(
+            records,
+            vec![
+                "Extra on right: .[3].(7) [array position]",
+                r#"Mismatched: .[1].(1 != {"v":2}) [array position]"#,
+                r#"Mismatched: .[2].({"v":1} != 1) [array position]"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_identities_pair_in_order_with_a_warning() {
+        let left = json!({"list": [{"type": "a", "name": "x", "v": 1}, {"type": "a", "name": "x", "v": 2}]});
+        let right = json!({"list": [{"type": "a", "name": "x", "v": 2}]});
+        let options = composite_identity();
+        let first = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        let second = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(
+            diff_strings(&first),
+            vec![
+                "Extra on left: .list.[1].({\"type\":\"a\",\"name\":\"x\",\"v\":2})",
+                "Mismatched: .list.[0].v.(1 != 2)",
+            ]
+        );
+        assert_eq!(first.warnings.len(), 1);
+        let warning = &first.warnings[0];
+        assert_eq!(warning.path().to_string(), ".list");
+        assert!(
+            warning.to_string().contains("occurs 2 times on the left"),
+            "{warning}"
+        );
+    }
+
+    #[test]
+    fn identity_keys_pair_reordered_elements() {
+        let left = json!({"users": [
+            {"id": 1, "name": "ann", "role": "admin"},
+            {"id": 2, "name": "bob", "role": "dev"},
+            {"id": 3, "name": "cid", "role": "dev"}
+        ]});
+        let right = json!({"users": [
+            {"id": 3, "name": "cid", "role": "dev"},
+            {"id": 1, "name": "ann", "role": "owner"},
+            {"id": 2, "name": "bob", "role": "dev"}
+        ]});
+        let options = CompareOptions::default().identity_keys(["id"]);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![r#"Mismatched: .users.[0].role.("admin" != "owner")"#]
+        );
+        assert!(diffs.warnings.is_empty());
+
+        // the first key an element has identifies it, elements without any pair by position
+        let left = json!([{"uid": "a", "v": 1}, {"id": 7, "v": 2}, {"v": 3}, {"id": 8}]);
+        let right = json!([{"v": 4}, {"id": 7, "v": 2}, {"uid": "a", "v": 1}, {"id": 9}]);
+        let options = CompareOptions::default().identity_keys(["uid", "id"]);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![
+                r#"Extra on left: .[3].({"id":8})"#,
+                r#"Extra on right: .[3].({"id":9})"#,
+                "Mismatched: .[2].v.(3 != 4)",
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicated_identity_keys_pair_in_order_with_a_warning() {
+        let left = json!([{"id": 1, "v": 1}, {"id": 1, "v": 2}, {"id": 2, "v": 3}]);
+        let right = json!([{"id": 2, "v": 3}, {"id": 1, "v": 2}, {"id": 1, "v": 5}]);
+        let options = CompareOptions::default().identity_keys(["id"]);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec!["Mismatched: .[0].v.(1 != 2)", "Mismatched: .[1].v.(2 != 5)"]
+        );
+        let warnings: Vec<String> = diffs.warnings.iter().map(ToString::to_string).collect();
+        assert_eq!(warnings.len(), 2, "{warnings:?}");
+        assert!(warnings[0].contains("id=1") && warnings[0].contains("on the left"));
+        assert!(warnings[1].contains("on the right"));
+    }
+
+    #[test]
+    fn identity_keys_at_pointers_take_precedence() {
+        let left = json!({"users": [{"id": 1, "email": "a@x"}, {"id": 2, "email": "b@x"}],
+                          "teams": [{"id": 1, "email": "t@x"}]});
+        let right = json!({"users": [{"id": 2, "email": "a@x"}, {"id": 3, "email": "b@x"}],
+                           "teams": [{"id": 1, "email": "u@x"}]});
+        let options = CompareOptions::default()
+            .identity_keys(["id"])
+            .identity_key_at("/users", "email");
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![
+                "Mismatched: .teams.[0].email.(\"t@x\" != \"u@x\")",
+                "Mismatched: .users.[0].id.(1 != 2)",
+                "Mismatched: .users.[1].id.(2 != 3)",
+            ]
+        );
+        assert_eq!(
+            CompareOptions::default()
+                .identity_key_at("users", "email")
+                .validate(),
+            Err(vec![OptionConflict::InvalidIdentityPointer("users".into())])
+        );
+    }
+
+    #[test]
+    fn diff_at_compares_scalars_at_the_pointer() {
+        let a = json!({"spec": {"replicas": 2, "ports": [80, 443]}, "status": "old"});
+        let b = json!({"spec": {"replicas": 3, "ports": [80, 8443]}, "status": "new"});
+        let options = CompareOptions::default();
+        let record = diff_at(&a, &b, "/spec/replicas", &options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.diff_type, DiffType::Mismatch);
+        assert_eq!(record.path.to_pointer(), "/spec/replicas");
+        assert_eq!(record.values, DiffValues::Pair(json!(2), json!(3)));
+        let record = diff_at(&a, &b, "/spec/ports/1", &options).unwrap().unwrap();
+        assert_eq!(
+            record.to_string(),
+            "Mismatched: .spec.ports.[1].(443 != 8443)"
+        );
+        assert_eq!(diff_at(&a, &b, "/spec/ports/0", &options).unwrap(), None);
+        let err = diff_at(&a, &b, "spec", &options).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::InvalidPointer);
+    }
+
+    #[test]
+    fn diff_at_reports_one_sided_and_container_values() {
+        let a = json!({"spec": {"limits": {"cpu": 1, "memory": 2}, "env": [{"k": "a"}]}});
+        let b =
+            json!({"spec": {"limits": {"cpu": 2, "memory": 4}, "env": [{"k": "b"}]}, "new": [1]});
+        let options = CompareOptions::default();
+        let record = diff_at(&a, &b, "/new", &options).unwrap().unwrap();
+        assert_eq!(record.to_string(), "Extra on right: .new.([1])");
+        let record = diff_at(&b, &a, "/new/0", &options).unwrap().unwrap();
+        assert_eq!(record.to_string(), "Extra on left: .new.[0].(1)");
+        assert_eq!(diff_at(&a, &b, "/gone", &options).unwrap(), None);
+
+        // a container differing in one place reports that diff, in several places both values
+        let record = diff_at(&a, &b, "/spec/env", &options).unwrap().unwrap();
+        assert_eq!(
+            record.to_string(),
+            r#"Mismatched: .spec.env.[0].k.("a" != "b")"#
+        );
+        let record = diff_at(&a, &b, "/spec/limits", &options).unwrap().unwrap();
+        assert_eq!(
+            record.to_string(),
+            r#"Mismatched: .spec.limits.({"cpu":1,"memory":2} != {"cpu":2,"memory":4})"#
+        );
+        let redacted = CompareOptions::default().no_value_capture(true);
+        let record = diff_at(&a, &b, "/spec/limits", &redacted).unwrap().unwrap();
+        assert_eq!(
+            record.values,
+            DiffValues::Redacted(ValueKind::Object(2), ValueKind::Object(2))
+        );
+        assert_eq!(diff_at(&a, &a, "/spec", &options).unwrap(), None);
+    }
+
+    #[test]
+    fn null_members_equal_missing_ones_when_enabled() {
+        let (left, right) = (json!({"a": null, "b": 1}), json!({"b": 1}));
+        let diffs = compare_serde_values(&left, &right, false, &[]).unwrap();
+        assert_eq!(diff_strings(&diffs), vec!["Extra on left: .a.(null)"]);
+        let options = CompareOptions::default().null_equals_missing(true);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert!(diffs.is_empty());
+
+        let left = json!({"outer": {"a": null, "list": [{"id": 1, "x": null}, null]}});
+        let right = json!({"outer": {"list": [{"id": 1, "y": null}, {"z": 0}]}, "b": null});
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        // null elements of arrays are values, and non-null members are still reported
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![r#"Mismatched: .outer.list.[1].(null != {"z":0})"#]
+        );
+        let diffs = compare_serde_values_with_options(&right, &left, &options).unwrap();
+        assert_eq!(diffs.all_diffs().len(), 1);
+    }
+
+    #[test]
+    fn per_side_ignores_treat_keys_as_absent_on_the_ignoring_side() {
+        let legacy = || vec![Regex::new("^legacy$").unwrap()];
+        let options = CompareOptions::default().ignore_keys_left(legacy());
+        let cases = [
+            (
+                json!({"legacy": 1}),
+                json!({"legacy": 1}),
+                vec!["Extra on right: .legacy.(1)"],
+            ),
+            (json!({"legacy": 1}), json!({}), vec![]),
+            (
+                json!({}),
+                json!({"legacy": 2}),
+                vec!["Extra on right: .legacy.(2)"],
+            ),
+            (json!({"a": 1}), json!({"a": 1}), vec![]),
+        ];
+        for (left, right, expected) in cases {
+            let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+            assert_eq!(diff_strings(&diffs), expected, "{left} vs {right}");
+        }
+
+        let options = CompareOptions::default().ignore_keys_right(legacy());
+        let diffs = compare_serde_values_with_options(
+            &json!({"legacy": 1}),
+            &json!({"legacy": 1}),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(diff_strings(&diffs), vec!["Extra on left: .legacy.(1)"]);
+    }
+
+    #[test]
+    fn per_side_ignores_default_to_the_shared_list() {
+        let options = CompareOptions::default()
+            .ignore_keys(vec![Regex::new("^(old|new)$").unwrap()])
+            .ignore_keys_right(vec![Regex::new("^new$").unwrap()]);
+        let left = json!({"old": 1, "new": 1, "v": 1});
+        let right = json!({"old": 2, "v": 1});
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert_eq!(diff_strings(&diffs), vec!["Extra on right: .old.(2)"]);
+
+        let options = options.sort_arrays(true).collect_counts(true);
+        let left = json!([{"id": 2, "old": 9}, {"id": 1, "old": 8}]);
+        let right = json!([{"id": 1, "new": 7}, {"id": 2, "new": 6}]);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert!(diffs.is_empty(), "{:?}", diff_strings(&diffs));
+        let counts = diffs.usage.leaves.unwrap();
+        assert_eq!((counts.left, counts.right, counts.equal), (2, 2, 2));
+    }
+
+    #[test]
+    fn lookalike_keys_are_reported() {
+        let left = json!({"user": {"name\u{200b}": "a", "first\u{a0}name": "b", "cafe\u{301}": 1}});
+        let right = json!({"user": {"name": "a", "first name": "b", "caf\u{e9}": 2}});
+        let diffs = compare_serde_values(&left, &right, false, &[]).unwrap();
+        let records: Vec<String> = diffs
+            .records_sorted()
+            .iter()
+            .map(|r| r.to_string())
+            .collect();
+        assert!(
+            records.contains(&r#"Key almost equal: .user.("name\u{200b}" ≈ "name")"#.to_string())
+        );
+        assert!(records.contains(
+            &r#"Key almost equal: .user.("first\u{a0}name" ≈ "first name")"#.to_string()
+        ));
+        assert!(
+            records.contains(&r#"Key almost equal: .user.("cafe\u{301}" ≈ "café")"#.to_string())
+        );
+        // the keys are still extras, with invisible characters escaped in their paths
+        assert!(records.contains(&r#"Extra on left: .user.name\u{200b}.("a")"#.to_string()));
+        assert!(records.contains(&r#"Extra on right: .user.name.("a")"#.to_string()));
+        assert_eq!(diffs.count_diffs().left_only, 3);
+
+        let options = CompareOptions::default().match_lookalike_keys(true);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec!["Mismatched: .user.cafe\\u{301}.(1 != 2)"]
+        );
+        assert_eq!(diffs.extra_records.len(), 3);
+        let equal = json!({"name": 1});
+        let diffs =
+            compare_serde_values_with_options(&json!({"name\u{feff}": 1}), &equal, &options)
+                .unwrap();
+        assert!(diffs.is_empty());
+        assert_eq!(diffs.extra_records[0].diff_type, DiffType::KeyAlmostEqual);
+    }
+
+    #[test]
+    fn config_keys_match_by_canonical_form() {
+        let options = CompareOptions::default().canonical_keys(true);
+        let spellings = [
+            "max_connections",
+            "maxConnections",
+            "MAX_CONNECTIONS",
+            "max-connections",
+        ];
+        for left_key in spellings {
+            for right_key in spellings.iter().filter(|key| **key != left_key) {
+                let (left, right) = (json!({ left_key: 10 }), json!({ *right_key: 20 }));
+                let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+                assert_eq!(
+                    diff_strings(&diffs),
+                    vec![format!("Mismatched: .{left_key}.(10 != 20)")]
+                );
+                assert_eq!(
+                    diffs.extra_records[0].values,
+                    DiffValues::KeyVariants {
+                        left: left_key.to_string(),
+                        right: right_key.to_string()
+                    }
+                );
+                assert!(diffs.warnings.is_empty());
+            }
+        }
+        let same = json!({"max_connections": 10});
+        let diffs = compare_serde_values_with_options(&same, &same, &options).unwrap();
+        assert!(diffs.is_empty() && diffs.extra_records.is_empty());
+    }
+
+    #[test]
+    fn canonical_keys_keep_genuine_extras() {
+        let options = CompareOptions::default().canonical_keys(true);
+        let left = json!({"maxConnections": 10, "timeout": 5});
+        let right = json!({"max_connections": 10, "time_out_ms": 5});
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![
+                "Extra on left: .timeout.(5)",
+                "Extra on right: .time_out_ms.(5)"
+            ]
+        );
+        assert_eq!(diffs.extra_records.len(), 1);
+        let diffs = compare_serde_values(&left, &right, false, &[]).unwrap();
+        assert_eq!(diffs.count_diffs().left_only, 2);
+    }
+
+    #[test]
+    fn object_sets_compare_as_their_keys() {
+        let options = CompareOptions::default()
+            .coerce_object_set("/tags")
+            .record_origins(true);
+        let compare = |left: Value, right: Value| {
+            compare_serde_values_with_options(&left, &right, &options).unwrap()
+        };
+        let set = json!({"tags": {"b": true, "a": true}});
+        assert!(compare(set.clone(), json!({"tags": ["a", "b"]})).is_empty());
+        assert!(compare(json!({"tags": {"a": null}}), json!({"tags": ["a"]})).is_empty());
+        assert!(compare(json!({"tags": ["a", "b"]}), set.clone()).is_empty());
+
+        let diffs = compare(set.clone(), json!({"tags": ["a", "b", "c"]}));
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![r#"Extra on right: .tags.[2].("c")"#]
+        );
+        let diffs = compare(
+            json!({"tags": {"a": null, "b": null}}),
+            json!({"tags": ["a"]}),
+        );
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![r#"Extra on left: .tags.[1].("b")"#]
+        );
+        assert_eq!(diffs.records()[0].origin, Some(Origin::ObjectSet));
+
+        // a value other than true is data, not membership
+        let diffs = compare(
+            json!({"tags": {"a": true, "b": false}}),
+            json!({"tags": ["a", "b"]}),
+        );
+        assert_eq!(
+            diffs.records()[0].to_string(),
+            "Type changed: .tags.(object != array)"
+        );
+        assert_eq!(diffs.records()[0].origin, Some(Origin::ObjectKey));
+        // only the configured pointer is coerced
+        let diffs = compare(json!({"other": {"a": true}}), json!({"other": ["a"]}));
+        assert_eq!(diffs.records().len(), 1);
+    }
+
+    #[test]
+    fn canonical_key_collisions_are_warned_about() {
+        let options = CompareOptions::default().canonical_keys(true);
+        let left = json!({"db": {"max_connections": 10, "maxConnections": 20}});
+        let right = json!({"db": {"MAX_CONNECTIONS": 10}});
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        // ambiguous keys are only matched exactly
+        assert_eq!(diffs.count_diffs().left_only, 2);
+        assert_eq!(diffs.count_diffs().right_only, 1);
+        assert_eq!(
+            diffs.warnings,
+            vec![Warning::CanonicalKeyCollision {
+                path: DiffPath(vec![OwnedPathElement::Object("db".to_string())]),
+                side: Side::Left,
+                keys: vec!["max_connections".to_string(), "maxConnections".to_string()],
+            }]
+        );
+        assert_eq!(
+            diffs.warnings[0].to_string(),
+            r#"Keys "max_connections", "maxConnections" on the left share a canonical form, they are only matched exactly"#
+        );
+    }
+
+    #[test]
+    fn distinct_keys_are_no_lookalikes() {
+        let diffs = compare_serde_values(&json!({"cafe": 1}), &json!({"caf\u{e9}": 1}), false, &[])
+            .unwrap();
+        assert!(diffs.extra_records.is_empty());
+        assert_eq!(diffs.count_diffs().left_only, 1);
+    }
+
+    #[test]
+    fn fingerprint_first_reports_only_changed_elements_of_shuffled_arrays() {
+        let item = |i: usize| json!({"name": format!("item {i}"), "size": i, "tags": ["t"]});
+        let left = json!({"items": (0..100).map(item).collect::<Vec<_>>()});
+        let shuffled: Vec<Value> = (0..100)
+            .map(|i| match i * 37 % 100 {
+                changed @ (13 | 71) => {
+                    json!({"name": format!("item {changed}"), "size": 0, "tags": ["t"]})
+                }
+                i => item(i),
+            })
+            .collect();
+        let right = json!({ "items": shuffled });
+        let options = CompareOptions::default().array_mode("/items", ArrayMode::FingerprintFirst);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![
+                "Mismatched: .items.[13].size.(13 != 0)",
+                "Mismatched: .items.[71].size.(71 != 0)"
+            ]
+        );
+        let sequence = compare_serde_values(&left, &right, false, &[]).unwrap();
+        assert!(sequence.all_diffs().len() > 2);
+    }
+
+    #[test]
+    fn fingerprints_ignore_ignored_keys() {
+        let left = json!([{"n": 1, "at": 1}, {"n": 2, "at": 1}, {"n": 3}]);
+        let right = json!([{"n": 3}, {"n": 2, "at": 9}, {"n": 1, "at": 5}, {"n": 4, "x": [1, 2]}]);
+        let options = CompareOptions::default()
+            .array_mode("", ArrayMode::FingerprintFirst)
+            .ignore_key_patterns(["^at$"])
+            .build()
+            .unwrap();
+        let diffs = options.compare(&left, &right).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![r#"Extra on right: .[3].({"n":4,"x":[1,2]})"#]
+        );
+        let options = CompareOptions::default()
+            .array_mode("", ArrayMode::FingerprintFirst)
+            .record_origins(true);
+        let diffs =
+            compare_serde_values_with_options(&json!([1, 2]), &json!([3, 1]), &options).unwrap();
+        assert_eq!(diffs.records()[0].origin, Some(Origin::ArrayFingerprint));
+    }
+
+    /// `b` rebuilt from the elements of `a` and the elements `script` takes from `b`,
+    /// checking that the script covers `a` in order.
+    fn apply_edit_script(a: &[Value], b: &[Value], script: &[EditOp]) -> Vec<Value> {
+        let mut rebuilt = Vec::new();
+        let mut next_a = 0;
+        for op in script {
+            let (a_start, a_len) = match *op {
+                EditOp::Equal {
+                    a_start,
+                    b_start,
+                    len,
+                } => {
+                    assert_eq!(a[a_start..a_start + len], b[b_start..b_start + len]);
+                    rebuilt.extend_from_slice(&a[a_start..a_start + len]);
+                    (a_start, len)
+                }
+                EditOp::Insert { b_start, len } => {
+                    rebuilt.extend_from_slice(&b[b_start..b_start + len]);
+                    (next_a, 0)
+                }
+                EditOp::Delete { a_start, len } => (a_start, len),
+                EditOp::Replace {
+                    a_start,
+                    a_len,
+                    b_start,
+                    b_len,
+                } => {
+                    rebuilt.extend_from_slice(&b[b_start..b_start + b_len]);
+                    (a_start, a_len)
+                }
+            };
+            assert_eq!(a_start, next_a, "{script:?}");
+            next_a += a_len;
+        }
+        assert_eq!(next_a, a.len());
+        rebuilt
+    }
+
+    #[test]
+    fn edit_scripts_rebuild_the_right_array() {
+        let cases = [
+            (json!([]), json!([1, 2])),
+            (json!([1, 2, 3]), json!([])),
+            (json!([1, 2, 3, 4, 5]), json!([0, 1, 3, 4, 6, 7])),
+            (
+                json!(["a", {"b": 1}, [2]]),
+                json!([{"b": 2}, "a", [2], null]),
+            ),
+        ];
+        for (a, b) in cases {
+            let (a, b) = (a.as_array().unwrap(), b.as_array().unwrap());
+            let script = array_edit_script(a, b, &CompareOptions::default());
+            assert_eq!(&apply_edit_script(a, b, &script), b, "{script:?}");
+        }
+    }
+
+    #[test]
+    fn edit_scripts_compare_the_prepared_arrays() {
+        let (a, b) = (json!([3, 1, 2]), json!([1, 2, 3, 4]));
+        let (a, b) = (a.as_array().unwrap(), b.as_array().unwrap());
+        let sorted = CompareOptions::default().sort_arrays(true);
+        assert_eq!(
+            array_edit_script(a, b, &sorted),
+            vec![
+                EditOp::Equal {
+                    a_start: 0,
+                    b_start: 0,
+                    len: 3
+                },
+                EditOp::Insert { b_start: 3, len: 1 }
+            ]
+        );
+        assert_eq!(array_edit_script(a, b, &CompareOptions::default()).len(), 3);
+    }
+
+    fn matrix(tolerance: f64, max_cells: Option<usize>) -> CompareOptions {
+        let mode = ArrayMode::Matrix {
+            tolerance,
+            max_cells,
+        };
+        CompareOptions::default()
+            .array_mode("/m", mode)
+            .record_origins(true)
+    }
+
+    #[test]
+    fn matrix_reports_changed_cells() {
+        let left = json!({"m": [[1, 2, 3], [4, 5, 6], [7, 8, 9]], "v": [[1], [2]]});
+        let right = json!({"m": [[1, 2, 3], [4, 0, 6], [7, 8, 9]], "v": [[2]]});
+        let diffs = compare_serde_values_with_options(&left, &right, &matrix(0.0, None)).unwrap();
+        // arrays at other paths are compared as sequences
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![
+                "Extra on left: .v.[0].([1])",
+                "Mismatched: .m.[1].[1].(5 != 0)"
+            ]
+        );
+        let cell = diffs
+            .records()
+            .into_iter()
+            .find(|r| r.diff_type == DiffType::Mismatch)
+            .unwrap();
+        assert_eq!(cell.origin, Some(Origin::MatrixCell));
+
+        let left = json!({"m": [[1.0, 2.0], [3.0, 4.0]]});
+        let right = json!({"m": [[1.05, 2.0], [3.0, 4.2]]});
+        let diffs = compare_serde_values_with_options(&left, &right, &matrix(0.1, None)).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec!["Mismatched: .m.[1].[1].(4.0 != 4.2)"]
+        );
+    }
+
+    #[test]
+    fn matrix_dimensions_must_match() {
+        let left = json!({"m": [[1, 2], [3, 4]]});
+        let right = json!({"m": [[1, 2, 0], [3, 4, 0]]});
+        let diffs = compare_serde_values_with_options(&left, &right, &matrix(0.0, None)).unwrap();
+        assert!(diffs.all_diffs().is_empty());
+        let records: Vec<String> = diffs.records().iter().map(|r| r.to_string()).collect();
+        assert_eq!(records, vec!["Dimensions differ: .m.([2,2] != [2,3])"]);
+        assert_eq!(diffs.count_diffs().unequal_values, 1);
+
+        // ragged arrays are no matrices and compared as sequences
+        let right = json!({"m": [[1, 2], [3]]});
+        let diffs = compare_serde_values_with_options(&left, &right, &matrix(0.0, None)).unwrap();
+        assert_eq!(diff_strings(&diffs), vec!["Extra on left: .m.[1].[1].(4)"]);
+    }
+
+    #[test]
+    fn matrix_caps_reported_cells() {
+        let left = json!({"m": [[0, 0, 0], [0, 0, 0]]});
+        let right = json!({"m": [[1, 1, 1], [1, 1, 0]]});
+        let diffs =
+            compare_serde_values_with_options(&left, &right, &matrix(0.0, Some(2))).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![
+                "Mismatched: .m.[0].[0].(0 != 1)",
+                "Mismatched: .m.[0].[1].(0 != 1)"
+            ]
+        );
+        let summary = diffs.extra_records.last().unwrap();
+        assert_eq!(summary.to_string(), "Mismatched: .m.(... 3 more)");
+        assert_eq!(diffs.count_diffs().unequal_values, 5);
+    }
+
+    fn sampled(seed: u64) -> CompareOptions {
+        CompareOptions::default().sample(SampleSpec {
+            seed,
+            array_stride: 10,
+            max_keys_per_object: Some(5),
+        })
+    }
+
+    fn wide_object(value: impl Fn(usize) -> Value) -> Value {
+        Value::Object((0..40).map(|i| (format!("key{i}"), value(i))).collect())
+    }
+
+    #[test]
+    fn sampling_is_deterministic_per_seed() {
+        let (left, right) = (wide_object(|_| json!(0)), wide_object(|_| json!(1)));
+        let sampled_keys = |seed| {
+            let diffs = compare_serde_values_with_options(&left, &right, &sampled(seed)).unwrap();
+            assert!(diffs.is_partial());
+            assert_eq!(diffs.usage.skipped_by_sampling, 35);
+            diff_strings(&diffs)
+        };
+        let first = sampled_keys(7);
+        assert_eq!(first.len(), 5);
+        assert_eq!(first, sampled_keys(7));
+        assert_ne!(first, sampled_keys(8));
+
+        let none = CompareOptions::default().sample(SampleSpec {
+            seed: 7,
+            array_stride: 1,
+            max_keys_per_object: Some(0),
+        });
+        let diffs = compare_serde_values_with_options(&left, &right, &none).unwrap();
+        assert!(diffs.is_partial() && diffs.is_empty_in_sample());
+        assert_eq!(diffs.usage.skipped_by_sampling, 40);
+    }
+
+    #[test]
+    fn diffs_inside_the_sample_are_found() {
+        let left =
+            json!({"list": (0..100).collect::<Vec<_>>(), "object": wide_object(|_| json!(0))});
+        let mut right = left.clone();
+        right["list"][30] = json!("changed");
+        right["list"][31] = json!("missed");
+        let diffs = compare_serde_values_with_options(&left, &right, &sampled(3)).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![r#"Mismatched: .list.[30].(30 != "changed")"#]
+        );
+        assert!(!diffs.is_empty());
+        assert!(!diffs.is_empty_in_sample());
+        assert_eq!(diffs.usage.skipped_by_sampling, 90 + 35);
+
+        // a key of the sample is found, whichever it is
+        let probe = compare_serde_values_with_options(
+            &wide_object(|_| json!(0)),
+            &wide_object(|_| json!(1)),
+            &sampled(3),
+        )
+        .unwrap();
+        let (_, entry) = &probe.all_diffs()[0];
+        let PathElement::Object(key) = entry.path[0] else {
+            panic!("expected a key, got {entry}");
+        };
+        let mut right = left.clone();
+        right["object"][key] = json!(2);
+        let diffs = compare_serde_values_with_options(&left, &right, &sampled(3)).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![format!("Mismatched: .object.{key}.(0 != 2)")]
+        );
+
+        let diffs = compare_serde_values_with_options(&left, &left, &sampled(3)).unwrap();
+        assert!(diffs.is_empty_in_sample() && !diffs.is_empty());
+        // small documents are compared fully
+        let diffs =
+            compare_serde_values_with_options(&json!([1]), &json!([1]), &sampled(3)).unwrap();
+        assert!(!diffs.is_partial() && diffs.is_empty());
+    }
+
+    #[test]
+    fn float_tolerance_skips_pairs_within_it() {
+        let options = CompareOptions::default().float_tolerance(0.0, 1e-9);
+        let left = json!({"sum": 0.30000000000000004, "items": [1.0, 2.5], "count": 3});
+        let right = json!({"sum": 0.3, "items": [1.0000000001, 2.6], "count": 3});
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        let diffs: Vec<String> = diffs
+            .all_diffs()
+            .iter()
+            .map(|(_, entry)| entry.to_string())
+            .collect();
+        assert_eq!(diffs, [".items.[1].(2.5 != 2.6)"]);
+        let invalid = CompareOptions::default().float_tolerance(f64::NAN, 0.0);
+        assert_eq!(
+            invalid.validate().unwrap_err(),
+            vec![OptionConflict::InvalidFloatTolerance]
+        );
+    }
+
+    /// Renderings of the diffs of `left` and `right` compared with `options`.
+    fn rendered_diffs(left: &Value, right: &Value, options: &CompareOptions) -> Vec<String> {
+        compare_serde_values_with_options(left, right, options)
+            .unwrap()
+            .all_diffs()
+            .iter()
+            .map(|(_, entry)| entry.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn ignored_pointers_exclude_array_index_subtrees() {
+        let left = json!({"items": [{"v": 1, "tags": [1]}, {"v": 2, "tags": [2]}, 3]});
+        let right = json!({"items": [{"v": 5, "tags": [1]}, {"v": 6, "tags": [7, 8]}]});
+        let options = CompareOptions::default().ignore_pointer("/items/1");
+        assert_eq!(
+            rendered_diffs(&left, &right, &options),
+            [".items.[0].v.(1 != 5)", ".items.[2].(3)"]
+        );
+        // extras at excluded indices are dropped as well
+        let options = options
+            .ignore_pointer("/items/2")
+            .ignore_pointer("/items/0/v");
+        assert!(rendered_diffs(&left, &right, &options).is_empty());
+        assert_eq!(
+            CompareOptions::default().ignore_pointer("items").validate(),
+            Err(vec![OptionConflict::InvalidIgnorePointer("items".into())])
+        );
+    }
 
-        assert_eq!(diffs.len(), 1);
+    #[test]
+    fn ignored_paths_exclude_keys_under_a_specific_parent() {
+        let left = json!({"id": 1, "metadata": {"id": 2, "at": 3}, "items": [{"id": 4, "n": 5}]});
+        let right = json!({"id": 6, "metadata": {"id": 7}, "items": [{"id": 8, "n": 9}]});
+        let options = CompareOptions::default()
+            .ignore_path_patterns([r"^\.items\.\[\d+\]\.id$"])
+            .ignore_pointer("/metadata/at")
+            .build()
+            .unwrap();
+        let diffs = |options: &CompareOptions| rendered_diffs(&left, &right, options);
         assert_eq!(
-            diffs.first().unwrap().to_string(),
-            r#".[0].c.[1].("f" != "e")"#
+            diffs(options.options()),
+            [
+                ".id.(1 != 6)",
+                ".items.[0].n.(5 != 9)",
+                ".metadata.id.(2 != 7)"
+            ]
+        );
+        let options = options
+            .options()
+            .clone()
+            .ignore_key_patterns(["^n$"])
+            .build()
+            .unwrap();
+        assert_eq!(
+            diffs(options.options()),
+            [".id.(1 != 6)", ".metadata.id.(2 != 7)"]
         );
     }
 
     #[test]
-    fn test_arrays_simple_diff() {
-        let data1 = r#"["a","b","c"]"#;
-        let data2 = r#"["a","b","d"]"#;
-        let diff = compare_strs(data1, data2, false, &[]).unwrap();
-        assert_eq!(diff.left_only, DiffTreeNode::Null);
-        assert_eq!(diff.right_only, DiffTreeNode::Null);
-        let diff = diff.unequal_values.get_diffs();
-        assert_eq!(diff.len(), 1);
-        assert_eq!(diff.first().unwrap().to_string(), r#".[2].("c" != "d")"#);
+    fn decimal_places_apply_globally_and_per_path() {
+        let left = json!({
+            "total": 10.004,
+            "rates": {"eur": 1.0851, "usd": 10.004},
+            "items": [{"price": 2.5}, {"price": 9007199254740993u64}]
+        });
+        let right = json!({
+            "total": 10.0049,
+            "rates": {"eur": 1.0859, "usd": 10.006},
+            "items": [{"price": 2.504}, {"price": 9007199254740992u64}]
+        });
+        let options = CompareOptions::default()
+            .decimal_places(2)
+            .decimal_places_at("/rates", 3)
+            .decimal_places_at("/rates/usd", 1);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec![
+                "Mismatched: .items.[1].price.(9007199254740993 != 9007199254740992)",
+                "Mismatched: .rates.eur.(1.0851 != 1.0859)",
+            ]
+        );
+        let exact = compare_serde_values(&left, &right, false, &[]).unwrap();
+        assert_eq!(exact.all_diffs().len(), 5);
+        // numbers in strings stay strings
+        let strings = compare_serde_values_with_options(&json!("1.001"), &json!("1.0"), &options);
+        assert!(!strings.unwrap().is_empty());
     }
 
     #[test]
-    fn test_arrays_more_complex_diff() {
-        let data1 = r#"["a","b","c"]"#;
-        let data2 = r#"["a","a","b","d"]"#;
-        let diff = compare_strs(data1, data2, false, &[]).unwrap();
-
-        let changes_diff = diff.unequal_values.get_diffs();
-        assert_eq!(diff.left_only, DiffTreeNode::Null);
+    fn array_context_anchors_changes_deep_inside_long_arrays() {
+        let log: Vec<Value> = (0..1000)
+            .map(|seq| json!({"seq": seq, "user": "a"}))
+            .collect();
+        let mut changed = log.clone();
+        changed[600]["user"] = json!("b");
+        changed.insert(801, json!({"seq": "late"}));
+        let (left, right) = (json!({"log": log}), json!({"log": changed}));
+        let options = CompareOptions::default().array_context(3);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        let records = diffs.records_sorted();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].path.to_string(), ".log.[600].user");
+        assert_eq!(
+            records[0].context,
+            Some(ArrayContext {
+                before: vec![(597, 597), (598, 598), (599, 599)],
+                after: vec![(601, 601), (602, 602), (603, 603)],
+            })
+        );
+        // the extra element is indexed into the right array
+        assert_eq!(records[1].path.to_string(), ".log.[801]");
+        assert_eq!(
+            records[1].context.as_ref().unwrap().to_string(),
+            "equal before 798/798, 799/799, 800/800; equal after 801/802, 802/803, 803/804"
+        );
+        let plain = compare_serde_values_with_options(&left, &right, &CompareOptions::default());
+        assert!(plain.unwrap().records().iter().all(|r| r.context.is_none()));
+        let edge = compare_serde_values_with_options(&json!([1, 2]), &json!([0, 2]), &options);
+        let context = edge.unwrap().records()[0].context.clone().unwrap();
+        assert_eq!((context.before, context.after), (vec![], vec![(1, 1)]));
+    }
 
-        assert_eq!(changes_diff.len(), 1);
+    #[test]
+    fn container_swaps_are_type_changes() {
+        let root = compare_serde_values(&json!({}), &json!([]), false, &[]).unwrap();
+        assert!(!root.is_empty());
+        let records = root.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].diff_type, DiffType::TypeChange);
+        assert_eq!(records[0].to_string(), "Type changed: .(object != array)");
+        // the entries keep the category of their tree
+        assert_eq!(root.all_diffs()[0].0, DiffType::Mismatch);
+        assert_eq!(root.all_diffs_with_type_changes()[0].0, DiffType::TypeChange);
+        // contents are not dumped and sorting does not change the classification
+        let left = json!({"config": {"a": [1, 2]}, "list": [], "value": {}});
+        let right = json!({"config": [{"a": 1}], "list": {}, "value": null});
+        let nested = compare_serde_values(&left, &right, true, &[]).unwrap();
+        let lines: Vec<String> = nested
+            .records_sorted()
+            .iter()
+            .map(|r| r.to_string())
+            .collect();
         assert_eq!(
-            changes_diff.first().unwrap().to_string(),
-            r#".[2].("c" != "d")"#
+            lines,
+            vec![
+                "Type changed: .config.(object != array)",
+                "Type changed: .list.(array != object)",
+                "Mismatched: .value.({} != null)",
+            ]
         );
-        let insertions = diff.right_only.get_diffs();
-        assert_eq!(insertions.len(), 1);
-        assert_eq!(insertions.first().unwrap().to_string(), r#".[0].("a")"#);
+        // empty containers are not the same as a missing or null value either
+        let options = CompareOptions::default().wildcard_values(vec![json!(null)]);
+        let empty =
+            compare_serde_values_with_options(&json!({"a": {}}), &json!({"a": []}), &options);
+        assert_eq!(empty.unwrap().records()[0].diff_type, DiffType::TypeChange);
+        let missing = compare_serde_values(&json!({"a": {}}), &json!({}), false, &[]).unwrap();
+        assert_eq!(missing.records()[0].diff_type, DiffType::LeftExtra);
     }
 
     #[test]
-    fn test_arrays_extra_left() {
-        let data1 = r#"["a","b","c"]"#;
-        let data2 = r#"["a","b"]"#;
-        let diff = compare_strs(data1, data2, false, &[]).unwrap();
+    fn file_errors_carry_their_file_and_side() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (valid, broken) = (
+            dir.path().join("valid.json"),
+            dir.path().join("broken.json"),
+        );
+        std::fs::write(&valid, r#"{"a": 1}"#).unwrap();
+        std::fs::write(&broken, r#"{"a": "#).unwrap();
+        let options = CompareOptions::default();
+        assert!(compare_files(&valid, &valid, &options).unwrap().is_empty());
 
-        let diffs = diff.left_only.get_diffs();
-        assert_eq!(diffs.len(), 1);
-        assert_eq!(diffs.first().unwrap().to_string(), r#".[2].("c")"#);
-        assert_eq!(diff.unequal_values, DiffTreeNode::Null);
-        assert_eq!(diff.right_only, DiffTreeNode::Null);
+        let err = compare_files(&valid, &broken, &options).unwrap_err();
+        assert_eq!(err.kind(), ErrorCode::ParseRight);
+        let context = ErrorContext::default()
+            .file(broken.display().to_string())
+            .side(Side::Right);
+        assert_eq!(err.context(), Some(&context));
+        assert!(matches!(err.without_context(), Error::ParseRight(_)));
+
+        let missing = dir.path().join("missing.json");
+        let err = compare_files(&missing, &valid, &options).unwrap_err();
+        assert_eq!(err.kind(), ErrorCode::Io);
+        assert_eq!(err.file(), Some(missing.display().to_string().as_str()));
+        assert_eq!(err.side(), Some(Side::Left));
+
+        let err = compare_files(&valid, &valid, &options.unwrap_right("/data")).unwrap_err();
+        assert_eq!(err.file(), Some(valid.display().to_string().as_str()));
+        assert_eq!(
+            (err.side(), err.pointer()),
+            (Some(Side::Right), Some("/data"))
+        );
     }
 
     #[test]
-    fn test_arrays_extra_right() {
-        let data1 = r#"["a","b"]"#;
-        let data2 = r#"["a","b","c"]"#;
-        let diff = compare_strs(data1, data2, false, &[]).unwrap();
+    fn inputs_above_max_input_bytes_are_rejected() {
+        let options = CompareOptions::default().max_input_bytes(Some(8));
+        let comparator = options.clone().build().unwrap();
+        assert!(comparator
+            .compare_strs("[1, 2]", "[1, 2]")
+            .unwrap()
+            .is_empty());
+        let err = comparator
+            .compare_strs("[1, 2]", "[1, 2, 3, 4]")
+            .unwrap_err();
+        assert!(matches!(
+            err.without_context(),
+            Error::InputTooLarge {
+                actual: 12,
+                limit: 8
+            }
+        ));
+        assert_eq!(
+            (err.kind(), err.side()),
+            (ErrorCode::LimitExceeded, Some(Side::Right))
+        );
+        let err = comparator
+            .compare_slices(b"\"too long\"", b"1")
+            .unwrap_err();
+        assert_eq!(err.side(), Some(Side::Left));
 
-        let diffs = diff.right_only.get_diffs();
-        assert_eq!(diffs.len(), 1);
-        assert_eq!(diffs.first().unwrap().to_string(), r#".[2].("c")"#);
-        assert_eq!(diff.unequal_values, DiffTreeNode::Null);
-        assert_eq!(diff.left_only, DiffTreeNode::Null);
+        let dir = tempfile::TempDir::new().unwrap();
+        let (small, large) = (dir.path().join("small.json"), dir.path().join("large.json"));
+        std::fs::write(&small, "[]").unwrap();
+        std::fs::write(&large, "[1, 2, 3, 4]").unwrap();
+        let err = compare_files(&small, &large, &options).unwrap_err();
+        assert_eq!(err.kind(), ErrorCode::LimitExceeded);
+        assert_eq!(err.file(), Some(large.display().to_string().as_str()));
     }
 
     #[test]
-    fn long_insertion_modification() {
-        let data1 = r#"["a","b","a"]"#;
-        let data2 = r#"["a","c","c","c","a"]"#;
-        let diff = compare_strs(data1, data2, false, &[]).unwrap();
-        let diffs = diff.unequal_values.get_diffs();
+    fn tolerant_parsing_skips_byte_order_marks_and_prefixes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (bom, plain) = (dir.path().join("bom.json"), dir.path().join("plain.json"));
+        std::fs::write(&bom, b"\xEF\xBB\xBF\n {\"a\": [1]}").unwrap();
+        std::fs::write(&plain, r#"{"a": [1]}"#).unwrap();
+        let err = compare_files(&bom, &plain, &CompareOptions::default()).unwrap_err();
+        assert_eq!(err.kind(), ErrorCode::ParseLeft);
+        let tolerant =
+            CompareOptions::default().parse_mode(ParseMode::Tolerant { skip_prefix: false });
+        let diffs = compare_files(&bom, &plain, &tolerant).unwrap();
+        assert!(diffs.is_empty() && diffs.warnings.is_empty());
+        let logged = "2024-05-01 12:00:00 INFO response: {\"a\": [2]}";
+        let err = compare_strs_with_options(r#"{"a": [1]}"#, logged, &tolerant).unwrap_err();
+        assert_eq!(err.kind(), ErrorCode::ParseRight);
+        let skipping =
+            CompareOptions::default().parse_mode(ParseMode::Tolerant { skip_prefix: true });
+        let diffs = compare_strs_with_options(r#"{"a": [1]}"#, logged, &skipping).unwrap();
+        assert_eq!(diffs.records()[0].to_string(), "Mismatched: .a.[0].(1 != 2)");
+        assert_eq!(
+            diffs.warnings,
+            vec![Warning::SkippedPrefix {
+                side: Side::Right,
+                bytes: 35
+            }]
+        );
+        let documents = skipping.build().unwrap();
+        let diffs = documents
+            .compare_multi_document_strs("# export\n[1]\n[2]", "[1] [3]")
+            .unwrap();
+        assert_eq!(diffs[0].warnings.len(), 1);
+        assert!(diffs[0].is_empty() && !diffs[1].is_empty());
+    }
 
-        assert_eq!(diffs.len(), 3);
-        let diffs: Vec<_> = diffs.into_iter().map(|d| d.to_string()).collect();
+    #[test]
+    fn scalar_roots_fail_when_containers_are_required() {
+        let options = CompareOptions::default().require_container_roots(true);
+        let compare = |a: Value, b: Value, options: &CompareOptions| {
+            compare_serde_values_with_options(&a, &b, options)
+        };
+        let err = compare(json!("null"), json!({}), &options).unwrap_err();
+        assert_eq!(
+            (err.code(), err.side()),
+            (ErrorCode::UnexpectedRootType, Some(Side::Left))
+        );
+        assert!(matches!(
+            err,
+            Error::UnexpectedRootType {
+                found: ValueKind::String,
+                ..
+            }
+        ));
+        let err = compare(json!([1]), json!(1), &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The right document is a number, not an object or array"
+        );
+        // the left side is reported first
+        let err = compare(json!(true), json!(null), &options).unwrap_err();
+        assert_eq!(err.side(), Some(Side::Left));
+        assert!(compare(json!({"a": []}), json!([]), &options).is_ok());
+        let unguarded = CompareOptions::default();
+        assert!(compare(json!(1), json!(2), &unguarded).is_ok_and(|diffs| !diffs.is_empty()));
+        assert!(compare(json!("a"), json!("a"), &unguarded).is_ok_and(|diffs| diffs.is_empty()));
+    }
 
-        assert!(diffs.contains(&r#".[3].(null != "c")"#.to_string()));
-        assert!(diffs.contains(&r#".[1].("b" != "c")"#.to_string()));
-        assert!(diffs.contains(&r#".[2].("a" != "c")"#.to_string()));
-        assert_eq!(diff.right_only, DiffTreeNode::Null);
-        assert_eq!(diff.left_only, DiffTreeNode::Null);
+    #[test]
+    fn unwrapped_payloads_are_compared() {
+        let wrapped = json!({"data": {"id": 1, "items": [1, 2]}, "meta": {"page": 3}});
+        let bare = json!({"id": 1, "items": [1, 3]});
+        let options = CompareOptions::default().unwrap_left("/data");
+        let diffs = compare_serde_values_with_options(&wrapped, &bare, &options).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec!["Mismatched: .items.[1].(2 != 3)"]
+        );
+        let both = options.clone().unwrap_right("/data");
+        let rewrapped = json!({"data": bare});
+        let diffs = compare_serde_values_with_options(&wrapped, &rewrapped, &both).unwrap();
+        assert_eq!(
+            diff_strings(&diffs),
+            vec!["Mismatched: .items.[1].(2 != 3)"]
+        );
+        let err = compare_serde_values_with_options(&bare, &bare, &options).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::PointerNotFound);
+        assert_eq!(
+            err.to_string(),
+            "Cannot unwrap the left document at /data, nearest existing ancestor is the document root"
+        );
+        assert_eq!(
+            (err.side(), err.pointer()),
+            (Some(Side::Left), Some("/data"))
+        );
+        let context = ErrorContext::default().side(Side::Left).pointer("/data");
+        let missing = Error::UnwrapNotFound(Side::Left, "/data".to_string(), String::new());
+        assert_eq!(err, missing.with_context(context));
+        let fallback = options.unwrap_fallback(true);
+        let diffs = compare_serde_values_with_options(&bare, &bare, &fallback).unwrap();
+        assert!(diffs.is_empty());
+        assert_eq!(
+            diffs.warnings,
+            vec![Warning::UnwrapFallback {
+                side: Side::Left,
+                pointer: "/data".to_string()
+            }]
+        );
+        let invalid = CompareOptions::default().unwrap_right("data").build();
+        assert!(
+            matches!(invalid, Err(Error::InvalidOptions(c)) if c == vec![OptionConflict::InvalidUnwrapPointer("data".to_string())])
+        );
     }
 
     #[test]
-    fn test_arrays_object_extra() {
-        let data1 = r#"["a","b"]"#;
-        let data2 = r#"["a","b", {"c": {"d": "e"} }]"#;
-        let diff = compare_strs(data1, data2, false, &[]).unwrap();
+    fn must_differ_paths_assert_changes() {
+        use crate::mismatch::Verdict;
+        let options = CompareOptions::default()
+            .expect("/version", Expectation::MustDiffer)
+            .expect("/steps/1", Expectation::MustDiffer);
+        let left = json!({"version": 1, "steps": [{"a": 1}, {"b": 1}], "data": "x"});
 
-        let diffs = diff.right_only.get_diffs();
-        assert_eq!(diffs.len(), 1);
+        let mut right = left.clone();
+        right["version"] = json!(2);
+        right["steps"][1]["b"] = json!(2);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert_eq!(diffs.verdict(), Verdict::Pass);
+        assert!(diffs.records().iter().all(|r| diffs.is_expected(r)));
+
+        right["data"] = json!("y");
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert_eq!(diffs.verdict(), Verdict::Fail);
+
+        let diffs = compare_serde_values_with_options(&left, &left, &options).unwrap();
+        assert_eq!(diffs.verdict(), Verdict::Fail);
+        let records: Vec<String> = diffs
+            .records_sorted()
+            .iter()
+            .map(|r| r.to_string())
+            .collect();
         assert_eq!(
-            diffs.first().unwrap().to_string(),
-            r#".[2].({"c":{"d":"e"}})"#
+            records,
+            vec![
+                r#"Expected change missing: .steps.[1].({"b":1} != {"b":1})"#,
+                "Expected change missing: .version.(1 != 1)",
+            ]
         );
-        assert_eq!(diff.unequal_values, DiffTreeNode::Null);
-        assert_eq!(diff.left_only, DiffTreeNode::Null);
+        assert!(!diffs.is_empty());
+
+        // a path missing on both sides is unchanged as well
+        let diffs = compare_serde_values_with_options(&json!({}), &json!({}), &options).unwrap();
+        let records = diffs.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].to_string(), "Expected change missing: .version");
+        assert_eq!(records[1].path.to_string(), ".steps.1");
+        // a removed path is a change
+        let diffs = compare_serde_values_with_options(&json!({"version": 1}), &json!({}), &options)
+            .unwrap();
+        assert_eq!(diffs.records().len(), 2);
+        assert!(diffs.is_expected(&diffs.records()[0]));
     }
 
     #[test]
-    fn nested_diff() {
-        let data1 = r#"{
-            "a":"b",
-            "b":{
-                "c":{
-                    "d":true,
-                    "e":5,
-                    "f":9,
-                    "h":{
-                        "i":true,
-                        "j":false
-                    }
-                }
-            }
-        }"#;
-        let data2 = r#"{
-            "a":"b",
-            "b":{
-                "c":{
-                    "d":true,
-                    "e":6,
-                    "g":0,
-                    "h":{
-                        "i":false,
-                        "k":false
-                    }
-                }
-            }
-        }"#;
+    fn conflicting_options_fail_to_build() {
+        let matrix = |tolerance| ArrayMode::Matrix {
+            tolerance,
+            max_cells: None,
+        };
+        let cases = [
+            (
+                sampled(1).sort_arrays(true).report_order_differences(true),
+                vec![OptionConflict::SampledOrder],
+            ),
+            (
+                CompareOptions::default()
+                    .array_mode("m", matrix(0.0))
+                    .array_mode("/n", matrix(-1.0)),
+                vec![
+                    OptionConflict::InvalidArrayModePointer("m".to_string()),
+                    OptionConflict::InvalidTolerance("/n".to_string()),
+                ],
+            ),
+            (
+                CompareOptions::default()
+                    .array_mode("/m", matrix(0.0))
+                    .array_mode("/m", ArrayMode::Sequence)
+                    .array_mode("/m", matrix(1.0)),
+                vec![OptionConflict::AmbiguousArrayMode("/m".to_string())],
+            ),
+            (
+                sampled(1).verify_multiplicity(true).max_depth(0),
+                vec![
+                    OptionConflict::MultiplicityWithoutSorting,
+                    OptionConflict::SampledMultiplicity,
+                    OptionConflict::ZeroMaxDepth,
+                ],
+            ),
+        ];
+        for (options, expected) in cases {
+            assert_eq!(options.validate(), Err(expected));
+            let err =
+                compare_serde_values_with_options(&json!(1), &json!(1), &options).unwrap_err();
+            assert_eq!(err.code(), ErrorCode::InvalidOptions);
+        }
+        let err = sampled(1).max_depth(0).build().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid options: max_depth 0 rejects every input"
+        );
+    }
 
-        let expected_left = DiffTreeNode::Node(hashmap! {
-        "b".to_string() => DiffTreeNode::Node(hashmap! {
-                "c".to_string() => DiffTreeNode::Node(hashmap! {
-                        "f".to_string() => DiffTreeNode::Null,
-                        "h".to_string() => DiffTreeNode::Node( hashmap! {
-                                "j".to_string() => DiffTreeNode::Null,
-                            }
-                        ),
-                }
-                ),
-            }),
-        });
-        let expected_right = DiffTreeNode::Node(hashmap! {
-            "b".to_string() => DiffTreeNode::Node(hashmap! {
-                    "c".to_string() => DiffTreeNode::Node(hashmap! {
-                            "g".to_string() => DiffTreeNode::Null,
-                            "h".to_string() => DiffTreeNode::Node(hashmap! {
-                                    "k".to_string() => DiffTreeNode::Null,
-                                }
-                            )
-                        }
-                    )
-                }
-            )
+    #[test]
+    fn option_warnings_are_reported_with_the_diffs() {
+        let keys_only = SampleSpec {
+            seed: 1,
+            array_stride: 1,
+            max_keys_per_object: Some(5),
+        };
+        let options = CompareOptions::default()
+            .sample(keys_only)
+            .collect_counts(true)
+            .report_order_differences(true);
+        assert_eq!(options.validate(), Ok(()));
+        let diffs =
+            compare_serde_values_with_options(&json!([1, 2]), &json!([2, 1]), &options).unwrap();
+        let warnings: Vec<_> = diffs.warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "report_order_differences has no effect without sort_arrays",
+                "collect_counts only counts the sampled leaves",
+            ]
+        );
+        assert_eq!(diffs.all_diffs().len(), 2);
+    }
+
+    const SECRETS: [&str; 7] = [
+        "hunter2", "s3cr3t", "48151623", "tok-aaaa", "tok-bbbb", "99887766", "leftover",
+    ];
+
+    fn assert_no_secret(output: &str) {
+        for secret in SECRETS {
+            assert!(!output.contains(secret), "{secret} leaked into {output}");
+        }
+    }
+
+    #[test]
+    fn no_value_capture_keeps_values_out_of_the_result() {
+        let left = json!({
+            "password": "hunter2",
+            "pin": 48151623,
+            "tokens": ["tok-aaaa", "tok-aaaa", {"id": "tok-bbbb"}, {"id": "tok-bbbb"}],
+            "gone": {"note": "leftover"}
         });
-        let expected_uneq = DiffTreeNode::Node(hashmap! {
-            "b".to_string() => DiffTreeNode::Node(hashmap! {
-                    "c".to_string() => DiffTreeNode::Node(hashmap! {
-                            "e".to_string() => DiffTreeNode::Value(json!(5), json!(6)),
-                            "h".to_string() => DiffTreeNode::Node(hashmap! {
-                                    "i".to_string() => DiffTreeNode::Value(json!(true), json!(false)),
-                                }
-                            )
-                        }
-                    )
-                }
-            )
+        let right = json!({
+            "password": "s3cr3t",
+            "pin": 99887766,
+            "tokens": ["tok-aaaa", {"id": "tok-bbbb"}],
+            "added": [true]
         });
-        let expected = Mismatch::new(expected_left, expected_right, expected_uneq);
+        let options = CompareOptions::default()
+            .no_value_capture(true)
+            .verify_multiplicity(true)
+            .array_identity(|_path, element| Some(element.get("id")?.to_string()));
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert!(!diffs.warnings.is_empty());
+        let records: Vec<_> = diffs.records().iter().map(|r| r.to_string()).collect();
+        assert!(records.contains(&"Mismatched: .password.(values differ)".to_string()));
+        assert!(records.contains(&"Extra on right: .added.(values differ)".to_string()));
+        assert_no_secret(&records.join("\n"));
+        assert_no_secret(&diff_strings(&diffs).join("\n"));
+        assert_no_secret(&format!("{diffs:?}"));
+        assert_no_secret(&crate::patch::to_json_patch(&diffs).to_string());
+        let leaf = diffs.unequal_values.get_key("pin").unwrap();
+        assert_eq!(
+            leaf,
+            &DiffTreeNode::Redacted(ValueKind::Number, ValueKind::Number)
+        );
 
-        let mismatch = compare_strs(data1, data2, false, &[]).unwrap();
-        assert_eq!(mismatch, expected, "Diff was incorrect.");
+        let sorted = CompareOptions::default()
+            .no_value_capture(true)
+            .verify_multiplicity(true)
+            .sort_arrays(true);
+        let diffs = compare_serde_values_with_options(&left, &right, &sorted).unwrap();
+        assert!(diffs
+            .extra_records
+            .iter()
+            .any(|r| r.diff_type == DiffType::MultiplicityChange));
+        assert_no_secret(&format!("{diffs:?}"));
+
+        let documents = compare_multi_document_strs("1", r#"1 "hunter2""#, &options).unwrap();
+        assert_no_secret(&format!("{documents:?}"));
     }
 
     #[test]
-    fn no_diff() {
-        let data1 = r#"{
-            "a":"b",
-            "b":{
-                "c":{
-                    "d":true,
-                    "e":5,
-                    "f":9,
-                    "h":{
-                        "i":true,
-                        "j":false
-                    }
-                }
-            }
-        }"#;
-        let data2 = r#"{
-            "a":"b",
-            "b":{
-                "c":{
-                    "d":true,
-                    "e":5,
-                    "f":9,
-                    "h":{
-                        "i":true,
-                        "j":false
-                    }
-                }
-            }
-        }"#;
+    fn errors_never_contain_values() {
+        let options = CompareOptions::default()
+            .no_value_capture(true)
+            .key_normalizer(crate::normalize::snake_case);
+        let colliding = json!({"fooBar": "hunter2", "foo_bar": "s3cr3t"});
+        let err = compare_serde_values_with_options(&colliding, &colliding, &options).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::KeyCollision);
+        assert_no_secret(&format!("{err} {err:?}"));
 
+        let deep = json!([[[["tok-aaaa"]]]]);
+        let options = options.max_depth(3);
+        let err = compare_serde_values_with_options(&deep, &json!(1), &options).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::LimitExceeded);
+        assert_no_secret(&format!("{err} {err:?}"));
+
+        let err = insert_child_key_diff(
+            DiffTreeNode::leaf("hunter2", "s3cr3t"),
+            DiffTreeNode::Null,
+            0,
+        )
+        .and_then(|node| insert_child_key_diff(node, DiffTreeNode::leaf(48151623, 99887766), 1))
+        .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::Structure);
+        assert_no_secret(&format!("{err} {err:?}"));
+    }
+
+    fn diff_strings(mismatch: &Mismatch) -> Vec<String> {
+        let mut diffs: Vec<_> = mismatch
+            .all_diffs()
+            .iter()
+            .map(|(t, e)| format!("{t}: {e}"))
+            .collect();
+        diffs.sort();
+        diffs
+    }
+
+    fn assert_repro_keeps_diffs(left: &Value, right: &Value, options: &CompareOptions) {
+        let mismatch = compare_serde_values_with_options(left, right, options).unwrap();
+        let (repro_left, repro_right) = mismatch.minimal_repro(left, right);
+        let repro = compare_serde_values_with_options(&repro_left, &repro_right, options).unwrap();
+        assert_eq!(diff_strings(&mismatch), diff_strings(&repro));
+    }
+
+    #[test]
+    fn minimal_repro_of_nested_diff() {
+        let left: Value = serde_json::from_str(NESTED_LEFT).unwrap();
+        let right: Value = serde_json::from_str(NESTED_RIGHT).unwrap();
+        let mismatch = compare_serde_values(&left, &right, false, &[]).unwrap();
+        let (repro_left, repro_right) = mismatch.minimal_repro(&left, &right);
         assert_eq!(
-            compare_strs(data1, data2, false, &[]).unwrap(),
-            Mismatch::new(DiffTreeNode::Null, DiffTreeNode::Null, DiffTreeNode::Null)
+            repro_left,
+            json!({"b": {"c": {"e": 5, "f": 9, "h": {"i": true, "j": false}}}})
         );
+        assert_eq!(
+            repro_right,
+            json!({"b": {"c": {"e": 6, "g": 0, "h": {"i": false, "k": false}}}})
+        );
+        assert_repro_keeps_diffs(&left, &right, &CompareOptions::default());
     }
 
     #[test]
-    fn no_json() {
-        let data1 = r#"{}"#;
-        let data2 = r#"{}"#;
+    fn minimal_repro_of_arrays() {
+        let left = json!({
+            "unchanged": {"x": [1, 2, 3]},
+            "items": [{"id": 1, "tags": ["a", "b"]}, {"id": 2, "tags": ["c"]}, 4, 5],
+            "matrix": [[1, 2], [3, 4]]
+        });
+        let right = json!({
+            "unchanged": {"x": [1, 2, 3]},
+            "items": [5, {"id": 2, "tags": ["c", "d"]}, {"id": 1, "tags": ["b", "a"]}],
+            "matrix": [[4, 3], [2, 0]]
+        });
+        for sort_arrays in [false, true] {
+            let options = CompareOptions::default().sort_arrays(sort_arrays);
+            assert_repro_keeps_diffs(&left, &right, &options);
+        }
+        let mismatch = compare_serde_values(&left, &right, true, &[]).unwrap();
+        let (repro_left, _) = mismatch.minimal_repro(&left, &right);
+        assert!(repro_left.get("unchanged").is_none());
+    }
 
+    #[test]
+    fn minimal_repro_of_root_mismatch() {
+        let left = json!({"a": 1});
+        let right = json!([1]);
+        let mismatch = compare_serde_values(&left, &right, false, &[]).unwrap();
+        assert_eq!(mismatch.minimal_repro(&left, &right), (left, right));
+        let mismatch = Mismatch::empty();
         assert_eq!(
-            compare_strs(data1, data2, false, &[]).unwrap(),
-            Mismatch::empty()
+            mismatch.minimal_repro(&json!({"a": 1}), &json!({"a": 1})),
+            (Value::Null, Value::Null)
         );
     }
 
+    fn nested(depth: usize, leaf: Value) -> Value {
+        // built by hand, as json! would serialize the inner value recursively
+        (1..depth).fold(leaf, |inner, level| {
+            if level % 2 == 0 {
+                Value::Array(vec![inner, json!(level)])
+            } else {
+                Value::Object(Map::from_iter([("k".to_string(), inner)]))
+            }
+        })
+    }
+
     #[test]
-    fn parse_err_source_one() {
-        let invalid_json1 = r#"{invalid: json}"#;
-        let valid_json2 = r#"{"a":"b"}"#;
-        compare_strs(invalid_json1, valid_json2, false, &[])
-            .expect_err("Parsing invalid JSON didn't throw an error");
+    fn deep_nesting_within_limit() {
+        let options = CompareOptions::default().sort_arrays(true);
+        let a = nested(DEFAULT_MAX_DEPTH, json!(1));
+        let b = nested(DEFAULT_MAX_DEPTH, json!(2));
+        let diff = compare_serde_values_with_options(&a, &b, &options).unwrap();
+        assert_eq!(diff.unequal_values.count_diffs(), 1);
+        assert_eq!(diff.usage.max_depth, DEFAULT_MAX_DEPTH);
     }
 
     #[test]
-    fn parse_err_source_two() {
-        let valid_json1 = r#"{"a":"b"}"#;
-        let invalid_json2 = r#"{invalid: json}"#;
-        compare_strs(valid_json1, invalid_json2, false, &[])
-            .expect_err("Parsing invalid JSON didn't throw an err");
+    fn deep_nesting_beyond_limit_errors() {
+        let a = nested(100_000, json!(1));
+        let b = json!(1);
+        let err = compare_serde_values_with_options(&a, &b, &CompareOptions::default());
+        assert!(matches!(
+            err,
+            Err(Error::DepthLimitExceeded(DEFAULT_MAX_DEPTH))
+        ));
+        let err =
+            compare_serde_values_with_options(&b, &a, &CompareOptions::default().max_depth(10));
+        assert!(matches!(err, Err(Error::DepthLimitExceeded(10))));
+        // serde_json drops values recursively, so leak the deep one
+        core::mem::forget(a);
+    }
+
+    fn nested_text(depth: usize, leaf: &str) -> String {
+        format!("{}{leaf}{}", "[".repeat(depth), "]".repeat(depth))
+    }
+
+    #[cfg(all(feature = "unbounded_depth", feature = "std"))]
+    #[test]
+    fn unbounded_depth_parses_inputs_within_max_depth() {
+        // comparing 300 levels in a debug build needs more than the default stack of test threads
+        let compare = || {
+            let (a, b) = (nested_text(300, "1"), nested_text(300, "2"));
+            let comparator = CompareOptions::default().max_depth(400).build().unwrap();
+            let diffs = comparator.compare_strs(&a, &b).unwrap();
+            assert_eq!(diffs.unequal_values.count_diffs(), 1);
+            assert_eq!(
+                diffs,
+                comparator
+                    .compare_slices(a.as_bytes(), b.as_bytes())
+                    .unwrap()
+            );
+            let documents = format!("{a}\n{b}");
+            let pairs = comparator
+                .compare_multi_document_strs(&documents, &documents)
+                .unwrap();
+            assert_eq!(pairs.len(), 2);
+            let err = compare_strs_with_options(&a, &b, &CompareOptions::default()).unwrap_err();
+            assert_eq!(err, Error::DepthLimitExceeded(DEFAULT_MAX_DEPTH));
+        };
+        std::thread::Builder::new()
+            .stack_size(16 << 20)
+            .spawn(compare)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[cfg(not(feature = "unbounded_depth"))]
+    #[test]
+    fn deep_inputs_suggest_unbounded_depth() {
+        let deep = nested_text(300, "1");
+        let comparator = CompareOptions::default().max_depth(400).build().unwrap();
+        let err = comparator.compare_strs("1", &deep).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::ParseRight);
+        assert!(
+            err.to_string()
+                .contains("enable the unbounded_depth feature"),
+            "{err}"
+        );
+    }
+
+    fn arbitrary_number() -> impl Strategy<Value = Value> {
+        prop_oneof![
+            any::<i64>().prop_map(Value::from),
+            any::<u64>().prop_map(Value::from),
+            any::<f64>().prop_map(Value::from),
+            Just(json!(0.0)),
+            Just(json!(-0.0)),
+            Just(json!(f64::MAX)),
+            Just(json!(f64::MIN_POSITIVE)),
+            Just(json!(u64::MAX)),
+            Just(json!(i64::MIN)),
+        ]
+    }
+
+    fn arbitrary_json() -> impl Strategy<Value = Value> {
+        let leaf = prop_oneof![
+            Just(Value::Null),
+            any::<bool>().prop_map(Value::Bool),
+            arbitrary_number(),
+            "[a-c]{0,2}".prop_map(Value::String),
+            Just(json!([])),
+            Just(json!({})),
+        ];
+        leaf.prop_recursive(8, 128, 8, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..8).prop_map(Value::Array),
+                prop::collection::btree_map("[a-d]", inner, 0..6)
+                    .prop_map(|m| Value::Object(m.into_iter().collect())),
+            ]
+        })
+    }
+
+    fn arbitrary_options() -> impl Strategy<Value = CompareOptions> {
+        (any::<bool>(), any::<bool>()).prop_map(|(sort_arrays, ignore)| {
+            let ignore_keys = if ignore {
+                vec![Regex::new("^[ab]$").unwrap()]
+            } else {
+                vec![]
+            };
+            CompareOptions::default()
+                .sort_arrays(sort_arrays)
+                .ignore_keys(ignore_keys)
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn comparison_never_panics(a in arbitrary_json(), b in arbitrary_json(), options in arbitrary_options()) {
+            compare_serde_values_with_options(&a, &b, &options).unwrap();
+            let template = options.mode(CompareMode::Template);
+            compare_serde_values_with_options(&a, &b, &template).unwrap();
+        }
+
+        #[test]
+        fn comparison_with_itself_is_empty(a in arbitrary_json(), options in arbitrary_options()) {
+            let diff = compare_serde_values_with_options(&a, &a, &options).unwrap();
+            prop_assert!(diff.is_empty());
+        }
+
+        #[test]
+        fn leaf_counts_cover_both_documents(a in arbitrary_json(), b in arbitrary_json(), sort_arrays in any::<bool>()) {
+            let no_keys = KeyMatcher::new(&[], &[]);
+            let options = CompareOptions::default().sort_arrays(sort_arrays).collect_counts(true);
+            let counts = compare_serde_values_with_options(&a, &b, &options).unwrap().usage.leaves.unwrap();
+            prop_assert_eq!(counts.left, scalar_leaves(&a, no_keys));
+            prop_assert_eq!(counts.right, scalar_leaves(&b, no_keys));
+            prop_assert!(counts.equal <= counts.left.min(counts.right));
+            let counts = compare_serde_values_with_options(&a, &a, &options).unwrap().usage.leaves.unwrap();
+            prop_assert_eq!(counts.equal, scalar_leaves(&a, no_keys));
+        }
+
+        #[test]
+        fn json_patch_reproduces_right(a in arbitrary_json(), b in arbitrary_json()) {
+            let diff = compare_serde_values(&a, &b, false, &[]).unwrap();
+            let patch = crate::patch::to_json_patch(&diff);
+            prop_assert_eq!(crate::patch::apply(&a, &patch).unwrap(), b);
+        }
+
+        #[test]
+        fn edit_scripts_rebuild_any_array(a in prop::collection::vec(0..4u8, 0..24), b in prop::collection::vec(0..4u8, 0..24)) {
+            let (a, b): (Vec<Value>, Vec<Value>) = (a.into_iter().map(Value::from).collect(), b.into_iter().map(Value::from).collect());
+            let script = array_edit_script(&a, &b, &CompareOptions::default());
+            prop_assert_eq!(apply_edit_script(&a, &b, &script), b);
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(16))]
+        #[test]
+        fn huge_arrays_never_panic(len_a in 0..2000usize, len_b in 0..2000usize, sort_arrays in any::<bool>()) {
+            let a = Value::Array((0..len_a).map(|i| json!(i % 7)).collect());
+            let b = Value::Array((0..len_b).map(|i| json!(i % 5)).collect());
+            let options = CompareOptions::default().sort_arrays(sort_arrays);
+            compare_serde_values_with_options(&a, &b, &options).unwrap();
+        }
     }
 }
@@ -0,0 +1,481 @@
+//! Comparison of [`RawValue`] documents that parses subtrees only where the raw texts differ.
+//! The top two levels of objects are split into their raw members, members with the same tokens on both sides
+//! are equal without being parsed. Only the differing members are parsed and compared like [`crate::compare_serde_values_with_options`] does.
+//! ```rust
+//! use json_diff_ng::{raw::compare_raw, CompareOptions};
+//! use serde_json::value::RawValue;
+//! let left = RawValue::from_string(r#"{"big": [1, 2, 3], "small": 1}"#.to_string()).unwrap();
+//! let right = RawValue::from_string(r#"{"big": [1,2,3], "small": 2}"#.to_string()).unwrap();
+//! let diffs = compare_raw(&left, &right, &CompareOptions::default()).unwrap();
+//! assert_eq!(diffs.all_diffs()[0].1.to_string(), ".small.(1 != 2)");
+//! ```
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Formatter;
+
+use serde::de::{Deserializer, MapAccess, Visitor};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use serde_json::{Map, Value};
+
+use crate::{CompareMode, CompareOptions, Error, Mismatch, ParseMode, Result};
+
+/// Levels of objects split into their members before parsing the rest.
+const LAZY_LEVELS: usize = 2;
+
+/// Compares `a` and `b` with `options`, with the same result as comparing the parsed values.
+/// Only options known not to depend on equal subtrees skip them, others like [`CompareOptions::collect_counts`],
+/// templates, key normalizers, array identities, expectations and observers parse both documents fully.
+/// When members are skipped, [`crate::prelude::ResourceUsage`] only counts the nodes that were compared.
+pub fn compare_raw(a: &RawValue, b: &RawValue, options: &CompareOptions) -> Result<Mismatch> {
+    let comparator = options.build()?;
+    // subtrees skipped below are not seen by the depth check of the comparison
+    for raw in [a, b] {
+        if raw_depth(raw.get().as_bytes()) > options.max_depth {
+            return Err(Error::DepthLimitExceeded(options.max_depth));
+        }
+    }
+    let levels = if needs_equal_subtrees(options) {
+        0
+    } else {
+        LAZY_LEVELS
+    };
+    let (a, b) = lazy_pair(a, b, levels)?;
+    comparator.compare(&a, &b)
+}
+
+/// Whether `options` may depend on members equal on both sides, which [`lazy_pair`] replaces by `null`.
+/// The options are destructured completely, so that every new option has to be classified as safe or not here.
+fn needs_equal_subtrees(options: &CompareOptions) -> bool {
+    let CompareOptions {
+        // safe: equal members produce no diffs or warnings under these options
+        sort_arrays: _,
+        ignore_keys: _,
+        include_keys_override: _,
+        ignore_key_patterns: _,
+        include_key_patterns: _,
+        ignore_paths: _,
+        ignore_path_patterns: _,
+        ignore_pointers: _,
+        max_depth: _,
+        max_input_bytes: _,
+        max_extras_per_object: _,
+        array_element_filter: _,
+        verify_multiplicity: _,
+        record_origins: _,
+        wildcard_values: _,
+        ignore_values: _,
+        ignore_value_patterns: _,
+        report_order_differences: _,
+        no_value_capture: _,
+        labels: _,
+        null_equals_missing: _,
+        array_modes: _,
+        decimal_places: _,
+        decimal_places_at: _,
+        absolute_tolerance: _,
+        relative_tolerance: _,
+        array_context: _,
+        string_diffs: _,
+        unwrap_fallback: _,
+        max_resolved_values: _,
+        require_container_roots: _,
+        // everything else needs the complete documents
+        ignore_keys_left,
+        ignore_keys_right,
+        observer,
+        parse_mode,
+        key_normalizer,
+        mode,
+        collect_counts,
+        array_identity,
+        identity_keys,
+        identity_keys_at,
+        match_lookalike_keys,
+        canonical_keys,
+        sample,
+        expectations,
+        unwrap_left,
+        unwrap_right,
+        pre_transform_left,
+        pre_transform_right,
+        resolve_internal_refs,
+        object_sets,
+    } = options;
+    ignore_keys_left.is_some()
+        || ignore_keys_right.is_some()
+        || observer.is_some()
+        || *parse_mode != ParseMode::Strict
+        || key_normalizer.is_some()
+        || *mode != CompareMode::Exact
+        || *collect_counts
+        || array_identity.is_some()
+        || !identity_keys.is_empty()
+        || !identity_keys_at.is_empty()
+        || *match_lookalike_keys
+        || *canonical_keys
+        || sample.is_some()
+        || !expectations.is_empty()
+        || unwrap_left.is_some()
+        || unwrap_right.is_some()
+        || pre_transform_left.is_some()
+        || pre_transform_right.is_some()
+        || *resolve_internal_refs
+        || !object_sets.is_empty()
+}
+
+/// Parses `a` and `b`, replacing members of objects found within `levels` that are equal on both sides by `null`.
+fn lazy_pair(a: &RawValue, b: &RawValue, levels: usize) -> Result<(Value, Value)> {
+    let is_object = |raw: &RawValue| raw.get().trim_start().starts_with('{');
+    if levels == 0 || !is_object(a) || !is_object(b) {
+        return Ok((parse(a, Error::ParseLeft)?, parse(b, Error::ParseRight)?));
+    }
+    let a_members = members(a).map_err(Error::ParseLeft)?;
+    let b_members = members(b).map_err(Error::ParseRight)?;
+    // the last of duplicate keys wins, like when parsing into a `Value`
+    let a_lookup: BTreeMap<&str, &RawValue> =
+        a_members.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+    let b_lookup: BTreeMap<&str, &RawValue> =
+        b_members.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+    let mut shared = BTreeMap::new();
+    for (key, a_value) in &a_lookup {
+        if let Some(b_value) = b_lookup.get(key) {
+            let pair = match same_tokens(a_value.get(), b_value.get()) {
+                true => (Value::Null, Value::Null),
+                false => lazy_pair(a_value, b_value, levels - 1)?,
+            };
+            shared.insert(*key, pair);
+        }
+    }
+    let mut a_map = Map::new();
+    for (key, value) in &a_members {
+        let value = match shared.get(key.as_str()) {
+            Some((value, _)) => value.clone(),
+            None => parse(value, Error::ParseLeft)?,
+        };
+        a_map.insert(key.clone(), value);
+    }
+    let mut b_map = Map::new();
+    for (key, value) in &b_members {
+        let value = match shared.get_mut(key.as_str()) {
+            Some((_, value)) => value.take(),
+            None => parse(value, Error::ParseRight)?,
+        };
+        b_map.insert(key.clone(), value);
+    }
+    Ok((Value::Object(a_map), Value::Object(b_map)))
+}
+
+fn parse(raw: &RawValue, error: fn(serde_json::Error) -> Error) -> Result<Value> {
+    serde_json::from_str(raw.get()).map_err(error)
+}
+
+/// Members of the raw object `raw` in document order, including duplicate keys.
+fn members(raw: &RawValue) -> serde_json::Result<Vec<(String, &RawValue)>> {
+    struct Members<'a>(Vec<(String, &'a RawValue)>);
+
+    impl<'de> Deserialize<'de> for Members<'de> {
+        fn deserialize<D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> core::result::Result<Self, D::Error> {
+            struct MembersVisitor;
+
+            impl<'de> Visitor<'de> for MembersVisitor {
+                type Value = Members<'de>;
+
+                fn expecting(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+                    write!(f, "a json object")
+                }
+
+                fn visit_map<M: MapAccess<'de>>(
+                    self,
+                    mut map: M,
+                ) -> core::result::Result<Self::Value, M::Error> {
+                    let mut members = Vec::new();
+                    while let Some(member) = map.next_entry()? {
+                        members.push(member);
+                    }
+                    Ok(Members(members))
+                }
+            }
+
+            deserializer.deserialize_map(MembersVisitor)
+        }
+    }
+
+    serde_json::from_str::<Members>(raw.get()).map(|members| members.0)
+}
+
+/// Whether both json texts consist of the same tokens, disregarding whitespace between them.
+fn same_tokens(a: &str, b: &str) -> bool {
+    let (mut a, mut b) = (Tokens::new(a.as_bytes()), Tokens::new(b.as_bytes()));
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return true,
+            (x, y) if x != y => return false,
+            _ => {}
+        }
+    }
+}
+
+/// Bytes of a json text without the whitespace outside of strings.
+struct Tokens<'a> {
+    bytes: core::slice::Iter<'a, u8>,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(text: &'a [u8]) -> Self {
+        Self {
+            bytes: text.iter(),
+            in_string: false,
+            escaped: false,
+        }
+    }
+}
+
+impl Iterator for Tokens<'_> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        loop {
+            let byte = *self.bytes.next()?;
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => {}
+                }
+                return Some(byte);
+            }
+            match byte {
+                b' ' | b'\t' | b'\n' | b'\r' => continue,
+                b'"' => self.in_string = true,
+                _ => {}
+            }
+            return Some(byte);
+        }
+    }
+}
+
+/// Nesting depth of a json text, counted like [`crate::CompareOptions::max_depth`].
+pub(crate) fn raw_depth(text: &[u8]) -> usize {
+    let (mut depth, mut max, mut tokens) = (0usize, 0, Tokens::new(text));
+    while let Some(byte) = tokens.next() {
+        match byte {
+            _ if tokens.in_string => {}
+            b'[' | b'{' => {
+                depth += 1;
+                max = max.max(depth);
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            b':' | b',' => {}
+            // scalars, including the closing quote of strings, are one level below their container
+            _ => max = max.max(depth + 1),
+        }
+    }
+    max
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use crate::{compare_serde_values_with_options, ErrorCode};
+
+    use super::*;
+
+    fn raw(text: &str) -> Box<RawValue> {
+        RawValue::from_string(text.to_string()).unwrap()
+    }
+
+    fn assert_same_as_parsed(a: &str, b: &str, options: &CompareOptions) {
+        let lazy = compare_raw(&raw(a), &raw(b), options);
+        let a: Value = serde_json::from_str(a).unwrap();
+        let b: Value = serde_json::from_str(b).unwrap();
+        let parsed = compare_serde_values_with_options(&a, &b, options);
+        match (lazy, parsed) {
+            (Ok(lazy), Ok(parsed)) => {
+                assert_eq!(lazy, parsed, "{options:?}");
+                assert_eq!(lazy.warnings, parsed.warnings, "{options:?}");
+            }
+            (lazy, parsed) => assert_eq!(lazy.err(), parsed.err(), "{options:?}"),
+        }
+    }
+
+    #[test]
+    fn results_match_the_parsed_comparison() {
+        let left = r#"{
+            "same": {"deep": [1, {"x": "a b"}], "s": "\"quoted\""},
+            "nested": {"equal": [1, 2], "changed": {"v": 1}, "gone": null},
+            "spaced": [3, 2, 1],
+            "escaped": "A",
+            "dup": 1, "dup": 2,
+            "left": true
+        }"#;
+        let right = r#"{"same":{"deep":[1,{"x":"a b"}],"s":"\"quoted\""},
+            "nested": {"changed": {"v": 2}, "equal": [1,2], "new": 0},
+            "spaced": [1, 2, 3], "escaped": "A", "dup": 3, "right": false}"#;
+        for options in [
+            CompareOptions::default(),
+            CompareOptions::default().sort_arrays(true),
+            CompareOptions::default().ignore_key_patterns(["^changed$"]),
+            CompareOptions::default().collect_counts(true),
+        ] {
+            assert_same_as_parsed(left, right, &options);
+        }
+        assert_same_as_parsed("[1, 2]", "[1, 3]", &CompareOptions::default());
+        assert_same_as_parsed(r#"{"a": 1}"#, "[1]", &CompareOptions::default());
+    }
+
+    #[test]
+    fn every_option_matches_the_parsed_comparison() {
+        use alloc::sync::Arc;
+        use regex::Regex;
+
+        use crate::normalize;
+        use crate::observer::NoopObserver;
+        use crate::transform::Transform;
+        use crate::process::SampleSpec;
+        use crate::{ArrayMode, Expectation, ValueRule};
+
+        let left = r##"{
+            "tags": {"a": true, "b": true},
+            "meta": {"v": 1, "Kind": "x"},
+            "data": {"items": [3, 1, 2], "same": {"n": 1.0001, "id": "x"}, "gone": null,
+                "ref": {"$ref": "#/meta"}, "Name": "a"}
+        }"##;
+        let right = r#"{
+            "tags": {"a": true, "b": true, "c": true},
+            "meta": {"v": 1, "Kind": "x"},
+            "data": {"items": [1, 2, 4], "same": {"n": 1.0001, "id": "x"},
+                "ref": {"v": 1, "Kind": "x"}, "name": "a"}
+        }"#;
+        let regex = |pattern: &str| Regex::new(pattern).unwrap();
+        let copy = json!([{"op": "copy", "from": "/meta", "path": "/data/copy"}]);
+        let copy = Transform::from_json(&copy).unwrap();
+        let matrix = ArrayMode::Matrix {
+            tolerance: 0.0,
+            max_cells: None,
+        };
+        let sample = SampleSpec::new(7).max_keys_per_object(Some(1));
+        let default = CompareOptions::default;
+        for options in [
+            default(),
+            default().sort_arrays(true),
+            default().ignore_keys(vec![regex("^v$")]),
+            default().ignore_keys_left(vec![regex("^v$")]),
+            default().ignore_keys_right(vec![regex("^v$")]),
+            default().ignore_key_patterns(["^v$"]).include_key_patterns(["^v$"]),
+            default().ignore_path_patterns([r"^\.meta"]),
+            default().ignore_pointer("/data/items"),
+            default().observer(Arc::new(NoopObserver)),
+            default().max_depth(16),
+            default().max_input_bytes(Some(10)),
+            default().parse_mode(ParseMode::Tolerant { skip_prefix: true }),
+            default().key_normalizer(normalize::lowercase),
+            default().mode(CompareMode::Template),
+            default().max_extras_per_object(Some(0)),
+            default().filter_array_elements(|_, value| *value != json!(3)),
+            default().sort_arrays(true).verify_multiplicity(true),
+            default().record_origins(true),
+            default().wildcard_values(vec![json!(null), json!("x")]),
+            default().ignore_values(vec![ValueRule::either_side(regex("^x$"))]),
+            default().ignore_value_patterns(["^a$"]),
+            default().collect_counts(true),
+            default().sort_arrays(true).report_order_differences(true),
+            default().array_identity(|_, value| Some(value.to_string())),
+            default().identity_keys(["id"]),
+            default().identity_key_at("/data/items", "id"),
+            default().no_value_capture(true),
+            default().labels("old", "new"),
+            default().match_lookalike_keys(true),
+            default().canonical_keys(true),
+            default().null_equals_missing(true),
+            default().array_mode("/data/items", matrix),
+            default().sample(sample),
+            default().decimal_places(2),
+            default().decimal_places_at("/data", 2),
+            default().float_tolerance(0.1, 0.0),
+            default().expect("/meta", Expectation::MustDiffer),
+            default().array_context(1),
+            default().string_diffs(1),
+            default().unwrap_left("/data").unwrap_right("/data"),
+            default().unwrap_left("/missing").unwrap_fallback(true),
+            default().pre_transform_left(copy.clone()),
+            default().pre_transform_right(copy),
+            default().resolve_internal_refs(true).max_resolved_values(64),
+            default().require_container_roots(true),
+            default().coerce_object_set("/tags"),
+        ] {
+            assert_same_as_parsed(left, right, &options);
+        }
+    }
+
+    #[test]
+    fn references_resolve_into_equal_members() {
+        let referenced = r##"{
+            "paths": {"/pets": {"$ref": "#/components/schemas/Pet"}},
+            "components": {"schemas": {"Pet": {"type": "object"}}}
+        }"##;
+        let inlined = r#"{
+            "paths": {"/pets": {"type": "object"}},
+            "components": {"schemas": {"Pet": {"type": "object"}}}
+        }"#;
+        let options = CompareOptions::default().resolve_internal_refs(true);
+        let diffs = compare_raw(&raw(referenced), &raw(inlined), &options).unwrap();
+        assert!(diffs.is_empty());
+        assert_same_as_parsed(referenced, inlined, &options);
+    }
+
+    #[test]
+    fn transformations_read_equal_members() {
+        use crate::transform::Transform;
+
+        let left = r#"{"old": {"id": 1}, "same": {"id": 2}}"#;
+        let right = r#"{"new": {"id": 2}, "same": {"id": 2}}"#;
+        let copy = json!([
+            {"op": "copy", "from": "/same", "path": "/new"},
+            {"op": "remove", "path": "/old"}
+        ]);
+        let copy = Transform::from_json(&copy).unwrap();
+        let options = CompareOptions::default().pre_transform_left(copy);
+        let diffs = compare_raw(&raw(left), &raw(right), &options).unwrap();
+        assert!(diffs.is_empty());
+        assert_same_as_parsed(left, right, &options);
+        let moved = json!([{"op": "move", "from": "/same", "path": "/new"}]);
+        let moved = Transform::from_json(&moved).unwrap();
+        let options = CompareOptions::default().pre_transform_right(moved);
+        assert_same_as_parsed(right, right, &options);
+    }
+
+    #[test]
+    fn tokens_ignore_whitespace_outside_strings_only() {
+        assert!(same_tokens("{\"a\": [1, 2]}", "{\"a\":[1,2]}\n"));
+        assert!(!same_tokens("\"a b\"", "\"ab\""));
+        assert!(!same_tokens("\"a\\\" b\"", "\"a\\\"b\""));
+        assert!(!same_tokens("1.0", "1.00"));
+        assert_eq!(raw_depth(b"1"), 1);
+        assert_eq!(raw_depth(b"[[]]"), 2);
+        assert_eq!(raw_depth(br#"{"a": [1, "[[["]}"#), 3);
+    }
+
+    #[test]
+    fn equal_deep_members_are_checked_for_depth() {
+        let deep = format!("{}1{}", "[".repeat(10), "]".repeat(10));
+        let text = format!(r#"{{"a": {deep}}}"#);
+        let options = CompareOptions::default().max_depth(5);
+        let err = compare_raw(&raw(&text), &raw(&text), &options).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::LimitExceeded);
+        let value: Value = serde_json::from_str(&text).unwrap();
+        assert!(compare_serde_values_with_options(&value, &value, &options).is_err());
+        assert!(
+            compare_raw(&raw(&text), &raw(&text), &CompareOptions::default())
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,1952 @@
+//! Rendering of a [`Mismatch`] into shareable reports: plain text, json, markdown, html and graphviz DOT.
+//! All reports name the compared documents by their [`Labels`], set with [`crate::CompareOptions::labels`].
+//! ```rust
+//! use json_diff_ng::{compare_serde_values_with_options, report, CompareOptions};
+//! use serde_json::json;
+//! let options = CompareOptions::default().labels("production", "staging");
+//! let diffs = compare_serde_values_with_options(&json!({"a": 1}), &json!({"a": 2}), &options).unwrap();
+//! assert!(report::to_markdown(&diffs).starts_with("# Diff of `production` and `staging`"));
+//! ```
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::normalize::escape_invisible;
+use crate::pointer;
+use crate::enums::OwnedPathElement;
+use crate::mismatch::{structural_hash, ElementSummary, Verdict};
+use crate::{DiffPath, DiffRecord, Error, Result, DiffType, DiffValues, Mismatch, Side};
+
+/// Names of the compared documents, e.g. file paths or environments.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Labels {
+    pub left: String,
+    pub right: String,
+}
+
+impl Labels {
+    pub fn new(left: impl Into<String>, right: impl Into<String>) -> Self {
+        Self {
+            left: left.into(),
+            right: right.into(),
+        }
+    }
+
+    pub fn get(&self, side: Side) -> &str {
+        match side {
+            Side::Left => &self.left,
+            Side::Right => &self.right,
+        }
+    }
+}
+
+impl Default for Labels {
+    fn default() -> Self {
+        Self::new("left", "right")
+    }
+}
+
+/// Wording of the [`to_text`], [`to_markdown`] and [`to_html`] reports, English by default.
+/// Templates name the documents as `{left}` and `{right}`, [`Messages::more`] the number of suppressed diffs as
+/// `{count}`. Paths and values are never translated. Messages deserialize from json, missing fields keep their
+/// English default.
+/// ```rust
+/// use json_diff_ng::report::{self, Messages};
+/// use json_diff_ng::compare_strs;
+/// let diffs = compare_strs(r#"{"a": 1}"#, r#"{"a": 2}"#, false, &[]).unwrap();
+/// let messages: Messages = serde_json::from_str(r#"{"mismatch": "Abweichung"}"#).unwrap();
+/// assert_eq!(report::to_text_with_messages(&diffs, &messages).lines().last(), Some("Abweichung: .a.(1 != 2)"));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct Messages {
+    pub root_mismatch: String,
+    pub left_extra: String,
+    pub right_extra: String,
+    pub mismatch: String,
+    pub multiplicity_change: String,
+    pub order_only: String,
+    pub key_almost_equal: String,
+    pub dimension_mismatch: String,
+    pub expected_change_missing: String,
+    pub type_change: String,
+    /// First line of the text report
+    pub text_header: String,
+    /// Heading of the markdown and html reports
+    pub heading: String,
+    /// Body of markdown and html reports without diffs
+    pub no_differences: String,
+    pub type_column: String,
+    pub path_column: String,
+    pub context_column: String,
+    pub size_column: String,
+    pub string_diff_column: String,
+    /// Columns of the element summaries of [`to_markdown_by_element`] and [`to_html_by_element`]
+    pub element_column: String,
+    pub changes_column: String,
+    pub changed_fields_column: String,
+    /// Value cell of a record standing for `{count}` suppressed diffs
+    pub more: String,
+    /// Heading of the number of diffs per category, see [`Classifier`]
+    pub categories: String,
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Self {
+            root_mismatch: DiffType::RootMismatch.to_string(),
+            left_extra: DiffType::LeftExtra.to_string(),
+            right_extra: DiffType::RightExtra.to_string(),
+            mismatch: DiffType::Mismatch.to_string(),
+            multiplicity_change: DiffType::MultiplicityChange.to_string(),
+            order_only: DiffType::OrderOnly.to_string(),
+            key_almost_equal: DiffType::KeyAlmostEqual.to_string(),
+            dimension_mismatch: DiffType::DimensionMismatch.to_string(),
+            expected_change_missing: DiffType::ExpectedChangeMissing.to_string(),
+            type_change: DiffType::TypeChange.to_string(),
+            text_header: "Comparing {left} (left) with {right} (right)".to_string(),
+            heading: "Diff of {left} and {right}".to_string(),
+            no_differences: "No differences.".to_string(),
+            type_column: "Type".to_string(),
+            path_column: "Path".to_string(),
+            context_column: "Context".to_string(),
+            size_column: "Size change".to_string(),
+            string_diff_column: "Changed lines".to_string(),
+            element_column: "Element".to_string(),
+            changes_column: "Changes".to_string(),
+            changed_fields_column: "Changed fields".to_string(),
+            more: "{count} more".to_string(),
+            categories: "Categories:".to_string(),
+        }
+    }
+}
+
+impl Messages {
+    /// Label of the category `diff_type`.
+    pub fn diff_type(&self, diff_type: DiffType) -> &str {
+        match diff_type {
+            DiffType::RootMismatch => &self.root_mismatch,
+            DiffType::LeftExtra => &self.left_extra,
+            DiffType::RightExtra => &self.right_extra,
+            DiffType::Mismatch => &self.mismatch,
+            DiffType::MultiplicityChange => &self.multiplicity_change,
+            DiffType::OrderOnly => &self.order_only,
+            DiffType::KeyAlmostEqual => &self.key_almost_equal,
+            DiffType::DimensionMismatch => &self.dimension_mismatch,
+            DiffType::ExpectedChangeMissing => &self.expected_change_missing,
+            DiffType::TypeChange => &self.type_change,
+        }
+    }
+
+    /// The [`DiffRecord`] display form with the label of its category, e.g. `Mismatched: .a.(1 != 2)`.
+    pub fn record(&self, record: &DiffRecord) -> String {
+        let mut line = format!("{}: ", self.diff_type(record.diff_type));
+        // writing to a String cannot fail
+        let _ = record.write_body(&mut line);
+        line
+    }
+
+    /// `template` with the placeholders of the documents replaced by `left` and `right`.
+    fn fill(template: &str, left: &str, right: &str) -> String {
+        template.replace("{left}", left).replace("{right}", right)
+    }
+}
+
+/// Counts of a comparison together with the labels of both documents.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Summary {
+    pub labels: Labels,
+    pub equal: bool,
+    pub left_only: usize,
+    pub right_only: usize,
+    pub unequal_values: usize,
+}
+
+/// Summarizes `mismatch`, see [`Mismatch::count_diffs`].
+pub fn summary(mismatch: &Mismatch) -> Summary {
+    let counts = mismatch.count_diffs();
+    Summary {
+        labels: mismatch.labels(),
+        equal: mismatch.is_empty(),
+        left_only: counts.left_only,
+        right_only: counts.right_only,
+        unequal_values: counts.unequal_values,
+    }
+}
+
+/// One line per diff below a header naming both documents.
+pub fn to_text(mismatch: &Mismatch) -> String {
+    to_text_with_messages(mismatch, &Messages::default())
+}
+
+/// Same as [`to_text`] worded by `messages`.
+pub fn to_text_with_messages(mismatch: &Mismatch, messages: &Messages) -> String {
+    let Labels { left, right } = mismatch.labels();
+    let mut text = Messages::fill(&messages.text_header, &left, &right);
+    text.push('\n');
+    for record in mismatch.records() {
+        text.push_str(&messages.record(&record));
+        text.push('\n');
+    }
+    text
+}
+
+/// Whether [`format_diffs`] colors its lines with ANSI escape codes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Plain text, e.g. for output that is piped or written to a file
+    #[default]
+    Never,
+    /// Left only diffs in red, right only diffs in green and all others in yellow
+    Always,
+}
+
+/// Human friendly listing of all diffs, grouped by the path of their parent and sorted by path.
+/// Each group starts with the parent path, `<root>` for the documents themselves, followed by one line per diff:
+/// `- key: value` for values only on the left, `+ key: value` for values only on the right and
+/// `~ key: left → right` for mismatched values. Other diffs name their category, e.g. `~ key: Order only`.
+/// ```rust
+/// use json_diff_ng::report::{format_diffs, ColorMode};
+/// use json_diff_ng::compare_strs;
+/// let diffs = compare_strs(r#"{"a": {"b": 1, "c": 2}}"#, r#"{"a": {"b": 3, "d": 4}}"#, false, &[]).unwrap();
+/// assert_eq!(format_diffs(&diffs, ColorMode::Never), ".a:\n  ~ b: 1 → 3\n  - c: 2\n  + d: 4\n");
+/// ```
+pub fn format_diffs(mismatch: &Mismatch, color: ColorMode) -> String {
+    format_diffs_with_options(mismatch, &FormatOptions::default().color(color))
+}
+
+/// Options of [`format_diffs_with_options`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FormatOptions {
+    pub color: ColorMode,
+    /// Values only present on one side are printed, otherwise only their keys
+    pub values: bool,
+    /// Diffs are followed by the pass that found them, see [`crate::CompareOptions::record_origins`]
+    pub explain: bool,
+    /// Groups and diffs are sorted by path, otherwise they are listed in the order of [`Mismatch::records`]
+    pub sorted: bool,
+    /// Names of the categories of diffs that are neither one-sided nor mismatched values
+    pub messages: Messages,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            color: ColorMode::default(),
+            values: true,
+            explain: false,
+            sorted: true,
+            messages: Messages::default(),
+        }
+    }
+}
+
+impl FormatOptions {
+    pub fn color(mut self, color: ColorMode) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn values(mut self, values: bool) -> Self {
+        self.values = values;
+        self
+    }
+
+    pub fn explain(mut self, explain: bool) -> Self {
+        self.explain = explain;
+        self
+    }
+
+    pub fn sorted(mut self, sorted: bool) -> Self {
+        self.sorted = sorted;
+        self
+    }
+
+    pub fn messages(mut self, messages: Messages) -> Self {
+        self.messages = messages;
+        self
+    }
+}
+
+/// Same as [`format_diffs`] configured by `options`. [Expected](Mismatch::is_expected) diffs are marked `(expected)`.
+/// ```rust
+/// use json_diff_ng::report::{format_diffs_with_options, FormatOptions};
+/// use json_diff_ng::compare_strs;
+/// let diffs = compare_strs(r#"{"a": {"b": 1, "c": 2}}"#, r#"{"a": {"b": 3, "d": 4}}"#, false, &[]).unwrap();
+/// let options = FormatOptions::default().values(false);
+/// assert_eq!(format_diffs_with_options(&diffs, &options), ".a:\n  ~ b: 1 → 3\n  - c\n  + d\n");
+/// ```
+pub fn format_diffs_with_options(mismatch: &Mismatch, options: &FormatOptions) -> String {
+    let records = match options.sorted {
+        true => mismatch.records_sorted(),
+        false => mismatch.records(),
+    };
+    // groups in order of their first diff, sorted by parent path if the diffs are
+    let mut groups: Vec<(DiffPath, Vec<(DiffRecord, bool)>)> = Vec::new();
+    let mut positions: BTreeMap<DiffPath, usize> = BTreeMap::new();
+    for mut record in records {
+        let expected = mismatch.is_expected(&record);
+        if !options.values && matches!(record.values, DiffValues::Single(_)) {
+            record.values = DiffValues::Empty;
+        }
+        let key = record.path.0.pop();
+        let parent = core::mem::replace(&mut record.path, DiffPath(key.into_iter().collect()));
+        let position = *positions.entry(parent.clone()).or_insert_with(|| {
+            groups.push((parent, Vec::new()));
+            groups.len() - 1
+        });
+        groups[position].1.push((record, expected));
+    }
+    if options.sorted {
+        groups.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+    let paint = |code: &str, text: String| match options.color {
+        ColorMode::Never => text,
+        ColorMode::Always => format!("\x1b[{code}m{text}\x1b[0m"),
+    };
+    let mut text = String::new();
+    for (parent, records) in groups {
+        let heading = match parent.is_root() {
+            true => "<root>".to_string(),
+            false => parent.to_string(),
+        };
+        text.push_str(&paint("1", format!("{heading}:")));
+        text.push('\n');
+        for (record, expected) in records {
+            // the path of each record is left with its last element
+            let key = match record.path.0.first() {
+                Some(key) => key.to_string(),
+                None => ".".to_string(),
+            };
+            let (code, mut line) = match (record.diff_type, &record.values) {
+                (DiffType::LeftExtra, DiffValues::Single(v)) => ("31", format!("- {key}: {v}")),
+                (DiffType::LeftExtra, _) => ("31", format!("- {key}")),
+                (DiffType::RightExtra, DiffValues::Single(v)) => ("32", format!("+ {key}: {v}")),
+                (DiffType::RightExtra, _) => ("32", format!("+ {key}")),
+                (DiffType::Mismatch | DiffType::RootMismatch, DiffValues::Pair(l, r)) => {
+                    ("33", format!("~ {key}: {l} → {r}"))
+                }
+                (diff_type, _) => {
+                    let mut line = format!("~ {key}: {}", options.messages.diff_type(diff_type));
+                    if record.has_printed_values() {
+                        line.push_str(" (");
+                        // writing to a String cannot fail
+                        let _ = record.write_values(&mut line);
+                        line.push(')');
+                    }
+                    ("33", line)
+                }
+            };
+            if expected {
+                line.push_str(" (expected)");
+            }
+            match record.origin {
+                Some(origin) if options.explain => line.push_str(&format!(" [{origin}]")),
+                _ => {}
+            }
+            text.push_str("  ");
+            text.push_str(&paint(code, line));
+            text.push('\n');
+        }
+    }
+    text
+}
+
+/// The [`Summary`], all diffs and the [warnings](Mismatch::warnings) as json, values are listed under the label of their side.
+/// Besides its display form, the path of each diff is given as array of keys and indices in `path_elements`,
+/// so the records can be read back by [`records_from_json`]. Mismatched strings, arrays and objects carry their
+/// [`DiffRecord::size_delta`] as `size_delta`. Each warning has its `kind`, `path` and `message`.
+pub fn to_json(mismatch: &Mismatch) -> Value {
+    let labels = mismatch.labels();
+    let diffs: Vec<Value> = mismatch
+        .records()
+        .iter()
+        .map(|record| diff_json(record, &labels))
+        .collect();
+    json!({"summary": summary(mismatch), "diffs": diffs, "warnings": warnings_json(mismatch)})
+}
+
+/// All diffs as json sorted by side, e.g. for CI jobs: values present on one side only under `left_only` and
+/// `right_only` with their `value`, all other diffs under `mismatches` with their `type` and, if known, the `left` and
+/// `right` value. Each entry has its path in display form as `path` and as array of keys and indices in
+/// `path_elements`. `equal` is the [`Mismatch::verdict`], like the exit status of the binary: expected diffs do not
+/// count, missing expected changes do.
+/// ```rust
+/// use json_diff_ng::{compare_strs, report};
+/// use serde_json::json;
+/// let diffs = compare_strs(r#"{"a": 1, "b": [1]}"#, r#"{"a": 2, "b": [1, 2]}"#, false, &[]).unwrap();
+/// assert_eq!(
+///     report::to_json_by_side(&diffs),
+///     json!({
+///         "equal": false,
+///         "left_only": [],
+///         "right_only": [{"path": ".b.[1]", "path_elements": ["b", 1], "value": 2}],
+///         "mismatches": [{"type": "Mismatched", "path": ".a", "path_elements": ["a"], "left": 1, "right": 2}]
+///     })
+/// );
+/// ```
+pub fn to_json_by_side(mismatch: &Mismatch) -> Value {
+    let (mut left_only, mut right_only, mut mismatches) = (Vec::new(), Vec::new(), Vec::new());
+    for record in mismatch.records_sorted() {
+        let mut diff = json!({
+            "path": record.path.to_string(),
+            "path_elements": path_elements(&record.path),
+        });
+        match (record.diff_type, record.values) {
+            (DiffType::LeftExtra, DiffValues::Single(value)) => {
+                diff["value"] = value;
+                left_only.push(diff);
+            }
+            (DiffType::RightExtra, DiffValues::Single(value)) => {
+                diff["value"] = value;
+                right_only.push(diff);
+            }
+            (DiffType::LeftExtra, _) => left_only.push(diff),
+            (DiffType::RightExtra, _) => right_only.push(diff),
+            (diff_type, values) => {
+                diff["type"] = Value::from(diff_type.to_string());
+                if let DiffValues::Pair(left, right) = values {
+                    diff["left"] = left;
+                    diff["right"] = right;
+                }
+                mismatches.push(diff);
+            }
+        }
+    }
+    json!({
+        "equal": mismatch.verdict() == Verdict::Pass,
+        "left_only": left_only,
+        "right_only": right_only,
+        "mismatches": mismatches,
+    })
+}
+
+/// Options of [`to_json_with_options`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct JsonOptions {
+    /// Arrays and objects found as value of several diffs are listed once under `shared`,
+    /// the diffs refer to them as `{"$ref": "#/shared/0"}`. Values of the documents that look like such a
+    /// reference are always listed under `shared`, so that every reference in the diffs is one
+    pub dedupe_values: bool,
+}
+
+impl JsonOptions {
+    pub fn dedupe_values(mut self, dedupe_values: bool) -> Self {
+        self.dedupe_values = dedupe_values;
+        self
+    }
+}
+
+/// Same as [`to_json`] configured by `options`. The records of a report with deduplicated values are read back
+/// with their references resolved by [`records_from_json`].
+/// ```rust
+/// use json_diff_ng::{compare_serde_values, report};
+/// use serde_json::json;
+/// let default = json!({"retries": 3, "backoff": "linear"});
+/// let right = json!({"a": {"policy": default}, "b": {"policy": default}});
+/// let diffs = compare_serde_values(&json!({"a": {}, "b": {}}), &right, false, &[]).unwrap();
+/// let options = report::JsonOptions::default().dedupe_values(true);
+/// let json = report::to_json_with_options(&diffs, &options);
+/// assert_eq!(json["shared"], json!([default]));
+/// assert_eq!(json["diffs"][1]["right"], json!({"$ref": "#/shared/0"}));
+/// ```
+pub fn to_json_with_options(mismatch: &Mismatch, options: &JsonOptions) -> Value {
+    let mut report = to_json(mismatch);
+    if options.dedupe_values {
+        let labels = mismatch.labels();
+        let shared = dedupe_values(
+            &mut report["diffs"],
+            &[&labels.left, &labels.right, "element"],
+        );
+        report["shared"] = Value::Array(shared);
+    }
+    report
+}
+
+/// Replaces the arrays and objects found more than once under `fields` of `diffs` by references,
+/// returning the referenced values in order of their first occurrence.
+fn dedupe_values(diffs: &mut Value, fields: &[&str]) -> Vec<Value> {
+    let Value::Array(diffs) = diffs else {
+        return Vec::new();
+    };
+    // distinct containers in order of their first occurrence with their number of occurrences,
+    // and their positions in there by hash
+    let mut distinct: Vec<(Value, usize)> = Vec::new();
+    let mut by_hash: BTreeMap<u64, Vec<usize>> = BTreeMap::new();
+    let find = |distinct: &[(Value, usize)], positions: &[usize], value: &Value| {
+        positions.iter().copied().find(|&n| distinct[n].0 == *value)
+    };
+    for diff in diffs.iter() {
+        for value in fields.iter().filter_map(|field| diff.get(*field)) {
+            if !matches!(value, Value::Array(_) | Value::Object(_)) {
+                continue;
+            }
+            let positions = by_hash.entry(structural_hash(value)).or_default();
+            match find(&distinct, positions, value) {
+                Some(n) => distinct[n].1 += 1,
+                None => {
+                    positions.push(distinct.len());
+                    distinct.push((value.clone(), 1));
+                }
+            }
+        }
+    }
+    let mut shared = Vec::new();
+    let references: Vec<Option<usize>> = distinct
+        .iter()
+        .map(|(value, occurrences)| {
+            (*occurrences > 1 || shared_reference(value).is_some()).then(|| {
+                shared.push(value.clone());
+                shared.len() - 1
+            })
+        })
+        .collect();
+    for diff in diffs.iter_mut() {
+        for field in fields {
+            let Some(value) = diff.get_mut(*field) else {
+                continue;
+            };
+            let positions = by_hash.get(&structural_hash(value));
+            let reference = positions
+                .and_then(|positions| find(&distinct, positions, value))
+                .and_then(|n| references[n]);
+            if let Some(index) = reference {
+                *value = json!({"$ref": format!("#/shared/{index}")});
+            }
+        }
+    }
+    shared
+}
+
+/// The target of `value` if it has the form of a reference to a shared value, `{"$ref": "..."}`.
+fn shared_reference(value: &Value) -> Option<&str> {
+    match value {
+        Value::Object(o) if o.len() == 1 => o.get("$ref").and_then(Value::as_str),
+        _ => None,
+    }
+}
+
+/// The report of [`to_json`] split into pages of up to `page_size` diffs, ordered by path so pages are stable
+/// across runs. Each page holds the [`Summary`], the warnings, its diffs, and under `page` its zero-based `index`,
+/// `total_pages` and `total_records`. Without any diffs there is a single empty page, a `page_size` of 0 is treated as 1.
+/// The diffs of each page can be read back by [`records_from_json`].
+/// ```rust
+/// use json_diff_ng::{compare_serde_values, report};
+/// use serde_json::json;
+/// let diffs = compare_serde_values(&json!([1, 2, 3]), &json!([4, 5, 6]), false, &[]).unwrap();
+/// let pages = report::to_value_paged(&diffs, 2);
+/// assert_eq!(pages.len(), 2);
+/// assert_eq!(pages[1]["page"], json!({"index": 1, "total_pages": 2, "total_records": 3}));
+/// assert_eq!(pages[1]["diffs"][0]["path"], ".[2]");
+/// ```
+pub fn to_value_paged(mismatch: &Mismatch, page_size: usize) -> Vec<Value> {
+    let labels = mismatch.labels();
+    let summary = summary(mismatch);
+    let warnings = warnings_json(mismatch);
+    let records = mismatch.records_sorted();
+    let page_size = page_size.max(1);
+    let total_pages = records.len().div_ceil(page_size).max(1);
+    let mut pages: Vec<&[DiffRecord]> = records.chunks(page_size).collect();
+    pages.resize(total_pages, &[]);
+    pages
+        .into_iter()
+        .enumerate()
+        .map(|(index, page)| {
+            let diffs: Vec<Value> = page.iter().map(|r| diff_json(r, &labels)).collect();
+            json!({
+                "page": {"index": index, "total_pages": total_pages, "total_records": records.len()},
+                "summary": summary,
+                "diffs": diffs,
+                "warnings": warnings,
+            })
+        })
+        .collect()
+}
+
+fn diff_json(record: &DiffRecord, labels: &Labels) -> Value {
+    let mut diff = json!({
+        "type": record.diff_type.to_string(),
+        "path": record.path.to_string(),
+        "path_elements": path_elements(&record.path),
+    });
+    if let (Value::Object(diff), Value::Object(values)) = (&mut diff, values_json(record, labels)) {
+        diff.extend(values);
+        if let Some(delta) = record.size_delta() {
+            diff.insert("size_delta".to_string(), json!(delta));
+        }
+    }
+    diff
+}
+
+fn warnings_json(mismatch: &Mismatch) -> Value {
+    mismatch
+        .warnings
+        .iter()
+        .map(|warning| {
+            let path = warning.path();
+            json!({
+                "kind": warning.kind(),
+                "path": path.to_string(),
+                "path_elements": path_elements(&path),
+                "message": warning.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn path_elements(path: &DiffPath) -> Value {
+    path.0
+        .iter()
+        .map(|element| match element {
+            OwnedPathElement::Object(key) => Value::from(key.as_str()),
+            OwnedPathElement::ArrayEntry(index) => Value::from(*index),
+        })
+        .collect()
+}
+
+/// Reads the records of a report written by [`to_json`], e.g. to compare stored reports with
+/// [`crate::prelude::compare_reports`]. Values not captured by the comparison are read back as pairs of kinds.
+/// In reports with `shared` values of [`JsonOptions::dedupe_values`] the references to them are replaced by the
+/// values, other reports are read as they are.
+pub fn records_from_json(report: &Value) -> Result<Vec<DiffRecord>> {
+    let invalid = |what: &str| Error::InvalidReport(what.to_string());
+    let shared = report.get("shared").and_then(Value::as_array);
+    let resolve = |value: &Value| -> Result<Value> {
+        let Some(shared) = shared else {
+            return Ok(value.clone());
+        };
+        match shared_reference(value) {
+            Some(reference) => reference
+                .strip_prefix("#/shared/")
+                .and_then(|index| shared.get(index.parse::<usize>().ok()?))
+                .cloned()
+                .ok_or_else(|| invalid("reference to a missing shared value")),
+            None => Ok(value.clone()),
+        }
+    };
+    let labels = &report["summary"]["labels"];
+    let label = |side: &str| labels[side].as_str().unwrap_or(side).to_string();
+    let (left, right) = (label("left"), label("right"));
+    let diffs = report["diffs"]
+        .as_array()
+        .ok_or_else(|| invalid("missing diffs array"))?;
+    diffs
+        .iter()
+        .map(|diff| {
+            let diff_type: DiffType = diff["type"]
+                .as_str()
+                .ok_or_else(|| invalid("diff without type"))?
+                .parse()?;
+            let elements = diff["path_elements"]
+                .as_array()
+                .ok_or_else(|| invalid("diff without path_elements"))?;
+            let path = elements
+                .iter()
+                .map(|element| match element {
+                    Value::String(key) => Ok(OwnedPathElement::Object(key.clone())),
+                    Value::Number(n) => n
+                        .as_u64()
+                        .map(|i| OwnedPathElement::ArrayEntry(i as usize))
+                        .ok_or_else(|| invalid("path element is no index")),
+                    _ => Err(invalid("path element is no key or index")),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let values = match (diff.get(&left), diff.get(&right)) {
+                _ if diff.get("suppressed").is_some() => DiffValues::Summary {
+                    suppressed: diff["suppressed"].as_u64().unwrap_or_default() as usize,
+                },
+                (Some(l), Some(r)) => match diff.get("element") {
+                    Some(element) => DiffValues::Multiplicity {
+                        element: resolve(element)?,
+                        left: l.as_u64().unwrap_or_default() as usize,
+                        right: r.as_u64().unwrap_or_default() as usize,
+                    },
+                    None => DiffValues::Pair(resolve(l)?, resolve(r)?),
+                },
+                (Some(v), None) | (None, Some(v)) => DiffValues::Single(resolve(v)?),
+                (None, None) => DiffValues::Empty,
+            };
+            Ok(DiffRecord {
+                diff_type,
+                path: DiffPath(path),
+                values,
+                origin: None,
+                context: None,
+                string_diff: None,
+            })
+        })
+        .collect()
+}
+
+fn values_json(record: &DiffRecord, labels: &Labels) -> Value {
+    let side = match record.diff_type {
+        DiffType::RightExtra => &labels.right,
+        _ => &labels.left,
+    };
+    match &record.values {
+        DiffValues::Empty => json!({}),
+        DiffValues::Single(v) => json!({ side: v }),
+        DiffValues::Pair(l, r) => json!({ &labels.left: l, &labels.right: r }),
+        DiffValues::Summary { suppressed } => json!({ "suppressed": suppressed }),
+        DiffValues::Multiplicity {
+            element,
+            left,
+            right,
+        } => json!({"element": element, &labels.left: left, &labels.right: right}),
+        DiffValues::Redacted(l, r) => {
+            json!({ &labels.left: l.to_string(), &labels.right: r.to_string() })
+        }
+        DiffValues::KeyVariants { left, right } => {
+            json!({ &labels.left: left, &labels.right: right })
+        }
+    }
+}
+
+/// Category of the diffs matching no rule of a [`Classifier`].
+pub const UNCATEGORIZED: &str = "uncategorized";
+
+/// Sorts diffs into named categories by their path, e.g. `pricing` for everything below `/prices`.
+/// Rules are tried in the order they were added, the first one matching the JSON pointer of a diff decides its
+/// category, diffs matching none are [`UNCATEGORIZED`]. Globs match pointers segment by segment, `*` stands for
+/// any part of one segment and a segment `**` for any number of segments. Regexes match anywhere in the pointer.
+/// ```rust
+/// use json_diff_ng::compare_serde_values;
+/// use json_diff_ng::report::Classifier;
+/// use serde_json::json;
+/// let classifier = Classifier::default()
+///     .glob("/prices/**", "pricing")?
+///     .regex("^/meta/", "metadata")?;
+/// let left = json!({"prices": {"eur": 1}, "meta": {"build": 1}, "name": "a"});
+/// let right = json!({"prices": {"eur": 2}, "meta": {"build": 2}, "name": "b"});
+/// let diffs = compare_serde_values(&left, &right, false, &[])?;
+/// let classified = diffs.classified_diffs(&classifier);
+/// assert_eq!(classified.keys().collect::<Vec<_>>(), ["metadata", "pricing", "uncategorized"]);
+/// assert_eq!(classified["pricing"][0].to_string(), "Mismatched: .prices.eur.(1 != 2)");
+/// # Ok::<(), json_diff_ng::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Classifier {
+    rules: Vec<(PathPattern, String)>,
+}
+
+/// What the pointers of a [`Classifier`] rule are matched with.
+#[derive(Clone, Debug)]
+enum PathPattern {
+    /// Unescaped segments of a glob
+    Glob(Vec<String>),
+    Regex(Regex),
+}
+
+impl Classifier {
+    /// Adds a rule sorting diffs whose pointer matches the glob `pattern`, e.g. `/items/*/price`, into `category`.
+    /// Like pointers, globs are empty or start with `/`.
+    pub fn glob(mut self, pattern: &str, category: impl Into<String>) -> Result<Self> {
+        let segments = match pattern.strip_prefix('/') {
+            Some(segments) => segments.split('/').map(pointer::unescape).collect(),
+            None if pattern.is_empty() => Vec::new(),
+            None => return Err(Error::InvalidPointer(pattern.to_string())),
+        };
+        self.rules
+            .push((PathPattern::Glob(segments), category.into()));
+        Ok(self)
+    }
+
+    /// Adds a rule sorting diffs whose pointer matches the regex `pattern` into `category`.
+    pub fn regex(mut self, pattern: &str, category: impl Into<String>) -> Result<Self> {
+        let regex = Regex::new(pattern).map_err(Error::Regex)?;
+        self.rules
+            .push((PathPattern::Regex(regex), category.into()));
+        Ok(self)
+    }
+
+    /// Reads the rules from an array of objects holding a `category` and either a `glob` or a `regex`,
+    /// e.g. `[{"glob": "/prices/**", "category": "pricing"}, {"regex": "^/meta", "category": "metadata"}]`.
+    pub fn from_json(value: &Value) -> Result<Self> {
+        let invalid = |what: String| Error::InvalidClassifier(what);
+        let rules = value
+            .as_array()
+            .ok_or_else(|| invalid("expected an array of rules".to_string()))?;
+        rules
+            .iter()
+            .enumerate()
+            .try_fold(Self::default(), |classifier, (index, rule)| {
+                let field = |name: &str| rule.get(name).and_then(Value::as_str);
+                let category = field("category")
+                    .ok_or_else(|| invalid(format!("rule {index} has no category")))?;
+                match (field("glob"), field("regex")) {
+                    (Some(glob), None) => classifier.glob(glob, category),
+                    (None, Some(regex)) => classifier.regex(regex, category),
+                    _ => Err(invalid(format!(
+                        "rule {index} needs either a glob or a regex"
+                    ))),
+                }
+            })
+    }
+
+    /// Category of `record`, the one of the first rule matching its path.
+    pub fn category(&self, record: &DiffRecord) -> &str {
+        let pointer = record.path.to_pointer();
+        let segments: Vec<String> = record
+            .path
+            .0
+            .iter()
+            .map(|element| match element {
+                OwnedPathElement::Object(key) => key.clone(),
+                OwnedPathElement::ArrayEntry(index) => index.to_string(),
+            })
+            .collect();
+        self.rules
+            .iter()
+            .find(|(pattern, _)| match pattern {
+                PathPattern::Glob(glob) => glob_matches(glob, &segments),
+                PathPattern::Regex(regex) => regex.is_match(&pointer),
+            })
+            .map_or(UNCATEGORIZED, |(_, category)| category)
+    }
+
+    /// Categories diffs can be sorted into, the ones of the rules and [`UNCATEGORIZED`].
+    pub fn categories(&self) -> BTreeSet<&str> {
+        let rules = self.rules.iter().map(|(_, category)| category.as_str());
+        rules.chain([UNCATEGORIZED]).collect()
+    }
+
+    /// Number of diffs per category of `mismatch`, summarized records count as the diffs they stand for.
+    pub fn counts(&self, mismatch: &Mismatch) -> BTreeMap<String, usize> {
+        mismatch
+            .classified_diffs(self)
+            .into_iter()
+            .map(|(category, records)| (category, records.iter().map(DiffRecord::count).sum()))
+            .collect()
+    }
+}
+
+fn glob_matches(glob: &[String], segments: &[String]) -> bool {
+    match glob.split_first() {
+        None => segments.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=segments.len()).any(|skipped| glob_matches(rest, &segments[skipped..]))
+        }
+        Some((first, rest)) => segments.split_first().is_some_and(|(segment, others)| {
+            segment_matches(first, segment) && glob_matches(rest, others)
+        }),
+    }
+}
+
+/// Whether `segment` matches `pattern`, in which `*` stands for any part of the segment.
+fn segment_matches(pattern: &str, segment: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = segment.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// The report of [`to_json`] with the number of diffs per category of `classifier` in `summary.categories`,
+/// see [`Classifier::counts`].
+pub fn to_json_classified(mismatch: &Mismatch, classifier: &Classifier) -> Value {
+    let mut report = to_json(mismatch);
+    report["summary"]["categories"] = json!(classifier.counts(mismatch));
+    report
+}
+
+/// How [`to_markdown_with_format`], [`to_html_with_format`] and [`dot_with_format`] render values.
+/// The default renders values as json, like [`to_markdown`] does. Numbers are formatted only where they are
+/// the value of a diff, numbers inside arrays and objects stay json so the containers remain readable.
+/// The json reports always hold the raw values.
+/// ```rust
+/// use json_diff_ng::report::ValueFormat;
+/// use serde_json::json;
+/// let format = ValueFormat::default().thousands_separator(Some('\'')).decimals(Some(2));
+/// assert_eq!(format.render(&json!(1234567)), "1'234'567");
+/// assert_eq!(format.render(&json!(-1234.5)), "-1'234.50");
+/// assert_eq!(format.render(&json!("a")), "\"a\"");
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct ValueFormat {
+    /// Separator between groups of three digits of the integer part, none by default
+    pub thousands_separator: Option<char>,
+    /// Separator of the integer and the fractional part, `.` by default
+    pub decimal_separator: char,
+    /// Fixed number of decimal places of numbers that are not integers
+    pub decimals: Option<usize>,
+    /// Numbers of at least this magnitude, or non-zero numbers below its reciprocal, are written in scientific notation
+    pub scientific_threshold: Option<f64>,
+    /// Whether strings are quoted and escaped like json, on by default
+    pub quote_strings: bool,
+}
+
+impl Default for ValueFormat {
+    fn default() -> Self {
+        Self {
+            thousands_separator: None,
+            decimal_separator: '.',
+            decimals: None,
+            scientific_threshold: None,
+            quote_strings: true,
+        }
+    }
+}
+
+impl ValueFormat {
+    pub fn thousands_separator(mut self, separator: Option<char>) -> Self {
+        self.thousands_separator = separator;
+        self
+    }
+
+    pub fn decimal_separator(mut self, separator: char) -> Self {
+        self.decimal_separator = separator;
+        self
+    }
+
+    pub fn decimals(mut self, decimals: Option<usize>) -> Self {
+        self.decimals = decimals;
+        self
+    }
+
+    pub fn scientific_threshold(mut self, threshold: Option<f64>) -> Self {
+        self.scientific_threshold = threshold;
+        self
+    }
+
+    pub fn quote_strings(mut self, quote_strings: bool) -> Self {
+        self.quote_strings = quote_strings;
+        self
+    }
+
+    /// Renders `value` as table cell.
+    pub fn render(&self, value: &Value) -> String {
+        match value {
+            Value::Number(n) => self.number(n),
+            Value::String(s) if !self.quote_strings => s.clone(),
+            value => value.to_string(),
+        }
+    }
+
+    fn number(&self, n: &serde_json::Number) -> String {
+        let is_integer = n.is_i64() || n.is_u64();
+        let x = n.as_f64().unwrap_or_default();
+        let magnitude = if x < 0.0 { -x } else { x };
+        let scientific = self.scientific_threshold.is_some_and(|threshold| {
+            magnitude >= threshold || (magnitude > 0.0 && magnitude * threshold < 1.0)
+        });
+        let text = match (scientific, self.decimals) {
+            (true, Some(decimals)) => format!("{x:.decimals$e}"),
+            (true, None) => format!("{x:e}"),
+            (false, Some(decimals)) if !is_integer => format!("{x:.decimals$}"),
+            (false, _) => n.to_string(),
+        };
+        if scientific || text.contains(['e', 'E']) {
+            return text.replace('.', &self.decimal_separator.to_string());
+        }
+        let (sign, unsigned) = match text.strip_prefix('-') {
+            Some(unsigned) => ("-", unsigned),
+            None => ("", text.as_str()),
+        };
+        let (integer, fraction) = match unsigned.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (unsigned, None),
+        };
+        let mut rendered = String::from(sign);
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                if let Some(separator) = self.thousands_separator {
+                    rendered.push(separator);
+                }
+            }
+            rendered.push(digit);
+        }
+        if let Some(fraction) = fraction {
+            rendered.push(self.decimal_separator);
+            rendered.push_str(fraction);
+        }
+        rendered
+    }
+}
+
+/// A table of all diffs with one value column per document, below a header naming both.
+/// With [`crate::CompareOptions::array_context`] a column holds the equal elements around array diffs, with
+/// [`crate::CompareOptions::string_diffs`] one the changed lines of long strings.
+pub fn to_markdown(mismatch: &Mismatch) -> String {
+    to_markdown_with_format(mismatch, &ValueFormat::default())
+}
+
+/// Same as [`to_markdown`] with values rendered by `format`.
+pub fn to_markdown_with_format(mismatch: &Mismatch, format: &ValueFormat) -> String {
+    to_markdown_with_messages(mismatch, format, &Messages::default())
+}
+
+/// Same as [`to_markdown_with_format`] worded by `messages`.
+pub fn to_markdown_with_messages(
+    mismatch: &Mismatch,
+    format: &ValueFormat,
+    messages: &Messages,
+) -> String {
+    markdown(mismatch, format, messages, false)
+}
+
+/// Same as [`to_markdown_with_messages`], but the diffs inside the elements of arrays are summarized with one row per
+/// element, see [`Mismatch::per_element_summary`]. Each such array gets its own table below the table of the other
+/// diffs. Arrays whose elements only differ as a whole, like arrays of numbers, are listed in the diff table.
+/// ```rust
+/// use json_diff_ng::report::{to_markdown_by_element, Messages, ValueFormat};
+/// use serde_json::json;
+/// let left = json!({"items": [{"id": 1, "a": 1}, {"id": 2, "a": 2, "b": 3}]});
+/// let right = json!({"items": [{"id": 1, "a": 1}, {"id": 2, "a": 4, "c": 5}]});
+/// let diffs = json_diff_ng::compare_serde_values(&left, &right, false, &[]).unwrap();
+/// let markdown = to_markdown_by_element(&diffs, &ValueFormat::default(), &Messages::default());
+/// assert!(markdown.contains("| `[1]` | 1 | 1 | Extra on left: 1, Extra on right: 1, Mismatched: 1 | `a`, `b`, `c` |"));
+/// ```
+pub fn to_markdown_by_element(
+    mismatch: &Mismatch,
+    format: &ValueFormat,
+    messages: &Messages,
+) -> String {
+    markdown(mismatch, format, messages, true)
+}
+
+fn markdown(
+    mismatch: &Mismatch,
+    format: &ValueFormat,
+    messages: &Messages,
+    by_element: bool,
+) -> String {
+    let Labels { left, right } = mismatch.labels();
+    let heading = Messages::fill(
+        &messages.heading,
+        &format!("`{left}`"),
+        &format!("`{right}`"),
+    );
+    let mut markdown = format!("# {heading}\n\n");
+    if mismatch.is_empty() {
+        markdown.push_str(&format!("{}\n", messages.no_differences));
+        return markdown;
+    }
+    let cell = |text: &str| text.replace('|', "\\|").replace('\n', "<br>");
+    let (records, arrays) = split_by_element(mismatch, by_element);
+    if records.is_empty() {
+        return markdown + &element_tables_markdown(mismatch, &arrays, messages);
+    }
+    let columns = optional_columns(&records, messages);
+    let optional_headers: String = columns
+        .iter()
+        .map(|(header, _)| format!(" {} |", cell(header)))
+        .collect();
+    markdown.push_str(&format!(
+        "| {} | {} | {} | {} |{optional_headers}\n|---|---|---|---|{}\n",
+        cell(&messages.type_column),
+        cell(&messages.path_column),
+        cell(&left),
+        cell(&right),
+        "---|".repeat(columns.len())
+    ));
+    for record in records {
+        let (l, r) = value_cells(&record, format, messages);
+        let optional: String = columns
+            .iter()
+            .map(|(_, value)| format!(" {} |", cell(&value(&record))))
+            .collect();
+        markdown.push_str(&format!(
+            "| {} | `{}` | {} | {} |{optional}\n",
+            cell(messages.diff_type(record.diff_type)),
+            cell(&record.path.to_string()),
+            cell(&l),
+            cell(&r)
+        ));
+    }
+    if !arrays.is_empty() {
+        markdown.push('\n');
+    }
+    markdown + &element_tables_markdown(mismatch, &arrays, messages)
+}
+
+/// One markdown table of [`ElementSummary`] rows per array of `arrays`, headed by the path of the array.
+fn element_tables_markdown(mismatch: &Mismatch, arrays: &[DiffPath], messages: &Messages) -> String {
+    let cell = |text: &str| text.replace('|', "\\|");
+    let Labels { left, right } = mismatch.labels();
+    let mut markdown = String::new();
+    for (n, array) in arrays.iter().enumerate() {
+        if n > 0 {
+            markdown.push('\n');
+        }
+        markdown.push_str(&format!(
+            "## `{}`\n\n| {} | {} | {} | {} | {} |\n|---|---|---|---|---|\n",
+            cell(&array.to_string()),
+            cell(&messages.element_column),
+            cell(&left),
+            cell(&right),
+            cell(&messages.changes_column),
+            cell(&messages.changed_fields_column)
+        ));
+        for summary in mismatch.per_element_summary(array) {
+            let fields: Vec<String> = summary
+                .changed_fields
+                .iter()
+                .map(|field| format!("`{}`", cell(field)))
+                .collect();
+            markdown.push_str(&format!(
+                "| `[{}]` | {} | {} | {} | {} |\n",
+                summary.index,
+                index_cell(summary.left_index),
+                index_cell(summary.right_index),
+                cell(&changes_cell(&summary, messages)),
+                fields.join(", ")
+            ));
+        }
+    }
+    markdown
+}
+
+/// A standalone html table of all diffs, like [`to_markdown`].
+pub fn to_html(mismatch: &Mismatch) -> String {
+    to_html_with_format(mismatch, &ValueFormat::default())
+}
+
+/// Same as [`to_html`] with values rendered by `format`.
+pub fn to_html_with_format(mismatch: &Mismatch, format: &ValueFormat) -> String {
+    to_html_with_messages(mismatch, format, &Messages::default())
+}
+
+/// Same as [`to_html_with_format`] worded by `messages`.
+pub fn to_html_with_messages(
+    mismatch: &Mismatch,
+    format: &ValueFormat,
+    messages: &Messages,
+) -> String {
+    html(mismatch, format, messages, false)
+}
+
+/// Same as [`to_html_with_messages`] with one row per changed array element, like [`to_markdown_by_element`].
+pub fn to_html_by_element(mismatch: &Mismatch, format: &ValueFormat, messages: &Messages) -> String {
+    html(mismatch, format, messages, true)
+}
+
+fn html(mismatch: &Mismatch, format: &ValueFormat, messages: &Messages, by_element: bool) -> String {
+    let Labels { left, right } = mismatch.labels();
+    let (left, right) = (escape_html(&left), escape_html(&right));
+    let heading = Messages::fill(
+        &escape_html(&messages.heading),
+        &format!("<code>{left}</code>"),
+        &format!("<code>{right}</code>"),
+    );
+    let mut html = format!("<h1>{heading}</h1>\n");
+    if mismatch.is_empty() {
+        html.push_str(&format!(
+            "<p>{}</p>\n",
+            escape_html(&messages.no_differences)
+        ));
+        return html;
+    }
+    let (records, arrays) = split_by_element(mismatch, by_element);
+    if records.is_empty() {
+        return html + &element_tables_html(mismatch, &arrays, messages);
+    }
+    let columns = optional_columns(&records, messages);
+    let optional_headers: String = columns
+        .iter()
+        .map(|(header, _)| format!("<th>{}</th>", escape_html(header)))
+        .collect();
+    html.push_str(&format!(
+        "<table>\n<tr><th>{}</th><th>{}</th><th>{left}</th><th>{right}</th>{optional_headers}</tr>\n",
+        escape_html(&messages.type_column),
+        escape_html(&messages.path_column)
+    ));
+    for record in records {
+        let (l, r) = value_cells(&record, format, messages);
+        let optional: String = columns
+            .iter()
+            .map(|(_, value)| {
+                let text = escape_html(&value(&record));
+                format!("<td>{}</td>", text.replace('\n', "<br>"))
+            })
+            .collect();
+        html.push_str(&format!(
+            "<tr><td>{}</td><td><code>{}</code></td><td>{}</td><td>{}</td>{optional}</tr>\n",
+            escape_html(messages.diff_type(record.diff_type)),
+            escape_html(&record.path.to_string()),
+            escape_html(&l),
+            escape_html(&r)
+        ));
+    }
+    html.push_str("</table>\n");
+    html + &element_tables_html(mismatch, &arrays, messages)
+}
+
+/// One html table of [`ElementSummary`] rows per array of `arrays`, like [`element_tables_markdown`].
+fn element_tables_html(mismatch: &Mismatch, arrays: &[DiffPath], messages: &Messages) -> String {
+    let Labels { left, right } = mismatch.labels();
+    let mut html = String::new();
+    for array in arrays {
+        html.push_str(&format!(
+            "<h2><code>{}</code></h2>\n<table>\n<tr><th>{}</th><th>{}</th><th>{}</th><th>{}</th><th>{}</th></tr>\n",
+            escape_html(&array.to_string()),
+            escape_html(&messages.element_column),
+            escape_html(&left),
+            escape_html(&right),
+            escape_html(&messages.changes_column),
+            escape_html(&messages.changed_fields_column)
+        ));
+        for summary in mismatch.per_element_summary(array) {
+            let fields: Vec<String> = summary
+                .changed_fields
+                .iter()
+                .map(|field| format!("<code>{}</code>", escape_html(field)))
+                .collect();
+            html.push_str(&format!(
+                "<tr><td><code>[{}]</code></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                summary.index,
+                index_cell(summary.left_index),
+                index_cell(summary.right_index),
+                escape_html(&changes_cell(&summary, messages)),
+                fields.join(", ")
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+    html
+}
+
+/// The records of the diff table and the arrays summarized per element instead, if `by_element`.
+/// Arrays are summarized if any of their diffs lies inside an element, only the outermost of nested arrays.
+fn split_by_element(mismatch: &Mismatch, by_element: bool) -> (Vec<DiffRecord>, Vec<DiffPath>) {
+    let records = mismatch.records();
+    if !by_element {
+        return (records, Vec::new());
+    }
+    // path of the outermost array of a record and whether the record lies inside one of its elements
+    let outermost_array = |record: &DiffRecord| {
+        let elements = &record.path.0;
+        let depth = elements
+            .iter()
+            .position(|element| matches!(element, OwnedPathElement::ArrayEntry(_)))?;
+        Some((DiffPath(elements[..depth].to_vec()), depth + 1 < elements.len()))
+    };
+    let arrays: BTreeSet<DiffPath> = records
+        .iter()
+        .filter_map(outermost_array)
+        .filter_map(|(array, inside)| inside.then_some(array))
+        .collect();
+    let records = records
+        .into_iter()
+        .filter(|record| match outermost_array(record) {
+            Some((array, _)) => !arrays.contains(&array),
+            None => true,
+        })
+        .collect();
+    (records, arrays.into_iter().collect())
+}
+
+/// The counts of an [`ElementSummary`] as table cell, e.g. `Mismatched: 3, Extra on right: 1`.
+fn changes_cell(summary: &ElementSummary, messages: &Messages) -> String {
+    let changes: Vec<String> = summary
+        .counts
+        .iter()
+        .map(|(diff_type, count)| format!("{}: {count}", messages.diff_type(*diff_type)))
+        .collect();
+    changes.join(", ")
+}
+
+/// Original index of an element as table cell, empty if it does not exist on the side.
+fn index_cell(index: Option<usize>) -> String {
+    index.map(|index| index.to_string()).unwrap_or_default()
+}
+
+/// A trailing table column with its header and cells.
+type Column<'m> = (&'m str, fn(&DiffRecord) -> String);
+
+/// The size change, context and string diff columns of the markdown and html tables, each present if any record has
+/// a value for it.
+fn optional_columns<'m>(records: &[DiffRecord], messages: &'m Messages) -> Vec<Column<'m>> {
+    let mut columns: Vec<Column<'m>> = Vec::new();
+    if records.iter().any(|record| !size_cell(record).is_empty()) {
+        columns.push((&messages.size_column, size_cell));
+    }
+    if records.iter().any(|record| record.context.is_some()) {
+        columns.push((&messages.context_column, context_cell));
+    }
+    if records.iter().any(|record| record.string_diff.is_some()) {
+        columns.push((&messages.string_diff_column, string_diff_cell));
+    }
+    columns
+}
+
+/// The [`crate::string_diff::StringDiff`] of a record as table cell, its lines are broken by the tables.
+fn string_diff_cell(record: &DiffRecord) -> String {
+    record
+        .string_diff
+        .as_ref()
+        .map(ToString::to_string)
+        .unwrap_or_default()
+}
+
+/// The [`DiffRecord::size_delta`] of a record as table cell, empty if it has none or it is zero.
+fn size_cell(record: &DiffRecord) -> String {
+    record
+        .size_delta()
+        .filter(|delta| !delta.is_zero())
+        .map(|delta| delta.to_string())
+        .unwrap_or_default()
+}
+
+/// The [`crate::ArrayContext`] of a record as table cell, empty outside of arrays.
+fn context_cell(record: &DiffRecord) -> String {
+    record
+        .context
+        .as_ref()
+        .map(ToString::to_string)
+        .unwrap_or_default()
+}
+
+/// Left and right value of a record as table cells.
+fn value_cells(record: &DiffRecord, format: &ValueFormat, messages: &Messages) -> (String, String) {
+    if let Some((l, r)) = record.values.kinds() {
+        if record.diff_type == DiffType::TypeChange {
+            return (l.type_name().to_string(), r.type_name().to_string());
+        }
+    }
+    match (&record.values, record.diff_type) {
+        (DiffValues::Single(v), DiffType::RightExtra) => (String::new(), format.render(v)),
+        (DiffValues::Single(v), _) => (format.render(v), String::new()),
+        (DiffValues::Pair(l, r), _) => (format.render(l), format.render(r)),
+        (DiffValues::Multiplicity { left, right, .. }, _) => (left.to_string(), right.to_string()),
+        (DiffValues::Redacted(l, r), _) => (l.to_string(), r.to_string()),
+        (DiffValues::KeyVariants { left, right }, _) => {
+            (escape_invisible(left), escape_invisible(right))
+        }
+        (DiffValues::Summary { suppressed }, _) => (
+            messages.more.replace("{count}", &suppressed.to_string()),
+            String::new(),
+        ),
+        (DiffValues::Empty, _) => (String::new(), String::new()),
+    }
+}
+
+/// Longest value label of a [`dot`] graph, longer values are cut off.
+const DOT_VALUE_CHARS: usize = 40;
+
+/// A graphviz DOT digraph of the diff tree: the root node names both documents, inner nodes are the keys and
+/// indices along the paths of all diffs, and each diff is a leaf colored by its [`DiffType`] holding its values.
+/// Node identifiers are the JSON pointers of the paths below `root`, leaves add `#` and their position.
+/// A comparison without any records yields a single `no differences` node.
+/// ```rust
+/// use json_diff_ng::{compare_serde_values, report};
+/// use serde_json::json;
+/// let diffs = compare_serde_values(&json!({"a": [1]}), &json!({"a": [2]}), false, &[]).unwrap();
+/// let dot = report::dot(&diffs);
+/// assert!(dot.contains(r#""root/a/0" -> "root/a/0#0";"#));
+/// ```
+pub fn dot(mismatch: &Mismatch) -> String {
+    dot_with_format(mismatch, &ValueFormat::default())
+}
+
+/// Same as [`dot`] with values rendered by `format`.
+pub fn dot_with_format(mismatch: &Mismatch, format: &ValueFormat) -> String {
+    let mut graph =
+        String::from("digraph diff {\n  node [shape=box, style=filled, fillcolor=white];\n");
+    let records = mismatch.records_sorted();
+    if records.is_empty() {
+        graph.push_str("  \"root\" [label=\"no differences\"];\n}\n");
+        return graph;
+    }
+    let Labels { left, right } = mismatch.labels();
+    let label = format!("{left} vs {right}");
+    graph.push_str(&format!("  \"root\" [label=\"{}\"];\n", escape_dot(&label)));
+    let messages = Messages::default();
+    let mut nodes = BTreeSet::new();
+    for (n, record) in records.iter().enumerate() {
+        let mut parent = "root".to_string();
+        for element in &record.path.0 {
+            let id = match element {
+                OwnedPathElement::Object(key) => format!("{parent}/{}", pointer::escape(key)),
+                OwnedPathElement::ArrayEntry(index) => format!("{parent}/{index}"),
+            };
+            if nodes.insert(id.clone()) {
+                let (id, label) = (escape_dot(&id), escape_dot(&element.to_string()));
+                graph.push_str(&format!("  \"{id}\" [label=\"{label}\"];\n"));
+                graph.push_str(&format!("  \"{}\" -> \"{id}\";\n", escape_dot(&parent)));
+            }
+            parent = id;
+        }
+        let (l, r) = value_cells(record, format, &messages);
+        let values = match (l.is_empty(), r.is_empty()) {
+            (false, false) => format!("{} != {}", truncate(&l), truncate(&r)),
+            _ => truncate(&format!("{l}{r}")),
+        };
+        let label = escape_dot(&format!("{}\n{values}", record.diff_type));
+        let (leaf, parent) = (escape_dot(&format!("{parent}#{n}")), escape_dot(&parent));
+        let color = dot_color(record.diff_type);
+        graph.push_str(&format!(
+            "  \"{leaf}\" [label=\"{label}\", fillcolor=\"{color}\"];\n  \"{parent}\" -> \"{leaf}\";\n"
+        ));
+    }
+    graph.push_str("}\n");
+    graph
+}
+
+fn dot_color(diff_type: DiffType) -> &'static str {
+    match diff_type {
+        DiffType::LeftExtra => "#f4cccc",
+        DiffType::RightExtra => "#d9ead3",
+        DiffType::Mismatch
+        | DiffType::RootMismatch
+        | DiffType::DimensionMismatch
+        | DiffType::TypeChange => "#fff2cc",
+        DiffType::MultiplicityChange => "#fce5cd",
+        DiffType::OrderOnly | DiffType::KeyAlmostEqual => "#eeeeee",
+        DiffType::ExpectedChangeMissing => "#ea9999",
+    }
+}
+
+/// Escapes `text` for a quoted DOT string, line breaks become DOT line breaks.
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(DOT_VALUE_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use crate::{
+        compare_serde_values, compare_serde_values_with_options, CompareOptions, Expectation,
+    };
+
+    use super::*;
+
+    fn labelled() -> Mismatch {
+        let options = CompareOptions::default().labels("production", "staging");
+        let left = json!({"replicas": 3, "debug": false});
+        let right = json!({"replicas": 1, "tier": "<b>"});
+        compare_serde_values_with_options(&left, &right, &options).unwrap()
+    }
+
+    #[test]
+    fn json_summary_names_both_documents() {
+        let report = to_json(&labelled());
+        assert_eq!(
+            report["summary"],
+            json!({
+                "labels": {"left": "production", "right": "staging"},
+                "equal": false,
+                "left_only": 1,
+                "right_only": 1,
+                "unequal_values": 1
+            })
+        );
+        let diffs = report["diffs"].as_array().unwrap();
+        assert!(diffs.contains(&json!({
+            "type": "Mismatched",
+            "path": ".replicas",
+            "path_elements": ["replicas"],
+            "production": 3,
+            "staging": 1
+        })));
+        assert!(diffs.contains(&json!({
+            "type": "Extra on right",
+            "path": ".tier",
+            "path_elements": ["tier"],
+            "staging": "<b>"
+        })));
+    }
+
+    #[test]
+    fn warnings_of_every_kind_are_serialized() {
+        let options = CompareOptions::default()
+            .report_order_differences(true)
+            .array_identity(|_path, element| Some(element.get("id")?.to_string()));
+        let left = json!({"items": [{"id": 1, "v": 1}, {"id": 1, "v": 2}]});
+        let right = json!({"items": [{"id": 1, "v": 1}]});
+        let mismatch = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        let kinds: Vec<_> = mismatch.warnings.iter().map(|w| w.kind()).collect();
+        assert_eq!(kinds, vec!["options", "duplicate_identity"]);
+        assert_eq!(
+            to_json(&mismatch)["warnings"],
+            json!([
+                {
+                    "kind": "options",
+                    "path": "",
+                    "path_elements": [],
+                    "message": "report_order_differences has no effect without sort_arrays"
+                },
+                {
+                    "kind": "duplicate_identity",
+                    "path": ".items",
+                    "path_elements": ["items"],
+                    "message": "Identity 1 occurs 2 times on the left, its elements are paired in order"
+                }
+            ])
+        );
+        let pages = to_value_paged(&mismatch, 1);
+        assert!(pages
+            .iter()
+            .all(|page| page["warnings"] == to_json(&mismatch)["warnings"]));
+        let equal = compare_serde_values_with_options(&right, &right, &options).unwrap();
+        assert!(equal.is_empty());
+        assert_eq!(equal.warnings.len(), 1);
+    }
+
+    fn german() -> Messages {
+        serde_json::from_value(json!({
+            "left_extra": "Nur links",
+            "right_extra": "Nur rechts",
+            "mismatch": "Abweichung",
+            "text_header": "Vergleich von {left} (links) mit {right} (rechts)",
+            "heading": "Unterschiede zwischen {left} und {right}",
+            "no_differences": "Keine Unterschiede.",
+            "type_column": "Typ",
+            "path_column": "Pfad",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn messages_translate_headers_but_not_paths_or_values() {
+        let mismatch = labelled();
+        let messages = german();
+        let format = ValueFormat::default();
+        let markdown = to_markdown_with_messages(&mismatch, &format, &messages);
+        assert!(markdown.starts_with(
+            "# Unterschiede zwischen `production` und `staging`\n\n| Typ | Pfad | production | staging |\n"
+        ));
+        assert!(markdown.contains("| Abweichung | `.replicas` | 3 | 1 |\n"));
+        let html = to_html_with_messages(&mismatch, &format, &messages);
+        assert!(html.starts_with(
+            "<h1>Unterschiede zwischen <code>production</code> und <code>staging</code></h1>\n"
+        ));
+        assert!(html.contains("<tr><th>Typ</th><th>Pfad</th>"));
+        assert!(html.contains("<td>Nur rechts</td><td><code>.tier</code></td>"));
+        let text = to_text_with_messages(&mismatch, &messages);
+        assert!(text.starts_with("Vergleich von production (links) mit staging (rechts)\n"));
+        assert!(text.contains("Nur links: .debug.(false)\n"));
+        assert!(text.contains("Abweichung: .replicas.(3 != 1)\n"));
+        let equal = compare_serde_values(&json!(1), &json!(1), false, &[]).unwrap();
+        assert!(to_markdown_with_messages(&equal, &format, &messages)
+            .ends_with("Keine Unterschiede.\n"));
+        // untranslated fields keep their English default
+        assert_eq!(messages.diff_type(DiffType::TypeChange), "Type changed");
+        assert_eq!(
+            to_text_with_messages(&mismatch, &Messages::default()),
+            to_text(&mismatch)
+        );
+        assert!(serde_json::from_value::<Messages>(json!({"mismatched": "x"})).is_err());
+    }
+
+    #[test]
+    fn mismatched_strings_report_their_size_delta() {
+        let left = json!({"note": "short", "n": 1});
+        let right = json!({"note": "much longer", "n": 2});
+        let mismatch = compare_serde_values(&left, &right, false, &[]).unwrap();
+        let markdown = to_markdown(&mismatch);
+        assert!(markdown
+            .contains("| Type | Path | left | right | Size change |\n|---|---|---|---|---|\n"));
+        assert!(markdown.contains("| Mismatched | `.n` | 1 | 2 |  |\n"));
+        assert!(markdown.contains(
+            r#"| Mismatched | `.note` | "short" | "much longer" | length +6, +6 bytes |"#
+        ));
+        assert!(to_html(&mismatch).contains("<td>length +6, +6 bytes</td>"));
+        let diffs = &to_json(&mismatch)["diffs"];
+        assert_eq!(diffs[1]["size_delta"], json!({"length": 6, "bytes": 6}));
+        assert_eq!(diffs[0].get("size_delta"), None);
+        let same_size = compare_serde_values(&json!("a"), &json!("b"), false, &[]).unwrap();
+        assert_eq!(
+            to_json(&same_size)["diffs"][0]["size_delta"],
+            json!({"length": 0, "bytes": 0})
+        );
+        assert!(!to_markdown(&same_size).contains("Size change"));
+        let top = mismatch.top_diffs(1, crate::mismatch::size_change);
+        assert_eq!(top[0].2.to_string(), r#".note.("short" != "much longer")"#);
+    }
+
+    #[test]
+    fn markdown_and_html_headers_carry_the_labels() {
+        let mismatch = labelled();
+        let markdown = to_markdown(&mismatch);
+        assert!(markdown.starts_with(
+            "# Diff of `production` and `staging`\n\n| Type | Path | production | staging |\n"
+        ));
+        assert!(markdown.contains("| Mismatched | `.replicas` | 3 | 1 |\n"));
+        let html = to_html(&mismatch);
+        assert!(html.starts_with("<h1>Diff of <code>production</code> and <code>staging</code>"));
+        assert!(html.contains("<td>&quot;&lt;b&gt;&quot;</td>"));
+        assert!(
+            to_text(&mismatch).starts_with("Comparing production (left) with staging (right)\n")
+        );
+    }
+
+    #[test]
+    fn tables_show_the_array_context_when_requested() {
+        let options = CompareOptions::default().array_context(1);
+        let left = json!({"log": ["a", "b", "c"], "n": 1});
+        let right = json!({"log": ["a", "x", "c"], "n": 2});
+        let mismatch = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        let markdown = to_markdown(&mismatch);
+        assert!(
+            markdown.contains("| Type | Path | left | right | Context |\n|---|---|---|---|---|\n")
+        );
+        assert!(markdown.contains(
+            "| Mismatched | `.log.[1]` | \"b\" | \"x\" | equal before 0/0; equal after 2/2 |\n"
+        ));
+        assert!(markdown.contains("| Mismatched | `.n` | 1 | 2 |  |\n"));
+        let html = to_html(&mismatch);
+        assert!(html.contains("<th>Context</th>"));
+        assert!(html.contains("<td>equal before 0/0; equal after 2/2</td></tr>"));
+        let plain = compare_serde_values_with_options(&left, &right, &CompareOptions::default());
+        assert!(!to_markdown(&plain.unwrap()).contains("Context"));
+    }
+
+    #[test]
+    fn tables_show_the_changed_lines_of_long_strings() {
+        let text: Vec<String> = (1..=40).map(|n| format!("paragraph {n}")).collect();
+        let mut edited = text.clone();
+        edited[22] = "paragraph 23, revised".to_string();
+        let (left, right) = (
+            json!({"body": text.join("\n")}),
+            json!({"body": edited.join("\n")}),
+        );
+        let options = CompareOptions::default().string_diffs(100);
+        let mismatch = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        let string_diff = mismatch.records()[0].string_diff.clone().unwrap();
+        assert_eq!(string_diff.hunks[0].left_line, 23);
+        let cell = "@@ -23 +23 @@<br>-paragraph 23<br>+paragraph 23, revised";
+        assert!(to_markdown(&mismatch).contains(&format!(" | {cell} |\n")));
+        let html = to_html(&mismatch);
+        assert!(html.contains("<th>Changed lines</th>"));
+        assert!(html.contains(&format!("<td>{cell}</td></tr>")));
+        let short = CompareOptions::default().string_diffs(10_000);
+        let mismatch = compare_serde_values_with_options(&left, &right, &short).unwrap();
+        assert!(mismatch.records()[0].string_diff.is_none());
+    }
+
+    #[test]
+    fn markdown_renders_numbers_by_the_value_format() {
+        let left = json!({"bytes": 1234567890, "ratio": 0.00000012, "name": "a", "list": [1000]});
+        let right = json!({"bytes": -98765, "ratio": 0.5, "name": "b", "list": [2000]});
+        let mismatch = compare_serde_values(&left, &right, false, &[]).unwrap();
+        let raw = to_markdown(&mismatch);
+        assert!(raw.contains("| Mismatched | `.bytes` | 1234567890 | -98765 |\n"));
+        assert!(raw.contains("| Mismatched | `.ratio` | 1.2e-7 | 0.5 |\n"));
+        let format = ValueFormat::default()
+            .thousands_separator(Some(','))
+            .decimals(Some(3))
+            .quote_strings(false);
+        let markdown = to_markdown_with_format(&mismatch, &format);
+        assert!(markdown.contains("| Mismatched | `.bytes` | 1,234,567,890 | -98,765 |\n"));
+        assert!(markdown.contains("| Mismatched | `.ratio` | 0.000 | 0.500 |\n"));
+        assert!(markdown.contains("| Mismatched | `.name` | a | b |\n"));
+        assert!(markdown.contains("| Mismatched | `.list.[0]` | 1,000 | 2,000 |\n"));
+        let scientific = format.clone().scientific_threshold(Some(1e6));
+        let markdown = to_markdown_with_format(&mismatch, &scientific);
+        assert!(markdown.contains("| Mismatched | `.bytes` | 1.235e9 | -98,765 |\n"));
+        assert!(markdown.contains("| Mismatched | `.ratio` | 1.200e-7 | 0.500 |\n"));
+        let european = format.thousands_separator(Some('.')).decimal_separator(',');
+        let html = to_html_with_format(&mismatch, &european);
+        assert!(html.contains("<td>1.234.567.890</td><td>-98.765</td>"));
+        assert!(html.contains("<td>0,000</td><td>0,500</td>"));
+        assert!(dot_with_format(&mismatch, &european).contains("1.234.567.890 != -98.765"));
+        // the json report keeps the raw values
+        assert_eq!(to_json(&mismatch)["diffs"][0]["left"], json!(1234567890));
+    }
+
+    /// Checks that `dot` is one digraph of statements with balanced quotes and brackets.
+    fn assert_balanced_dot(dot: &str) {
+        let body = dot
+            .strip_prefix("digraph diff {\n")
+            .and_then(|body| body.strip_suffix("}\n"))
+            .unwrap();
+        for line in body.lines() {
+            assert!(line.starts_with("  ") && line.ends_with(';'), "{line}");
+            let (mut quoted, mut escaped, mut brackets) = (false, false, 0);
+            for c in line.chars() {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' if quoted => escaped = true,
+                    '"' => quoted = !quoted,
+                    '[' if !quoted => brackets += 1,
+                    ']' if !quoted => brackets -= 1,
+                    _ => {}
+                }
+            }
+            assert!(!quoted && brackets == 0, "{line}");
+        }
+    }
+
+    #[test]
+    fn dot_graph_of_nested_diffs() {
+        let left = json!({"a": {"say \"hi\"": 1, "back\\slash": [true, 2]}, "gone": "x"});
+        let right = json!({"a": {"say \"hi\"": 2, "back\\slash": [false, 2, "y"]}});
+        let graph = dot(&compare_serde_values(&left, &right, false, &[]).unwrap());
+        assert_balanced_dot(&graph);
+        for statement in [
+            r##""root" [label="left vs right"];"##,
+            r##""root/a/say \"hi\"" [label="say \"hi\""];"##,
+            r##""root/a" -> "root/a/say \"hi\"";"##,
+            r##""root/a/back\\slash/0" [label="[0]"];"##,
+            r##""root/a/back\\slash/2#1" [label="Extra on right\n\"y\"", fillcolor="#d9ead3"];"##,
+            r##""root/a/say \"hi\"#2" [label="Mismatched\n1 != 2", fillcolor="#fff2cc"];"##,
+            r##""root/gone#3" [label="Extra on left\n\"x\"", fillcolor="#f4cccc"];"##,
+        ] {
+            assert!(graph.contains(statement), "{statement} missing in\n{graph}");
+        }
+        // every inner node is declared once
+        assert_eq!(graph.matches(r#""root/a" [label"#).count(), 1);
+
+        let equal = compare_serde_values(&left, &left, false, &[]).unwrap();
+        assert_eq!(
+            dot(&equal),
+            "digraph diff {\n  node [shape=box, style=filled, fillcolor=white];\n  \"root\" [label=\"no differences\"];\n}\n"
+        );
+    }
+
+    #[test]
+    fn pages_cover_all_diffs_once() {
+        let left: Vec<u32> = (0..25).collect();
+        let right: Vec<u32> = (100..125).collect();
+        let mismatch = compare_serde_values(&json!(left), &json!(right), false, &[]).unwrap();
+        let pages = to_value_paged(&mismatch, 10);
+        assert_eq!(pages.len(), 3);
+        let mut paths = Vec::new();
+        for (index, page) in pages.iter().enumerate() {
+            assert_eq!(
+                page["page"],
+                json!({"index": index, "total_pages": 3, "total_records": 25})
+            );
+            assert_eq!(page["summary"], to_json(&mismatch)["summary"]);
+            let records = records_from_json(page).unwrap();
+            assert_eq!(records.len(), [10, 10, 5][index]);
+            paths.extend(records.into_iter().map(|r| r.path));
+        }
+        let expected: Vec<_> = mismatch
+            .records_sorted()
+            .into_iter()
+            .map(|r| r.path)
+            .collect();
+        assert_eq!(paths, expected);
+        assert_eq!(pages, to_value_paged(&mismatch, 10));
+
+        let equal = compare_serde_values(&json!(left), &json!(left), false, &[]).unwrap();
+        let pages = to_value_paged(&equal, 10);
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0]["diffs"], json!([]));
+    }
+
+    #[test]
+    fn records_are_read_back_from_json() {
+        let left = json!({"a": [1, {"b.c": 2}], "gone": true, "same": 0});
+        let right = json!({"a": [1, {"b.c": 3}, 4], "same": 0});
+        for options in [
+            CompareOptions::default(),
+            CompareOptions::default().labels("old", "new"),
+        ] {
+            let mismatch = compare_serde_values_with_options(&left, &right, &options).unwrap();
+            let records = records_from_json(&to_json(&mismatch)).unwrap();
+            assert_eq!(records, mismatch.records());
+        }
+        let err = records_from_json(&json!({"diffs": [{"type": "Nope"}]})).unwrap_err();
+        assert_eq!(err.code(), crate::ErrorCode::InvalidReport);
+    }
+
+    #[test]
+    fn repeated_values_are_referenced_once() {
+        let default = json!({"retries": 3, "steps": [1, 2, 4]});
+        let mut left = serde_json::Map::new();
+        let mut right = serde_json::Map::new();
+        for n in 0..100 {
+            left.insert(format!("s{n:02}"), json!({"policy": null}));
+            right.insert(format!("s{n:02}"), json!({"policy": default, "list": [n]}));
+        }
+        let mismatch = compare_serde_values(&left.into(), &right.into(), false, &[]).unwrap();
+        let options = JsonOptions::default().dedupe_values(true);
+        let report = to_json_with_options(&mismatch, &options);
+        assert_eq!(report["shared"], json!([default]));
+        let diffs = report["diffs"].as_array().unwrap();
+        let references = diffs
+            .iter()
+            .filter(|diff| diff["right"] == json!({"$ref": "#/shared/0"}))
+            .count();
+        assert_eq!(references, 100);
+        // values occurring once stay in place
+        assert!(diffs.iter().any(|diff| diff["right"] == json!([7])));
+        assert_eq!(records_from_json(&report).unwrap(), mismatch.records());
+        let plain = to_json_with_options(&mismatch, &JsonOptions::default());
+        assert_eq!(plain, to_json(&mismatch));
+        assert!(plain.get("shared").is_none());
+    }
+
+    #[test]
+    fn values_looking_like_references_are_read_back_as_they_are() {
+        let left = json!({"schema": {"$ref": "#/shared/0"}, "other": {"$ref": "#/shared/7"}});
+        let right = json!({"schema": {"type": "object"}, "other": [1]});
+        let mismatch = compare_serde_values(&left, &right, false, &[]).unwrap();
+        assert_eq!(records_from_json(&to_json(&mismatch)).unwrap(), mismatch.records());
+        let options = JsonOptions::default().dedupe_values(true);
+        let report = to_json_with_options(&mismatch, &options);
+        assert_eq!(report["shared"], json!([{"$ref": "#/shared/7"}]));
+        assert_eq!(records_from_json(&report).unwrap(), mismatch.records());
+    }
+
+    #[test]
+    fn formatted_diffs_are_grouped_by_parent_and_colored_by_side() {
+        let left = json!({"a": {"b": [1, 2], "c": "x"}, "d": 1, "e": {"f": null}});
+        let right = json!({"a": {"b": [1], "c": 2}, "d": 1, "g": true});
+        let diffs = compare_serde_values(&left, &right, false, &[]).unwrap();
+        assert_eq!(
+            format_diffs(&diffs, ColorMode::Never),
+            "<root>:\n  - e: {\"f\":null}\n  + g: true\n.a:\n  ~ c: \"x\" → 2\n.a.b:\n  - [1]: 2\n"
+        );
+        let colored = format_diffs(&diffs, ColorMode::Always);
+        assert!(colored.starts_with("\x1b[1m<root>:\x1b[0m\n  \x1b[31m- e: {\"f\":null}\x1b[0m\n"));
+        assert!(colored.contains("  \x1b[32m+ g: true\x1b[0m\n"));
+        assert!(colored.contains("  \x1b[33m~ c: \"x\" → 2\x1b[0m\n"));
+        let diffs = compare_serde_values(&json!({}), &json!([]), false, &[]).unwrap();
+        assert_eq!(
+            format_diffs(&diffs, ColorMode::Never),
+            "<root>:\n  ~ .: Type changed (object != array)\n"
+        );
+        let diffs = compare_serde_values(&json!([1]), &json!([1]), false, &[]).unwrap();
+        assert_eq!(format_diffs(&diffs, ColorMode::Always), "");
+    }
+
+    #[test]
+    fn formatted_diffs_follow_the_report_options() {
+        let options = CompareOptions::default()
+            .record_origins(true)
+            .expect("/z", Expectation::MustDiffer);
+        let left = json!({"z": 1, "a": {}, "b": {"c": 1}});
+        let right = json!({"z": 2, "a": [], "b": {}});
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        let messages = Messages {
+            type_change: "Typ".into(),
+            ..Messages::default()
+        };
+        let options = FormatOptions::default().values(false).messages(messages);
+        assert_eq!(
+            format_diffs_with_options(&diffs, &options),
+            "<root>:\n  ~ a: Typ (object != array)\n  ~ z: 1 → 2 (expected)\n.b:\n  - c\n"
+        );
+        let explained = format_diffs_with_options(&diffs, &options.clone().explain(true));
+        assert!(explained.contains("  ~ z: 1 → 2 (expected) ["), "{explained}");
+        let left = json!({"a": {"b": 1}, "c": 1});
+        let diffs = compare_serde_values(&left, &json!({"a": {"b": 2}}), false, &[]).unwrap();
+        let sorted = "<root>:\n  - c\n.a:\n  ~ b: 1 → 2\n";
+        assert_eq!(format_diffs_with_options(&diffs, &options), sorted);
+        let unsorted = ".a:\n  ~ b: 1 → 2\n<root>:\n  - c\n";
+        assert_eq!(format_diffs_with_options(&diffs, &options.sorted(false)), unsorted);
+    }
+
+    #[test]
+    fn tables_by_element_summarize_changed_array_elements() {
+        let left = json!({"items": [{"id": 1, "name": "a"}, {"id": 2, "price": {"net": 1}}], "n": [1]});
+        let right = json!({"items": [{"id": 1, "name": "b"}, {"id": 2, "price": {"net": 2}, "stock": 4}], "n": [2]});
+        let diffs = compare_serde_values(&left, &right, false, &[]).unwrap();
+        let (format, messages) = (ValueFormat::default(), Messages::default());
+        let markdown = to_markdown_by_element(&diffs, &format, &messages);
+        assert_eq!(
+            markdown,
+            "# Diff of `left` and `right`\n\n\
+             | Type | Path | left | right |\n|---|---|---|---|\n\
+             | Mismatched | `.n.[0]` | 1 | 2 |\n\n\
+             ## `.items`\n\n\
+             | Element | left | right | Changes | Changed fields |\n|---|---|---|---|---|\n\
+             | `[0]` | 0 | 0 | Mismatched: 1 | `name` |\n\
+             | `[1]` | 1 | 1 | Extra on right: 1, Mismatched: 1 | `price`, `stock` |\n"
+        );
+        let html = to_html_by_element(&diffs, &format, &messages);
+        assert!(html.contains("<h2><code>.items</code></h2>"));
+        assert!(html.contains(
+            "<tr><td><code>[1]</code></td><td>1</td><td>1</td><td>Extra on right: 1, Mismatched: 1</td>\
+             <td><code>price</code>, <code>stock</code></td></tr>"
+        ));
+        assert!(!html.contains("net"));
+        let flat = to_markdown(&diffs);
+        assert!(flat.contains("`.items.[1].price.net`") && !flat.contains("## "));
+    }
+
+    #[test]
+    fn labels_default_to_the_sides() {
+        let mismatch = compare_serde_values(&json!(1), &json!(1), false, &[]).unwrap();
+        assert_eq!(summary(&mismatch).labels, Labels::default());
+        assert_eq!(
+            to_markdown(&mismatch),
+            "# Diff of `left` and `right`\n\nNo differences.\n"
+        );
+    }
+
+    #[test]
+    fn first_matching_rule_classifies_a_diff() {
+        let left = json!({"prices": {"eur": 1, "usd": 1}, "items": [{"price": 1, "name": "a"}], "meta": 1});
+        let right = json!({"prices": {"eur": 2, "usd": 2}, "items": [{"price": 2, "name": "b"}], "meta": 2});
+        let diffs = compare_serde_values(&left, &right, false, &[]).unwrap();
+        let rules = json!([
+            {"glob": "/prices/usd", "category": "us"},
+            {"glob": "/prices/**", "category": "pricing"},
+            {"glob": "/items/*/pri*", "category": "pricing"},
+            {"regex": "^/prices", "category": "never"},
+        ]);
+        let classifier = Classifier::from_json(&rules).unwrap();
+        let classified: Vec<(String, Vec<String>)> = diffs
+            .classified_diffs(&classifier)
+            .into_iter()
+            .map(|(category, records)| {
+                let pointers = records.iter().map(|r| r.path.to_pointer()).collect();
+                (category, pointers)
+            })
+            .collect();
+        let expected = [
+            ("pricing", vec!["/items/0/price", "/prices/eur"]),
+            ("uncategorized", vec!["/items/0/name", "/meta"]),
+            ("us", vec!["/prices/usd"]),
+        ];
+        let expected: Vec<(String, Vec<String>)> = expected
+            .into_iter()
+            .map(|(category, pointers)| {
+                (
+                    category.to_string(),
+                    pointers.into_iter().map(String::from).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(classified, expected);
+        let report = to_json_classified(&diffs, &classifier);
+        assert_eq!(
+            report["summary"]["categories"],
+            json!({"pricing": 2, "uncategorized": 2, "us": 1})
+        );
+        let err = Classifier::from_json(&json!([{"glob": "/a"}])).unwrap_err();
+        assert_eq!(err.code(), crate::ErrorCode::InvalidClassifier);
+        assert!(Classifier::default().glob("prices", "pricing").is_err());
+    }
+}
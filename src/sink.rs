@@ -0,0 +1,397 @@
+//! Streaming comparison that hands each diff to a callback as soon as it is found, without building a [`Mismatch`].
+//! Paths are borrowed from a buffer reused for the whole comparison, so reporting a diff does not allocate.
+//! ```rust
+//! use std::ops::ControlFlow;
+//! use json_diff_ng::{sink::compare_with_sink, CompareOptions};
+//! use serde_json::json;
+//! let mut first = None;
+//! let flow = compare_with_sink(&json!({"a": [1, 2]}), &json!({"a": [1, 3]}), &CompareOptions::default(), &mut |diff_type, path, _| {
+//!     first = Some((diff_type, path.len()));
+//!     ControlFlow::Break(())
+//! }).unwrap();
+//! assert!(flow.is_break());
+//! assert_eq!(first, Some((json_diff_ng::DiffType::Mismatch, 2)));
+//! ```
+//!
+//! [`Mismatch`]: crate::Mismatch
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::ControlFlow;
+
+use serde_json::{Map, Value};
+
+use crate::process::{
+    align_block, align_sorted_block, exceeds_depth, intersect_maps, prepare_arrays, sequence_diff, BlockPair,
+    ListDiffHandler,
+};
+use crate::{CompareMode, CompareOptions, DiffType, Error, PathElement, Result, ValueKind};
+
+/// Receives the type, path and values of each diff found by [`compare_with_sink`].
+/// One-sided diffs carry their value twice, like the entries of [`crate::Mismatch::all_diffs`].
+pub type DiffSink<'s> =
+    dyn FnMut(DiffType, &[PathElement], Option<(&Value, &Value)>) -> ControlFlow<()> + 's;
+
+/// Compares `a` and `b` like [`crate::compare_serde_values_with_options`], but reports every diff to `sink` immediately.
+/// The comparison stops as soon as `sink` returns [`ControlFlow::Break`], which is passed on to the caller.
+/// The diffs are those of [`crate::Mismatch::all_diffs_with_type_changes`], in no particular order, and keys
+/// looking alike are reported as [`DiffType::KeyAlmostEqual`] at their object with both spellings as string values.
+/// Values are passed as `None` with [`CompareOptions::no_value_capture`]. Options that need the whole result before reporting, like template mode,
+/// array identities, multiplicity verification, order differences, extra limits, array modes, sampling and wildcards in sorted arrays,
+/// are rejected with [`Error::Misc`]. Unwrap pointers apply, a fallback to the whole document is not reported.
+pub fn compare_with_sink(
+    a: &Value,
+    b: &Value,
+    options: &CompareOptions,
+    sink: &mut DiffSink,
+) -> Result<ControlFlow<()>> {
+    let comparator = options.build()?;
+    let options = comparator.options();
+    if let Some(option) = unsupported_option(options) {
+        return Err(Error::Misc(format!(
+            "compare_with_sink does not support {option}"
+        )));
+    }
+    let (a, b) = options.transformed(a, b)?;
+    let (a, b, _) = options.refs_resolved(a, b)?;
+    let (a, b, _) = options.unwrapped(&a, &b)?;
+    if exceeds_depth(a, options.max_depth) || exceeds_depth(b, options.max_depth) {
+        return Err(Error::DepthLimitExceeded(options.max_depth));
+    }
+    let mut walker = Walker {
+        options,
+        sink,
+        path: Vec::new(),
+        flow: ControlFlow::Continue(()),
+    };
+    walker.walk(a, b)?;
+    Ok(walker.flow)
+}
+
+fn unsupported_option(options: &CompareOptions) -> Option<&'static str> {
+    [
+        (options.mode == CompareMode::Template, "template mode"),
+        (options.has_array_identity(), "array identities"),
+        (options.verify_multiplicity, "multiplicity verification"),
+        (options.report_order_differences, "order differences"),
+        (options.max_extras_per_object.is_some(), "extra limits"),
+        (!options.array_modes.is_empty(), "array modes"),
+        (options.sample.is_some(), "sampling"),
+        (options.has_decimal_places(), "decimal places"),
+        (options.has_float_tolerance(), "float tolerances"),
+        (!options.expectations.is_empty(), "expectations"),
+        (options.array_context.is_some(), "array context"),
+        (!options.object_sets.is_empty(), "object sets"),
+        (options.has_ignored_paths(), "ignored paths"),
+        (
+            options.sort_arrays && !options.wildcard_values.is_empty(),
+            "wildcards in sorted arrays",
+        ),
+    ]
+    .into_iter()
+    .find_map(|(set, name)| set.then_some(name))
+}
+
+struct Walker<'a, 's, 'f> {
+    options: &'a CompareOptions,
+    sink: &'s mut DiffSink<'f>,
+    /// Path to the values currently compared
+    path: Vec<PathElement<'a>>,
+    flow: ControlFlow<()>,
+}
+
+impl<'a> Walker<'a, '_, '_> {
+    fn walk(&mut self, a: &'a Value, b: &'a Value) -> Result<()> {
+        if self.options.is_wildcard(a)
+            || self.options.is_wildcard(b)
+            || self.options.is_ignored_value_pair(a, b)
+        {
+            return Ok(());
+        }
+        match (a, b) {
+            (Value::Object(a), Value::Object(b)) => self.walk_objects(a, b),
+            (Value::Array(a), Value::Array(b)) => self.walk_arrays(a, b),
+            (a, b) => {
+                if a != b {
+                    let diff_type =
+                        match ValueKind::is_container_change(ValueKind::of(a), ValueKind::of(b)) {
+                            true => DiffType::TypeChange,
+                            false => DiffType::Mismatch,
+                        };
+                    self.emit(diff_type, None, a, b);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn walk_objects(&mut self, a: &'a Map<String, Value>, b: &'a Map<String, Value>) -> Result<()> {
+        let diff = intersect_maps(a, b, self.options)?;
+        for (left, right) in &diff.lookalikes {
+            let (left, right) = (Value::from(left.as_str()), Value::from(right.as_str()));
+            self.emit(DiffType::KeyAlmostEqual, None, &left, &right);
+            if self.flow.is_break() {
+                return Ok(());
+            }
+        }
+        for (key, map, diff_type) in diff
+            .left_only
+            .iter()
+            .map(|key| (key, a, DiffType::LeftExtra))
+            .chain(
+                diff.right_only
+                    .iter()
+                    .map(|key| (key, b, DiffType::RightExtra)),
+            )
+        {
+            // the key is borrowed from its map, so it outlives the sets of the intersection
+            let Some((key, value)) = map.get_key_value(key) else {
+                continue;
+            };
+            self.emit(diff_type, Some(PathElement::Object(key)), value, value);
+            if self.flow.is_break() {
+                return Ok(());
+            }
+        }
+        for (a_key, b_key) in &diff.intersection {
+            let (Some((key, a_value)), Some(b_value)) = (a.get_key_value(a_key), b.get(b_key))
+            else {
+                return Err(Error::Structure(format!(
+                    "Key {a_key} of the key intersection is missing in an object"
+                )));
+            };
+            self.path.push(PathElement::Object(key));
+            let result = self.walk(a_value, b_value);
+            self.path.pop();
+            result?;
+            if self.flow.is_break() {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    /// Mirrors the pairing of [`crate::process`]: deleted and inserted ranges are one-sided,
+    /// replaced blocks are aligned by similarity. Indices refer to the prepared arrays.
+    fn walk_arrays(&mut self, a: &'a [Value], b: &'a [Value]) -> Result<()> {
+        let ((prepared_a, left_origins), (prepared_b, right_origins)) =
+            prepare_arrays(a, b, self.options, &self.path);
+        // prepared arrays may be sorted copies, their elements are reported from the original arrays
+        let left = |i: usize| left_origins.as_ref().map_or(&a[i], |o| &a[o[i]]);
+        let right = |j: usize| right_origins.as_ref().map_or(&b[j], |o| &b[o[j]]);
+        let ListDiffHandler {
+            replaced,
+            deletion,
+            insertion,
+            ..
+        } = sequence_diff(&prepared_a, &prepared_b);
+        let one_sided = deletion
+            .into_iter()
+            .flat_map(|(o, ol)| (o..o + ol).map(|i| (DiffType::LeftExtra, i, left(i))))
+            .chain(
+                insertion
+                    .into_iter()
+                    .flat_map(|(n, nl)| (n..n + nl).map(|j| (DiffType::RightExtra, j, right(j)))),
+            );
+        for (diff_type, index, value) in one_sided {
+            self.emit(
+                diff_type,
+                Some(PathElement::ArrayEntry(index)),
+                value,
+                value,
+            );
+            if self.flow.is_break() {
+                return Ok(());
+            }
+        }
+        let keys = self.options.key_matchers();
+        for (o, ol, n, nl) in replaced {
+            let (Some(block_a), Some(block_b)) =
+                (prepared_a.get(o..o + ol), prepared_b.get(n..n + nl))
+            else {
+                return Err(Error::Structure(format!(
+                    "Replaced block {o}+{ol} / {n}+{nl} exceeds the arrays"
+                )));
+            };
+            let block_pairs = match self.options.sort_arrays {
+                true => align_sorted_block(block_a, block_b, keys),
+                false => align_block(block_a, block_b, keys),
+            };
+            for pair in block_pairs {
+                match pair {
+                    BlockPair::Both(i, j) => {
+                        self.path.push(PathElement::ArrayEntry(o + i));
+                        let result = self.walk(left(o + i), right(n + j));
+                        self.path.pop();
+                        result?;
+                    }
+                    BlockPair::Left(i) => {
+                        let element = Some(PathElement::ArrayEntry(o + i));
+                        self.emit(DiffType::LeftExtra, element, left(o + i), left(o + i));
+                    }
+                    BlockPair::Right(j) => {
+                        let element = Some(PathElement::ArrayEntry(n + j));
+                        self.emit(DiffType::RightExtra, element, right(n + j), right(n + j));
+                    }
+                }
+                if self.flow.is_break() {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reports a diff at the current path, extended by `element` if given.
+    fn emit(
+        &mut self,
+        diff_type: DiffType,
+        element: Option<PathElement<'a>>,
+        a: &Value,
+        b: &Value,
+    ) {
+        let values = (!self.options.no_value_capture).then_some((a, b));
+        match element {
+            Some(element) => {
+                self.path.push(element);
+                self.flow = (self.sink)(diff_type, &self.path, values);
+                self.path.pop();
+            }
+            None => self.flow = (self.sink)(diff_type, &self.path, values),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use crate::{compare_serde_values_with_options, DiffValues, ErrorCode};
+
+    use super::*;
+
+    type Collected = Vec<(DiffType, String, Option<(Value, Value)>)>;
+
+    fn collect(a: &Value, b: &Value, options: &CompareOptions) -> Collected {
+        let mut diffs = Vec::new();
+        let flow = compare_with_sink(a, b, options, &mut |diff_type, path, values| {
+            let path = path.iter().map(|e| format!("{e:?}")).collect::<String>();
+            let values = values.map(|(l, r)| (l.clone(), r.clone()));
+            diffs.push((diff_type, path, values));
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        assert!(flow.is_continue());
+        diffs.sort_by_key(|d| format!("{d:?}"));
+        diffs
+    }
+
+    fn expected(a: &Value, b: &Value, options: &CompareOptions) -> Collected {
+        let mismatch = compare_serde_values_with_options(a, b, options).unwrap();
+        let mut diffs: Collected = mismatch
+            .all_diffs_with_type_changes()
+            .into_iter()
+            .map(|(diff_type, entry)| {
+                let path = entry.path.iter().map(|e| format!("{e:?}")).collect();
+                let values = entry.values.map(|(l, r)| (l.clone(), r.clone()));
+                (diff_type, path, values)
+            })
+            .collect();
+        for record in &mismatch.extra_records {
+            if let DiffValues::KeyVariants { left, right } = &record.values {
+                let path = record.path.0.iter().map(|e| format!("{e:?}"));
+                let values = (Value::from(left.as_str()), Value::from(right.as_str()));
+                diffs.push((record.diff_type, path.collect(), Some(values)));
+            }
+        }
+        diffs.sort_by_key(|d| format!("{d:?}"));
+        diffs
+    }
+
+    #[test]
+    fn sink_reports_the_diffs_of_all_diffs() {
+        let left = json!({
+            "name": "a",
+            "gone": {"x": 1},
+            "_id": 1,
+            "list": [1, 2, {"k": "v", "_id": 2}, [4, 5], 6],
+            "nested": {"deep": [{"a": 1}, {"a": 2}], "same": true},
+            "swapped": {}
+        });
+        let right = json!({
+            "name": "b",
+            "new": null,
+            "_id": 3,
+            "list": [6, 1, {"k": "w", "_id": 4}, [5], 7, 8],
+            "nested": {"deep": [{"a": 2}, {"a": 3}, {"a": 1}], "same": true},
+            "swapped": []
+        });
+        let cases = [
+            CompareOptions::default(),
+            CompareOptions::default().sort_arrays(true),
+            CompareOptions::default().ignore_key_patterns(["^_id$"]),
+            CompareOptions::default()
+                .sort_arrays(true)
+                .ignore_key_patterns(["^_id$"]),
+            CompareOptions::default().ignore_value_patterns(["^[a-w]$"]),
+        ];
+        for options in cases {
+            let diffs = collect(&left, &right, &options);
+            assert!(!diffs.is_empty());
+            assert_eq!(diffs, expected(&left, &right, &options));
+        }
+        assert!(collect(&left, &left, &CompareOptions::default()).is_empty());
+        let left = json!({"o": {"name\u{200b}": 1, "userId": 1}});
+        let right = json!({"o": {"name": 2, "user_id": 2}});
+        for options in [
+            CompareOptions::default(),
+            CompareOptions::default().match_lookalike_keys(true),
+            CompareOptions::default().canonical_keys(true),
+        ] {
+            let diffs = collect(&left, &right, &options);
+            assert!(diffs.iter().any(|(t, _, _)| *t == DiffType::KeyAlmostEqual));
+            assert_eq!(diffs, expected(&left, &right, &options));
+        }
+        let (scalar, other) = (json!(1), json!("1"));
+        let options = CompareOptions::default();
+        assert_eq!(
+            collect(&scalar, &other, &options),
+            expected(&scalar, &other, &options)
+        );
+    }
+
+    #[test]
+    fn break_stops_the_comparison() {
+        let left = json!({"a": [1, 2, 3], "b": 1, "c": 2});
+        let right = json!({"a": [4, 5, 6], "b": 2, "c": 3});
+        let mut calls = 0;
+        let flow = compare_with_sink(&left, &right, &CompareOptions::default(), &mut |_, _, _| {
+            calls += 1;
+            ControlFlow::Break(())
+        })
+        .unwrap();
+        assert!(flow.is_break());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn values_are_withheld_without_value_capture() {
+        let options = CompareOptions::default().no_value_capture(true);
+        let diffs = collect(&json!({"a": 1}), &json!({"a": 2, "b": 3}), &options);
+        assert_eq!(diffs.len(), 2);
+        assert!(diffs.iter().all(|(_, _, values)| values.is_none()));
+    }
+
+    #[test]
+    fn options_needing_the_whole_result_are_rejected() {
+        let options = CompareOptions::default()
+            .sort_arrays(true)
+            .report_order_differences(true);
+        let err = compare_with_sink(&json!([]), &json!([]), &options, &mut |_, _, _| {
+            ControlFlow::Continue(())
+        })
+        .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::Other);
+        assert!(err.to_string().contains("order differences"));
+    }
+}
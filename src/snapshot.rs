@@ -0,0 +1,259 @@
+//! Binary snapshots of parsed documents, for comparing the same large golden document against new outputs
+//! without parsing it on every run. A snapshot holds the parsed document as is with its structural hash and a
+//! fingerprint of the options changing how it is parsed, and only loads with the same ones.
+//! ```rust
+//! use json_diff_ng::snapshot::PreparedDocument;
+//! use json_diff_ng::CompareOptions;
+//! use serde_json::json;
+//! let comparator = CompareOptions::default().build().unwrap();
+//! let dir = tempfile::TempDir::new().unwrap();
+//! let path = dir.path().join("golden.snap");
+//! PreparedDocument::parse(&comparator, br#"{"a": [1, 2]}"#).unwrap().save(&path).unwrap();
+//! let golden = PreparedDocument::load(&path, &comparator).unwrap();
+//! let diffs = comparator.compare(golden.value(), &json!({"a": [1, 3]})).unwrap();
+//! assert_eq!(diffs.all_diffs().len(), 1);
+//! ```
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use vg_errortools::FatIOError;
+
+use crate::mismatch::{stable_hash, structural_hash};
+use crate::process::Comparator;
+use crate::{CompareOptions, Error, Result, Side};
+
+/// Starts every snapshot file, the last byte is the version of the format.
+const MAGIC: &[u8; 8] = b"jdsnap\x00\x03";
+
+/// A parsed document for comparisons with the [`Comparator`] it was prepared for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreparedDocument {
+    value: Value,
+    fingerprint: u64,
+}
+
+/// Contents of a snapshot file following [`MAGIC`].
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    fingerprint: u64,
+    /// [`structural_hash`] of the document, to detect damaged files decoding to another document
+    hash: u64,
+    document: Node,
+}
+
+/// A json value in a form serializers without self-description like bincode can read back.
+#[derive(Serialize, Deserialize)]
+enum Node {
+    Null,
+    Bool(bool),
+    PosInt(u64),
+    NegInt(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Node>),
+    /// Members in document order
+    Object(Vec<(String, Node)>),
+    /// Number with all its digits, written with the `arbitrary_precision` feature
+    Decimal(String),
+}
+
+impl PreparedDocument {
+    /// Prepares `value` for comparisons with `comparator`.
+    pub fn new(comparator: &Comparator, value: Value) -> Self {
+        Self {
+            value,
+            fingerprint: fingerprint(comparator.options()),
+        }
+    }
+
+    /// Parses `json` for comparisons with `comparator`, within its [`CompareOptions::max_depth`].
+    pub fn parse(comparator: &Comparator, json: &[u8]) -> Result<Self> {
+        let value = match comparator.parse(json, Side::Left) {
+            Ok((value, _)) => value,
+            // the document may end up on either side
+            Err(Error::ParseLeft(e)) => return Err(Error::JSON(e)),
+            Err(e) => return Err(e),
+        };
+        Ok(Self::new(comparator, value))
+    }
+
+    /// The prepared document.
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    pub fn into_value(self) -> Value {
+        self.value
+    }
+
+    /// Writes the snapshot to the file `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let snapshot = Snapshot {
+            fingerprint: self.fingerprint,
+            hash: structural_hash(&self.value),
+            document: Node::from(&self.value),
+        };
+        let mut bytes = MAGIC.to_vec();
+        bincode::serialize_into(&mut bytes, &snapshot)
+            .map_err(|e| Error::InvalidSnapshot(e.to_string()))?;
+        let path = path.as_ref();
+        std::fs::write(path, bytes).map_err(|e| io_error(e, path))
+    }
+
+    /// Reads the snapshot file `path` for comparisons with `comparator`. Fails with [`Error::InvalidSnapshot`] if the
+    /// file is no snapshot or damaged, or if it was prepared with another [`CompareOptions::max_depth`] or
+    /// [`CompareOptions::parse_mode`] than the ones of `comparator`.
+    pub fn load(path: impl AsRef<Path>, comparator: &Comparator) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| io_error(e, path))?;
+        let invalid = |message: &str| Error::InvalidSnapshot(message.to_string());
+        let contents = bytes
+            .strip_prefix(MAGIC)
+            .ok_or_else(|| invalid("not a snapshot of this version"))?;
+        let snapshot: Snapshot =
+            bincode::deserialize(contents).map_err(|e| Error::InvalidSnapshot(e.to_string()))?;
+        if snapshot.fingerprint != fingerprint(comparator.options()) {
+            return Err(invalid("prepared with other options"));
+        }
+        let value = Value::from(snapshot.document);
+        if structural_hash(&value) != snapshot.hash {
+            return Err(invalid("the document does not match its hash"));
+        }
+        Ok(Self {
+            value,
+            fingerprint: snapshot.fingerprint,
+        })
+    }
+}
+
+/// Whether `path` names a snapshot file, by its `.snap` extension.
+pub fn is_snapshot_path(path: impl AsRef<Path>) -> bool {
+    path.as_ref().extension().is_some_and(|e| e == "snap")
+}
+
+/// Hash of the options changing how a document is parsed. The others only apply when comparing, so documents
+/// are stored as parsed and compared with whatever options load them.
+fn fingerprint(options: &CompareOptions) -> u64 {
+    stable_hash((options.max_depth as u64, options.parse_mode))
+}
+
+fn io_error(e: std::io::Error, path: &Path) -> Error {
+    Error::from(FatIOError::from_std_io_err(e, path.into()))
+}
+
+impl From<&Value> for Node {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Null => Node::Null,
+            Value::Bool(b) => Node::Bool(*b),
+            Value::Number(n) if cfg!(feature = "arbitrary_precision") => {
+                Node::Decimal(n.to_string())
+            }
+            Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+                (Some(n), _) => Node::PosInt(n),
+                (None, Some(n)) => Node::NegInt(n),
+                (None, None) => Node::Float(n.as_f64().unwrap_or_default()),
+            },
+            Value::String(s) => Node::String(s.clone()),
+            Value::Array(a) => Node::Array(a.iter().map(Node::from).collect()),
+            Value::Object(o) => {
+                Node::Object(o.iter().map(|(k, v)| (k.clone(), v.into())).collect())
+            }
+        }
+    }
+}
+
+impl From<Node> for Value {
+    fn from(node: Node) -> Self {
+        match node {
+            Node::Null => Value::Null,
+            Node::Bool(b) => Value::Bool(b),
+            Node::PosInt(n) => Value::Number(n.into()),
+            Node::NegInt(n) => Value::Number(n.into()),
+            Node::Float(n) => Number::from_f64(n).map_or(Value::Null, Value::Number),
+            Node::Decimal(n) => n.parse().map_or(Value::Null, Value::Number),
+            Node::String(s) => Value::String(s),
+            Node::Array(a) => Value::Array(a.into_iter().map(Value::from).collect()),
+            Node::Object(o) => Value::Object(
+                o.into_iter()
+                    .map(|(k, v)| (k, Value::from(v)))
+                    .collect::<Map<String, Value>>(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+    use crate::ErrorCode;
+
+    #[test]
+    fn reloaded_snapshots_compare_like_fresh_parses() {
+        let golden = br#"{"b": [1, 2.5, -3, {"z": null, "a": "x"}], "a": {"deep": [[true]]}, "big": 18446744073709551615, "price": 12345678901234567890.125}"#;
+        let actual = json!({"a": {"deep": [[false]]}, "b": [1, 2.5, -4, {"a": "y"}], "big": 1});
+        let options = CompareOptions::default().ignore_key_patterns(["^z$"]);
+        let comparator = options.build().unwrap();
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("golden.snap");
+        PreparedDocument::parse(&comparator, golden)
+            .unwrap()
+            .save(&path)
+            .unwrap();
+        let loaded = PreparedDocument::load(&path, &comparator).unwrap();
+        let fresh: Value = serde_json::from_slice(golden).unwrap();
+        assert_eq!(loaded.value(), &fresh);
+        // members keep their order, not only their values
+        assert_eq!(loaded.value().to_string(), fresh.to_string());
+        assert_eq!(
+            comparator.compare(loaded.value(), &actual).unwrap(),
+            comparator.compare(&fresh, &actual).unwrap()
+        );
+        let comparing = CompareOptions::default()
+            .sort_arrays(true)
+            .record_origins(true)
+            .max_input_bytes(Some(1))
+            .key_normalizer(|key: &str| key.to_uppercase())
+            .labels("golden", "actual")
+            .build()
+            .unwrap();
+        let loaded = PreparedDocument::load(&path, &comparing).unwrap();
+        assert_eq!(
+            comparing.compare(loaded.value(), &actual).unwrap(),
+            comparing.compare(&fresh, &actual).unwrap()
+        );
+    }
+
+    #[test]
+    fn snapshots_only_load_with_their_options() {
+        let comparator = CompareOptions::default().build().unwrap();
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("golden.snap");
+        PreparedDocument::new(&comparator, json!([1]))
+            .save(&path)
+            .unwrap();
+        let shallow = CompareOptions::default().max_depth(8).build().unwrap();
+        let err = PreparedDocument::load(&path, &shallow).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::InvalidSnapshot);
+        assert_eq!(
+            err.to_string(),
+            "Invalid snapshot: prepared with other options"
+        );
+
+        let json = dir.path().join("golden.json");
+        std::fs::write(&json, "[1]").unwrap();
+        let err = PreparedDocument::load(&json, &comparator).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid snapshot: not a snapshot of this version"
+        );
+        let mut truncated = std::fs::read(&path).unwrap();
+        truncated.pop();
+        std::fs::write(&path, truncated).unwrap();
+        assert!(PreparedDocument::load(&path, &comparator).is_err());
+        assert!(is_snapshot_path(&path) && !is_snapshot_path(&json));
+    }
+}
@@ -1,103 +1,194 @@
-use std::borrow::Cow;
+//! Deep sorting of arrays, for comparisons ignoring the order of array elements.
+//! Values of the same type are ordered by their contents: numbers numerically, strings and object keys
+//! lexicographically, arrays and objects element by element. Values of different types are ordered by a fixed
+//! precedence of their types, `null < bool < number < string < array < object`, so mixed-type arrays sort alike on
+//! both sides whatever their input order, and elements of the same type end up next to each other.
+//! ```rust
+//! use json_diff_ng::sort::sort_value;
+//! use serde_json::json;
+//! let sorted = sort_value(&json!([{"a": 1}, "x", [2], 1, null, true, "b", 0]), &[]);
+//! assert_eq!(sorted, json!([null, true, 0, 1, "b", "x", [2], {"a": 1}]));
+//! ```
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
 
 use regex::Regex;
 use serde_json::Value;
 
+use crate::keys::KeyMatcher;
+
 /// Returns a deep-sorted copy of the [`serde_json::Value`]
 pub fn sort_value(v: &Value, ignore_keys: &[Regex]) -> Value {
+    sort_value_filtered(v, KeyMatcher::new(ignore_keys, &[]))
+}
+
+pub(crate) fn sort_value_filtered(v: &Value, keys: KeyMatcher) -> Value {
     match v {
         Value::Array(a) => Value::Array(
             preprocess_array(
                 true,
-                &a.iter().map(|e| sort_value(e, ignore_keys)).collect(),
-                ignore_keys,
+                &a.iter()
+                    .map(|e| sort_value_filtered(e, keys))
+                    .collect::<Vec<_>>(),
+                keys,
+                None,
             )
             .into_owned(),
         ),
         Value::Object(a) => Value::Object(
             a.iter()
-                .map(|(k, v)| (k.clone(), sort_value(v, ignore_keys)))
+                .map(|(k, v)| (k.clone(), sort_value_filtered(v, keys)))
                 .collect(),
         ),
         v => v.clone(),
     }
 }
 
+/// Drops the elements `keep` rejects, then sorts the array if requested.
 pub(crate) fn preprocess_array<'a>(
     sort_arrays: bool,
-    a: &'a Vec<Value>,
-    ignore_keys: &[Regex],
-) -> Cow<'a, Vec<Value>> {
-    if sort_arrays || !ignore_keys.is_empty() {
-        let mut owned = a.to_owned();
-        owned.sort_by(|a, b| compare_values(a, b, ignore_keys));
-        Cow::Owned(owned)
-    } else {
-        Cow::Borrowed(a)
+    a: &'a [Value],
+    keys: KeyMatcher,
+    keep: Option<&dyn Fn(&Value) -> bool>,
+) -> Cow<'a, [Value]> {
+    preprocess_array_with_origins(sort_arrays, a, keys, keep).0
+}
+
+/// Same as [`preprocess_array`], additionally returns the original index of each element when elements were moved or dropped.
+pub(crate) fn preprocess_array_with_origins<'a>(
+    sort_arrays: bool,
+    a: &'a [Value],
+    keys: KeyMatcher,
+    keep: Option<&dyn Fn(&Value) -> bool>,
+) -> (Cow<'a, [Value]>, Option<Vec<usize>>) {
+    let keep = keep.filter(|keep| !a.iter().all(keep));
+    let sort = sort_arrays || keys.ignores_any();
+    if keep.is_none() && !sort {
+        return (Cow::Borrowed(a), None);
+    }
+    let mut origins: Vec<usize> = match keep {
+        Some(keep) => (0..a.len()).filter(|i| keep(&a[*i])).collect(),
+        None => (0..a.len()).collect(),
+    };
+    if sort {
+        // elements equal apart from ignored keys are ordered by their complete values, so both sides order them
+        // alike whatever their input order, and the stable sort keeps completely equal ones in place
+        let complete = KeyMatcher::new(&[], &[]);
+        origins.sort_by(|i, j| {
+            compare_values(&a[*i], &a[*j], keys)
+                .then_with(|| compare_values(&a[*i], &a[*j], complete))
+        });
+    }
+    let values = origins.iter().map(|i| a[*i].clone()).collect();
+    (Cow::Owned(values), Some(origins))
+}
+
+/// Position of the type of `value` in the order of values of different types, see the [module docs](self).
+pub(crate) fn type_rank(value: &Value) -> u8 {
+    match value {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::Array(_) => 4,
+        Value::Object(_) => 5,
     }
 }
-fn compare_values(a: &Value, b: &Value, ignore_keys: &[Regex]) -> std::cmp::Ordering {
+
+fn compare_values(a: &Value, b: &Value, keys: KeyMatcher) -> core::cmp::Ordering {
     match (a, b) {
-        (Value::Null, Value::Null) => std::cmp::Ordering::Equal,
-        (Value::Null, _) => std::cmp::Ordering::Less,
-        (_, Value::Null) => std::cmp::Ordering::Greater,
         (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
         (Value::Number(a), Value::Number(b)) => {
             if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
                 return a.cmp(&b);
             }
             if let (Some(a), Some(b)) = (a.as_f64(), b.as_f64()) {
-                return a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal);
+                return a.partial_cmp(&b).unwrap_or(core::cmp::Ordering::Equal);
             }
             // Handle other number types if needed
-            std::cmp::Ordering::Equal
+            core::cmp::Ordering::Equal
         }
         (Value::String(a), Value::String(b)) => a.cmp(b),
         (Value::Array(a), Value::Array(b)) => {
-            let a = preprocess_array(true, a, ignore_keys);
-            let b = preprocess_array(true, b, ignore_keys);
+            let a = preprocess_array(true, a, keys, None);
+            let b = preprocess_array(true, b, keys, None);
             for (a, b) in a.iter().zip(b.iter()) {
-                let cmp = compare_values(a, b, ignore_keys);
-                if cmp != std::cmp::Ordering::Equal {
+                let cmp = compare_values(a, b, keys);
+                if cmp != core::cmp::Ordering::Equal {
                     return cmp;
                 }
             }
             a.len().cmp(&b.len())
         }
         (Value::Object(a), Value::Object(b)) => {
-            let mut keys_a: Vec<_> = a.keys().collect();
-            let mut keys_b: Vec<_> = b.keys().collect();
+            // ignored keys must not count towards the lengths either, or the order is not total
+            let mut keys_a: Vec<_> = a.keys().filter(|k| keys.is_compared(k)).collect();
+            let mut keys_b: Vec<_> = b.keys().filter(|k| keys.is_compared(k)).collect();
             keys_a.sort();
             keys_b.sort();
-            for (key_a, key_b) in keys_a
-                .iter()
-                .filter(|a| ignore_keys.iter().all(|r| !r.is_match(a)))
-                .zip(
-                    keys_b
-                        .iter()
-                        .filter(|a| ignore_keys.iter().all(|r| !r.is_match(a))),
-                )
-            {
+            for (key_a, key_b) in keys_a.iter().zip(keys_b.iter()) {
                 let cmp = key_a.cmp(key_b);
-                if cmp != std::cmp::Ordering::Equal {
+                if cmp != core::cmp::Ordering::Equal {
                     return cmp;
                 }
-                let value_a = &a[*key_a];
-                let value_b = &b[*key_b];
-                let cmp = compare_values(value_a, value_b, ignore_keys);
-                if cmp != std::cmp::Ordering::Equal {
+                // keys were taken from the maps themselves
+                let (Some(value_a), Some(value_b)) = (a.get(*key_a), b.get(*key_b)) else {
+                    debug_assert!(false, "object key vanished while sorting");
+                    continue;
+                };
+                let cmp = compare_values(value_a, value_b, keys);
+                if cmp != core::cmp::Ordering::Equal {
                     return cmp;
                 }
             }
             keys_a.len().cmp(&keys_b.len())
         }
-        (Value::Object(_), _) => std::cmp::Ordering::Less,
-        (_, Value::Object(_)) => std::cmp::Ordering::Greater,
-        (Value::Bool(_), _) => std::cmp::Ordering::Less,
-        (_, Value::Bool(_)) => std::cmp::Ordering::Greater,
-        (Value::Number(_), _) => std::cmp::Ordering::Less,
-        (_, Value::Number(_)) => std::cmp::Ordering::Greater,
-        (Value::String(_), _) => std::cmp::Ordering::Less,
-        (_, Value::String(_)) => std::cmp::Ordering::Greater,
+        // nulls are all equal, values of different types are ordered by type
+        (a, b) => type_rank(a).cmp(&type_rank(b)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{compare_serde_values, compare_serde_values_with_options, CompareOptions};
+
+    #[test]
+    fn mixed_types_sort_alike_on_both_sides() {
+        let left = json!([{"a": 1}, "x", 2, [1], null, false, "b", 1.5, {"a": 0}, true]);
+        let right = json!([true, 1.5, {"a": 0}, [1], "b", false, null, {"a": 1}, 2, "x"]);
+        let expected = json!([null, false, true, 1.5, 2, "b", "x", [1], {"a": 0}, {"a": 1}]);
+        assert_eq!(sort_value(&left, &[]), expected);
+        assert_eq!(sort_value(&right, &[]), expected);
+        assert!(compare_serde_values(&left, &right, true, &[])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn mixed_type_arrays_pair_elements_of_the_same_type() {
+        let left = json!([{"id": 1, "v": "a"}, "x", 3, {"id": 2, "v": "b"}]);
+        let right = json!(["y", {"id": 2, "v": "b"}, 4, {"id": 1, "v": "A"}, null]);
+        let options = CompareOptions::default().sort_arrays(true);
+        let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        let mut rendered: Vec<String> = diffs
+            .all_diffs()
+            .iter()
+            .map(|(t, e)| format!("{t}: {e}"))
+            .collect();
+        rendered.sort();
+        assert_eq!(
+            rendered,
+            vec![
+                "Extra on right: .[0].(null)",
+                "Mismatched: .[0].(3 != 4)",
+                r#"Mismatched: .[1].("x" != "y")"#,
+                r#"Mismatched: .[2].v.("a" != "A")"#,
+            ]
+        );
+        let swapped = compare_serde_values_with_options(&right, &left, &options).unwrap();
+        assert_eq!(swapped.all_diffs().len(), diffs.all_diffs().len());
     }
 }
@@ -0,0 +1,169 @@
+//! Line diffs of long mismatched strings, so a report of two large texts differing in one line points at that line.
+//! Records of mismatched strings carry one with [`crate::CompareOptions::string_diffs`], the values stay as they are.
+//! ```rust
+//! use json_diff_ng::{compare_serde_values_with_options, CompareOptions};
+//! use serde_json::json;
+//! let options = CompareOptions::default().string_diffs(16);
+//! let left = json!({"log": "boot\nlogin\nread\nlogout"});
+//! let right = json!({"log": "boot\nlogin\nwrite\nlogout"});
+//! let diffs = compare_serde_values_with_options(&left, &right, &options).unwrap();
+//! let string_diff = diffs.records()[0].string_diff.clone().unwrap();
+//! assert_eq!(string_diff.first_difference, 11);
+//! assert_eq!(string_diff.to_string(), "@@ -3 +3 @@\n-read\n+write");
+//! ```
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+
+use crate::myers::{self, Diff, Replace};
+
+/// Changed lines of two strings. Displays as a unified diff without context lines, hunks separated by newlines.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StringDiff {
+    /// Byte offset of the first difference, the length of the common prefix of both strings
+    pub first_difference: usize,
+    /// Runs of changed lines in order
+    pub hunks: Vec<LineHunk>,
+}
+
+/// Consecutive lines of the left string replaced by lines of the right string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineHunk {
+    /// 1-based number of the first removed line, or of the left line the added lines are inserted before
+    pub left_line: usize,
+    /// 1-based number of the first added line, or of the right line following the removed lines
+    pub right_line: usize,
+    pub removed: Vec<String>,
+    pub added: Vec<String>,
+}
+
+impl StringDiff {
+    /// The changed lines between `left` and `right`, lines are separated by `\n`.
+    pub fn between(left: &str, right: &str) -> Self {
+        let first_difference = left
+            .char_indices()
+            .zip(right.chars())
+            .find(|((_, l), r)| l != r)
+            .map_or(left.len().min(right.len()), |((offset, _), _)| offset);
+        let left_lines: Vec<&str> = left.split('\n').collect();
+        let right_lines: Vec<&str> = right.split('\n').collect();
+        let mut hunks = Replace::new(Hunks {
+            left: &left_lines,
+            right: &right_lines,
+            hunks: Vec::new(),
+        });
+        myers::diff(&mut hunks, &left_lines, &right_lines);
+        Self {
+            first_difference,
+            hunks: hunks.into_inner().hunks,
+        }
+    }
+}
+
+/// Collects the ranges of the myers diff of two lists of lines as [`LineHunk`]s.
+struct Hunks<'s> {
+    left: &'s [&'s str],
+    right: &'s [&'s str],
+    hunks: Vec<LineHunk>,
+}
+
+impl Hunks<'_> {
+    fn push(&mut self, old: usize, old_len: usize, new: usize, new_len: usize) {
+        let lines = |lines: &[&str]| lines.iter().map(ToString::to_string).collect();
+        self.hunks.push(LineHunk {
+            left_line: old + 1,
+            right_line: new + 1,
+            removed: lines(&self.left[old..old + old_len]),
+            added: lines(&self.right[new..new + new_len]),
+        });
+    }
+}
+
+impl Diff for Hunks<'_> {
+    fn delete(&mut self, old: usize, len: usize, new: usize) {
+        self.push(old, len, new, 0);
+    }
+    fn insert(&mut self, old: usize, new: usize, new_len: usize) {
+        self.push(old, 0, new, new_len);
+    }
+    fn replace(&mut self, old: usize, old_len: usize, new: usize, new_len: usize) {
+        self.push(old, old_len, new, new_len);
+    }
+}
+
+impl Display for LineHunk {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        // like in unified diffs, an empty range starts at the line before it and a single line has no length
+        let range = |line: usize, len: usize| match len {
+            0 => alloc::format!("{},0", line - 1),
+            1 => line.to_string(),
+            len => alloc::format!("{line},{len}"),
+        };
+        write!(
+            f,
+            "@@ -{} +{} @@",
+            range(self.left_line, self.removed.len()),
+            range(self.right_line, self.added.len())
+        )?;
+        for line in &self.removed {
+            write!(f, "\n-{line}")?;
+        }
+        for line in &self.added {
+            write!(f, "\n+{line}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for StringDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        for (n, hunk) in self.hunks.iter().enumerate() {
+            if n > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{hunk}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::format;
+
+    use super::*;
+
+    #[test]
+    fn one_changed_line_is_reported_with_its_number() {
+        let left: Vec<String> = (1..=200).map(|n| format!("line {n} of the text")).collect();
+        let mut right = left.clone();
+        right[136] = "line 137 of the edited text".to_string();
+        let diff = StringDiff::between(&left.join("\n"), &right.join("\n"));
+        assert_eq!(
+            diff.hunks,
+            vec![LineHunk {
+                left_line: 137,
+                right_line: 137,
+                removed: vec!["line 137 of the text".to_string()],
+                added: vec!["line 137 of the edited text".to_string()],
+            }]
+        );
+        let prefix: usize = left[..136].iter().map(|line| line.len() + 1).sum();
+        assert_eq!(diff.first_difference, prefix + "line 137 of the ".len());
+        assert_eq!(
+            diff.to_string(),
+            "@@ -137 +137 @@\n-line 137 of the text\n+line 137 of the edited text"
+        );
+    }
+
+    #[test]
+    fn insertions_and_deletions_use_empty_ranges() {
+        let diff = StringDiff::between("a\nb\nc\nd", "a\nc\nd\ne\nf");
+        assert_eq!(
+            diff.to_string(),
+            "@@ -2 +1,0 @@\n-b\n@@ -4,0 +4,2 @@\n+e\n+f"
+        );
+        assert_eq!(diff.first_difference, 2);
+        assert_eq!(StringDiff::between("ü", "üa").first_difference, 2);
+    }
+}
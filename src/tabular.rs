@@ -0,0 +1,308 @@
+//! Row-wise comparison of CSV and TSV exports, with json-aware comparison of columns holding json documents.
+//! Each row becomes an object of its cells keyed by the column names of the header, json columns hold the
+//! parsed documents and all other columns the cells as strings. Paired rows are compared like any other
+//! json values, so the paths of their diffs start with the column name.
+//! ```rust
+//! use json_diff_ng::tabular::{compare_csv, RowOutcome, TableOptions};
+//! let left = "id,name,payload\n1,a,\"{\"\"tags\"\": [1, 2]}\"\n2,b,{}\n";
+//! let right = "id,name,payload\n1,a,\"{\"\"tags\"\": [1, 3]}\"\n";
+//! let rows = compare_csv(left, right, &["payload"], &TableOptions::default().key_column("id")).unwrap();
+//! assert_eq!(rows.len(), 2);
+//! let RowOutcome::Compared(mismatch) = &rows[0].outcome else { panic!() };
+//! assert_eq!(mismatch.all_diffs()[0].1.to_string(), ".payload.tags.[1].(2 != 3)");
+//! assert_eq!((rows[1].key.as_str(), &rows[1].outcome), ("2", &RowOutcome::LeftOnly));
+//! ```
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value};
+
+use crate::process::Comparator;
+use crate::{CompareOptions, Error, Mismatch, Result, Side};
+
+/// How the tables of [`compare_csv`] are read and their rows paired and compared.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct TableOptions {
+    /// Field delimiter, `,` by default
+    pub delimiter: u8,
+    /// Column whose values pair the rows of both tables, rows are paired by position without it
+    pub key_column: Option<String>,
+    /// Options of the row comparisons
+    pub compare: CompareOptions,
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            key_column: None,
+            compare: CompareOptions::default(),
+        }
+    }
+}
+
+impl TableOptions {
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Reads tab separated tables.
+    pub fn tsv(self) -> Self {
+        self.delimiter(b'\t')
+    }
+
+    pub fn key_column(mut self, column: impl Into<String>) -> Self {
+        self.key_column = Some(column.into());
+        self
+    }
+
+    pub fn compare_options(mut self, options: CompareOptions) -> Self {
+        self.compare = options;
+        self
+    }
+}
+
+/// A row that differs between the tables of [`compare_csv`].
+#[derive(Debug, PartialEq)]
+pub struct RowDiff {
+    /// Value of the key column of the row, or its 1-based position below the header without a key column
+    pub key: String,
+    pub outcome: RowOutcome,
+}
+
+/// How a row differs between the tables.
+#[derive(Debug, PartialEq)]
+pub enum RowOutcome {
+    /// The row exists in both tables, with these differences
+    Compared(Box<Mismatch>),
+    /// The row exists in the left table only
+    LeftOnly,
+    /// The row exists in the right table only
+    RightOnly,
+}
+
+/// Compares the tables `a` and `b` row by row, the first record of each is its header. Cells of the
+/// `json_columns` are parsed as json, empty ones as `null`, all other cells are compared as strings.
+/// Returns the differing rows, pairs in the order of the left table followed by rows of the right table only.
+pub fn compare_csv(
+    a: &str,
+    b: &str,
+    json_columns: &[&str],
+    options: &TableOptions,
+) -> Result<Vec<RowDiff>> {
+    let comparator = options.compare.clone().build()?;
+    let left = read_table(a, Side::Left, json_columns, options)?;
+    let right = read_table(b, Side::Right, json_columns, options)?;
+    match &options.key_column {
+        Some(_) => compare_keyed(left, right, &comparator),
+        None => compare_positional(left, right, &comparator),
+    }
+}
+
+/// Rows of a table with their keys.
+type Rows = Vec<(String, Value)>;
+
+fn compare_positional(left: Rows, right: Rows, comparator: &Comparator) -> Result<Vec<RowDiff>> {
+    let mut left = left.into_iter();
+    let mut right = right.into_iter();
+    let mut diffs = Vec::new();
+    loop {
+        let outcome = match (left.next(), right.next()) {
+            (Some((key, l)), Some((_, r))) => compared(key, &l, &r, comparator)?,
+            (Some((key, _)), None) => Some((key, RowOutcome::LeftOnly)),
+            (None, Some((key, _))) => Some((key, RowOutcome::RightOnly)),
+            (None, None) => return Ok(diffs),
+        };
+        diffs.extend(outcome.map(|(key, outcome)| RowDiff { key, outcome }));
+    }
+}
+
+fn compare_keyed(left: Rows, right: Rows, comparator: &Comparator) -> Result<Vec<RowDiff>> {
+    let positions: BTreeMap<&str, usize> = right
+        .iter()
+        .enumerate()
+        .map(|(position, (key, _))| (key.as_str(), position))
+        .collect();
+    let mut paired = vec![false; right.len()];
+    let mut diffs = Vec::new();
+    for (key, l) in &left {
+        let outcome = match positions.get(key.as_str()) {
+            Some(&position) => {
+                paired[position] = true;
+                compared(key.clone(), l, &right[position].1, comparator)?
+            }
+            None => Some((key.clone(), RowOutcome::LeftOnly)),
+        };
+        diffs.extend(outcome.map(|(key, outcome)| RowDiff { key, outcome }));
+    }
+    diffs.extend(
+        right
+            .into_iter()
+            .zip(paired)
+            .filter(|(_, paired)| !paired)
+            .map(|((key, _), _)| RowDiff {
+                key,
+                outcome: RowOutcome::RightOnly,
+            }),
+    );
+    Ok(diffs)
+}
+
+/// Compares a pair of rows, `None` if they are equal.
+fn compared(
+    key: String,
+    left: &Value,
+    right: &Value,
+    comparator: &Comparator,
+) -> Result<Option<(String, RowOutcome)>> {
+    let mismatch = comparator.compare(left, right)?;
+    Ok((!mismatch.is_empty()).then(|| (key, RowOutcome::Compared(Box::new(mismatch)))))
+}
+
+/// Parses `table` into rows keyed by their key column or position. Errors name positions, never cell contents.
+fn read_table(
+    table: &str,
+    side: Side,
+    json_columns: &[&str],
+    options: &TableOptions,
+) -> Result<Rows> {
+    let invalid = |message: String| Error::InvalidTable(side, message);
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .from_reader(table.as_bytes());
+    let header: Vec<String> = reader
+        .headers()
+        .map_err(|e| invalid(e.to_string()))?
+        .iter()
+        .map(String::from)
+        .collect();
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|column| column == name)
+            .ok_or_else(|| invalid(format!("the header has no column {name:?}")))
+    };
+    let json = json_columns
+        .iter()
+        .map(|name| column(name))
+        .collect::<Result<Vec<usize>>>()?;
+    let key_column = options.key_column.as_deref().map(column).transpose()?;
+    let mut keys = BTreeMap::new();
+    let mut rows = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| invalid(e.to_string()))?;
+        let position = index + 1;
+        let mut row = Map::new();
+        for (i, (name, cell)) in header.iter().zip(&record).enumerate() {
+            let value = match (json.contains(&i), cell.trim().is_empty()) {
+                (true, true) => Value::Null,
+                (true, false) => serde_json::from_str(cell)
+                    .map_err(|e| invalid(format!("row {position}, column {name:?}: {e}")))?,
+                (false, _) => Value::String(cell.to_string()),
+            };
+            row.insert(name.clone(), value);
+        }
+        let key = match key_column {
+            Some(column) => {
+                let key = record.get(column).unwrap_or_default().to_string();
+                if let Some(first) = keys.insert(key.clone(), position) {
+                    return Err(invalid(format!(
+                        "row {position} repeats the key of row {first}"
+                    )));
+                }
+                key
+            }
+            None => position.to_string(),
+        };
+        rows.push((key, Value::Object(row)));
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ErrorCode;
+
+    const LEFT: &str = "\
+id,name,payload
+1,alpha,\"{\"\"limits\"\": {\"\"cpu\"\": 2}, \"\"tags\"\": [\"\"a\"\"]}\"
+2,beta,{}
+3,gamma,
+";
+
+    #[test]
+    fn rows_pair_by_position() {
+        let right = "\
+id,name,payload
+1,alpha,\"{\"\"tags\"\": [\"\"a\"\"],   \"\"limits\"\": {\"\"cpu\"\": 4}}\"
+2,Beta,{}
+";
+        let rows = compare_csv(LEFT, right, &["payload"], &TableOptions::default()).unwrap();
+        let rendered: Vec<(String, Vec<String>)> = rows
+            .iter()
+            .map(|row| {
+                let diffs = match &row.outcome {
+                    RowOutcome::Compared(mismatch) => mismatch
+                        .all_diffs()
+                        .iter()
+                        .map(|(_, entry)| entry.to_string())
+                        .collect(),
+                    other => vec![format!("{other:?}")],
+                };
+                (row.key.clone(), diffs)
+            })
+            .collect();
+        assert_eq!(
+            rendered,
+            vec![
+                (
+                    "1".to_string(),
+                    vec![".payload.limits.cpu.(2 != 4)".to_string()]
+                ),
+                (
+                    "2".to_string(),
+                    vec![r#".name.("beta" != "Beta")"#.to_string()]
+                ),
+                ("3".to_string(), vec!["LeftOnly".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn rows_pair_by_key_column() {
+        let left = "id\tname\tpayload\n1\talpha\t{\"cpu\": 2}\n2\tbeta\t{}\n3\tgamma\t\n";
+        let right = "id\tname\tpayload\n4\tdelta\t[]\n3\tgamma\t\n1\talpha\t{ \"cpu\" :2 }\n";
+        let options = TableOptions::default().tsv().key_column("id");
+        let rows = compare_csv(left, right, &["payload"], &options).unwrap();
+        let outcomes: Vec<(&str, &RowOutcome)> = rows
+            .iter()
+            .map(|row| (row.key.as_str(), &row.outcome))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![("2", &RowOutcome::LeftOnly), ("4", &RowOutcome::RightOnly)]
+        );
+    }
+
+    #[test]
+    fn invalid_tables_name_the_side_and_position() {
+        let options = TableOptions::default();
+        let broken = "id,name,payload\n1,alpha,{\n";
+        let err = compare_csv(LEFT, broken, &["payload"], &options).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::ParseRight);
+        assert!(
+            err.to_string().contains("row 1, column \"payload\""),
+            "{err}"
+        );
+        let err = compare_csv(LEFT, LEFT, &["blob"], &options).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::ParseLeft);
+        let duplicated = "id,name\n1,a\n1,b\n";
+        let err = compare_csv(duplicated, duplicated, &[], &options.key_column("id")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid left table: row 2 repeats the key of row 1"
+        );
+    }
+}
@@ -0,0 +1,46 @@
+use serde_json::Value;
+
+const TYPE_NAMES: [&str; 8] = [
+    "integer", "number", "string", "boolean", "object", "array", "null", "any",
+];
+
+/// Whether `name` is a type name of [`crate::CompareMode::Template`].
+pub(crate) fn is_type_name(name: &str) -> bool {
+    TYPE_NAMES.contains(&name)
+}
+
+/// Whether `value` is of the template type `name`, unknown names never match.
+pub(crate) fn matches_type(name: &str, value: &Value) -> bool {
+    match name {
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "null" => value.is_null(),
+        "any" => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn type_names_match_their_values() {
+        assert!(matches_type("integer", &json!(-3)));
+        assert!(!matches_type("integer", &json!(1.5)));
+        assert!(matches_type("number", &json!(1.5)));
+        assert!(matches_type("boolean", &json!(false)));
+        assert!(matches_type("null", &json!(null)));
+        assert!(matches_type("array", &json!([])));
+        assert!(matches_type("object", &json!({})));
+        assert!(matches_type("any", &json!("x")));
+        assert!(!matches_type("string", &json!(1)));
+        assert!(!is_type_name("user"));
+    }
+}
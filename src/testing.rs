@@ -0,0 +1,157 @@
+//! Helpers for testing code that compares documents, enabled by the `testing` feature.
+//! Comparisons with [`crate::CompareOptions::sort_arrays`] are stable under permutations of array elements: a document
+//! compared with any [`shuffle_arrays`] of itself has no diffs, with or without ignored keys.
+//! ```rust
+//! use json_diff_ng::testing::shuffle_arrays;
+//! use json_diff_ng::{compare_serde_values_with_options, CompareOptions};
+//! use serde_json::json;
+//! let document = json!({"items": [{"id": 1, "tags": ["a", "b", "c"]}, {"id": 2, "tags": []}, 3]});
+//! let shuffled = shuffle_arrays(&document, 7);
+//! assert_eq!(shuffled, shuffle_arrays(&document, 7));
+//! let options = CompareOptions::default().sort_arrays(true);
+//! assert!(compare_serde_values_with_options(&document, &shuffled, &options).unwrap().is_empty());
+//! ```
+use alloc::vec::Vec;
+
+use serde_json::Value;
+
+/// Copy of `value` with the elements of every array, at any depth, permuted. The permutation only depends on `seed`
+/// and the shape of `value`, so a failing test can be reproduced from its seed.
+pub fn shuffle_arrays(value: &Value, seed: u64) -> Value {
+    shuffled(value, &mut SplitMix64(seed))
+}
+
+fn shuffled(value: &Value, rng: &mut SplitMix64) -> Value {
+    match value {
+        Value::Array(a) => {
+            let mut elements: Vec<Value> = a.iter().map(|e| shuffled(e, rng)).collect();
+            // Fisher-Yates
+            for i in (1..elements.len()).rev() {
+                let j = (rng.next() % (i as u64 + 1)) as usize;
+                elements.swap(i, j);
+            }
+            Value::Array(elements)
+        }
+        Value::Object(o) => Value::Object(
+            o.iter()
+                .map(|(k, v)| (k.clone(), shuffled(v, rng)))
+                .collect(),
+        ),
+        v => v.clone(),
+    }
+}
+
+/// Small deterministic generator, the same on every platform and independent of `std`.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+    use regex::Regex;
+    use serde_json::json;
+
+    use super::*;
+    use crate::sort::sort_value;
+    use crate::{compare_serde_values_with_options, CompareOptions};
+
+    fn arbitrary_json() -> impl Strategy<Value = Value> {
+        let leaf = prop_oneof![
+            Just(Value::Null),
+            any::<bool>().prop_map(Value::Bool),
+            (-3..3i64).prop_map(Value::from),
+            Just(json!(0.5)),
+            "[a-c]{0,2}".prop_map(Value::String),
+            Just(json!([])),
+            Just(json!({})),
+        ];
+        leaf.prop_recursive(6, 96, 6, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..6).prop_map(Value::Array),
+                prop::collection::btree_map("[a-d]", inner, 0..5)
+                    .prop_map(|m| Value::Object(m.into_iter().collect())),
+            ]
+        })
+    }
+
+    fn sorted_options(ignore: bool) -> CompareOptions {
+        let ignore_keys = match ignore {
+            true => vec![Regex::new("^[ab]$").unwrap()],
+            false => vec![],
+        };
+        CompareOptions::default()
+            .sort_arrays(true)
+            .ignore_keys(ignore_keys)
+    }
+
+    #[test]
+    fn shuffling_is_deterministic_and_deep() {
+        let value =
+            json!({"a": [1, 2, 3, 4, 5, 6, 7, 8], "b": [[1, 2, 3, 4], {"c": [5, 6, 7, 8]}]});
+        assert_eq!(shuffle_arrays(&value, 1), shuffle_arrays(&value, 1));
+        assert_ne!(shuffle_arrays(&value, 1), shuffle_arrays(&value, 2));
+        assert_eq!(
+            sort_value(&shuffle_arrays(&value, 1), &[]),
+            sort_value(&value, &[])
+        );
+        assert_eq!(shuffle_arrays(&json!("a"), 1), json!("a"));
+    }
+
+    #[test]
+    fn elements_only_differing_in_nested_order_pair_up() {
+        let left = json!([[{}, [], null, null], [null, null, null, []]]);
+        let right = json!([[null, null, null, []], [null, [], {}, null]]);
+        let options = sorted_options(false);
+        let diff = compare_serde_values_with_options(&left, &right, &options).unwrap();
+        assert!(diff.is_empty(), "{:?}", diff.records());
+        let options = options.verify_multiplicity(true);
+        let diff = compare_serde_values_with_options(
+            &json!([[null, false]]),
+            &json!([[false, null]]),
+            &options,
+        );
+        assert!(diff.unwrap().is_empty());
+    }
+
+    #[test]
+    fn objects_with_ignored_keys_sort_in_any_order() {
+        let left = json!([{"a": 0.5, "b": null}, {"a": 0.5, "c": null}, null, {"c": false}]);
+        let right = json!([{"c": false}, null, {"a": 0.5, "c": null}, {"a": 0.5, "b": null}]);
+        let diff = compare_serde_values_with_options(&left, &right, &sorted_options(true)).unwrap();
+        assert!(diff.is_empty(), "{:?}", diff.records());
+    }
+
+    proptest! {
+        #[test]
+        fn shuffled_arrays_hold_the_same_elements(a in arbitrary_json(), seed in any::<u64>()) {
+            prop_assert_eq!(sort_value(&shuffle_arrays(&a, seed), &[]), sort_value(&a, &[]));
+        }
+
+        #[test]
+        fn sorted_comparison_ignores_permutations(a in arbitrary_json(), seed in any::<u64>(), ignore in any::<bool>()) {
+            let shuffled = shuffle_arrays(&a, seed);
+            let options = sorted_options(ignore);
+            let diff = compare_serde_values_with_options(&a, &shuffled, &options).unwrap();
+            prop_assert!(diff.is_empty(), "{:?}", diff.records());
+            let diff = compare_serde_values_with_options(&shuffled, &a, &options).unwrap();
+            prop_assert!(diff.is_empty(), "{:?}", diff.records());
+        }
+
+        #[test]
+        fn multiplicity_survives_permutations(a in arbitrary_json(), seed in any::<u64>(), ignore in any::<bool>()) {
+            let shuffled = shuffle_arrays(&a, seed);
+            let options = sorted_options(ignore).verify_multiplicity(true);
+            let diff = compare_serde_values_with_options(&a, &shuffled, &options).unwrap();
+            prop_assert!(diff.is_empty(), "{:?}", diff.records());
+        }
+    }
+}
@@ -0,0 +1,629 @@
+//! Transformations of documents before comparison, e.g. to follow a schema migration.
+//! ```rust
+//! use json_diff_ng::transform::{apply_mapping, Mapping};
+//! use serde_json::json;
+//! let mapping = Mapping::new([("/user/name", "/profile/displayName")]);
+//! let old = json!({"user": {"name": "ada", "id": 1}});
+//! let migrated = apply_mapping(&old, &mapping).unwrap();
+//! assert_eq!(migrated, json!({"user": {"id": 1}, "profile": {"displayName": "ada"}}));
+//! ```
+//! A [`Transform`] makes small edits to one side, like dropping a subtree or defaulting a missing value:
+//! ```rust
+//! use json_diff_ng::transform::{apply_transform, Transform};
+//! use serde_json::json;
+//! let transform = Transform::from_json(&json!([
+//!     {"op": "remove", "path": "/debug"},
+//!     {"op": "move", "from": "/user", "path": "/owner"},
+//!     {"op": "default", "path": "/retries", "value": 0}
+//! ]))
+//! .unwrap();
+//! let document = json!({"debug": {"trace": true}, "user": "ada"});
+//! assert_eq!(apply_transform(&document, &transform).unwrap(), json!({"owner": "ada", "retries": 0}));
+//! ```
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::enums::OwnedPathElement;
+use crate::patch::apply_operation;
+use crate::pointer::{self, array_index, unescape};
+use crate::{DiffPath, Error, Result};
+
+/// Moves of values from one JSON pointer to another, applied in order by [`apply_mapping`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Mapping {
+    pub moves: Vec<(String, String)>,
+}
+
+impl Mapping {
+    pub fn new<F: Into<String>, T: Into<String>>(moves: impl IntoIterator<Item = (F, T)>) -> Self {
+        Self {
+            moves: moves
+                .into_iter()
+                .map(|(from, to)| (from.into(), to.into()))
+                .collect(),
+        }
+    }
+
+    /// Reads a mapping from json, either an object from source to target pointers
+    /// like `{"/user/name": "/profile/displayName"}` or an array of `[source, target]` pairs.
+    pub fn from_json(value: &Value) -> Result<Self> {
+        let invalid = || {
+            Error::InvalidMapping(
+                "expected an object of pointers or an array of [source, target] pairs".to_string(),
+            )
+        };
+        let moves = match value {
+            Value::Object(o) => o
+                .iter()
+                .map(|(from, to)| Some((from.clone(), to.as_str()?.to_string())))
+                .collect::<Option<Vec<_>>>(),
+            Value::Array(a) => a
+                .iter()
+                .map(|pair| match pair.as_array()?.as_slice() {
+                    [Value::String(from), Value::String(to)] => Some((from.clone(), to.clone())),
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        };
+        Ok(Self {
+            moves: moves.ok_or_else(invalid)?,
+        })
+    }
+}
+
+/// Applies `mapping` to `value` like [`apply_mapping_with_warnings`], dropping the warnings.
+pub fn apply_mapping(value: &Value, mapping: &Mapping) -> Result<Value> {
+    apply_mapping_with_warnings(value, mapping).map(|(value, _)| value)
+}
+
+/// Moves the values at the source pointers of `mapping` to their target pointers, creating intermediate objects.
+/// Content not mapped stays untouched, a value already at a target is replaced.
+/// Moves whose source does not resolve are skipped with a warning.
+pub fn apply_mapping_with_warnings(
+    value: &Value,
+    mapping: &Mapping,
+) -> Result<(Value, Vec<String>)> {
+    let mut value = value.clone();
+    let mut warnings = Vec::new();
+    for (from, to) in &mapping.moves {
+        let (from_tokens, to_tokens) = (tokens(from)?, tokens(to)?);
+        if to_tokens.is_empty() {
+            return Err(Error::InvalidMapping(format!(
+                "the document root cannot be the target of {from}"
+            )));
+        }
+        let Some(moved) = take(&mut value, &from_tokens) else {
+            warnings.push(format!("Mapping source {from} does not resolve, skipped"));
+            continue;
+        };
+        put(&mut value, &to_tokens, moved)
+            .map_err(|at| Error::InvalidMapping(format!("target {to} is blocked by {at}")))?;
+    }
+    Ok((value, warnings))
+}
+
+/// Edits of a document, applied in order by [`apply_transform`], e.g. before comparing it with
+/// [`crate::CompareOptions::pre_transform_left`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Transform {
+    pub operations: Vec<Operation>,
+}
+
+/// Operation of a [`Transform`], written in json like a JSON patch operation, e.g. `{"op": "remove", "path": "/debug"}`.
+/// `add`, `remove`, `replace`, `move` and `copy` follow RFC 6902, `default` stores its value at `path` only if
+/// nothing is there yet, creating missing objects on the way.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Operation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Default { path: String, value: Value },
+}
+
+impl Transform {
+    pub fn new(operations: Vec<Operation>) -> Self {
+        Self { operations }
+    }
+
+    /// Reads a transformation from a json array of operations, see [`Operation`].
+    /// Fails with [`Error::InvalidTransform`] naming the index of the first malformed operation.
+    pub fn from_json(value: &Value) -> Result<Self> {
+        let Value::Array(operations) = value else {
+            return Err(Error::Misc(
+                "a transformation must be an array of operations".to_string(),
+            ));
+        };
+        let operations = operations
+            .iter()
+            .enumerate()
+            .map(|(index, operation)| {
+                Operation::deserialize(operation)
+                    .map_err(|e| Error::InvalidTransform(index, e.to_string()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { operations })
+    }
+}
+
+/// Applies the operations of `transform` to a copy of `value` in order.
+/// Fails with [`Error::InvalidTransform`] naming the index of the first operation that does not apply,
+/// e.g. because its path does not resolve.
+pub fn apply_transform(value: &Value, transform: &Transform) -> Result<Value> {
+    let mut value = value.clone();
+    for (index, operation) in transform.operations.iter().enumerate() {
+        apply_transform_operation(&mut value, operation)
+            .map_err(|message| Error::InvalidTransform(index, message))?;
+    }
+    Ok(value)
+}
+
+fn apply_transform_operation(
+    value: &mut Value,
+    operation: &Operation,
+) -> core::result::Result<(), String> {
+    let tokens = |pointer: &str| tokens(pointer).map_err(|e| e.to_string());
+    match operation {
+        Operation::Add { path, value: added } => {
+            apply_operation(value, &json!({"op": "add", "path": path, "value": added}))
+        }
+        Operation::Remove { path } => {
+            apply_operation(value, &json!({"op": "remove", "path": path}))
+        }
+        Operation::Replace {
+            path,
+            value: replaced,
+        } => apply_operation(
+            value,
+            &json!({"op": "replace", "path": path, "value": replaced}),
+        ),
+        Operation::Move { from, path } => {
+            if path.starts_with(&format!("{from}/")) {
+                return Err(format!("{from} cannot be moved into its own child {path}"));
+            }
+            let moved =
+                take(value, &tokens(from)?).ok_or_else(|| format!("{from} does not resolve"))?;
+            apply_operation(value, &json!({"op": "add", "path": path, "value": moved}))
+        }
+        Operation::Copy { from, path } => {
+            tokens(from)?;
+            let copied = value
+                .pointer(from)
+                .cloned()
+                .ok_or_else(|| format!("{from} does not resolve"))?;
+            apply_operation(value, &json!({"op": "add", "path": path, "value": copied}))
+        }
+        Operation::Default {
+            path,
+            value: default,
+        } => {
+            let path_tokens = tokens(path)?;
+            if value.pointer(path).is_some() {
+                return Ok(());
+            }
+            put(value, &path_tokens, default.clone())
+                .map_err(|at| format!("{path} is blocked by {at}"))
+        }
+    }
+}
+
+fn tokens(pointer: &str) -> Result<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(tokens) = pointer.strip_prefix('/') else {
+        return Err(Error::InvalidPointer(pointer.to_string()));
+    };
+    Ok(tokens.split('/').map(unescape).collect())
+}
+
+/// Removes the value at `tokens`, the document root is replaced by null.
+fn take(value: &mut Value, tokens: &[String]) -> Option<Value> {
+    let Some((last, parents)) = tokens.split_last() else {
+        return Some(value.take());
+    };
+    let mut parent = value;
+    for token in parents {
+        parent = match parent {
+            Value::Object(o) => o.get_mut(token),
+            Value::Array(a) => array_index(token).and_then(|i| a.get_mut(i)),
+            _ => None,
+        }?;
+    }
+    match parent {
+        Value::Object(o) => o.shift_remove(last),
+        Value::Array(a) => array_index(last)
+            .filter(|i| *i < a.len())
+            .map(|i| a.remove(i)),
+        _ => None,
+    }
+}
+
+/// Stores `moved` at `tokens`, creating missing objects on the way.
+/// Fails with the pointer of a scalar or array in the way.
+fn put(value: &mut Value, tokens: &[String], moved: Value) -> core::result::Result<(), String> {
+    let mut current = value;
+    let mut resolved = String::new();
+    for (n, token) in tokens.iter().enumerate() {
+        let last = n + 1 == tokens.len();
+        current = match current {
+            Value::Object(o) if last => {
+                o.insert(token.clone(), moved);
+                return Ok(());
+            }
+            Value::Object(o) => o
+                .entry(token.clone())
+                .or_insert_with(|| Value::Object(Map::new())),
+            Value::Array(a) => match array_index(token).and_then(|i| a.get_mut(i)) {
+                Some(element) if last => {
+                    *element = moved;
+                    return Ok(());
+                }
+                Some(element) => element,
+                None => return Err(format!("the array at {}", or_root(&resolved))),
+            },
+            _ => return Err(format!("the value at {}", or_root(&resolved))),
+        };
+        resolved.push('/');
+        resolved.push_str(&crate::pointer::escape(token));
+    }
+    Ok(())
+}
+
+fn or_root(pointer: &str) -> &str {
+    if pointer.is_empty() {
+        "the document root"
+    } else {
+        pointer
+    }
+}
+
+/// Copy of `value` with every internal reference `{"$ref": "#/components/schemas/Foo"}` replaced by the value at its
+/// JSON pointer, expanded in turn. Only objects holding nothing but a `$ref` string are references. References to other
+/// documents, i.e. not starting with `#`, are kept and returned with their path in the copy.
+/// References that lead back to themselves fail with [`Error::RefCycle`], unresolvable ones with
+/// [`Error::PointerNotFound`], expansions nesting deeper than `max_depth` with [`Error::DepthLimitExceeded`] and
+/// copies of more than `max_values` values with [`Error::RefExpansionTooLarge`].
+/// ```rust
+/// use json_diff_ng::transform::resolve_internal_refs;
+/// use serde_json::json;
+/// let spec = json!({"root": {"$ref": "#/defs/a"}, "defs": {"a": {"b": {"$ref": "#/defs/b"}}, "b": 1}});
+/// let (resolved, external) = resolve_internal_refs(&spec, 128, 1024).unwrap();
+/// assert_eq!(resolved["root"], json!({"b": 1}));
+/// assert!(external.is_empty());
+/// let cyclic = json!({"a": {"$ref": "#/b"}, "b": {"c": {"$ref": "#/a"}}});
+/// let err = resolve_internal_refs(&cyclic, 128, 1024).unwrap_err();
+/// assert_eq!(err.to_string(), "Cyclic $ref: #/b -> #/a -> #/b");
+/// ```
+pub fn resolve_internal_refs(
+    value: &Value,
+    max_depth: usize,
+    max_values: usize,
+) -> Result<(Value, Vec<(DiffPath, String)>)> {
+    let mut resolver = RefResolver {
+        document: value,
+        max_depth,
+        values: 0,
+        max_values,
+        expanding: Vec::new(),
+        path: DiffPath::default(),
+        external: Vec::new(),
+    };
+    let resolved = resolver.resolve(value)?;
+    Ok((resolved, resolver.external))
+}
+
+struct RefResolver<'a> {
+    document: &'a Value,
+    max_depth: usize,
+    /// Values in the copy so far
+    values: usize,
+    max_values: usize,
+    /// Pointers of the references currently being expanded, outermost first
+    expanding: Vec<&'a str>,
+    /// Path of the resolved value in the copy
+    path: DiffPath,
+    external: Vec<(DiffPath, String)>,
+}
+
+impl<'a> RefResolver<'a> {
+    fn resolve(&mut self, value: &'a Value) -> Result<Value> {
+        if self.path.0.len() > self.max_depth {
+            return Err(Error::DepthLimitExceeded(self.max_depth));
+        }
+        match value {
+            Value::Object(o) => match o.get("$ref").and_then(Value::as_str) {
+                Some(reference) if o.len() == 1 => self.expand(reference, value),
+                _ => {
+                    self.count()?;
+                    let mut resolved = Map::new();
+                    for (key, child) in o {
+                        self.path.0.push(OwnedPathElement::Object(key.clone()));
+                        let child = self.resolve(child);
+                        self.path.0.pop();
+                        resolved.insert(key.clone(), child?);
+                    }
+                    Ok(Value::Object(resolved))
+                }
+            },
+            Value::Array(a) => {
+                self.count()?;
+                let mut resolved = Vec::with_capacity(a.len());
+                for (index, element) in a.iter().enumerate() {
+                    self.path.0.push(OwnedPathElement::ArrayEntry(index));
+                    let element = self.resolve(element);
+                    self.path.0.pop();
+                    resolved.push(element?);
+                }
+                Ok(Value::Array(resolved))
+            }
+            value => {
+                self.count()?;
+                Ok(value.clone())
+            }
+        }
+    }
+
+    /// Counts one more value of the copy against the budget.
+    fn count(&mut self) -> Result<()> {
+        self.values += 1;
+        if self.values > self.max_values {
+            return Err(Error::RefExpansionTooLarge(self.max_values));
+        }
+        Ok(())
+    }
+
+    /// The value `reference` refers to, resolved, or `value` itself if it refers to another document.
+    fn expand(&mut self, reference: &'a str, value: &'a Value) -> Result<Value> {
+        let Some(target) = reference.strip_prefix('#') else {
+            self.count()?;
+            self.external
+                .push((self.path.clone(), reference.to_string()));
+            return Ok(value.clone());
+        };
+        if let Some(start) = self.expanding.iter().position(|pointer| *pointer == target) {
+            let cycle = self.expanding[start..].iter().chain([&target]);
+            return Err(Error::RefCycle(
+                cycle.map(|pointer| format!("#{pointer}")).collect(),
+            ));
+        }
+        let target_value = pointer::extract(self.document, target)?;
+        self.expanding.push(target);
+        let resolved = self.resolve(target_value);
+        self.expanding.pop();
+        resolved
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use crate::mismatch::Warning;
+    use crate::{
+        compare_serde_values, compare_serde_values_with_options, CompareOptions, ErrorCode, Side,
+    };
+    use crate::process::DEFAULT_MAX_RESOLVED_VALUES;
+
+    use super::*;
+
+    #[test]
+    fn renamed_and_moved_keys_leave_only_real_changes() {
+        let old = json!({"user": {"name": "ada", "mail": "a@b.c", "age": 36}, "tags": ["x"]});
+        let new = json!({
+            "user": {"age": 37, "email": "a@b.c"},
+            "profile": {"displayName": "ada"},
+            "tags": ["x"]
+        });
+        let mapping = Mapping::from_json(&json!({
+            "/user/mail": "/user/email",
+            "/user/name": "/profile/displayName"
+        }))
+        .unwrap();
+        let (mapped, warnings) = apply_mapping_with_warnings(&old, &mapping).unwrap();
+        assert!(warnings.is_empty());
+        let diffs = compare_serde_values(&mapped, &new, false, &[]).unwrap();
+        let diffs: Vec<String> = diffs.records().iter().map(|r| r.to_string()).collect();
+        assert_eq!(diffs, vec!["Mismatched: .user.age.(36 != 37)"]);
+    }
+
+    #[test]
+    fn absent_source_is_skipped_with_a_warning() {
+        let value = json!({"a": 1});
+        let mapping = Mapping::from_json(&json!([["/missing/key", "/b"]])).unwrap();
+        let (mapped, warnings) = apply_mapping_with_warnings(&value, &mapping).unwrap();
+        assert_eq!(mapped, value);
+        assert_eq!(
+            warnings,
+            vec!["Mapping source /missing/key does not resolve, skipped"]
+        );
+    }
+
+    #[test]
+    fn malformed_mappings_are_errors() {
+        let err = Mapping::from_json(&json!({"/a": 1})).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::InvalidMapping);
+        let blocked = Mapping::new([("/a", "/b/c")]);
+        let err = apply_mapping(&json!({"a": 1, "b": 2}), &blocked).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid key mapping: target /b/c is blocked by the value at /b"
+        );
+        let err = apply_mapping(&json!({}), &Mapping::new([("a", "/b")])).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::InvalidPointer);
+    }
+
+    fn transform(operations: Value) -> Transform {
+        Transform::from_json(&operations).unwrap()
+    }
+
+    #[test]
+    fn each_operation_applies() {
+        let document = json!({"a": {"b": 1}, "list": [1, 2], "debug": true});
+        let cases = [
+            (
+                json!({"op": "add", "path": "/list/1", "value": 9}),
+                json!({"a": {"b": 1}, "list": [1, 9, 2], "debug": true}),
+            ),
+            (
+                json!({"op": "remove", "path": "/debug"}),
+                json!({"a": {"b": 1}, "list": [1, 2]}),
+            ),
+            (
+                json!({"op": "replace", "path": "/a/b", "value": 2}),
+                json!({"a": {"b": 2}, "list": [1, 2], "debug": true}),
+            ),
+            (
+                json!({"op": "move", "from": "/a/b", "path": "/c"}),
+                json!({"a": {}, "list": [1, 2], "debug": true, "c": 1}),
+            ),
+            (
+                json!({"op": "copy", "from": "/list/0", "path": "/list/-"}),
+                json!({"a": {"b": 1}, "list": [1, 2, 1], "debug": true}),
+            ),
+            (
+                json!({"op": "default", "path": "/a/b", "value": 5}),
+                document.clone(),
+            ),
+            (
+                json!({"op": "default", "path": "/x/y", "value": 0}),
+                json!({"a": {"b": 1}, "list": [1, 2], "debug": true, "x": {"y": 0}}),
+            ),
+        ];
+        for (operation, expected) in cases {
+            let transformed = apply_transform(&document, &transform(json!([operation]))).unwrap();
+            assert_eq!(transformed, expected, "{operation}");
+        }
+    }
+
+    #[test]
+    fn referenced_and_inlined_schemas_compare_equal() {
+        let referenced = json!({
+            "paths": {"/pets": {"items": [{"$ref": "#/components/schemas/Pet"}]}},
+            "components": {"schemas": {"Pet": {"name": {"$ref": "#/components/schemas/Name"}}, "Name": "string"}}
+        });
+        let inlined = json!({
+            "paths": {"/pets": {"items": [{"name": "string"}]}},
+            "components": {"schemas": {"Pet": {"name": "string"}, "Name": "string"}}
+        });
+        let options = CompareOptions::default();
+        let diff = compare_serde_values_with_options(&referenced, &inlined, &options).unwrap();
+        assert!(!diff.is_empty());
+        let options = options.resolve_internal_refs(true);
+        let diff = compare_serde_values_with_options(&referenced, &inlined, &options).unwrap();
+        assert!(diff.is_empty(), "{:?}", diff.records());
+        assert!(diff.warnings.is_empty());
+    }
+
+    #[test]
+    fn cyclic_refs_are_listed_in_the_error() {
+        let cyclic = json!({"a": {"$ref": "#/b/c"}, "b": {"c": {"d": {"$ref": "#/b/c"}}}});
+        let options = CompareOptions::default().resolve_internal_refs(true);
+        let err = compare_serde_values_with_options(&json!({}), &cyclic, &options).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::RefCycle);
+        assert_eq!(err.side(), Some(Side::Right));
+        assert_eq!(
+            err.without_context().to_string(),
+            "Cyclic $ref: #/b/c -> #/b/c"
+        );
+        let missing = json!({"a": {"$ref": "#/nowhere"}});
+        let err = resolve_internal_refs(&missing, 128, 1024).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::PointerNotFound);
+        let deep = json!({"a": {"$ref": "#/b"}, "b": {"c": {"d": 1}}});
+        let err = resolve_internal_refs(&deep, 1, 1024).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::LimitExceeded);
+    }
+
+    #[test]
+    fn doubling_refs_fail_at_the_value_budget() {
+        // each level refers twice to the one before, so level 64 would expand into 2^64 copies of level 0
+        let mut levels = Map::new();
+        levels.insert("l0".to_string(), json!([1]));
+        for level in 1..=64 {
+            let previous = json!({"$ref": format!("#/l{}", level - 1)});
+            levels.insert(format!("l{level}"), json!([previous, previous]));
+        }
+        let doubling = Value::Object(levels);
+        let options = CompareOptions::default().resolve_internal_refs(true);
+        let err = compare_serde_values_with_options(&doubling, &doubling, &options).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::LimitExceeded);
+        assert_eq!(err.side(), Some(Side::Left));
+        assert_eq!(
+            err.without_context(),
+            &Error::RefExpansionTooLarge(DEFAULT_MAX_RESOLVED_VALUES)
+        );
+        let small = json!({"a": [1], "b": [{"$ref": "#/a"}, {"$ref": "#/a"}]});
+        let (resolved, _) = resolve_internal_refs(&small, 128, 8).unwrap();
+        assert_eq!(resolved["b"], json!([[1], [1]]));
+        assert!(resolve_internal_refs(&small, 128, 7).is_err());
+    }
+
+    #[test]
+    fn external_refs_are_kept_with_a_warning() {
+        let external = json!({"pet": {"$ref": "https://example.com/pet.json#/Pet"}});
+        let options = CompareOptions::default().resolve_internal_refs(true);
+        let diff = compare_serde_values_with_options(&external, &external, &options).unwrap();
+        assert!(diff.is_empty());
+        assert_eq!(
+            diff.warnings,
+            [
+                Warning::ExternalRef {
+                    path: DiffPath(vec![OwnedPathElement::Object("pet".into())]),
+                    side: Side::Left,
+                    reference: "https://example.com/pet.json#/Pet".into(),
+                },
+                Warning::ExternalRef {
+                    path: DiffPath(vec![OwnedPathElement::Object("pet".into())]),
+                    side: Side::Right,
+                    reference: "https://example.com/pet.json#/Pet".into(),
+                },
+            ]
+        );
+        assert_eq!(
+            diff.warnings[0].to_string(),
+            "$ref https://example.com/pet.json#/Pet on the left refers to another document, it is compared as it is"
+        );
+    }
+
+    #[test]
+    fn failing_operations_are_named_by_index() {
+        let document = json!({"a": 1, "b": [1]});
+        let missing = transform(json!([
+            {"op": "remove", "path": "/a"},
+            {"op": "move", "from": "/a", "path": "/c"}
+        ]));
+        let err = apply_transform(&document, &missing).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::InvalidTransform);
+        assert_eq!(
+            err.to_string(),
+            "Invalid transformation: operation 1: /a does not resolve"
+        );
+        for operation in [
+            json!({"op": "remove", "path": "/x/y"}),
+            json!({"op": "replace", "path": "/x", "value": 1}),
+            json!({"op": "copy", "from": "/b/3", "path": "/c"}),
+            json!({"op": "default", "path": "/a/x", "value": 1}),
+            json!({"op": "move", "from": "/b", "path": "/b/0"}),
+        ] {
+            let err = apply_transform(&document, &transform(json!([operation]))).unwrap_err();
+            assert!(
+                matches!(err, Error::InvalidTransform(0, _)),
+                "{operation}: {err}"
+            );
+        }
+        let err = Transform::from_json(
+            &json!([{"op": "remove", "path": "/a"}, {"op": "wrap", "path": "/a"}]),
+        );
+        assert!(matches!(err, Err(Error::InvalidTransform(1, _))));
+        let err = Transform::from_json(&json!([{"op": "default", "path": "/a"}]));
+        assert!(matches!(err, Err(Error::InvalidTransform(0, _))));
+    }
+}
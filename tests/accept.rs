@@ -0,0 +1,98 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+fn accept(dir: &Path, actual: &str, golden: &str, flags: &[&str]) -> Output {
+    let actual_path = dir.join("actual.json");
+    let golden_path = dir.join("golden.json");
+    fs::write(&actual_path, actual).unwrap();
+    fs::write(&golden_path, golden).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+        .arg("accept")
+        .arg(&actual_path)
+        .arg(&golden_path)
+        .args(flags)
+        .output()
+        .unwrap()
+}
+
+fn read(dir: &Path, name: &str) -> String {
+    fs::read_to_string(dir.join(name)).unwrap()
+}
+
+#[test]
+fn equal_golden_is_left_alone() {
+    let dir = TempDir::new().unwrap();
+    let output = accept(dir.path(), r#"{"b": 1, "a": 2}"#, r#"{"a":2,"b":1}"#, &[]);
+    assert!(output.status.success());
+    assert_eq!(read(dir.path(), "golden.json"), r#"{"a":2,"b":1}"#);
+    assert!(!dir.path().join("golden.json.bak").exists());
+}
+
+#[test]
+fn confirmed_update_writes_canonical_golden_and_backup() {
+    let dir = TempDir::new().unwrap();
+    let output = accept(
+        dir.path(),
+        r#"{"b": 1, "a": [3]}"#,
+        r#"{"a": [2]}"#,
+        &["--yes"],
+    );
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Mismatched: .a.[0].(3 != 2)"), "{stdout}");
+    assert_eq!(
+        read(dir.path(), "golden.json"),
+        "{\n  \"a\": [\n    3\n  ],\n  \"b\": 1\n}\n"
+    );
+    assert_eq!(read(dir.path(), "golden.json.bak"), r#"{"a": [2]}"#);
+    let leftovers = fs::read_dir(dir.path()).unwrap().count();
+    assert_eq!(leftovers, 3);
+}
+
+#[test]
+fn update_without_backup() {
+    let dir = TempDir::new().unwrap();
+    let output = accept(dir.path(), "[1]", "[2]", &["--yes", "--no-backup"]);
+    assert!(output.status.success());
+    assert_eq!(read(dir.path(), "golden.json"), "[\n  1\n]\n");
+    assert!(!dir.path().join("golden.json.bak").exists());
+}
+
+#[test]
+fn declined_update_keeps_golden() {
+    let dir = TempDir::new().unwrap();
+    // stdout is captured, so there is no terminal to confirm on
+    let output = accept(dir.path(), "[1]", "[2]", &[]);
+    assert!(!output.status.success());
+    assert_eq!(read(dir.path(), "golden.json"), "[2]");
+    assert!(!dir.path().join("golden.json.bak").exists());
+}
+
+#[test]
+fn pointers_and_mappings_are_rejected() {
+    let dir = TempDir::new().unwrap();
+    let actual = dir.path().join("actual.json");
+    let golden = dir.path().join("golden.json");
+    fs::write(&actual, r#"{"spec": {"a": 1}, "meta": 2}"#).unwrap();
+    fs::write(&golden, r#"{"a": 2}"#).unwrap();
+    for flags in [["--left-pointer", "/spec"], ["--map", "mapping.json"]] {
+        let output = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+            .args(flags)
+            .arg("accept")
+            .arg(&actual)
+            .arg(&golden)
+            .arg("--yes")
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(2));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("accept does not support pointers or --map"),
+            "{stderr}"
+        );
+        assert_eq!(read(dir.path(), "golden.json"), r#"{"a": 2}"#);
+    }
+}
@@ -0,0 +1,515 @@
+use std::process::{Command, Output};
+
+fn json_diff(flags: &[&str], left: &str, right: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+        .args(flags)
+        .arg("direct")
+        .arg(left)
+        .arg(right)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+const LEFT: &str = r#"{"user": {"name": "Jane", "address": {"city": "Oslo", "zip": "0150"}}}"#;
+const RIGHT: &str = r#"{"user": {"name": "Jane"}, "added": [1, 2]}"#;
+
+#[test]
+fn removed_nested_object_is_printed_with_its_content() {
+    let output = json_diff(&[], LEFT, RIGHT);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = stdout(&output);
+    assert!(stdout.contains(r#"Extra on left: .user.address.({"city":"Oslo","zip":"0150"})"#));
+    assert!(stdout.contains("Extra on right: .added.([1,2])"));
+}
+
+#[test]
+fn grouped_output_prints_values() {
+    let output = json_diff(&["--group-by-root"], LEFT, RIGHT);
+    assert!(
+        stdout(&output).contains(r#"Extra on left: .user.address.({"city":"Oslo","zip":"0150"})"#)
+    );
+}
+
+#[test]
+fn no_values_prints_paths_only() {
+    let output = json_diff(&["--no-values"], LEFT, RIGHT);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = stdout(&output);
+    assert!(stdout.contains("Extra on left: .user.address\n"));
+    assert!(stdout.contains("Extra on right: .added\n"));
+    assert!(!stdout.contains("Oslo"));
+}
+
+fn write_inputs(dir: &std::path::Path, left: &str, right: &str) -> (String, String) {
+    let (left_path, right_path) = (dir.join("left.json"), dir.join("right.json"));
+    std::fs::write(&left_path, left).unwrap();
+    std::fs::write(&right_path, right).unwrap();
+    (
+        left_path.to_string_lossy().into_owned(),
+        right_path.to_string_lossy().into_owned(),
+    )
+}
+
+#[test]
+fn patch_prints_only_the_json_patch() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let (left, right) = write_inputs(dir.path(), LEFT, RIGHT);
+    let output = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+        .args(["patch", &left, &right, "--check"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let patch: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(patch
+        .as_array()
+        .unwrap()
+        .contains(&serde_json::json!({"op": "remove", "path": "/user/address"})));
+}
+
+#[test]
+fn patch_refuses_sorted_arrays() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let (left, right) = write_inputs(dir.path(), "[2, 1]", "[1, 3]");
+    let output = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+        .args(["--sort-arrays", "patch", &left, &right])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn dirs_reports_per_file() {
+    let (left, right) = (
+        tempfile::TempDir::new().unwrap(),
+        tempfile::TempDir::new().unwrap(),
+    );
+    std::fs::write(left.path().join("same.json"), "[1]").unwrap();
+    std::fs::write(right.path().join("same.json"), "[1]").unwrap();
+    std::fs::write(left.path().join("changed.json"), r#"{"a": 1}"#).unwrap();
+    std::fs::write(right.path().join("changed.json"), r#"{"a": 2}"#).unwrap();
+    std::fs::write(right.path().join("broken.json"), "{").unwrap();
+    std::fs::write(left.path().join("broken.json"), "{}").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+        .arg("dirs")
+        .args([left.path(), right.path()])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let stdout = stdout(&output);
+    assert!(
+        stdout.contains("changed.json:\n  Mismatched: .a.(1 != 2)\n"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("broken.json: Error [parse_right]"),
+        "{stdout}"
+    );
+    assert!(!stdout.contains("same.json"), "{stdout}");
+}
+
+#[test]
+fn csv_compares_json_columns_per_row() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let (left, right) = (dir.path().join("left.csv"), dir.path().join("right.csv"));
+    std::fs::write(
+        &left,
+        "id,name,payload\n1,a,\"{\"\"n\"\": 1, \"\"k\"\": [1]}\"\n2,b,{}\n3,c,{}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        &right,
+        "id,name,payload\n2,b,{}\n1,a,\"{\"\"k\"\": [1], \"\"n\"\": 2}\"\n",
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+        .args(["csv", "--json-cols", "payload", "--key-col", "id"])
+        .args([&left, &right])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let printed = stdout(&output);
+    assert!(
+        printed.contains("Row 1:\n  Mismatched: .payload.n.(1 != 2)\n"),
+        "{printed}"
+    );
+    assert!(printed.contains("Row 3: only in "), "{printed}");
+    assert!(!printed.contains("Row 2"), "{printed}");
+    // paired by position, the swapped rows differ as strings
+    let output = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+        .args(["csv", "--json-cols", "payload"])
+        .args([&left, &right])
+        .output()
+        .unwrap();
+    assert!(stdout(&output).contains(r#"Mismatched: .id.("1" != "2")"#));
+}
+
+#[test]
+fn top_prints_the_largest_deltas() {
+    let output = json_diff(
+        &["--top", "2"],
+        r#"{"a": 1, "b": 10, "c": 5}"#,
+        r#"{"a": 2, "b": 50, "c": 25}"#,
+    );
+    assert_eq!(output.status.code(), Some(1));
+    let printed = stdout(&output);
+    let b = printed
+        .find("Mismatched: .b.(10 != 50) (score 40)")
+        .unwrap();
+    let c = printed.find("Mismatched: .c.(5 != 25) (score 20)").unwrap();
+    assert!(b < c && !printed.contains(".a."), "{printed}");
+}
+
+#[test]
+fn labels_default_to_file_paths() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let (left, right) = write_inputs(dir.path(), "1", "2");
+    let output = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+        .args(["file", &left, &right])
+        .output()
+        .unwrap();
+    assert!(stdout(&output).contains(&format!("Comparing {left} with {right}\n")));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+        .args(["--label-left", "production", "file", &left, &right])
+        .output()
+        .unwrap();
+    assert!(stdout(&output).contains(&format!("Comparing production with {right}\n")));
+}
+
+#[test]
+fn map_moves_left_keys_before_comparing() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let map = dir.path().join("map.json");
+    std::fs::write(
+        &map,
+        r#"{"/user/name": "/profile/displayName", "/user/nick": "/profile/nick"}"#,
+    )
+    .unwrap();
+    let map = map.to_string_lossy();
+    let output = json_diff(
+        &["--map", &map],
+        r#"{"user": {"name": "Jane", "id": 1}}"#,
+        r#"{"user": {"id": 1}, "profile": {"displayName": "Jane"}}"#,
+    );
+    assert_eq!(output.status.code(), Some(0), "{}", stdout(&output));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Warning: Mapping source /user/nick does not resolve, skipped"));
+}
+
+#[test]
+fn quiet_suppresses_warnings() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let map = dir.path().join("map.json");
+    std::fs::write(&map, r#"{"/missing": "/moved"}"#).unwrap();
+    let map = map.to_string_lossy();
+    let output = json_diff(&["--map", &map], r#"{"a": 1}"#, r#"{"a": 2}"#);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Warning: Mapping source /missing"));
+    let output = json_diff(&["--map", &map, "--quiet"], r#"{"a": 1}"#, r#"{"a": 2}"#);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+    assert!(stdout(&output).contains("Mismatched: .a.(1 != 2)"));
+}
+
+#[test]
+fn messages_translate_the_diff_categories() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let messages = dir.path().join("de.json");
+    std::fs::write(&messages, r#"{"mismatch": "Abweichung"}"#).unwrap();
+    let messages = messages.to_string_lossy();
+    let output = json_diff(&["--messages", &messages], r#"{"a": 1}"#, r#"{"a": 2}"#);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("Abweichung: .a.(1 != 2)\n"));
+}
+
+#[test]
+fn report_diff_lists_changes_between_reports() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let write = |name: &str, left: &str, right: &str| {
+        let left: serde_json::Value = serde_json::from_str(left).unwrap();
+        let right: serde_json::Value = serde_json::from_str(right).unwrap();
+        let mismatch = json_diff_ng::compare_serde_values(&left, &right, false, &[]).unwrap();
+        let path = dir.path().join(name);
+        std::fs::write(&path, json_diff_ng::report::to_json(&mismatch).to_string()).unwrap();
+        path.to_string_lossy().into_owned()
+    };
+    let old = write("old.json", r#"{"a": 1, "b": 1}"#, r#"{"a": 2}"#);
+    let new = write("new.json", r#"{"a": 1, "c": 1}"#, r#"{"a": 3}"#);
+    let output = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+        .args(["report-diff", &old, &new])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stdout(&output),
+        "Appeared: Extra on left: .c.(1)\n\
+         Disappeared: Extra on left: .b.(1)\n\
+         Changed: Mismatched: .a.(1 != 2)\n     to: Mismatched: .a.(1 != 3)\n"
+    );
+    let output = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+        .args(["report-diff", &old, &old])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn dot_format_prints_only_the_graph() {
+    let output = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+        .args(["--format", "dot", "direct", r#"{"a": 1}"#, r#"{"a": 2}"#])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let graph = stdout(&output);
+    assert!(graph.starts_with("digraph diff {\n"), "{graph}");
+    assert!(graph.contains(r##""root/a#0" [label="Mismatched\n1 != 2", fillcolor="#fff2cc"];"##));
+    assert!(graph.ends_with("}\n"));
+    let output = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+        .args(["--format", "dot", "direct", "[1]", "[1]"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).contains(r#""root" [label="no differences"];"#));
+}
+
+#[test]
+fn dot_format_supports_sorted_arrays() {
+    let output = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+        .args(["--format", "dot", "-s", "direct", "[2, 1]", "[1, 2]"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stdout(&output));
+    assert!(stdout(&output).contains(r#""root" [label="no differences"];"#));
+}
+
+#[test]
+fn page_size_prints_one_page_of_the_json_report() {
+    let page = |index: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+            .args([
+                "--page-size",
+                "2",
+                "--page",
+                index,
+                "direct",
+                "[1, 2, 3]",
+                "[4, 5, 6]",
+            ])
+            .output()
+            .unwrap();
+        (output.status.code(), stdout(&output))
+    };
+    let (status, first) = page("1");
+    assert_eq!(status, Some(1));
+    let first: serde_json::Value = serde_json::from_str(&first).unwrap();
+    assert_eq!(
+        first["page"],
+        serde_json::json!({"index": 1, "total_pages": 2, "total_records": 3})
+    );
+    assert_eq!(first["diffs"][0]["path"], ".[2]");
+    let (status, _) = page("2");
+    assert_eq!(status, Some(2));
+}
+
+#[test]
+fn page_size_supports_sorted_arrays() {
+    let output = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+        .args(["--page-size", "1", "-s", "direct", "[3, 1]", "[1, 2]"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{}", stdout(&output));
+    let page: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(page["page"]["total_records"], 1);
+}
+
+#[test]
+fn must_differ_paths_fail_when_unchanged() {
+    let run = |right: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+            .args([
+                "--must-differ",
+                "/version",
+                "direct",
+                r#"{"version": 1, "a": 1}"#,
+                right,
+            ])
+            .output()
+            .unwrap();
+        (output.status.code(), stdout(&output))
+    };
+    let (status, output) = run(r#"{"version": 2, "a": 1}"#);
+    assert_eq!(status, Some(0), "{output}");
+    assert!(output.contains("Mismatched: .version.(1 != 2) (expected)\n"));
+    let (status, output) = run(r#"{"version": 1, "a": 1}"#);
+    assert_eq!(status, Some(1));
+    assert!(output.contains("Expected change missing: .version.(1 != 1)\n"));
+}
+
+#[test]
+fn unwrap_compares_wrapped_and_bare_payloads() {
+    let wrapped = r#"{"data": {"id": 1, "tags": ["a"]}}"#;
+    let bare = r#"{"id": 1, "tags": ["a"]}"#;
+    let output = json_diff(&["--left-pointer", "/data"], wrapped, bare);
+    assert_eq!(output.status.code(), Some(0), "{}", stdout(&output));
+    let output = json_diff(&["--left-pointer", "/payload"], wrapped, bare);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Cannot unwrap the left document"));
+    let flags = ["--left-pointer", "/payload", "--unwrap-fallback"];
+    let output = json_diff(&flags, wrapped, bare);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Warning: JSON pointer /payload does not resolve in the left document"));
+    assert!(stdout(&output).contains(".data"));
+    let flags = ["--multi-doc", "--left-pointer", "/data"];
+    let output = json_diff(&flags, r#"{"data": 1} {"data": 2}"#, "1 2");
+    assert_eq!(output.status.code(), Some(0), "{}", stdout(&output));
+}
+
+#[test]
+fn key_case_matches_keys_spelled_differently() {
+    let left = r#"{"CustomerId": 7, "Name": "Jane"}"#;
+    let right = r#"{"customerId": 7, "name": "Joan"}"#;
+    let output = json_diff(&["--key-case", "lower"], left, right);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("Mismatched: .Name.(\"Jane\" != \"Joan\")\n"));
+    let output = json_diff(&["--key-case", "lower"], r#"{"a": 1, "A": 2}"#, right);
+    assert_eq!(output.status.code(), Some(5));
+}
+
+#[test]
+fn transforms_are_read_from_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let (left, right) = write_inputs(dir.path(), r#"{"a": 1, "debug": true}"#, r#"{"a": 1}"#);
+    let transform = dir.path().join("transform.json");
+    let run = |operations: &str| {
+        std::fs::write(&transform, operations).unwrap();
+        Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+            .args([
+                "--transform-left",
+                transform.to_str().unwrap(),
+                "file",
+                &left,
+                &right,
+            ])
+            .output()
+            .unwrap()
+    };
+    let output = run(r#"[{"op": "remove", "path": "/debug"}]"#);
+    assert_eq!(output.status.code(), Some(0), "{}", stdout(&output));
+    let output = run(r#"[{"op": "remove", "path": "/debug"}, {"op": "remove", "path": "/gone"}]"#);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("[invalid_transform]"), "{stderr}");
+    assert!(
+        stderr.contains("operation 1: /gone does not resolve"),
+        "{stderr}"
+    );
+}
+
+fn json_diff_stdin(args: &[&str], stdin: &str) -> Output {
+    use std::io::Write;
+    let mut child = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn dash_reads_one_file_from_stdin() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let (expected, _) = write_inputs(dir.path(), r#"{"a": 1}"#, "");
+    let output = json_diff_stdin(&["file", &expected, "-"], r#"{"a": 2}"#);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("Mismatched: .a.(1 != 2)"));
+    let output = json_diff_stdin(&["file", "-", &expected], "{");
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Error parsing left json"), "{stderr}");
+}
+
+#[test]
+fn stdin_holds_two_concatenated_documents() {
+    let output = json_diff_stdin(&["stdin"], "{\"a\": 1}\n{\"a\": 2}\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("Mismatched: .a.(1 != 2)"));
+    // two dashes read both files from the same stream
+    let output = json_diff_stdin(&["file", "-", "-"], "[1][1]");
+    assert_eq!(output.status.code(), Some(0));
+    let output = json_diff_stdin(&["stdin"], r#"{"a": 1} {"a": "#);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("<stdin>: Error parsing right json"),
+        "{stderr}"
+    );
+    let output = json_diff_stdin(&["stdin"], "1");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("stdin holds no right document"), "{stderr}");
+    let output = json_diff_stdin(&["stdin"], "1 2 3");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("more than two json documents"), "{stderr}");
+}
+
+#[test]
+fn resolve_refs_expands_internal_references() {
+    let referenced = r##"{"pet": {"$ref": "#/defs/pet"}, "defs": {"pet": {"name": "string"}}}"##;
+    let inlined = r#"{"pet": {"name": "string"}, "defs": {"pet": {"name": "string"}}}"#;
+    assert_eq!(json_diff(&[], referenced, inlined).status.code(), Some(1));
+    let output = json_diff(&["--resolve-refs"], referenced, inlined);
+    assert_eq!(output.status.code(), Some(0), "{}", stdout(&output));
+    let cyclic = r##"{"a": {"$ref": "#/b"}, "b": {"$ref": "#/a"}}"##;
+    let output = json_diff(&["--resolve-refs"], cyclic, "{}");
+    assert_eq!(output.status.code(), Some(5));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("[ref_cycle]"), "{stderr}");
+    assert!(stderr.contains("#/b -> #/a -> #/b"), "{stderr}");
+}
+
+#[test]
+fn json_format_supports_sorted_arrays() {
+    let output = json_diff(&["--format", "json", "-s"], "[2, 1]", "[1, 2]");
+    assert_eq!(output.status.code(), Some(0), "{}", stdout(&output));
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(report["equal"], true);
+}
+
+#[test]
+fn colored_output_follows_no_values() {
+    let output = json_diff(&["--color", "always", "--no-values"], LEFT, RIGHT);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = stdout(&output);
+    assert!(stdout.contains("\x1b[31m- address\x1b[0m\n"), "{stdout}");
+    assert!(stdout.contains("\x1b[32m+ added\x1b[0m\n"), "{stdout}");
+    assert!(!stdout.contains("Oslo"), "{stdout}");
+}
+
+#[test]
+fn files_starting_with_a_byte_order_mark_are_parsed() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let (left, right) = write_inputs(dir.path(), "\u{feff}{\"a\": 1}", "{\"a\": 2}");
+    let output = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+        .args(["file", &left, &right])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains(".a.(1 != 2)"));
+    let output = Command::new(env!("CARGO_BIN_EXE_json_diff_ng"))
+        .args(["file", &left, &left])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+}
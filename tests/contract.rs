@@ -0,0 +1,413 @@
+//! The contract of the binary: exit statuses, output shape and flag interactions.
+//! New cases describe their inputs with [`direct`] or [`files`] and assert on the returned [`Assert`].
+use assert_cmd::assert::Assert;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+const LEFT: &str = r#"{"id": 1, "tags": ["a", "b"], "meta": {"build": 7}}"#;
+const RIGHT: &str = r#"{"id": 2, "tags": ["b", "a"], "meta": {"build": 8}}"#;
+
+fn json_diff() -> Command {
+    Command::cargo_bin("json_diff_ng").unwrap()
+}
+
+/// Compares `left` and `right` given on the command line.
+fn direct(flags: &[&str], left: &str, right: &str) -> Assert {
+    json_diff()
+        .args(flags)
+        .args(["direct", left, right])
+        .assert()
+}
+
+/// Compares `left` and `right` written to files, the directory lives as long as the returned handle.
+fn files(flags: &[&str], left: &str, right: &str) -> (TempDir, Assert) {
+    let dir = TempDir::new().unwrap();
+    let (left_path, right_path) = (dir.path().join("left.json"), dir.path().join("right.json"));
+    std::fs::write(&left_path, left).unwrap();
+    std::fs::write(&right_path, right).unwrap();
+    let assert = json_diff()
+        .args(flags)
+        .arg("file")
+        .args([&left_path, &right_path])
+        .assert();
+    (dir, assert)
+}
+
+#[test]
+fn equal_inputs_exit_with_0() {
+    direct(&[], LEFT, LEFT)
+        .code(0)
+        .stdout(predicate::str::contains("Mismatched").not());
+    files(&[], LEFT, LEFT).1.code(0);
+}
+
+#[test]
+fn differing_inputs_exit_with_1_in_both_modes() {
+    let expected = ".id.(1 != 2)";
+    direct(&[], LEFT, RIGHT)
+        .code(1)
+        .stdout(predicate::str::contains(expected));
+    files(&[], LEFT, RIGHT)
+        .1
+        .code(1)
+        .stdout(predicate::str::contains(expected))
+        .stdout(predicate::str::contains("left.json with"));
+}
+
+#[test]
+fn sort_arrays_ignores_element_order() {
+    let reordered = r#"{"id": 1, "tags": ["b", "a"], "meta": {"build": 7}}"#;
+    direct(&[], LEFT, reordered).code(1);
+    direct(&["--sort-arrays"], LEFT, reordered).code(0);
+}
+
+#[test]
+fn excluded_keys_are_not_compared() {
+    direct(&["--sort-arrays", "-e", "id", "-e", "build"], LEFT, RIGHT).code(0);
+    direct(&["-e", "build"], LEFT, RIGHT)
+        .code(1)
+        .stdout(predicate::str::contains(".id.(1 != 2)"))
+        .stdout(predicate::str::contains("build").not());
+    let by_path = [
+        "--sort-arrays",
+        "--exclude-pointers",
+        "/meta",
+        "--exclude-paths",
+    ];
+    direct(&[&by_path[..], &[r"^\.id$"]].concat(), LEFT, RIGHT).code(0);
+    direct(&[&by_path[..], &[r"^\.meta\.id$"]].concat(), LEFT, RIGHT)
+        .code(1)
+        .stdout(predicate::str::contains(".id.(1 != 2)"));
+}
+
+#[test]
+fn missing_files_exit_with_2() {
+    json_diff()
+        .args(["file", "does-not-exist.json", "neither.json"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::starts_with("Error [io]:"));
+}
+
+#[test]
+fn malformed_json_exits_with_2_naming_the_side() {
+    direct(&[], "{", LEFT)
+        .code(2)
+        .stderr(predicate::str::starts_with("Error [parse_left]:"));
+    files(&[], LEFT, "[1,")
+        .1
+        .code(2)
+        .stderr(predicate::str::starts_with("Error [parse_right]:"));
+}
+
+#[test]
+fn other_errors_exit_with_2() {
+    direct(&["-e", "("], LEFT, RIGHT)
+        .code(2)
+        .stderr(predicate::str::starts_with("Error [regex]:"));
+    direct(&["--format", "dot", "--multi-doc"], LEFT, RIGHT).code(2);
+}
+
+#[test]
+fn unknown_flags_are_usage_errors() {
+    direct(&["--no-such-flag"], LEFT, RIGHT)
+        .code(2)
+        .stderr(predicate::str::contains("Usage"));
+}
+
+#[test]
+fn piped_formats_print_no_progress() {
+    files(&["--format", "dot"], LEFT, RIGHT)
+        .1
+        .code(1)
+        .stdout(predicate::str::starts_with("digraph diff {"));
+}
+
+/// Runs git in `repo` with a fixed identity, so commits work without a global configuration.
+fn git(repo: &std::path::Path, args: &[&str]) {
+    let status = std::process::Command::new("git")
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(repo)
+        .output()
+        .unwrap()
+        .status;
+    assert!(status.success(), "git {args:?} failed");
+}
+
+#[test]
+fn git_mode_compares_revisions() {
+    let repo = TempDir::new().unwrap();
+    let file = repo.path().join("config.json");
+    git(repo.path(), &["init", "-q"]);
+    for (content, message) in [(LEFT, "first"), (RIGHT, "second")] {
+        std::fs::write(&file, content).unwrap();
+        git(repo.path(), &["add", "config.json"]);
+        git(repo.path(), &["commit", "-q", "-m", message]);
+    }
+    let in_repo = |flags: &[&str], revisions: [&str; 3]| {
+        json_diff()
+            .current_dir(repo.path())
+            .args(flags)
+            .arg("git")
+            .args(revisions)
+            .assert()
+    };
+    in_repo(&["-e", "id"], ["HEAD~1", "HEAD", "config.json"])
+        .code(1)
+        .stdout(predicate::str::contains(".meta.build.(7 != 8)"))
+        .stdout(predicate::str::contains(
+            "HEAD~1:config.json with HEAD:config.json",
+        ));
+    in_repo(&[], ["HEAD", "HEAD", "config.json"]).code(0);
+    in_repo(&[], ["nope", "HEAD", "config.json"])
+        .code(6)
+        .stderr(predicate::str::contains("unknown revision nope"));
+    in_repo(&[], ["HEAD~1", "HEAD", "missing.json"])
+        .code(2)
+        .stderr(predicate::str::contains(
+            "missing.json does not exist in revision HEAD~1",
+        ));
+}
+
+#[test]
+fn streamed_diffs_end_with_their_count() {
+    let document = |offset: usize| {
+        let items: Vec<String> = (0..2000)
+            .map(|i| {
+                format!(
+                    r#"{{"id": {i}, "value": {}}}"#,
+                    i % 7 + (i % 4 == 0) as usize * offset
+                )
+            })
+            .collect();
+        format!("[{}]", items.join(","))
+    };
+    let (_dir, assert) = files(&["--stream"], &document(0), &document(1));
+    let stdout = String::from_utf8(assert.code(1).get_output().stdout.clone()).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    let (summary, diffs) = lines.split_last().unwrap();
+    assert_eq!(diffs.len(), 500);
+    assert_eq!(*summary, format!("{} diffs", diffs.len()));
+    assert!(diffs.iter().all(|line| line.starts_with("Mismatched: .[")));
+
+    let (_dir, assert) = files(
+        &["--stream", "--max-diffs", "10"],
+        &document(0),
+        &document(1),
+    );
+    let stdout = String::from_utf8(assert.code(1).get_output().stdout.clone()).unwrap();
+    assert_eq!(stdout.lines().count(), 11);
+    assert!(stdout.ends_with("Stopped after 10 diffs\n"));
+    direct(&["--stream"], LEFT, LEFT)
+        .code(0)
+        .stdout("0 diffs\n");
+    direct(&["--stream"], "{\"name\u{200b}\": 1}", r#"{"name": 1}"#)
+        .code(1)
+        .stdout(predicate::str::starts_with(
+            "Key almost equal: .(\"name\\u{200b}\" ≈ \"name\")\n",
+        ));
+}
+
+#[test]
+fn fail_on_category_fails_only_for_its_categories() {
+    let dir = TempDir::new().unwrap();
+    let rules = dir.path().join("rules.json");
+    // the regex matches /meta/build as well, the earlier glob wins
+    std::fs::write(
+        &rules,
+        r#"[{"glob": "/meta/**", "category": "infra"}, {"regex": "^/(id|meta)", "category": "pricing"}]"#,
+    )
+    .unwrap();
+    let rules = rules.to_str().unwrap();
+    direct(&["--classify", rules], LEFT, RIGHT)
+        .code(1)
+        .stdout(predicate::str::ends_with(
+            "Categories:\n  infra: 1\n  pricing: 1\n  uncategorized: 2\n",
+        ));
+    let fail_on_infra = ["--classify", rules, "--fail-on-category", "infra"];
+    direct(&fail_on_infra, LEFT, RIGHT).code(1);
+    direct(
+        &[&fail_on_infra[..], &["-e", "build"]].concat(),
+        LEFT,
+        RIGHT,
+    )
+    .code(0)
+    .stdout(predicate::str::contains(".id.(1 != 2)"));
+    direct(&["--fail-on-category", "infra"], LEFT, RIGHT).code(2);
+    direct(&["--classify", rules, "--fail-on-category", "infar"], LEFT, RIGHT)
+        .code(2)
+        .stderr(predicate::str::starts_with(
+            "Error [invalid_classifier]: Invalid classifier: --fail-on-category infar names no category",
+        ));
+    let json = direct(
+        &[&fail_on_infra[..], &["-e", "build", "--format", "json"]].concat(),
+        LEFT,
+        RIGHT,
+    )
+    .code(0);
+    let report: serde_json::Value = serde_json::from_slice(&json.get_output().stdout).unwrap();
+    assert_eq!(
+        report["summary"]["categories"],
+        serde_json::json!({"pricing": 1})
+    );
+    direct(&["--classify", rules, "--format", "dot"], LEFT, RIGHT).code(2);
+    let messages = dir.path().join("messages.json");
+    std::fs::write(&messages, r#"{"categories": "Kategorien:"}"#).unwrap();
+    let translated = [
+        "--classify",
+        rules,
+        "--messages",
+        messages.to_str().unwrap(),
+    ];
+    direct(&translated, LEFT, RIGHT)
+        .code(1)
+        .stdout(predicate::str::contains("\nKategorien:\n  infra: 1\n"));
+    let broken = dir.path().join("broken.json");
+    std::fs::write(&broken, r#"[{"glob": "/a"}]"#).unwrap();
+    direct(&["--classify", broken.to_str().unwrap()], LEFT, RIGHT)
+        .code(2)
+        .stderr(predicate::str::starts_with("Error [invalid_classifier]:"));
+}
+
+#[test]
+fn scalar_roots_exit_with_5_when_containers_are_required() {
+    direct(&["--require-container"], "\"null\"", LEFT)
+        .code(5)
+        .stderr(predicate::str::starts_with(
+            "Error [unexpected_root_type]: The left document is a string, not an object or array",
+        ));
+    direct(&["--require-container"], LEFT, "42").code(5);
+    direct(&["--require-container"], "1", "1").code(5);
+    direct(&["--require-container"], LEFT, LEFT).code(0);
+    direct(&[], "1", "1").code(0);
+}
+
+#[test]
+fn inputs_above_max_input_size_exit_with_5_naming_the_file() {
+    let large = format!("[{}]", vec!["1"; 100].join(","));
+    let (dir, assert) = files(&["--max-input-size", "64"], LEFT, &large);
+    let right = dir.path().join("right.json");
+    assert.code(5).stderr(predicate::str::starts_with(format!(
+        "Error [limit_exceeded]: {}: Input of 201 bytes exceeds the maximum input size of 64 bytes",
+        right.display()
+    )));
+    files(&["--max-input-size", "201"], &large, &large)
+        .1
+        .code(0);
+}
+
+#[test]
+fn snapshots_stand_in_for_their_json_files() {
+    let dir = TempDir::new().unwrap();
+    let (golden, actual) = (
+        dir.path().join("golden.json"),
+        dir.path().join("actual.json"),
+    );
+    std::fs::write(&golden, LEFT).unwrap();
+    std::fs::write(&actual, RIGHT).unwrap();
+    let snapshot = dir.path().join("golden.snap");
+    let run =
+        |flags: &[&str], args: &[&std::path::Path]| json_diff().args(flags).args(args).assert();
+    run(&["-e", "build", "prepare"], &[&golden, &snapshot])
+        .code(0)
+        .stdout(format!("Prepared {}\n", snapshot.display()));
+    let fresh = run(&["-e", "build", "file"], &[&golden, &actual]).code(1);
+    let fresh = String::from_utf8(fresh.get_output().stdout.clone()).unwrap();
+    let loaded = run(&["-e", "build", "file"], &[&snapshot, &actual]).code(1);
+    let loaded = String::from_utf8(loaded.get_output().stdout.clone()).unwrap();
+    assert_eq!(
+        loaded,
+        fresh.replace(&*golden.to_string_lossy(), &snapshot.to_string_lossy())
+    );
+    run(&["-e", "build", "file"], &[&actual, &snapshot])
+        .code(1)
+        .stdout(predicate::str::contains(".id.(2 != 1)"));
+    // the snapshot holds the document as parsed, options of the comparison may differ from `prepare`
+    let fresh = run(&["--explain", "file"], &[&golden, &actual]).code(1);
+    let fresh = String::from_utf8(fresh.get_output().stdout.clone()).unwrap();
+    let loaded = run(&["--explain", "file"], &[&snapshot, &actual]).code(1);
+    let loaded = String::from_utf8(loaded.get_output().stdout.clone()).unwrap();
+    assert_eq!(
+        loaded,
+        fresh.replace(&*golden.to_string_lossy(), &snapshot.to_string_lossy())
+    );
+    std::fs::write(&snapshot, LEFT).unwrap();
+    run(&["file"], &[&snapshot, &actual])
+        .code(2)
+        .stderr(predicate::str::starts_with(format!(
+            "Error [invalid_snapshot]: {}: Invalid snapshot: not a snapshot of this version",
+            snapshot.display()
+        )));
+}
+
+#[test]
+fn identity_keys_pair_reordered_elements() {
+    let left = r#"{"users": [{"id": 1, "email": "a@x", "role": "dev"}, {"id": 2, "email": "b@x", "role": "dev"}]}"#;
+    let right = r#"{"users": [{"id": 2, "email": "b@x", "role": "dev"}, {"id": 1, "email": "a@x", "role": "ops"}]}"#;
+    direct(&["--identity-key", "id"], left, right)
+        .code(1)
+        .stdout(predicate::str::contains(
+            r#".users.[0].role.("dev" != "ops")"#,
+        ))
+        .stdout(predicate::str::contains("email").not());
+    direct(&["--identity-key-at", "/users=email"], left, right)
+        .code(1)
+        .stdout(predicate::str::contains("email").not());
+    direct(&["--identity-key-at", "/users"], left, right)
+        .code(2)
+        .stderr(predicate::str::contains("expected POINTER=KEY"));
+}
+
+#[test]
+fn json_output_is_one_document_sorted_by_side() {
+    let (_dir, assert) = files(
+        &["--output", "json"],
+        LEFT,
+        r#"{"id": 2, "tags": ["a"], "new": null}"#,
+    );
+    let output = assert.code(1).get_output().stdout.clone();
+    let report: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(
+        report,
+        serde_json::json!({
+            "equal": false,
+            "left_only": [
+                {"path": ".meta", "path_elements": ["meta"], "value": {"build": 7}},
+                {"path": ".tags.[1]", "path_elements": ["tags", 1], "value": "b"}
+            ],
+            "right_only": [{"path": ".new", "path_elements": ["new"], "value": null}],
+            "mismatches": [
+                {"type": "Mismatched", "path": ".id", "path_elements": ["id"], "left": 1, "right": 2}
+            ]
+        })
+    );
+    let output = direct(&["--format", "json"], LEFT, LEFT)
+        .code(0)
+        .get_output()
+        .stdout
+        .clone();
+    let report: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(report["equal"], true);
+    let equal = |args: &[&str], right: &str, code: i32| {
+        let output = direct(args, LEFT, right)
+            .code(code)
+            .get_output()
+            .stdout
+            .clone();
+        serde_json::from_slice::<serde_json::Value>(&output).unwrap()["equal"].clone()
+    };
+    let changed_id = r#"{"id": 2, "tags": ["a", "b"], "meta": {"build": 7}}"#;
+    assert_eq!(
+        equal(&["--format", "json", "--must-differ", "/id"], changed_id, 0),
+        true
+    );
+    assert_eq!(
+        equal(&["--format", "json", "--must-differ", "/id"], LEFT, 1),
+        false
+    );
+    direct(&["--output", "json"], "{", LEFT)
+        .code(2)
+        .stdout(predicate::str::is_empty());
+}